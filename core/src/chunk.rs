@@ -0,0 +1,217 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::WireError;
+use crate::raptor::check_packet;
+
+/// Chunk format version carried in the first header byte.
+pub const CHUNK_VERSION: u8 = 1;
+/// Version of chunks in a transfer of several files, whose header also names the file.
+pub const MULTI_FILE_VERSION: u8 = 2;
+/// Version of chunks in an endless piped stream, whose header also numbers the segment.
+pub const STREAM_VERSION: u8 = 3;
+/// Version of chunks that also name their transfer, for an output that holds several transfers
+/// (files appended to it later).
+pub const TAGGED_VERSION: u8 = 4;
+/// Version of chunks that carry a part of the manifest of a transfer of several files, rather
+/// than a RaptorQ packet. See [`crate::manifest`].
+pub const MANIFEST_VERSION: u8 = 5;
+/// Set on the version byte of a chunk whose header ends with [`SEQUENCE_SIZE`] bytes numbering
+/// the frame it was shown on, for a receiver to measure the channel. Any version may carry it.
+pub const SEQUENCE_FLAG: u8 = 0x80;
+pub const SEQUENCE_SIZE: usize = 4;
+pub const HEADER_SIZE: usize = 11; // 1 (version) + 4 (transfer len) + 4 (esi) + 2 (packet size)
+pub const MULTI_FILE_HEADER_SIZE: usize = 13; // 1 (version) + 1 (file id) + 1 (file count) + 10
+pub const STREAM_HEADER_SIZE: usize = 15; // 1 (version) + 4 (segment) + 10
+pub const TAGGED_HEADER_SIZE: usize = 15; // 1 (version) + 2 (transfer) + 1 (file id) + 1 (file count) + 10
+pub const MANIFEST_HEADER_SIZE: usize = 6; // 1 (version) + 2 (transfer) + 1 (file count) + 1 (part) + 1 (part count)
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkHeader {
+    pub version: u8,
+    pub file_id: u8,      // File index, 0 in version 1
+    pub file_count: u8,   // Files in the transfer, 1 in version 1
+    pub segment: u32,     // Segment of a piped stream, 0 in other versions
+    pub transfer: u16,    // Transfer within an output, 0 before version 4
+    pub total: u32,       // Transfer Length; number of parts in version 5
+    pub index: u32,       // ESI; part number in version 5
+    pub packet_size: u16, // Packet Size; 0 in version 5
+    /// Frame the chunk was shown on, with [`SEQUENCE_FLAG`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sequence: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chunk {
+    pub header: ChunkHeader,
+    pub data: Vec<u8>,
+}
+
+impl ChunkHeader {
+    /// Encoded size of this header.
+    pub fn size(&self) -> usize {
+        let size = match self.version {
+            MULTI_FILE_VERSION => MULTI_FILE_HEADER_SIZE,
+            STREAM_VERSION => STREAM_HEADER_SIZE,
+            TAGGED_VERSION => TAGGED_HEADER_SIZE,
+            MANIFEST_VERSION => MANIFEST_HEADER_SIZE,
+            _ => HEADER_SIZE,
+        };
+        if self.sequence.is_some() {
+            size + SEQUENCE_SIZE
+        } else {
+            size
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.size()];
+        bytes[0] = self.version;
+        if let Some(sequence) = self.sequence {
+            bytes[0] |= SEQUENCE_FLAG;
+            let end = bytes.len();
+            bytes[end - SEQUENCE_SIZE..].copy_from_slice(&sequence.to_be_bytes());
+        }
+        if self.version == MANIFEST_VERSION {
+            bytes[1..3].copy_from_slice(&self.transfer.to_be_bytes());
+            bytes[3] = self.file_count;
+            bytes[4] = self.index as u8;
+            bytes[5] = self.total as u8;
+            return bytes;
+        }
+        let rest = match self.version {
+            MULTI_FILE_VERSION => {
+                bytes[1] = self.file_id;
+                bytes[2] = self.file_count;
+                &mut bytes[3..]
+            }
+            STREAM_VERSION => {
+                bytes[1..5].copy_from_slice(&self.segment.to_be_bytes());
+                &mut bytes[5..]
+            }
+            TAGGED_VERSION => {
+                bytes[1..3].copy_from_slice(&self.transfer.to_be_bytes());
+                bytes[3] = self.file_id;
+                bytes[4] = self.file_count;
+                &mut bytes[5..]
+            }
+            _ => &mut bytes[1..],
+        };
+        rest[0..4].copy_from_slice(&self.total.to_be_bytes());
+        rest[4..8].copy_from_slice(&self.index.to_be_bytes());
+        rest[8..10].copy_from_slice(&self.packet_size.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), WireError> {
+        if bytes.is_empty() {
+            return Err(WireError::EmptyHeader);
+        }
+        let version = bytes[0] & !SEQUENCE_FLAG;
+        let sequenced = bytes[0] & SEQUENCE_FLAG != 0;
+        let header_size = match version {
+            CHUNK_VERSION => HEADER_SIZE,
+            MULTI_FILE_VERSION => MULTI_FILE_HEADER_SIZE,
+            STREAM_VERSION => STREAM_HEADER_SIZE,
+            TAGGED_VERSION => TAGGED_HEADER_SIZE,
+            MANIFEST_VERSION => MANIFEST_HEADER_SIZE,
+            _ => return Err(WireError::UnsupportedVersion(version)),
+        };
+
+        let sequence_end = header_size + if sequenced { SEQUENCE_SIZE } else { 0 };
+        if bytes.len() < sequence_end {
+            return Err(WireError::HeaderTooShort);
+        }
+        let sequence = sequenced.then(|| {
+            let field = &bytes[header_size..sequence_end];
+            u32::from_be_bytes([field[0], field[1], field[2], field[3]])
+        });
+        if version == MANIFEST_VERSION {
+            let header = ChunkHeader {
+                version,
+                file_id: 0,
+                file_count: bytes[3],
+                segment: 0,
+                transfer: u16::from_be_bytes([bytes[1], bytes[2]]),
+                total: bytes[5] as u32,
+                index: bytes[4] as u32,
+                packet_size: 0,
+                sequence,
+            };
+            return Ok((header, sequence_end));
+        }
+        let (file_id, file_count, segment, transfer, rest) = match version {
+            MULTI_FILE_VERSION => (bytes[1], bytes[2], 0, 0, &bytes[3..]),
+            STREAM_VERSION => {
+                let segment = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+                (0, 1, segment, 0, &bytes[5..])
+            }
+            TAGGED_VERSION => {
+                let transfer = u16::from_be_bytes([bytes[1], bytes[2]]);
+                (bytes[3], bytes[4], 0, transfer, &bytes[5..])
+            }
+            _ => (0, 1, 0, 0, &bytes[1..]),
+        };
+        let total = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let index = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]);
+        let packet_size = u16::from_be_bytes([rest[8], rest[9]]);
+        Ok((
+            ChunkHeader {
+                version,
+                file_id,
+                file_count,
+                segment,
+                transfer,
+                total,
+                index,
+                packet_size,
+                sequence,
+            },
+            sequence_end,
+        ))
+    }
+}
+
+impl Chunk {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WireError> {
+        let header_bytes = self.header.to_bytes();
+        let mut result = Vec::with_capacity(header_bytes.len() + self.data.len());
+        result.extend_from_slice(&header_bytes);
+        result.extend_from_slice(&self.data);
+        Ok(result)
+    }
+
+    /// Parses a chunk, rejecting one whose packet does not fit its header.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let (header, header_len) = ChunkHeader::from_bytes(bytes)?;
+        if header.version == MANIFEST_VERSION {
+            if header.index >= header.total {
+                return Err(WireError::InvalidManifest);
+            }
+        } else {
+            check_packet(&header, &bytes[header_len..])?;
+        }
+        let data = bytes[header_len..].to_vec();
+
+        Ok(Chunk { header, data })
+    }
+
+    /// Whether this chunk holds a part of a manifest rather than a packet.
+    pub fn is_manifest(&self) -> bool {
+        self.header.version == MANIFEST_VERSION
+    }
+
+    /// Base45 text stored in a QR code for this chunk.
+    pub fn to_text(&self) -> Result<String, WireError> {
+        Ok(base45::encode(&self.to_bytes()?))
+    }
+
+    /// Parses the base45 text read from a QR code.
+    pub fn from_text(text: &str) -> Result<Self, WireError> {
+        let bytes = base45::decode(text).map_err(|_| WireError::InvalidText)?;
+        Self::from_bytes(&bytes)
+    }
+}
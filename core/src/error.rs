@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Failures while parsing or reassembling the wire format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    EmptyHeader,
+    UnsupportedVersion(u8),
+    HeaderTooShort,
+    InvalidText,
+    InvalidPacket,
+    PackedTooShort,
+    MissingFilenameTerminator,
+    InvalidFilename,
+    ChecksumMismatch { expected: Vec<u8>, actual: Vec<u8> },
+    Decompress,
+    InvalidManifest,
+    MissingDictionary(u32),
+    UnsupportedCompression(u8),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::EmptyHeader => write!(f, "Invalid header: empty"),
+            WireError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported chunk version: {}. Only versions 1 to 5 are supported.",
+                version
+            ),
+            WireError::HeaderTooShort => write!(f, "Invalid header: too short"),
+            WireError::InvalidText => write!(f, "Invalid chunk text: not base45"),
+            WireError::InvalidPacket => {
+                write!(
+                    f,
+                    "Invalid packet: does not fit the parameters in its header"
+                )
+            }
+            WireError::PackedTooShort => write!(f, "Invalid packed data: too short"),
+            WireError::MissingFilenameTerminator => {
+                write!(f, "Invalid packed data: missing filename terminator")
+            }
+            WireError::InvalidFilename => write!(f, "Invalid filename: not valid UTF-8"),
+            WireError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: expected {:?}, got {:?}",
+                expected, actual
+            ),
+            WireError::Decompress => write!(f, "Failed to decompress data"),
+            WireError::InvalidManifest => write!(f, "Invalid manifest"),
+            WireError::MissingDictionary(id) => {
+                write!(
+                    f,
+                    "Compressed with dictionary {:08x}, which was not supplied",
+                    id
+                )
+            }
+            WireError::UnsupportedCompression(algorithm) => write!(
+                f,
+                "Unsupported compression algorithm: {}. Only none (0) and zlib (1) are supported.",
+                algorithm
+            ),
+        }
+    }
+}
+
+impl core::error::Error for WireError {}
@@ -0,0 +1,32 @@
+//! Wire format of the fountain QR protocol, usable without `std` (requires `alloc`).
+//!
+//! This crate holds everything a receiver needs to turn QR code text into the original file:
+//! chunk header parsing, base45 framing, RaptorQ reassembly and the packed
+//! `[checksum][filename]\0[content]` payload. Scanning QR codes is left to the platform.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod chunk;
+pub mod error;
+pub mod manifest;
+pub mod packed;
+pub mod raptor;
+
+pub use chunk::{
+    Chunk, ChunkHeader, CHUNK_VERSION, HEADER_SIZE, MANIFEST_HEADER_SIZE, MANIFEST_VERSION,
+    MULTI_FILE_HEADER_SIZE, MULTI_FILE_VERSION, SEQUENCE_FLAG, SEQUENCE_SIZE, STREAM_HEADER_SIZE,
+    STREAM_VERSION, TAGGED_HEADER_SIZE, TAGGED_VERSION,
+};
+pub use error::WireError;
+pub use manifest::{Manifest, ManifestAssembler, ManifestEntry, ManifestResult};
+pub use packed::{
+    calculate_checksum, clean_filename, decompress, decompress_with_dictionaries, dictionary_id,
+    pack_data, unpack_data, Compression, CHECKSUM_SIZE, COMPRESSION_FIELD_SIZE, FALLBACK_FILENAME,
+    MAX_FILENAME_LEN, ZLIB_FDICT,
+};
+pub use raptor::{
+    check_packet, encode_chunks, encode_file_chunks, encode_round_chunks, encode_segment_chunks,
+    encode_tagged_chunks, is_source_packet, AssembleResult, ChunkAssembler,
+};
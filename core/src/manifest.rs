@@ -0,0 +1,198 @@
+//! Manifest of a transfer of several files: the name, size and SHA-256 of each, sent in chunks
+//! of its own so a receiver knows what is coming before any file decodes.
+//!
+//! The manifest is `[File count 1B]` then, per file, `[Size 8B] [SHA-256 32B] [Name length 1B]
+//! [Name]`, cut into parts that each fill one chunk. Parts are not RaptorQ packets: every one
+//! is needed, and as the frames loop, a missed part comes around again.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::chunk::{Chunk, ChunkHeader, MANIFEST_HEADER_SIZE, MANIFEST_VERSION};
+use crate::error::WireError;
+use crate::packed::clean_filename;
+
+/// One file listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Name the file is written under, as [`clean_filename`] makes it.
+    pub name: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    pub sha256: [u8; 32],
+}
+
+/// Files of a transfer, in file ID order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Lists `files`, given as contents and names.
+    pub fn new(files: &[(&[u8], &str)]) -> Self {
+        let files = files
+            .iter()
+            .map(|(data, name)| ManifestEntry {
+                name: clean_filename(name),
+                size: data.len() as u64,
+                sha256: Sha256::digest(data).into(),
+            })
+            .collect();
+        Self { files }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(self.files.len() as u8);
+        for file in &self.files {
+            bytes.extend_from_slice(&file.size.to_be_bytes());
+            bytes.extend_from_slice(&file.sha256);
+            bytes.push(file.name.len() as u8);
+            bytes.extend_from_slice(file.name.as_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let (&count, mut rest) = bytes.split_first().ok_or(WireError::InvalidManifest)?;
+        let mut files = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            if rest.len() < 8 + 32 + 1 {
+                return Err(WireError::InvalidManifest);
+            }
+            let (size, after) = rest.split_at(8);
+            let (sha256, after) = after.split_at(32);
+            let name_len = after[0] as usize;
+            let name = after
+                .get(1..1 + name_len)
+                .ok_or(WireError::InvalidManifest)?;
+            let name = core::str::from_utf8(name).map_err(|_| WireError::InvalidFilename)?;
+            files.push(ManifestEntry {
+                name: clean_filename(name),
+                size: u64::from_be_bytes(size.try_into().map_err(|_| WireError::InvalidManifest)?),
+                sha256: sha256.try_into().map_err(|_| WireError::InvalidManifest)?,
+            });
+            rest = &after[1 + name_len..];
+        }
+        if !rest.is_empty() {
+            return Err(WireError::InvalidManifest);
+        }
+        Ok(Self { files })
+    }
+
+    /// Cuts the manifest into chunks of transfer `transfer` that are at most `payload_size`
+    /// bytes each, header included. `None` if it takes more than 255 chunks, or the payload
+    /// has no room past the header.
+    pub fn to_chunks(&self, transfer: u16, payload_size: usize) -> Option<Vec<Chunk>> {
+        let part_size = payload_size.checked_sub(MANIFEST_HEADER_SIZE)?;
+        if part_size == 0 {
+            return None;
+        }
+        let bytes = self.to_bytes();
+        let parts = bytes.chunks(part_size);
+        let part_count = u8::try_from(parts.len()).ok()?;
+        Some(
+            parts
+                .enumerate()
+                .map(|(part, data)| Chunk {
+                    header: ChunkHeader {
+                        version: MANIFEST_VERSION,
+                        file_id: 0,
+                        file_count: self.files.len() as u8,
+                        segment: 0,
+                        transfer,
+                        total: part_count as u32,
+                        index: part as u32,
+                        packet_size: 0,
+                        sequence: None,
+                    },
+                    data: data.to_vec(),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Outcome of pushing a manifest chunk into a [`ManifestAssembler`].
+#[derive(Debug)]
+pub enum ManifestResult {
+    /// A new part was stored, but more are needed.
+    Accepted,
+    /// The part had already been received, or the manifest is already complete.
+    Duplicate,
+    /// The last missing part arrived.
+    Complete(Manifest),
+}
+
+/// Collects the parts of one transfer's manifest.
+#[derive(Debug, Default)]
+pub struct ManifestAssembler {
+    parts: Vec<Option<Vec<u8>>>,
+    complete: bool,
+}
+
+impl ManifestAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a manifest chunk. A part count that disagrees with the parts seen so far starts
+    /// over, as the manifest it belongs to must differ.
+    pub fn push(&mut self, chunk: Chunk) -> Result<ManifestResult, WireError> {
+        let header = &chunk.header;
+        if header.version != MANIFEST_VERSION || header.index >= header.total {
+            return Err(WireError::InvalidManifest);
+        }
+        if self.parts.len() != header.total as usize {
+            self.parts = alloc::vec![None; header.total as usize];
+            self.complete = false;
+        }
+        if self.complete || self.parts[header.index as usize].is_some() {
+            return Ok(ManifestResult::Duplicate);
+        }
+        self.parts[header.index as usize] = Some(chunk.data);
+        if self.parts.iter().any(Option::is_none) {
+            return Ok(ManifestResult::Accepted);
+        }
+
+        let bytes: Vec<u8> = self.parts.iter().flatten().flatten().copied().collect();
+        let manifest = Manifest::from_bytes(&bytes)?;
+        self.complete = true;
+        Ok(ManifestResult::Complete(manifest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let first = b"first file".repeat(10);
+        let manifest = Manifest::new(&[(&first, "a.txt"), (b"", "dir/b.bin")]);
+        assert_eq!(manifest.files[1].name, "dir_b.bin");
+        assert_eq!(manifest.files[0].size, 100);
+
+        // Parts arrive in any order, and repeat as the frames loop.
+        let chunks = manifest.to_chunks(0, 40).unwrap();
+        assert!(chunks.len() > 2);
+        let mut assembler = ManifestAssembler::new();
+        let mut result = None;
+        for chunk in chunks.iter().rev().chain(&chunks) {
+            let parsed = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+            assert!(parsed.is_manifest());
+            match assembler.push(parsed).unwrap() {
+                ManifestResult::Complete(manifest) => result = Some(manifest),
+                ManifestResult::Accepted => assert!(result.is_none()),
+                ManifestResult::Duplicate => assert!(result.is_some()),
+            }
+        }
+        assert_eq!(result.unwrap(), manifest);
+        assert_eq!(manifest.files[1].sha256[..4], [0xe3, 0xb0, 0xc4, 0x42]);
+
+        assert!(manifest.to_chunks(0, MANIFEST_HEADER_SIZE).is_none());
+        assert!(Manifest::from_bytes(&[2, 0]).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use miniz_oxide::inflate::{
+    self,
+    core::{inflate_flags, DecompressorOxide},
+    TINFLStatus,
+};
+use sha2::{Digest, Sha256};
+
+use crate::error::WireError;
+
+pub const CHECKSUM_SIZE: usize = 8;
+/// Longest filename embedded in a transfer, in bytes: the usual limit of file systems.
+pub const MAX_FILENAME_LEN: usize = 255;
+/// Longest extension kept whole when a filename is shortened, dot included.
+const MAX_EXTENSION_LEN: usize = 16;
+/// Name given to a file whose embedded name is empty or only dots.
+pub const FALLBACK_FILENAME: &str = "file";
+/// Flag of a zlib header telling that a preset dictionary, named by its ID, was used.
+pub const ZLIB_FDICT: u8 = 0x20;
+/// Size of the field naming a transfer's compression: `[Algorithm 4 bits | 0xF] [Level 1B]`.
+pub const COMPRESSION_FIELD_SIZE: usize = 2;
+/// Low nibble of the first byte of the compression field. It is the compression method zlib
+/// reserves, so the field never reads as the start of a zlib stream, which is what transfers
+/// made before the field start with.
+const COMPRESSION_MARKER: u8 = 0x0f;
+/// Size of the Adler-32 that ends stored data, as it ends a zlib stream.
+pub const STORED_TRAILER_SIZE: usize = 4;
+
+/// How the packed data of a transfer is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Stored as is, for data that does not shrink.
+    None,
+    /// A zlib stream, possibly with a preset dictionary.
+    Zlib,
+}
+
+impl Compression {
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zlib => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Zlib),
+            _ => None,
+        }
+    }
+
+    /// Field that starts a transfer compressed this way at `level`.
+    pub fn field(self, level: u8) -> [u8; COMPRESSION_FIELD_SIZE] {
+        [self.id() << 4 | COMPRESSION_MARKER, level]
+    }
+
+    /// Reads the compression field at the start of `data`, returning the compression and
+    /// the data after the field. Data without the field is a zlib stream.
+    pub fn parse(data: &[u8]) -> Result<(Self, &[u8]), WireError> {
+        match data.first() {
+            Some(&first) if first & 0x0f == COMPRESSION_MARKER => {
+                let compression = Self::from_id(first >> 4)
+                    .ok_or(WireError::UnsupportedCompression(first >> 4))?;
+                let rest = data
+                    .get(COMPRESSION_FIELD_SIZE..)
+                    .ok_or(WireError::Decompress)?;
+                Ok((compression, rest))
+            }
+            _ => Ok((Compression::Zlib, data)),
+        }
+    }
+}
+
+pub fn calculate_checksum(data: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    let result = hasher.finalize();
+    result[..CHECKSUM_SIZE].to_vec()
+}
+
+/// Turns `filename` into a single path component that is safe to create on the receiving
+/// side: path separators and control characters become `_`, and a name longer than
+/// [`MAX_FILENAME_LEN`] bytes is cut short, keeping its extension. Unicode is kept as is.
+pub fn clean_filename(filename: &str) -> String {
+    let name: String = filename
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if name.trim_matches('.').is_empty() {
+        return FALLBACK_FILENAME.to_string();
+    }
+    if name.len() <= MAX_FILENAME_LEN {
+        return name;
+    }
+
+    let extension = match name.rfind('.') {
+        Some(dot) if dot > 0 && name.len() - dot <= MAX_EXTENSION_LEN => &name[dot..],
+        _ => "",
+    };
+    let mut end = MAX_FILENAME_LEN - extension.len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut short = String::with_capacity(end + extension.len());
+    short.push_str(&name[..end]);
+    short.push_str(extension);
+    short
+}
+
+// Pack data: [Checksum 8B] [Filename] [\0] [Content]
+pub fn pack_data(data: &[u8], filename: &str) -> Vec<u8> {
+    let checksum = calculate_checksum(data);
+    let clean_filename = clean_filename(filename);
+
+    let mut packed = Vec::with_capacity(CHECKSUM_SIZE + clean_filename.len() + 1 + data.len());
+    packed.extend_from_slice(&checksum);
+    packed.extend_from_slice(clean_filename.as_bytes());
+    packed.push(0); // Null terminator
+    packed.extend_from_slice(data);
+    packed
+}
+
+// Unpack data: -> (Filename, Content)
+pub fn unpack_data(packed: &[u8]) -> Result<(String, Vec<u8>), WireError> {
+    if packed.len() < CHECKSUM_SIZE + 2 {
+        // Min: Checksum + 1 char + \0
+        return Err(WireError::PackedTooShort);
+    }
+
+    let expected_checksum = &packed[..CHECKSUM_SIZE];
+
+    let null_idx = packed[CHECKSUM_SIZE..]
+        .iter()
+        .position(|&b| b == 0)
+        .map(|pos| CHECKSUM_SIZE + pos)
+        .ok_or(WireError::MissingFilenameTerminator)?;
+
+    // Names from other encoders may be anything; never let one escape the output directory.
+    let filename_bytes = &packed[CHECKSUM_SIZE..null_idx];
+    let filename = clean_filename(&String::from_utf8_lossy(filename_bytes));
+
+    let content = packed[null_idx + 1..].to_vec();
+
+    let actual_checksum = calculate_checksum(&content);
+    if actual_checksum != expected_checksum {
+        return Err(WireError::ChecksumMismatch {
+            expected: expected_checksum.to_vec(),
+            actual: actual_checksum,
+        });
+    }
+
+    Ok((filename, content))
+}
+
+/// Decompresses the data that RaptorQ reassembles, as its compression field names it.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, WireError> {
+    decompress_with_dictionaries(data, &[])
+}
+
+/// Data stored without compression, once its trailing Adler-32 is checked.
+fn unwrap_stored(stored: &[u8]) -> Result<Vec<u8>, WireError> {
+    let split = stored
+        .len()
+        .checked_sub(STORED_TRAILER_SIZE)
+        .ok_or(WireError::Decompress)?;
+    let (data, adler) = stored.split_at(split);
+    if adler2::adler32_slice(data).to_be_bytes() != adler {
+        return Err(WireError::Decompress);
+    }
+    Ok(data.to_vec())
+}
+
+/// ID of a preset dictionary, as a zlib header names it: the Adler-32 of its bytes.
+pub fn dictionary_id(dictionary: &[u8]) -> u32 {
+    adler2::adler32_slice(dictionary)
+}
+
+/// Like [`decompress`], for a stream that may have been deflated with a preset dictionary
+/// shared out of band. The dictionary ID in its header picks one of `dictionaries`.
+pub fn decompress_with_dictionaries(
+    data: &[u8],
+    dictionaries: &[Vec<u8>],
+) -> Result<Vec<u8>, WireError> {
+    let data = match Compression::parse(data)? {
+        (Compression::None, stored) => return unwrap_stored(stored),
+        (Compression::Zlib, data) => data,
+    };
+    let flags = *data.get(1).ok_or(WireError::Decompress)?;
+    if flags & ZLIB_FDICT == 0 {
+        return miniz_oxide::inflate::decompress_to_vec_zlib(data)
+            .map_err(|_| WireError::Decompress);
+    }
+
+    // [CMF] [FLG] [Dictionary ID 4B] [Deflate stream] [Adler-32 4B]
+    if data.len() < 10 {
+        return Err(WireError::Decompress);
+    }
+    let id = u32::from_be_bytes([data[2], data[3], data[4], data[5]]);
+    let dictionary = dictionaries
+        .iter()
+        .find(|dictionary| dictionary_id(dictionary) == id)
+        .ok_or(WireError::MissingDictionary(id))?;
+    let (deflated, adler) = data[6..].split_at(data.len() - 10);
+    let inflated = inflate_with_dictionary(deflated, dictionary)?;
+    if adler2::adler32_slice(&inflated).to_be_bytes() != adler {
+        return Err(WireError::Decompress);
+    }
+    Ok(inflated)
+}
+
+/// Inflates a raw deflate stream whose matches may reach back into `dictionary`, placed
+/// before the output as if it had been inflated first.
+fn inflate_with_dictionary(mut input: &[u8], dictionary: &[u8]) -> Result<Vec<u8>, WireError> {
+    let flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+    let mut inflater = Box::<DecompressorOxide>::default();
+    let mut out = dictionary.to_vec();
+    let mut out_pos = out.len();
+    out.resize(out_pos + input.len().saturating_mul(2).max(64), 0);
+    loop {
+        let (status, consumed, written) =
+            inflate::core::decompress(&mut inflater, input, &mut out, out_pos, flags);
+        out_pos += written;
+        match status {
+            TINFLStatus::Done => {
+                out.truncate(out_pos);
+                out.drain(..dictionary.len());
+                return Ok(out);
+            }
+            TINFLStatus::HasMoreOutput => {
+                input = input.get(consumed..).ok_or(WireError::Decompress)?;
+                let len = out.len().saturating_mul(2);
+                out.resize(len, 0);
+            }
+            _ => return Err(WireError::Decompress),
+        }
+    }
+}
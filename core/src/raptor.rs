@@ -0,0 +1,554 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use raptorq::{
+    extended_source_block_symbols, Decoder, Encoder, EncodingPacket, ObjectTransmissionInformation,
+};
+
+use crate::chunk::{
+    Chunk, ChunkHeader, CHUNK_VERSION, MULTI_FILE_VERSION, STREAM_VERSION, TAGGED_VERSION,
+};
+use crate::error::WireError;
+use crate::packed::{decompress_with_dictionaries, unpack_data};
+
+/// RaptorQ parameters of the transfer a chunk belongs to.
+pub fn transmission_info(header: &ChunkHeader) -> ObjectTransmissionInformation {
+    ObjectTransmissionInformation::with_defaults(header.total as u64, header.packet_size)
+}
+
+/// Largest number of source symbols in one RaptorQ source block (K'max in RFC 6330).
+const MAX_BLOCK_SYMBOLS: u64 = 56_403;
+/// Encoding symbol IDs are 24-bit in RaptorQ, and the encoder panics on a larger one.
+const ESI_LIMIT: u32 = 1 << 24;
+
+/// Checks that a chunk's packet fits the RaptorQ parameters its header declares. Both come
+/// from a scanned image, so they can hold anything, while RaptorQ trusts them and panics on a
+/// packet that does not fit.
+pub fn check_packet(header: &ChunkHeader, data: &[u8]) -> Result<(), WireError> {
+    if header.total == 0 || header.packet_size == 0 {
+        return Err(WireError::InvalidPacket);
+    }
+    let config = transmission_info(header);
+    let symbols = (header.total as u64).div_ceil(config.symbol_size() as u64);
+    // The number of source blocks is kept in a byte: more than that wraps around.
+    if symbols > config.source_blocks() as u64 * MAX_BLOCK_SYMBOLS {
+        return Err(WireError::InvalidPacket);
+    }
+    match data {
+        [block, _, _, _, symbol @ ..]
+            if *block < config.source_blocks() && symbol.len() == config.symbol_size() as usize =>
+        {
+            Ok(())
+        }
+        _ => Err(WireError::InvalidPacket),
+    }
+}
+
+/// Number of source packets in a transfer, the minimum needed to decode it.
+pub fn source_packets(header: &ChunkHeader) -> usize {
+    let config = transmission_info(header);
+    (header.total as u64).div_ceil(config.symbol_size() as u64) as usize
+}
+
+/// Whether a chunk carries one of the source packets of its block rather than a repair packet.
+/// Source blocks are sized as RFC 6330 partitions them: the first ones one symbol longer.
+pub fn is_source_packet(chunk: &Chunk) -> bool {
+    let [block, a, b, c, ..] = chunk.data[..] else {
+        return false;
+    };
+    let blocks = transmission_info(&chunk.header).source_blocks().max(1) as u64;
+    let symbols = source_packets(&chunk.header) as u64;
+    let short = symbols / blocks;
+    let long_blocks = symbols - short * blocks;
+    let block_symbols = if u64::from(block) < long_blocks {
+        short + 1
+    } else {
+        short
+    };
+    u64::from(u32::from_be_bytes([0, a, b, c])) < block_symbols
+}
+
+/// Splits compressed data into chunks: all source packets, then `repair_packets` repair
+/// packets per source block.
+pub fn encode_chunks(compressed: &[u8], packet_size: u16, repair_packets: u32) -> Vec<Chunk> {
+    encode_file_chunks(compressed, packet_size, repair_packets, 0, 1)
+}
+
+/// Like [`encode_chunks`], for file `file_id` of a transfer of `file_count` files. Chunks of a
+/// multi-file transfer carry the version 2 header.
+pub fn encode_file_chunks(
+    compressed: &[u8],
+    packet_size: u16,
+    repair_packets: u32,
+    file_id: u8,
+    file_count: u8,
+) -> Vec<Chunk> {
+    encode_round_chunks(
+        compressed,
+        packet_size,
+        repair_packets,
+        file_id,
+        file_count,
+        0,
+    )
+}
+
+/// Like [`encode_file_chunks`], for round `round` of an endless carousel. Round 0 is the
+/// regular set of chunks; every later round has as many chunks per source block, all of them
+/// repair packets that no earlier round sent, so each round adds new information.
+///
+/// Once the repair symbol IDs (or the chunk indexes) run out, which takes days of showing a
+/// carousel, the rounds start over from round 1: its chunks come again with the same indexes,
+/// and the receiver counts them as duplicates.
+pub fn encode_round_chunks(
+    compressed: &[u8],
+    packet_size: u16,
+    repair_packets: u32,
+    file_id: u8,
+    file_count: u8,
+    round: u32,
+) -> Vec<Chunk> {
+    let version = if file_count > 1 {
+        MULTI_FILE_VERSION
+    } else {
+        CHUNK_VERSION
+    };
+    let header = ChunkHeader {
+        version,
+        file_id,
+        file_count,
+        segment: 0,
+        transfer: 0,
+        total: compressed.len() as u32,
+        index: 0,
+        packet_size,
+        sequence: None,
+    };
+    if round == 0 {
+        return encode_with_header(compressed, repair_packets, header);
+    }
+
+    let encoder = Encoder::with_defaults(compressed, packet_size);
+    let blocks = encoder.get_block_encoders();
+    let sources: Vec<u32> = blocks
+        .iter()
+        .map(|block| block.source_packets().len() as u32)
+        .collect();
+    let round_len: u32 = sources.iter().map(|source| source + repair_packets).sum();
+    let rounds = sources
+        .iter()
+        .map(|&source| {
+            let free = ESI_LIMIT
+                .saturating_sub(extended_source_block_symbols(source))
+                .saturating_sub(repair_packets);
+            free / (source + repair_packets)
+        })
+        .chain([(u32::MAX / round_len.max(1)).saturating_sub(1)])
+        .min()
+        .unwrap_or(1)
+        .max(1);
+    let round = (round - 1) % rounds + 1;
+
+    let packets: Vec<EncodingPacket> = blocks
+        .iter()
+        .zip(&sources)
+        .flat_map(|(block, &source)| {
+            let per_round = source + repair_packets;
+            block.repair_packets(repair_packets + (round - 1) * per_round, per_round)
+        })
+        .collect();
+    // Rounds have the same length, so packet indexes never repeat across rounds.
+    let first_index = round * packets.len() as u32;
+    chunks_with_header(packets, header, first_index)
+}
+
+/// Like [`encode_chunks`], for segment `segment` of an endless piped stream. Each segment is a
+/// separate RaptorQ object, and its chunks carry the version 3 header.
+pub fn encode_segment_chunks(
+    compressed: &[u8],
+    packet_size: u16,
+    repair_packets: u32,
+    segment: u32,
+) -> Vec<Chunk> {
+    let header = ChunkHeader {
+        version: STREAM_VERSION,
+        file_id: 0,
+        file_count: 1,
+        segment,
+        transfer: 0,
+        total: compressed.len() as u32,
+        index: 0,
+        packet_size,
+        sequence: None,
+    };
+    encode_with_header(compressed, repair_packets, header)
+}
+
+/// Like [`encode_file_chunks`], for transfer `transfer` of an output that holds several. The
+/// chunks carry the version 4 header, even for a single file.
+pub fn encode_tagged_chunks(
+    compressed: &[u8],
+    packet_size: u16,
+    repair_packets: u32,
+    transfer: u16,
+    file_id: u8,
+    file_count: u8,
+) -> Vec<Chunk> {
+    let header = ChunkHeader {
+        version: TAGGED_VERSION,
+        file_id,
+        file_count,
+        segment: 0,
+        transfer,
+        total: compressed.len() as u32,
+        index: 0,
+        packet_size,
+        sequence: None,
+    };
+    encode_with_header(compressed, repair_packets, header)
+}
+
+/// Encodes `compressed` into chunks that all share `header`, apart from the packet index.
+fn encode_with_header(compressed: &[u8], repair_packets: u32, header: ChunkHeader) -> Vec<Chunk> {
+    let packets =
+        Encoder::with_defaults(compressed, header.packet_size).get_encoded_packets(repair_packets);
+    chunks_with_header(packets, header, 0)
+}
+
+/// Wraps packets in chunks that share `header`, numbered from `first_index`.
+fn chunks_with_header(
+    packets: Vec<EncodingPacket>,
+    header: ChunkHeader,
+    first_index: u32,
+) -> Vec<Chunk> {
+    packets
+        .into_iter()
+        .enumerate()
+        .map(|(i, packet)| Chunk {
+            header: ChunkHeader {
+                index: first_index + i as u32,
+                ..header.clone()
+            },
+            data: packet.serialize(),
+        })
+        .collect()
+}
+
+/// Outcome of pushing one chunk to a [`ChunkAssembler`].
+#[derive(Debug)]
+pub enum AssembleResult {
+    /// A new packet was added, but more are needed.
+    Accepted,
+    /// The packet had already been received.
+    Duplicate,
+    /// Enough packets have arrived and the original file is reconstructed.
+    Complete { filename: String, data: Vec<u8> },
+}
+
+/// RaptorQ parameters a chunk declares: the transfer length and packet size.
+type Params = (u32, u16);
+
+fn params_of(header: &ChunkHeader) -> Params {
+    (header.total, header.packet_size)
+}
+
+/// Collects chunks of one transfer, in any order, until the file can be reconstructed.
+///
+/// The RaptorQ decoder is set up with the parameters of the first chunk. A misread QR code can
+/// still carry a valid-looking header with the wrong length or packet size, so every chunk's
+/// parameters are counted, and once another set is declared by more chunks than the current
+/// one, decoding starts over with it. Chunks that disagree are kept aside, not fed.
+#[derive(Default)]
+pub struct ChunkAssembler {
+    chunks: BTreeMap<(Params, u32), Chunk>,
+    votes: BTreeMap<Params, usize>,
+    params: Option<Params>,
+    decoder: Option<Decoder>,
+    dictionaries: Vec<Vec<u8>>,
+}
+
+impl ChunkAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like [`ChunkAssembler::new`], for a transfer that may have been compressed with one of
+    /// `dictionaries` (see [`decompress_with_dictionaries`]).
+    pub fn with_dictionaries(dictionaries: Vec<Vec<u8>>) -> Self {
+        Self {
+            dictionaries,
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, chunk: Chunk) -> Result<AssembleResult, WireError> {
+        check_packet(&chunk.header, &chunk.data)?;
+        let params = params_of(&chunk.header);
+        let key = (params, chunk.header.index);
+        if self.chunks.contains_key(&key) {
+            return Ok(AssembleResult::Duplicate);
+        }
+
+        let packet = EncodingPacket::deserialize(&chunk.data);
+        self.chunks.insert(key, chunk);
+        let votes = self.votes.entry(params).or_default();
+        *votes += 1;
+        let votes = *votes;
+
+        let current = *self.params.get_or_insert(params);
+        if params == current {
+            return self.decode(packet);
+        }
+        if votes <= self.votes[&current] {
+            return Ok(AssembleResult::Accepted);
+        }
+
+        // More chunks now contradict the parameters decoding started with than agree with
+        // them: start over with the parameters of the majority.
+        self.params = Some(params);
+        self.decoder = None;
+        let packets: Vec<EncodingPacket> = self
+            .chunks
+            .range((params, 0)..=(params, u32::MAX))
+            .map(|(_, chunk)| EncodingPacket::deserialize(&chunk.data))
+            .collect();
+        for packet in packets {
+            if let AssembleResult::Complete { filename, data } = self.decode(packet)? {
+                return Ok(AssembleResult::Complete { filename, data });
+            }
+        }
+        Ok(AssembleResult::Accepted)
+    }
+
+    /// Feeds a packet that agrees with the current parameters to the RaptorQ decoder.
+    fn decode(&mut self, packet: EncodingPacket) -> Result<AssembleResult, WireError> {
+        let Some((total, packet_size)) = self.params else {
+            return Ok(AssembleResult::Accepted);
+        };
+        let decoder = self.decoder.get_or_insert_with(|| {
+            Decoder::new(ObjectTransmissionInformation::with_defaults(
+                total as u64,
+                packet_size,
+            ))
+        });
+
+        match decoder.decode(packet) {
+            Some(mut result_data) => {
+                result_data.truncate(total as usize);
+                let (filename, data) = unpack_data(&decompress_with_dictionaries(
+                    &result_data,
+                    &self.dictionaries,
+                )?)?;
+                Ok(AssembleResult::Complete { filename, data })
+            }
+            None => Ok(AssembleResult::Accepted),
+        }
+    }
+
+    /// Number of unique packets received so far, including any whose parameters disagree with
+    /// the majority.
+    pub fn num_packets(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Packets whose parameters disagree with the ones decoding uses, most likely misread.
+    pub fn rejected_packets(&self) -> usize {
+        let used = self.params.map_or(0, |params| self.votes[&params]);
+        self.chunks.len() - used
+    }
+
+    /// Minimum number of packets needed to decode, unknown until the first packet arrives.
+    pub fn expected_packets(&self) -> Option<usize> {
+        let params = self.params?;
+        let (_, chunk) = self.chunks.range((params, 0)..=(params, u32::MAX)).next()?;
+        Some(source_packets(&chunk.header))
+    }
+
+    /// Unique packets received so far, grouped by their parameters and ordered by packet
+    /// index within each group.
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{
+        HEADER_SIZE, MULTI_FILE_HEADER_SIZE, SEQUENCE_FLAG, SEQUENCE_SIZE, STREAM_HEADER_SIZE,
+        TAGGED_HEADER_SIZE,
+    };
+    use crate::packed::pack_data;
+
+    #[test]
+    fn test_assemble_from_text() {
+        let content = b"Wire format test. ".repeat(30);
+        let compressed =
+            miniz_oxide::deflate::compress_to_vec_zlib(&pack_data(&content, "a.txt"), 9);
+        let chunks = encode_chunks(&compressed, 64, 4);
+
+        let mut assembler = ChunkAssembler::new();
+        let mut result = None;
+        for chunk in chunks.iter().rev() {
+            let parsed = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+            if let AssembleResult::Complete { filename, data } = assembler.push(parsed).unwrap() {
+                result = Some((filename, data));
+                break;
+            }
+        }
+
+        let (filename, data) = result.expect("reverse order should still decode");
+        assert_eq!(filename, "a.txt");
+        assert_eq!(data, content);
+        assert!(assembler.expected_packets().unwrap() <= assembler.num_packets());
+        assert!(matches!(
+            Chunk::from_bytes(&[6]),
+            Err(WireError::UnsupportedVersion(6))
+        ));
+    }
+
+    #[test]
+    fn test_multi_file_header_roundtrip() {
+        let chunk = &encode_file_chunks(&[7; 100], 32, 1, 2, 3)[0];
+        assert_eq!(chunk.header.size(), MULTI_FILE_HEADER_SIZE);
+
+        let parsed = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+        assert_eq!(parsed.header.version, MULTI_FILE_VERSION);
+        assert_eq!((parsed.header.file_id, parsed.header.file_count), (2, 3));
+        assert_eq!(parsed.header.total, 100);
+        assert_eq!(parsed.data, chunk.data);
+        assert!(matches!(
+            Chunk::from_bytes(&chunk.to_bytes().unwrap()[..HEADER_SIZE]),
+            Err(WireError::HeaderTooShort)
+        ));
+    }
+
+    #[test]
+    fn test_stream_header_roundtrip() {
+        let chunk = &encode_segment_chunks(&[7; 100], 32, 1, 70_000)[0];
+        assert_eq!(chunk.header.size(), STREAM_HEADER_SIZE);
+
+        let parsed = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+        assert_eq!(parsed.header.version, STREAM_VERSION);
+        assert_eq!(parsed.header.segment, 70_000);
+        assert_eq!(parsed.header.total, 100);
+        assert_eq!(parsed.data, chunk.data);
+    }
+
+    #[test]
+    fn test_tagged_header_roundtrip() {
+        let chunk = &encode_tagged_chunks(&[7; 100], 32, 1, 300, 1, 2)[0];
+        assert_eq!(chunk.header.size(), TAGGED_HEADER_SIZE);
+
+        let parsed = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+        assert_eq!(parsed.header.version, TAGGED_VERSION);
+        assert_eq!(parsed.header.transfer, 300);
+        assert_eq!((parsed.header.file_id, parsed.header.file_count), (1, 2));
+        assert_eq!(parsed.data, chunk.data);
+    }
+
+    #[test]
+    fn test_sequenced_header_roundtrip() {
+        let mut chunk = encode_tagged_chunks(&[7; 100], 32, 1, 300, 1, 2)[0].clone();
+        chunk.header.sequence = Some(123_456);
+        assert_eq!(chunk.header.size(), TAGGED_HEADER_SIZE + SEQUENCE_SIZE);
+
+        let bytes = chunk.to_bytes().unwrap();
+        assert_eq!(bytes[0], TAGGED_VERSION | SEQUENCE_FLAG);
+        let parsed = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+        assert_eq!(parsed.header.version, TAGGED_VERSION);
+        assert_eq!(parsed.header.sequence, Some(123_456));
+        assert_eq!(parsed.header.transfer, 300);
+        assert_eq!(parsed.data, chunk.data);
+        assert!(matches!(
+            Chunk::from_bytes(&bytes[..TAGGED_HEADER_SIZE + 2]),
+            Err(WireError::HeaderTooShort)
+        ));
+    }
+
+    #[test]
+    fn test_misread_first_chunk_is_outvoted() {
+        let content = b"Parameter cross-check test. ".repeat(30);
+        let compressed =
+            miniz_oxide::deflate::compress_to_vec_zlib(&pack_data(&content, "m.txt"), 9);
+        let chunks = encode_chunks(&compressed, 64, 4);
+
+        // A wrong packet size does not fit the packet, and is rejected outright.
+        let mut misread = chunks[3].clone();
+        misread.header.packet_size = 48;
+        let mut assembler = ChunkAssembler::new();
+        assert!(matches!(
+            assembler.push(misread.clone()),
+            Err(WireError::InvalidPacket)
+        ));
+
+        // A wrong length does fit, so the first chunk seen sets up decoding with it.
+        misread.header.packet_size = 64;
+        misread.header.total += 1000;
+        assert!(matches!(
+            assembler.push(misread).unwrap(),
+            AssembleResult::Accepted
+        ));
+        let mut result = None;
+        for chunk in chunks {
+            if let AssembleResult::Complete { data, .. } = assembler.push(chunk).unwrap() {
+                result = Some(data);
+                break;
+            }
+        }
+        assert_eq!(result.expect("the majority should win"), content);
+        assert_eq!(assembler.rejected_packets(), 1);
+        assert!(assembler.expected_packets().unwrap() < assembler.num_packets());
+    }
+
+    #[test]
+    fn test_later_rounds_carry_fresh_packets() {
+        let content = b"Endless carousel test. ".repeat(40);
+        let compressed =
+            miniz_oxide::deflate::compress_to_vec_zlib(&pack_data(&content, "e.txt"), 9);
+        let first = encode_round_chunks(&compressed, 64, 2, 0, 1, 0);
+        let second = encode_round_chunks(&compressed, 64, 2, 0, 1, 1);
+        assert_eq!(first.len(), encode_chunks(&compressed, 64, 2).len());
+        assert_eq!(second.len(), first.len());
+        assert!(second
+            .iter()
+            .all(|c| first.iter().all(|f| f.data != c.data)));
+        let sources = first.iter().filter(|c| is_source_packet(c)).count();
+        assert_eq!(sources, source_packets(&first[0].header));
+        assert!(first[..sources].iter().all(is_source_packet));
+        assert!(!second.iter().any(is_source_packet));
+
+        // A receiver that missed all of round 0 still decodes from the fresh packets.
+        let mut assembler = ChunkAssembler::new();
+        let mut result = None;
+        for chunk in second
+            .into_iter()
+            .chain(encode_round_chunks(&compressed, 64, 2, 0, 1, 2))
+        {
+            if let AssembleResult::Complete { data, .. } = assembler.push(chunk).unwrap() {
+                result = Some(data);
+                break;
+            }
+        }
+        assert_eq!(result.expect("fresh rounds should decode"), content);
+    }
+
+    #[test]
+    fn test_rounds_wrap_within_the_symbol_ids() {
+        let content = b"Days of carousel. ".repeat(40);
+        let compressed =
+            miniz_oxide::deflate::compress_to_vec_zlib(&pack_data(&content, "d.txt"), 9);
+        let mut assembler = ChunkAssembler::new();
+        let mut result = None;
+        for chunk in encode_round_chunks(&compressed, 64, 2, 0, 1, u32::MAX)
+            .into_iter()
+            .chain(encode_round_chunks(&compressed, 64, 2, 0, 1, u32::MAX - 1))
+        {
+            if let AssembleResult::Complete { data, .. } = assembler.push(chunk).unwrap() {
+                result = Some(data);
+                break;
+            }
+        }
+        assert_eq!(result.expect("late rounds should decode"), content);
+    }
+}
@@ -0,0 +1,20 @@
+//! A sequence of chunks fed to one assembler, as from the frames of a GIF. Each chunk is
+//! prefixed with its length in one byte.
+
+#![no_main]
+
+use fountain_core::{Chunk, ChunkAssembler};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut assembler = ChunkAssembler::new();
+    let mut rest = data;
+    while let Some((&len, tail)) = rest.split_first() {
+        let (bytes, tail) = tail.split_at((len as usize).min(tail.len()));
+        rest = tail;
+        if let Ok(chunk) = Chunk::from_bytes(bytes) {
+            let _ = assembler.push(chunk);
+        }
+    }
+    assert!(assembler.num_packets() >= assembler.rejected_packets());
+});
@@ -0,0 +1,14 @@
+//! Chunk header parsing, on the bytes that base45 decoding of a QR code yields.
+
+#![no_main]
+
+use fountain_core::{Chunk, ChunkAssembler};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(chunk) = Chunk::from_bytes(data) else {
+        return;
+    };
+    assert_eq!(chunk.to_bytes().unwrap(), data);
+    let _ = ChunkAssembler::new().push(chunk);
+});
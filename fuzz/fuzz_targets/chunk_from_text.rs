@@ -0,0 +1,13 @@
+//! Base45 text as read from a QR code.
+
+#![no_main]
+
+use fountain_core::Chunk;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    if let Ok(chunk) = Chunk::from_text(text) {
+        let again = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+        assert_eq!(again.data, chunk.data);
+    }
+});
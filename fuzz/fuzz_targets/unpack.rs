@@ -0,0 +1,13 @@
+//! Decompression and unpacking of the data RaptorQ reassembles.
+
+#![no_main]
+
+use fountain_core::{decompress, unpack_data};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = unpack_data(data);
+    if let Ok(inflated) = decompress(data) {
+        let _ = unpack_data(&inflated);
+    }
+});
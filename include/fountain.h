@@ -0,0 +1,140 @@
+#ifndef FOUNTAIN_H
+#define FOUNTAIN_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit; run ./script/rust/gen-header.sh. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Outcome of feeding one frame to a `FountainDecoder`.
+typedef enum FountainFeedStatus {
+  FOUNTAIN_FEED_STATUS_ERROR = -1,
+  FOUNTAIN_FEED_STATUS_NO_CHUNK = 0,
+  FOUNTAIN_FEED_STATUS_ACCEPTED = 1,
+  FOUNTAIN_FEED_STATUS_DUPLICATE = 2,
+  FOUNTAIN_FEED_STATUS_COMPLETE = 3,
+} FountainFeedStatus;
+
+// Streaming decoder that accumulates packets from camera or screen frames.
+typedef struct FountainDecoder FountainDecoder;
+
+// QR code frames produced by `fountain_encode`, each stored as PNG bytes.
+typedef struct FountainFrames FountainFrames;
+
+// Message of the last error raised on this thread, or NULL if there was none.
+// The string stays valid until the next failing call on the same thread.
+const char *fountain_last_error(void);
+
+// Encodes `len` bytes at `data` into QR code frames.
+//
+// `chunk_size` of 0 picks the largest payload that fits. Returns NULL on failure.
+// Free the result with `fountain_frames_free`.
+//
+// # Safety
+// `data` must point to `len` readable bytes and `filename` to a NUL-terminated UTF-8 string.
+struct FountainFrames *fountain_encode(const uint8_t *data,
+                                       size_t len,
+                                       const char *filename,
+                                       size_t chunk_size,
+                                       uint32_t pixel_scale);
+
+// Number of frames in `frames`.
+//
+// # Safety
+// `frames` must be a pointer returned by `fountain_encode` and not yet freed.
+size_t fountain_frames_count(const struct FountainFrames *frames);
+
+// Payload size (bytes) per QR code that the encoder settled on.
+//
+// # Safety
+// `frames` must be a pointer returned by `fountain_encode` and not yet freed.
+size_t fountain_frames_payload_size(const struct FountainFrames *frames);
+
+// PNG bytes of frame `index`, with the length written to `out_len`. Returns NULL if `index`
+// is out of range. The data is owned by `frames` and valid until it is freed.
+//
+// # Safety
+// `frames` must be a pointer returned by `fountain_encode` and not yet freed, and `out_len`
+// must be writable.
+const uint8_t *fountain_frames_png(const struct FountainFrames *frames,
+                                   size_t index,
+                                   size_t *out_len);
+
+// Releases frames returned by `fountain_encode`. Passing NULL is a no-op.
+//
+// # Safety
+// `frames` must be NULL or a pointer returned by `fountain_encode` that was not freed yet.
+void fountain_frames_free(struct FountainFrames *frames);
+
+// Creates a decoder. Free it with `fountain_decoder_free`.
+struct FountainDecoder *fountain_decoder_new(void);
+
+// Feeds one RGBA frame of `width` x `height` pixels (`width * height * 4` bytes).
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new` and `rgba` must point to
+// `width * height * 4` readable bytes.
+enum FountainFeedStatus fountain_decoder_feed_rgba(struct FountainDecoder *decoder,
+                                                   const uint8_t *rgba,
+                                                   uint32_t width,
+                                                   uint32_t height);
+
+// Unique packets received so far.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new`.
+size_t fountain_decoder_packets_received(const struct FountainDecoder *decoder);
+
+// Minimum number of packets needed to decode, or 0 until the first packet has arrived.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new`.
+size_t fountain_decoder_packets_expected(const struct FountainDecoder *decoder);
+
+// Original filename of the decoded file, or NULL until decoding is complete. In a multi-file
+// transfer this is the first file. The string is owned by `decoder`.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new`.
+const char *fountain_decoder_filename(const struct FountainDecoder *decoder);
+
+// Contents of the decoded file, with the length written to `out_len`, or NULL until decoding
+// is complete. In a multi-file transfer this is the first file. The data is owned by `decoder`.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new` and `out_len` must be writable.
+const uint8_t *fountain_decoder_data(const struct FountainDecoder *decoder, size_t *out_len);
+
+// Number of files decoded so far. A multi-file transfer reports each file as it completes,
+// while `fountain_decoder_feed_rgba` returns `FOUNTAIN_FEED_STATUS_ACCEPTED` until the last.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new`.
+size_t fountain_decoder_file_count(const struct FountainDecoder *decoder);
+
+// Original filename of decoded file `index`, or NULL if `index` is out of range.
+// The string is owned by `decoder`.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new`.
+const char *fountain_decoder_file_name_at(const struct FountainDecoder *decoder, size_t index);
+
+// Contents of decoded file `index`, with the length written to `out_len`, or NULL if `index`
+// is out of range. The data is owned by `decoder`.
+//
+// # Safety
+// `decoder` must be a live pointer from `fountain_decoder_new` and `out_len` must be writable.
+const uint8_t *fountain_decoder_file_data_at(const struct FountainDecoder *decoder,
+                                             size_t index,
+                                             size_t *out_len);
+
+// Releases a decoder. Passing NULL is a no-op.
+//
+// # Safety
+// `decoder` must be NULL or a pointer from `fountain_decoder_new` that was not freed yet.
+void fountain_decoder_free(struct FountainDecoder *decoder);
+
+#endif  /* FOUNTAIN_H */
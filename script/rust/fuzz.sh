@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+# Runs one fuzz target (default: all, one after another) for a while.
+# Needs a nightly toolchain and `cargo install cargo-fuzz`.
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")/../.."
+
+SECONDS_PER_TARGET="${FUZZ_SECONDS:-60}"
+TARGETS="${1:-chunk_from_bytes chunk_from_text unpack assemble}"
+
+for target in ${TARGETS}; do
+    cargo +nightly fuzz run "${target}" -- -max_total_time="${SECONDS_PER_TARGET}"
+done
@@ -0,0 +1,15 @@
+#!/usr/bin/env bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")/../.."
+
+case "$(uname)" in
+    Darwin) library=target/release/libfountain.dylib ;;
+    *) library=target/release/libfountain.so ;;
+esac
+
+cargo build --release --lib --features uniffi
+for language in kotlin swift; do
+    cargo run --quiet --features uniffi --bin uniffi-bindgen -- generate \
+        --library "$library" --language "$language" --out-dir "bindings/$language"
+done
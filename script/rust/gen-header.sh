@@ -0,0 +1,6 @@
+#!/usr/bin/env bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")/../.."
+
+cbindgen --quiet --config cbindgen.toml --crate fountain --output include/fountain.h
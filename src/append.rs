@@ -0,0 +1,231 @@
+//! Appending files to an output written earlier, as a new transfer.
+//!
+//! The existing GIF or image directory is scanned once to learn its payload size, QR version,
+//! module size and the transfers it already holds. The new files are then encoded with the
+//! version 4 header, which names their transfer, at the same payload size and QR version, so
+//! every frame of the output keeps the same size. Decode all transfers with
+//! [`crate::DecodeOptions::all_transfers`].
+
+use image::codecs::gif::{GifDecoder, GifEncoder};
+use image::{AnimationDecoder, Delay, DynamicImage, Frame};
+use qrcode::Version;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Instant;
+use tracing::info;
+
+use crate::chunk::Chunk;
+use crate::chunker::{
+    build_tagged_chunks, IMG_MIN_PAYLOAD_SIZE, IMG_REDUCTION_STEP, IMG_REDUNDANCY_FACTOR,
+};
+use crate::encode::{compress_files, image_stem, read_inputs, EncodeResult, Encoder, QrFrames};
+use crate::error::{FountainError, Result};
+use crate::qr::{decode_qr_from_dynamic_image, generate_qr_image};
+use crate::sink::{GifSink, ImageDirSink};
+use crate::source::{open_source, InputFrame};
+use crate::stream::decode_qr_bytes_to_chunk;
+
+/// ID that RaptorQ puts in front of each packet's symbol.
+const PACKET_ID_SIZE: usize = 4;
+
+/// What an existing output tells about how it was encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ExistingOutput {
+    /// Largest payload size for the new chunks. The symbol of a packet is rounded down to a
+    /// multiple of 8 and sent with its ID, so leaving room for the ID keeps every new frame
+    /// within the bytes of the existing ones.
+    payload_size: usize,
+    version: Version,
+    pixel_scale: u32,
+    /// Transfer ID for the files appended next.
+    next_transfer: u16,
+}
+
+impl ExistingOutput {
+    /// Scans every frame of an output whose codes have `margin` modules around them on each
+    /// side: the quiet zone, and any corner markers.
+    /// The first packet found gives the payload size and QR version, and the width of its frame
+    /// the module size.
+    fn scan(frames: impl Iterator<Item = DynamicImage>, margin: u32) -> Result<Self> {
+        let mut first: Option<(Chunk, u32)> = None;
+        let mut last_transfer = 0;
+        for img in frames {
+            let Some(chunk) = decode_qr_from_dynamic_image(&img)
+                .ok()
+                .and_then(|qr_bytes| decode_qr_bytes_to_chunk(&qr_bytes))
+            else {
+                continue;
+            };
+            if chunk.header.version == fountain_core::STREAM_VERSION {
+                return Err(FountainError::PipedStream);
+            }
+            last_transfer = last_transfer.max(chunk.header.transfer);
+            // A manifest part may be short; only a packet tells the payload size.
+            if !chunk.is_manifest() {
+                first.get_or_insert((chunk, img.width()));
+            }
+        }
+        let Some((chunk, width)) = first else {
+            return Err(FountainError::NoChunksFound);
+        };
+
+        let (_, version) = generate_qr_image(chunk.to_text()?.as_bytes(), None, 1)?;
+        let Version::Normal(n) = version else {
+            return Err(FountainError::Qr(format!(
+                "Unexpected QR version {:?}",
+                version
+            )));
+        };
+        let modules = 17 + 4 * n as u32 + 2 * margin;
+        Ok(Self {
+            payload_size: chunk.header.size() + chunk.data.len() - PACKET_ID_SIZE,
+            version,
+            pixel_scale: (width / modules).max(1),
+            next_transfer: last_transfer.saturating_add(1),
+        })
+    }
+}
+
+impl Encoder {
+    /// Appends the files to a GIF written earlier, as a new transfer after the ones it already
+    /// holds. The frames keep the payload size, QR version and module size of the GIF, and the
+    /// new frames are shown as long as its first frame. The GIF is rewritten in place.
+    pub fn append_to_gif(&self, input_paths: &[&Path], gif: &Path) -> Result<EncodeResult> {
+        let started = Instant::now();
+        if !gif.exists() {
+            return Err(FountainError::InputNotFound(gif.to_path_buf()));
+        }
+        let old_frames = GifDecoder::new(BufReader::new(File::open(gif)?))?
+            .into_frames()
+            .collect_frames()?;
+        let existing = ExistingOutput::scan(
+            old_frames
+                .iter()
+                .map(|frame| DynamicImage::ImageRgba8(frame.buffer().clone())),
+            self.options().frame_margin(),
+        )?;
+        let delay = old_frames
+            .first()
+            .map_or(Delay::from_numer_denom_ms(0, 1), Frame::delay);
+
+        let mut frames = self.appended_frames(input_paths, &existing)?;
+        let partial = gif.with_extension("gif.partial");
+        {
+            let mut encoder = GifEncoder::new(File::create(&partial)?);
+            encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+            encoder.encode_frames(old_frames)?;
+            let mut sink = GifSink::with_encoder(encoder, delay)
+                .strict_palette(self.options().strict_palette)
+                .repeat_frames(self.options().repeat_frames);
+            self.write_to_sink(&mut frames, &mut sink)?;
+        }
+        fs::rename(&partial, gif)?;
+
+        Ok(frames.result(vec![gif.to_string_lossy().to_string()], started))
+    }
+
+    /// Like [`Encoder::append_to_gif`], for an image directory written earlier. The new images
+    /// are named `<file>_t<transfer>_NNNN.png`; existing images are left alone.
+    pub fn append_to_images(
+        &self,
+        input_paths: &[&Path],
+        output_dir: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut source = open_source(output_dir)?;
+        // Packet files tell nothing about the frames, so only images are scanned.
+        let existing = ExistingOutput::scan(
+            source.frames().filter_map(|frame| match frame.frame {
+                Ok(InputFrame::Image(img)) => Some(img),
+                _ => None,
+            }),
+            self.options().frame_margin(),
+        )?;
+
+        let mut frames = self.appended_frames(input_paths, &existing)?;
+        let stem = format!(
+            "{}_t{}",
+            image_stem(frames.filename()),
+            existing.next_transfer
+        );
+        let mut sink = ImageDirSink::new(output_dir).with_stem(stem);
+        let output_files = self.write_to_sink(&mut frames, &mut sink)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    fn appended_frames(
+        &self,
+        input_paths: &[&Path],
+        existing: &ExistingOutput,
+    ) -> Result<QrFrames> {
+        let files = read_inputs(input_paths, self.options().max_memory)?;
+        let borrowed: Vec<(&[u8], &str)> = files
+            .iter()
+            .map(|(data, filename)| (data.as_slice(), filename.as_str()))
+            .collect();
+        let compressed = compress_files(&borrowed, self.options())?;
+        self.options().check_memory(
+            files.iter().map(|(data, _)| data.len()).sum(),
+            &compressed,
+            IMG_REDUNDANCY_FACTOR,
+        )?;
+
+        // How densely a QR version holds a chunk depends on its text, so shrink the payload
+        // until every new chunk fits the version of the existing frames.
+        let mut payload_size = existing.payload_size;
+        let chunks = loop {
+            let chunks = build_tagged_chunks(
+                &compressed,
+                payload_size,
+                IMG_REDUNDANCY_FACTOR,
+                existing.next_transfer,
+            );
+            if fit_version(&chunks, existing.version)? {
+                break chunks;
+            }
+            if payload_size <= IMG_MIN_PAYLOAD_SIZE {
+                return Err(FountainError::QrCapacityExceeded {
+                    min_size: IMG_MIN_PAYLOAD_SIZE,
+                });
+            }
+            payload_size = payload_size
+                .saturating_sub(IMG_REDUCTION_STEP)
+                .max(IMG_MIN_PAYLOAD_SIZE);
+        };
+        info!(
+            "Appending as transfer {} ({} bytes per QR code, version {:?})",
+            existing.next_transfer, payload_size, existing.version
+        );
+
+        Ok(QrFrames {
+            total: chunks.len(),
+            parity: 0,
+            chunks: chunks.into_iter(),
+            effective_size: payload_size,
+            filename: files[0].1.clone(),
+            pixel_scale: existing.pixel_scale,
+            quiet_zone: self.options().quiet_zone,
+            fixed_version: Some(existing.version),
+            total_bytes: files.iter().map(|(data, _)| data.len()).sum(),
+            compressed_bytes: compressed.iter().map(Vec::len).sum(),
+            cover: None,
+            receipt: None,
+            caption: self.options().caption.clone(),
+            markers: self.options().markers,
+            #[cfg(feature = "color")]
+            color_matrix: None,
+            canvas: self.options().canvas,
+        })
+    }
+}
+
+/// Whether every chunk renders as a QR code of `version`.
+fn fit_version(chunks: &[Chunk], version: Version) -> Result<bool> {
+    for chunk in chunks {
+        if generate_qr_image(chunk.to_text()?.as_bytes(), Some(version), 1).is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,130 @@
+//! Directory inputs travel as an uncompressed tar archive named `<dir>.tar`. The transfer
+//! compresses the packed data anyway, so gzip or zstd on top would only cost time.
+
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+
+use crate::chunk::{clean_filename, embedded_filename};
+use crate::error::{FountainError, Result};
+
+/// Extension of the archive that a directory is sent as.
+pub const ARCHIVE_EXTENSION: &str = "tar";
+
+/// Packs `dir` into an in-memory tar archive whose entries sit under the directory's own name.
+/// Returns the archive together with the filename to embed in the transfer.
+pub fn archive_directory(dir: &Path) -> Result<(Vec<u8>, String)> {
+    let canonical = dir.canonicalize()?;
+    let name = canonical
+        .file_name()
+        .map(embedded_filename)
+        .ok_or_else(|| FountainError::InvalidFilename(dir.to_path_buf()))?;
+    // A long name is shortened with the extension on, so the entries sit under the directory
+    // that extraction will look for.
+    let archive_name = clean_filename(&format!("{}.{}", name, ARCHIVE_EXTENSION));
+    let name = archive_name
+        .strip_suffix(&format!(".{}", ARCHIVE_EXTENSION))
+        .unwrap_or(&name);
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    builder.append_dir_all(name, dir)?;
+    let archive = builder.into_inner()?;
+    Ok((archive, archive_name))
+}
+
+/// Whether `path` names an archive produced by [`archive_directory`].
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case(ARCHIVE_EXTENSION))
+        .unwrap_or(false)
+}
+
+/// Unpacks the archive at `path` next to it and deletes the archive. Returns the directory it
+/// held. Fails if that directory already exists, unless `overwrite` is set.
+///
+/// The archive comes from whoever sent the transfer, so every entry must be a file or
+/// directory under that one directory: anything else, such as a file next to it or a link,
+/// fails with [`FountainError::InvalidFilename`] before a single entry is written.
+pub fn extract_archive(path: &Path, overwrite: bool) -> Result<PathBuf> {
+    let dest = path.parent().unwrap_or(Path::new("."));
+    let stem = path
+        .file_stem()
+        .ok_or_else(|| FountainError::InvalidFilename(path.to_path_buf()))?;
+    let extracted = dest.join(stem);
+    if extracted.exists() && !overwrite {
+        return Err(FountainError::OutputExists(extracted));
+    }
+
+    for entry in tar::Archive::new(File::open(path)?).entries()? {
+        let entry = entry?;
+        let entry_path = entry.path()?;
+        let mut components = entry_path.components();
+        let inside = components.next() == Some(Component::Normal(stem))
+            && components.all(|component| matches!(component, Component::Normal(_)));
+        let kind = entry.header().entry_type();
+        if !inside || !(kind.is_file() || kind.is_dir()) {
+            return Err(FountainError::InvalidFilename(entry_path.into_owned()));
+        }
+    }
+    for entry in tar::Archive::new(File::open(path)?).entries()? {
+        entry?.unpack_in(dest)?;
+    }
+    fs::remove_file(path)?;
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("photos");
+        fs::create_dir_all(src.join("2024")).unwrap();
+        fs::write(src.join("a.txt"), b"top level").unwrap();
+        fs::write(src.join("2024").join("b.txt"), b"nested").unwrap();
+
+        let (archive, filename) = archive_directory(&src).unwrap();
+        assert_eq!(filename, "photos.tar");
+
+        let out = tempdir().unwrap();
+        let tar_path = out.path().join(&filename);
+        fs::write(&tar_path, archive).unwrap();
+        assert!(is_archive(&tar_path));
+
+        let extracted = extract_archive(&tar_path, false).unwrap();
+        assert_eq!(extracted, out.path().join("photos"));
+        assert_eq!(fs::read(extracted.join("a.txt")).unwrap(), b"top level");
+        assert_eq!(fs::read(extracted.join("2024/b.txt")).unwrap(), b"nested");
+        assert!(!tar_path.exists());
+
+        fs::write(&tar_path, archive_directory(&src).unwrap().0).unwrap();
+        let err = extract_archive(&tar_path, false).expect_err("Existing directory");
+        assert!(matches!(err, FountainError::OutputExists(_)));
+        extract_archive(&tar_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_extract_rejects_entries_outside_the_directory() {
+        let out = tempdir().unwrap();
+        fs::write(out.path().join("notes.txt"), b"keep me").unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [("photos/a.txt", &b"inside"[..]), ("notes.txt", b"replaced")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        let tar_path = out.path().join("photos.tar");
+        fs::write(&tar_path, builder.into_inner().unwrap()).unwrap();
+
+        let err = extract_archive(&tar_path, true).expect_err("Entry outside the directory");
+        assert!(matches!(err, FountainError::InvalidFilename(p) if p == Path::new("notes.txt")));
+        assert_eq!(fs::read(out.path().join("notes.txt")).unwrap(), b"keep me");
+        assert!(!out.path().join("photos").exists());
+    }
+}
@@ -0,0 +1,402 @@
+//! Tokio variants of the file-based encode and decode entry points.
+//!
+//! File and network I/O is awaited; rendering and scanning QR codes still runs on the calling
+//! task, one frame at a time. To decode a download, pass the response body to
+//! [`Decoder::decode_gif_reader_async`] as an [`AsyncRead`].
+
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::io::{Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, info};
+
+use crate::chunk::Chunk;
+use crate::cover::cover_file_name;
+use crate::decode::{
+    is_packet_file, is_packet_lines, is_qr_image, output_paths_for, write_files, DecodeResult,
+    Decoder, Scan, ScanStats,
+};
+use crate::encode::{
+    frame_file_name, image_stem, input_filename, EncodeResult, Encoder, QrFrames, RECEIPT_EXTENSION,
+};
+use crate::error::{FountainError, Result};
+use crate::progress::Progress;
+use crate::qr::image_from_bytes;
+use crate::source::{FrameSource, GifSource, InputFrame, PacketLinesSource, SourceFrame};
+use crate::stream::DecodedFile;
+
+async fn read_file(path: &Path) -> Result<Vec<u8>> {
+    match fs::read(path).await {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Err(FountainError::InputNotFound(path.to_path_buf()))
+        }
+        result => Ok(result?),
+    }
+}
+
+impl Encoder {
+    /// Async version of [`Encoder::encode_to_images`].
+    pub async fn encode_to_images_async(
+        &self,
+        input_path: &Path,
+        output_dir: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let data = read_file(input_path).await?;
+        fs::create_dir_all(output_dir).await?;
+
+        let mut frames = self.frames_from_bytes(&data, &input_filename(input_path)?)?;
+        let stem = image_stem(frames.filename());
+        let total = frames.total();
+        let regular = total - frames.parity();
+
+        let mut output_files = Vec::with_capacity(total + 1);
+        if let Some(cover) = frames.cover_image()? {
+            let cover_filename = cover_file_name(&stem);
+            fs::write(output_dir.join(&cover_filename), png_bytes(&cover)?).await?;
+            output_files.push(cover_filename);
+        }
+        let progress = Progress::new(Some(total as u64), "QR codes");
+
+        for (i, qr_image) in frames.by_ref().enumerate() {
+            self.options().check_cancelled()?;
+            let png = png_bytes(&qr_image?)?;
+
+            let output_filename = frame_file_name(&stem, i, regular);
+            fs::write(output_dir.join(&output_filename), png).await?;
+
+            self.image_written(&progress, i, total, &output_filename);
+            output_files.push(output_filename);
+        }
+        drop(progress);
+
+        let receipt = format!("{}.{}", stem, RECEIPT_EXTENSION);
+        if write_receipt(&frames, &output_dir.join(&receipt)).await? {
+            output_files.push(receipt);
+        }
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Async version of [`Encoder::encode_to_gif`]. The GIF is built in memory and written
+    /// once complete.
+    pub async fn encode_to_gif_async(
+        &self,
+        input_path: &Path,
+        output_gif: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let data = read_file(input_path).await?;
+        let mut frames = self.frames_from_bytes(&data, &input_filename(input_path)?)?;
+
+        let mut gif = Vec::new();
+        self.write_gif(&mut frames, &mut gif)?;
+
+        if let Some(parent) = output_gif.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(output_gif, gif).await?;
+
+        let mut output_files = vec![output_gif.to_string_lossy().to_string()];
+        let receipt = output_gif.with_extension(RECEIPT_EXTENSION);
+        if write_receipt(&frames, &receipt).await? {
+            output_files.push(receipt.to_string_lossy().to_string());
+        }
+        Ok(frames.result(output_files, started))
+    }
+}
+
+impl Decoder {
+    /// Async version of [`Decoder::decode`], taking the same inputs.
+    pub async fn decode_async(&self, input: &Path) -> Result<DecodeResult> {
+        let metadata = match fs::metadata(input).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(FountainError::InputNotFound(input.to_path_buf()))
+            }
+            result => result?,
+        };
+
+        if metadata.is_dir() {
+            info!("Decoding QR codes from directory: {}", input.display());
+            return self.decode_images_async(input).await;
+        }
+
+        let is_gif = input
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("gif"))
+            .unwrap_or(false);
+        if is_gif {
+            self.decode_gif_async(input).await
+        } else if is_packet_lines(input) {
+            self.decode_packet_lines_async(input).await
+        } else {
+            Err(FountainError::UnsupportedInput(input.to_path_buf()))
+        }
+    }
+
+    /// Async version of [`Decoder::decode_packet_lines`].
+    pub async fn decode_packet_lines_async(&self, input_file: &Path) -> Result<DecodeResult> {
+        let text = String::from_utf8(read_file(input_file).await?)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+        info!("Decoding packets from text file: {}", input_file.display());
+        let (files, stats) = {
+            let mut source = PacketLinesSource::from_text(&text);
+            Scan::new(source.len_hint(), self.options()).run(&mut source)?
+        };
+        self.write_decoded(files, Path::new("."), stats).await
+    }
+
+    /// Async version of [`Decoder::decode_gif`].
+    pub async fn decode_gif_async(&self, input_file: &Path) -> Result<DecodeResult> {
+        let gif = read_file(input_file).await?;
+        info!("Decoding QR codes from GIF: {}", input_file.display());
+        self.decode_gif_bytes(gif).await
+    }
+
+    /// Decodes a GIF read to the end from `reader`, such as an HTTP response body or a socket.
+    /// Without an explicit output path the file is written to the current directory.
+    pub async fn decode_gif_reader_async<R: AsyncRead + Unpin>(
+        &self,
+        mut reader: R,
+    ) -> Result<DecodeResult> {
+        let mut gif = Vec::new();
+        reader.read_to_end(&mut gif).await?;
+        self.decode_gif_bytes(gif).await
+    }
+
+    async fn decode_gif_bytes(&self, gif: Vec<u8>) -> Result<DecodeResult> {
+        let (files, stats) = {
+            let mut source = GifSource::from_reader(Cursor::new(gif))?;
+            Scan::new(source.len_hint(), self.options()).run(&mut source)?
+        };
+        self.write_decoded(files, Path::new("."), stats).await
+    }
+
+    /// Async version of [`Decoder::decode_images`], which also reads `.pkt` packet files.
+    pub async fn decode_images_async(&self, input_dir: &Path) -> Result<DecodeResult> {
+        let mut entries = match fs::read_dir(input_dir).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(FountainError::InputNotFound(input_dir.to_path_buf()))
+            }
+            result => result?,
+        };
+        let mut frame_files: Vec<PathBuf> = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if is_qr_image(&path) || is_packet_file(&path) {
+                frame_files.push(path);
+            }
+        }
+
+        if frame_files.is_empty() {
+            return Err(FountainError::NoImagesFound(input_dir.to_path_buf()));
+        }
+        frame_files.sort();
+
+        let packets = frame_files
+            .iter()
+            .filter(|path| is_packet_file(path))
+            .count();
+        if packets < frame_files.len() {
+            info!("Found {} QR code image(s)", frame_files.len() - packets);
+        }
+        if packets > 0 {
+            info!("Found {} packet file(s)", packets);
+        }
+
+        let mut scan = Scan::new(Some(frame_files.len() as u64), self.options());
+        let mut files = scan.resume()?;
+        if files.is_none() {
+            for path in frame_files {
+                if scan.should_stop() {
+                    break;
+                }
+                let label = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                let frame = if is_packet_file(&path) {
+                    load_packet(&path).await
+                } else {
+                    load_image(&path).await.map(InputFrame::Image)
+                };
+                files = scan.feed_frame(
+                    SourceFrame {
+                        frame,
+                        label,
+                        timestamp: None,
+                    },
+                    None,
+                )?;
+                if files.is_some() {
+                    break;
+                }
+            }
+        }
+        let (files, stats) = scan.conclude(files)?;
+
+        self.write_decoded(files, input_dir.parent().unwrap_or(Path::new(".")), stats)
+            .await
+    }
+
+    async fn write_decoded(
+        &self,
+        files: Vec<DecodedFile>,
+        default_dir: &Path,
+        stats: ScanStats,
+    ) -> Result<DecodeResult> {
+        let output_paths = if self.options().check_only {
+            // Nothing is written, so there is no I/O to await.
+            write_files(&files, default_dir, self.options())?
+        } else {
+            let output_paths = output_paths_for(&files, default_dir, self.options())?;
+            for (file, output_path) in files.iter().zip(&output_paths) {
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(output_path, &file.data).await?;
+            }
+            output_paths
+        };
+        self.options().report_complete();
+
+        if let Some(session_path) = self.options().session_file.as_deref() {
+            if fs::try_exists(session_path).await? {
+                fs::remove_file(session_path).await?;
+                debug!("Removed finished session {}", session_path.display());
+            }
+        }
+
+        Ok(stats.result(files, &output_paths))
+    }
+}
+
+/// Async version of `QrFrames::write_receipt`.
+async fn write_receipt(frames: &QrFrames, path: &Path) -> Result<bool> {
+    let Some(image) = frames.receipt_image()? else {
+        return Ok(false);
+    };
+    fs::write(path, png_bytes(&image)?).await?;
+    info!("Wrote the receipt to {}", path.display());
+    Ok(true)
+}
+
+fn png_bytes(image: &RgbImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+async fn load_image(path: &Path) -> Result<DynamicImage> {
+    let bytes = fs::read(path).await?;
+    image_from_bytes(&bytes)
+}
+
+async fn load_packet(path: &Path) -> Result<InputFrame> {
+    let bytes = fs::read(path).await?;
+    Ok(InputFrame::Packet(Chunk::from_bytes(&bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::DecodeOptions;
+    use crate::encode::{EncodeOptions, PacketEncoding};
+    use tempfile::tempdir;
+
+    fn assert_send<T: Send>(future: T) -> T {
+        future
+    }
+
+    #[tokio::test]
+    async fn test_async_roundtrip() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("async.txt");
+        let content = b"Async roundtrip test. ".repeat(40);
+        fs::write(&input, &content).await.unwrap();
+
+        let encoder = Encoder::new(
+            EncodeOptions::new()
+                .chunk_size(300)
+                .cover(true)
+                .receipt(true),
+        );
+        let gif = dir.path().join("async.gif");
+        assert_send(encoder.encode_to_gif_async(&input, &gif))
+            .await
+            .unwrap();
+        let images = dir.path().join("frames");
+        let encoded = encoder
+            .encode_to_images_async(&input, &images)
+            .await
+            .unwrap();
+        assert!(encoded.num_chunks > 1);
+        let cover = cover_file_name(&image_stem("async.txt"));
+        assert_eq!(encoded.output_files[0], cover);
+        assert!(images.join(&cover).exists());
+        let receipt = format!("{}.{}", image_stem("async.txt"), RECEIPT_EXTENSION);
+        assert_eq!(encoded.output_files.last(), Some(&receipt));
+        assert!(images.join(&receipt).exists());
+        assert!(gif.with_extension(RECEIPT_EXTENSION).exists());
+
+        let from_gif = dir.path().join("from_gif.txt");
+        let decoder = Decoder::new(DecodeOptions::new().output(&from_gif));
+        let reader = fs::File::open(&gif).await.unwrap();
+        assert_send(decoder.decode_gif_reader_async(reader))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&from_gif).await.unwrap(), content);
+
+        let from_images = dir.path().join("from_images.txt");
+        let decoder = Decoder::new(DecodeOptions::new().output(&from_images));
+        assert_send(decoder.decode_async(&images)).await.unwrap();
+        assert_eq!(fs::read(&from_images).await.unwrap(), content);
+
+        let err = decoder
+            .decode_async(&dir.path().join("missing.gif"))
+            .await
+            .expect_err("Decoding a missing file should fail");
+        assert!(matches!(err, FountainError::InputNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_async_decode_packets() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("packets.txt");
+        let content = b"Async packet test. ".repeat(40);
+        fs::write(&input, &content).await.unwrap();
+
+        let encoder = Encoder::new(EncodeOptions::new().chunk_size(300));
+        let packets = dir.path().join("packets");
+        encoder.export_packets(&[&input], &packets).unwrap();
+        let lines = dir.path().join("lines.txt");
+        encoder
+            .encode_files_to_packets(
+                &[&input],
+                std::fs::File::create(&lines).unwrap(),
+                PacketEncoding::Base64,
+            )
+            .unwrap();
+
+        let checked = dir.path().join("checked");
+        fs::create_dir(&checked).await.unwrap();
+        let decoder = Decoder::new(
+            DecodeOptions::new()
+                .output(checked.join("decoded.txt"))
+                .check_only(true),
+        );
+        let result = decoder.decode_async(&packets).await.unwrap();
+        assert!(result.output_files.is_empty());
+        let mut entries = fs::read_dir(&checked).await.unwrap();
+        assert!(entries.next_entry().await.unwrap().is_none());
+
+        for source in [packets, lines] {
+            let output = dir.path().join("decoded.txt");
+            let decoder = Decoder::new(DecodeOptions::new().output(&output).overwrite(true));
+            decoder.decode_async(&source).await.unwrap();
+            assert_eq!(fs::read(&output).await.unwrap(), content);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Throughput of the whole channel, measured on a synthetic payload: `fountain-encode bench`.
+
+use image::imageops::{blur, resize, FilterType};
+use image::{DynamicImage, GrayImage, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::decode::{DecodeOptions, Decoder};
+use crate::encode::Encoder;
+use crate::error::Result;
+
+/// Name the synthetic payload is encoded under.
+const BENCH_FILENAME: &str = "bench.bin";
+
+/// How a [`Encoder::bench`] run went. Frame and byte rates come from the timings, which cover
+/// rendering and scanning but not the camera simulation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchResult {
+    /// Size of the synthetic payload.
+    pub payload_bytes: usize,
+    #[serde(rename = "effective_payload_size")]
+    pub effective_size: usize,
+    /// Frames rendered, parity frames included.
+    pub frames: usize,
+    /// Frames the decoder scanned before the payload was complete.
+    pub frames_scanned: usize,
+    /// Whether the frames went through the simulated camera.
+    pub camera: bool,
+    #[serde(rename = "encode_ms", with = "crate::millis")]
+    pub encode_duration: Duration,
+    #[serde(rename = "decode_ms", with = "crate::millis")]
+    pub decode_duration: Duration,
+}
+
+impl BenchResult {
+    /// Frames rendered per second.
+    pub fn encode_fps(&self) -> f64 {
+        self.frames as f64 / self.encode_duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Frames scanned per second.
+    pub fn decode_fps(&self) -> f64 {
+        self.frames_scanned as f64 / self.decode_duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Payload bytes through encoding and decoding per second.
+    pub fn throughput(&self) -> f64 {
+        let total = self.encode_duration + self.decode_duration;
+        self.payload_bytes as f64 / total.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl Encoder {
+    /// Encodes `payload_bytes` of random data with these options, decodes the frames again and
+    /// times both, to compare settings such as chunk sizes. With `camera`, each frame is
+    /// rescaled, blurred and speckled with noise first, roughly as a phone capture of a screen.
+    pub fn bench(&self, payload_bytes: usize, camera: bool) -> Result<BenchResult> {
+        let payload = synthetic_payload(payload_bytes);
+
+        let started = Instant::now();
+        let frames = self.frames_from_bytes(&payload, BENCH_FILENAME)?;
+        let effective_size = frames.effective_size();
+        let images = frames.collect::<Result<Vec<RgbImage>>>()?;
+        let encode_duration = started.elapsed();
+
+        let frames = images.len();
+        let captures: Vec<DynamicImage> = images
+            .into_iter()
+            .enumerate()
+            .map(|(i, image)| {
+                if camera {
+                    DynamicImage::ImageLuma8(camera_capture(&image, i as u32))
+                } else {
+                    DynamicImage::ImageRgb8(image)
+                }
+            })
+            .collect();
+
+        let scanned = Arc::new(AtomicUsize::new(0));
+        let counter = scanned.clone();
+        let mut options = DecodeOptions::new().on_frame_scanned(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        options.cancel = self.options().cancel.clone();
+        let started = Instant::now();
+        // The decoder checks the payload's checksum, so a run that returns is a correct one.
+        Decoder::new(options).decode_frames(captures)?;
+        let decode_duration = started.elapsed();
+
+        Ok(BenchResult {
+            payload_bytes,
+            effective_size,
+            frames,
+            frames_scanned: scanned.load(Ordering::Relaxed),
+            camera,
+            encode_duration,
+            decode_duration,
+        })
+    }
+}
+
+/// Random-looking bytes that do not compress, so every setting carries the full payload.
+fn synthetic_payload(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// `frame` as a camera would see it: in grey, scaled by 1.5 with smoothing, slightly out of
+/// focus and with sensor noise.
+fn camera_capture(frame: &RgbImage, seed: u32) -> GrayImage {
+    let gray = DynamicImage::ImageRgb8(frame.clone()).to_luma8();
+    let (width, height) = (gray.width() * 3 / 2, gray.height() * 3 / 2);
+    let mut capture = blur(&resize(&gray, width, height, FilterType::Triangle), 0.8);
+    let mut state = seed.wrapping_mul(2_654_435_761).wrapping_add(1);
+    for pixel in capture.pixels_mut() {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let noise = (state >> 24) as i32 - 128;
+        pixel.0[0] = (i32::from(pixel.0[0]) + noise * 24 / 128).clamp(0, 255) as u8;
+    }
+    capture
+}
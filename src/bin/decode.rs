@@ -1,53 +1,521 @@
 use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{ArgAction, Parser};
+use serde_json::json;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info};
 
-use fountain::{decode_from_gif, decode_from_images, qr::QR_FILE_EXTENSION};
+use fountain::archive::{extract_archive, is_archive};
+use fountain::cli::{
+    exit_code_for, init_logging, json_summary, notify, parse_duration, parse_size, read_delta_base,
+    read_dictionary, run_exec_hook, HelperCommand, Notify,
+};
+use fountain::{CancelFlag, DecodeOptions, Decoder, FountainError, FrameOutcome, ScannedFrame};
 
 #[derive(Parser)]
 #[command(name = "fountain-decode")]
 #[command(author, version, about = "Decode QR code images back to original file", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input directory (containing images) or GIF file
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<HelperCommand>,
 
-    /// Output file path (defaults to original filename in current directory)
+    /// Input directory (containing images, or packets from `fountain-encode --export-packets`),
+    /// GIF file, or .txt file with one packet per line (base64, or QR text as `zbarimg` prints
+    /// it). Several inputs, such as partial recordings, are decoded together as one; with
+    /// --stitch, one input per part; with --diff, the two to compare
+    #[arg(required_unless_present_any = ["estimate", "http"])]
+    input: Vec<PathBuf>,
+
+    /// Output file path (defaults to original filename in current directory). For a transfer
+    /// of several files, the directory to write them to
     #[arg(short, long)]
     output: Option<PathBuf>,
+
+    /// Session file to resume from: previously collected packets are loaded from it, and if the
+    /// input is still not enough to finish, all packets are saved back for the next run
+    #[arg(long, value_name = "SESSION")]
+    resume: Option<PathBuf>,
+
+    /// Overwrite the output file if it already exists
+    #[arg(short, long)]
+    force: bool,
+
+    /// If a decoded file is a directory sent as a tar archive, unpack it next to the archive and
+    /// delete the archive
+    #[arg(short = 'x', long)]
+    extract: bool,
+
+    /// Run a command on each decoded file once it is written and verified, with `{}` standing
+    /// for its path (e.g. `gpg --import {}`). With --extract, the extracted directory is passed
+    /// instead of the archive. A failing command fails the decode
+    #[arg(long, value_name = "CMD", conflicts_with_all = ["pipe", "print"])]
+    exec: Option<String>,
+
+    /// Decode the parts of a file sent with `fountain-encode --split-size`, given in any order,
+    /// then join them and verify the whole file
+    #[arg(long, conflicts_with_all = ["resume", "pipe"])]
+    stitch: bool,
+
+    /// Decode a piped stream (from `fountain-encode --pipe`) and write it to the output file,
+    /// or to stdout
+    #[arg(long, conflicts_with_all = ["resume", "extract", "json"])]
+    pipe: bool,
+
+    /// Print the decoded text (e.g. from `fountain-encode --text`) to stdout instead of writing
+    /// a file. Fails if the content is not UTF-8 text
+    #[arg(long, conflicts_with_all = ["output", "force", "extract", "stitch", "pipe", "json"])]
+    print: bool,
+
+    /// Rebuild the files and verify their checksums, but write nothing: report whether the
+    /// input still decodes, with the size and SHA-256 of each file and the usual statistics,
+    /// e.g. to check archived GIFs now and then
+    #[arg(long, conflicts_with_all = ["output", "force", "extract", "exec", "resume", "salvage", "pipe", "print", "diff", "estimate"])]
+    check_only: bool,
+
+    /// Compare two inputs, e.g. two recordings of one transfer, without decoding to a file:
+    /// report the packets they share (by ESI), those only one holds, and whether each alone or
+    /// both together would decode
+    #[arg(long, conflicts_with_all = ["output", "resume", "force", "extract", "exec", "stitch", "pipe", "print", "all"])]
+    diff: bool,
+
+    /// Tell how many more packets the --resume session and the inputs need to decode, and how
+    /// many seconds of capture that takes at the sender's --interval, without decoding
+    #[arg(long, conflicts_with_all = ["output", "force", "extract", "exec", "stitch", "pipe", "print", "diff", "all"])]
+    estimate: bool,
+
+    /// Frame interval of the sender in milliseconds, to turn --estimate's packets into seconds
+    #[arg(long, value_name = "MS", default_value_t = 2000, requires = "estimate")]
+    interval: u64,
+
+    /// Receive the frames from a phone instead of reading an input: serve a page on this
+    /// address (e.g. `0.0.0.0:9000`) where the phone takes photos of the codes, or uploads
+    /// photos, GIFs or a screen recording of them, and decode them as they arrive. The page
+    /// shows the progress; nothing needs installing on the phone
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["input", "stitch", "pipe", "print", "diff", "estimate"])]
+    http: Option<String>,
+
+    /// Decode every transfer in the input, such as files added with `fountain-encode --append`,
+    /// instead of stopping at the first one to complete
+    #[arg(long, conflicts_with_all = ["stitch", "pipe"])]
+    all: bool,
+
+    /// Give up after scanning for this long (e.g. `90`, `30s`, `5m`), saving the session with
+    /// --resume, and report what the frames held
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Give up after scanning this many frames, saving the session with --resume, and report
+    /// what the frames held
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_frames: Option<u64>,
+
+    /// Give up once no new packet has arrived for this long (e.g. `30s`, `2m`), saving the
+    /// session with --resume, and exit with status 16, so an unattended receive that stopped
+    /// making progress ends
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["pipe", "diff", "estimate"])]
+    stall: Option<Duration>,
+
+    /// When decoding ends, successfully or not, ring the terminal bell, or with `desktop` also
+    /// show a desktop notification (notify-send, or osascript on macOS)
+    #[arg(long, value_enum, value_name = "HOW", num_args = 0..=1, default_missing_value = "bell")]
+    notify: Option<Notify>,
+
+    /// Fail as soon as the first packets tell the transfer's size if decoding it would take
+    /// more memory than this (e.g. 512MB), instead of running out of memory halfway through
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_memory: Option<usize>,
+
+    /// Read the codes of this many frames at once, one per thread (default: one per core), e.g.
+    /// 1 to leave the other cores of a shared server alone
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+
+    /// Preset compression dictionary the sender used with `fountain-encode --dict`. Give it
+    /// several times to offer several; each transfer names the one it needs
+    #[arg(long, value_name = "FILE", conflicts_with = "pipe")]
+    dict: Vec<PathBuf>,
+
+    /// Local copy of the file the sender diffed against with `fountain-encode --delta-base`;
+    /// the received delta is applied to it and the new file written
+    #[arg(long, value_name = "FILE", conflicts_with = "pipe")]
+    delta_base: Option<PathBuf>,
+
+    /// Also read the frames of a txqr sender app. Its message carries no name and is written
+    /// as `txqr.bin` unless --output is given
+    #[arg(long, conflicts_with_all = ["pipe", "stitch", "resume"])]
+    txqr: bool,
+
+    /// The input is a camera's view of a screen, such as a phone video of a monitor: frames
+    /// without a readable code are tried again with rolling-shutter bands and moiré evened
+    /// out, and averaged with the frame before
+    #[arg(long, conflicts_with_all = ["pipe", "diff", "estimate"])]
+    filmed_screen: bool,
+
+    /// If decoding fails, write what was collected into this directory instead of nothing:
+    /// the packets as `.pkt` files and a session file, and with --txqr the bytes of the message
+    /// received. A PARTIAL.txt note in it tells what it holds
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["pipe", "diff", "estimate"])]
+    salvage: Option<PathBuf>,
+
+    /// Record what each frame held (new or duplicate packet, a QR code that is not a chunk, no
+    /// code at all) and how long detection took, and print a table with the detection hit rate
+    /// and average time per frame at the end, even if decoding fails. -v lists every frame
+    #[arg(long, conflicts_with_all = ["pipe", "diff", "estimate"])]
+    stats: bool,
+
+    /// Print a machine-readable JSON summary on stdout; progress messages go to stderr
+    #[arg(long)]
+    json: bool,
+
+    /// Increase log verbosity (-v for debug, -vv for trace)
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Decrease log verbosity (-q for warnings only, -qq for errors only)
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Cli::parse();
+    if let Some(command) = &args.command {
+        return exit_code_for(command.run::<Cli>());
+    }
+    init_logging(args.verbose, args.quiet);
+    let result = run(&args);
+    let message = match &result {
+        Ok(()) => "Decoding finished".to_string(),
+        Err(e) => format!("Decoding failed: {}", e),
+    };
+    let code = exit_code_for(result);
+    if let Some(kind) = args.notify {
+        notify(kind, &message);
+    }
+    code
+}
 
-    if !args.input.exists() {
-        anyhow::bail!("Input path does not exist: {}", args.input.display());
+fn run(args: &Cli) -> Result<()> {
+    if args.estimate && args.resume.is_none() && args.input.is_empty() {
+        anyhow::bail!("--estimate needs a --resume session or an input.");
     }
+    if args.diff && args.input.len() != 2 {
+        anyhow::bail!("--diff compares exactly two inputs.");
+    }
+    if args.input.len() > 1 && (args.pipe || args.print) {
+        anyhow::bail!("--pipe and --print take a single input.");
+    }
+
+    let mut options = DecodeOptions::new()
+        .overwrite(args.force)
+        .all_transfers(args.all)
+        .txqr(args.txqr)
+        .filmed_screen(args.filmed_screen)
+        .check_only(args.check_only);
+    options.output = args.output.clone();
+    options.session_file = args.resume.clone();
+    options.salvage_dir = args.salvage.clone();
+    options.timeout = args.timeout;
+    options.max_frames = args.max_frames.map(|n| n as usize);
+    options.stall_timeout = args.stall;
+    options.max_memory = args.max_memory;
+    options.threads = args.threads.map(|n| n as usize);
+    for path in &args.dict {
+        options = options.dictionary(read_dictionary(path)?);
+    }
+    if let Some(path) = &args.delta_base {
+        options = options.delta_base(read_delta_base(path)?);
+    }
+
+    // Ctrl+C stops the scan after the current frame, still saving the session.
+    let cancel = CancelFlag::default();
+    let flag = cancel.clone();
+    ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))?;
+    let mut options = options.cancel_flag(cancel);
 
-    let result = if args.input.is_dir() {
-        println!("Decoding QR codes from directory: {}", args.input.display());
-        decode_from_images(&args.input, args.output.as_deref())?
+    if !args.stats {
+        return run_decoder(args, options);
+    }
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let recorded = frames.clone();
+    options = options.on_frame_scanned(move |frame| {
+        debug!(
+            "    {}: {} ({})",
+            frame.label,
+            outcome_name(frame.outcome),
+            frame
+                .detect_time
+                .map_or_else(|| "no detection".to_string(), |t| format!("{:.1?}", t))
+        );
+        recorded.lock().unwrap().push(frame.clone());
+    });
+    let result = run_decoder(args, options);
+    log_frame_stats(&frames.lock().unwrap());
+    result
+}
+
+fn run_decoder(args: &Cli, options: DecodeOptions) -> Result<()> {
+    if args.estimate {
+        return run_estimate(args, Decoder::new(options));
+    }
+    if args.pipe {
+        return run_pipe(&args.input[0], args, Decoder::new(options));
+    }
+    if args.print {
+        return run_print(&args.input[0], Decoder::new(options));
+    }
+    if args.diff {
+        return run_diff(
+            &args.input[0],
+            &args.input[1],
+            Decoder::new(options),
+            args.json,
+        );
+    }
+
+    let decoder = Decoder::new(options);
+    let inputs: Vec<&Path> = args.input.iter().map(PathBuf::as_path).collect();
+    let result = if let Some(addr) = &args.http {
+        let server = decoder.receive_http(addr.as_str())?;
+        info!(
+            "Open http://<this computer's address>:{}/ on the phone",
+            server.local_addr()?.port()
+        );
+        server.run()?
+    } else if args.stitch {
+        decoder.decode_split(&inputs)?
     } else {
-        let is_gif = args
-            .input
-            .extension()
-            .map(|ext| ext.to_ascii_lowercase() == "gif")
-            .unwrap_or(false);
-
-        if is_gif {
-            decode_from_gif(&args.input, args.output.as_deref())?
+        decoder.decode_inputs(&inputs)?
+    };
+
+    if args.check_only {
+        info!("Check passed: the input decodes and every file verifies; nothing was written");
+    }
+    info!("Successfully decoded {} QR code(s)", result.num_chunks);
+    info!("Original filename: {}", result.original_filename);
+    for output_file in &result.output_files {
+        info!("Output file: {}", output_file);
+    }
+    info!(
+        "Scanned {} frame(s), {} duplicate packet(s); {} bytes in {:.2?} ({:.0} bytes/s)",
+        result.frames_scanned,
+        result.duplicate_packets,
+        result.total_bytes,
+        result.duration,
+        result.throughput()
+    );
+
+    let mut extracted_paths = Vec::new();
+    for output_file in &result.output_files {
+        let output_path = Path::new(output_file);
+        let final_path = if args.extract && is_archive(output_path) {
+            let dir = extract_archive(output_path, args.force)?;
+            info!("Extracted directory: {}", dir.display());
+            extracted_paths.push(dir.to_string_lossy().to_string());
+            dir
         } else {
+            output_path.to_path_buf()
+        };
+        if let Some(command) = &args.exec {
+            run_exec_hook(command, &final_path)?;
+        }
+    }
+
+    if args.json {
+        let summary = json_summary(
+            &result,
+            json!({
+                "input": args.input.first().map(|input| input.to_string_lossy()),
+                "inputs": args.input.iter().map(|input| input.to_string_lossy()).collect::<Vec<_>>(),
+                "extracted_paths": extracted_paths,
+                "throughput_bytes_per_sec": result.throughput(),
+            }),
+        );
+        println!("{}", summary);
+    }
+
+    Ok(())
+}
+
+fn outcome_name(outcome: FrameOutcome) -> &'static str {
+    match outcome {
+        FrameOutcome::Unreadable => "unreadable",
+        FrameOutcome::NoCode => "no code",
+        FrameOutcome::NotChunk => "not a chunk",
+        FrameOutcome::Duplicate => "duplicate",
+        FrameOutcome::New => "new",
+    }
+}
+
+/// Logs a table of what the scanned frames held, with the detection hit rate and time.
+fn log_frame_stats(frames: &[ScannedFrame]) {
+    let total = frames.len().max(1);
+    info!("{:<12} {:>8} {:>7}", "Outcome", "Frames", "Share");
+    for outcome in [
+        FrameOutcome::New,
+        FrameOutcome::Duplicate,
+        FrameOutcome::NotChunk,
+        FrameOutcome::NoCode,
+        FrameOutcome::Unreadable,
+    ] {
+        let count = frames.iter().filter(|f| f.outcome == outcome).count();
+        let share = count as f64 * 100.0 / total as f64;
+        info!("{:<12} {:>8} {:>6.1}%", outcome_name(outcome), count, share);
+    }
+
+    let timed: Vec<(&ScannedFrame, Duration)> = frames
+        .iter()
+        .filter_map(|frame| Some((frame, frame.detect_time?)))
+        .collect();
+    if timed.is_empty() {
+        info!("No frame needed QR detection");
+        return;
+    }
+    let detected = timed.iter().filter(|(f, _)| f.outcome.detected()).count();
+    let hit_rate = detected as f64 * 100.0 / timed.len() as f64;
+    info!(
+        "Detection hit rate: {} of {} image(s) ({:.1}%)",
+        detected,
+        timed.len(),
+        hit_rate
+    );
+    let detect_total: Duration = timed.iter().map(|&(_, t)| t).sum();
+    let slowest = timed.iter().map(|&(_, t)| t).max().unwrap_or_default();
+    let mean_ms = detect_total.as_secs_f64() * 1000.0 / timed.len() as f64;
+    let slowest_ms = slowest.as_secs_f64() * 1000.0;
+    info!(
+        "Detection time: {:.1}ms per image on average, {:.1}ms at most",
+        mean_ms, slowest_ms
+    );
+}
+
+fn run_print(input: &Path, decoder: Decoder) -> Result<()> {
+    let files = decoder.decode_files(input)?;
+    let mut stdout = io::stdout().lock();
+    for file in files {
+        let Ok(text) = String::from_utf8(file.data) else {
             anyhow::bail!(
-                "Unsupported input file type: {}. Only directories (containing {} files) or GIF files are supported.",
-                args.input.display(),
-                QR_FILE_EXTENSION
+                "{} is not text; decode it without --print to write it to a file.",
+                file.filename
             );
+        };
+        info!("Decoded text from {}:", file.filename);
+        stdout.write_all(text.as_bytes())?;
+        if !text.ends_with('\n') {
+            stdout.write_all(b"\n")?;
         }
-    };
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_diff(a: &Path, b: &Path, decoder: Decoder, json: bool) -> Result<()> {
+    let diff = decoder.diff(a, b)?;
+    let verdict = |complete| if complete { "decodes" } else { "incomplete" };
+
+    info!(
+        "{}: {} packet(s), {} only here, {}",
+        a.display(),
+        diff.packets_a,
+        diff.only_a,
+        verdict(diff.complete_a)
+    );
+    info!(
+        "{}: {} packet(s), {} only here, {}",
+        b.display(),
+        diff.packets_b,
+        diff.only_b,
+        verdict(diff.complete_b)
+    );
+    info!("In both:  {} packet(s)", diff.common_esis.len());
+    debug!("Shared ESIs: {:?}", diff.common_esis);
+    info!(
+        "Combined: {} of {} source packet(s), {}",
+        diff.combined_packets(),
+        diff.expected_packets,
+        verdict(diff.complete_combined)
+    );
+    if !diff.complete_combined {
+        info!("About {} more packet(s) needed", diff.missing_packets());
+    } else if !diff.complete_a && !diff.complete_b {
+        info!("Merging the two completes the transfer");
+    }
+
+    if json {
+        let summary = json!({
+            "inputs": [a.to_string_lossy(), b.to_string_lossy()],
+            "packets": [diff.packets_a, diff.packets_b],
+            "only": [diff.only_a, diff.only_b],
+            "complete": [diff.complete_a, diff.complete_b],
+            "common_esis": diff.common_esis,
+            "combined_packets": diff.combined_packets(),
+            "expected_packets": diff.expected_packets,
+            "complete_combined": diff.complete_combined,
+            "missing_packets": diff.missing_packets(),
+        });
+        println!("{}", summary);
+    }
+    Ok(())
+}
+
+fn run_estimate(args: &Cli, decoder: Decoder) -> Result<()> {
+    let inputs: Vec<&Path> = args.input.iter().map(PathBuf::as_path).collect();
+    let estimate = decoder.estimate(&inputs)?;
 
-    println!();
-    println!("Successfully decoded {} QR code(s)", result.num_chunks);
-    println!("Original filename: {}", result.original_filename);
-    println!("Output file: {}", result.output_path);
+    info!(
+        "Have {} of at least {} source packet(s)",
+        estimate.packets, estimate.expected_packets
+    );
+    let loop_secs = estimate.loop_secs(args.interval);
+    info!(
+        "The loop holds at least {} frame(s), {:.0}s at {}ms per frame",
+        estimate.loop_frames, loop_secs, args.interval
+    );
+    if estimate.complete {
+        info!("The packets already decode; nothing more to capture");
+    } else {
+        let capture_secs = estimate.capture_secs(args.interval);
+        info!(
+            "About {} more packet(s) needed: capture about {} frame(s), {:.0}s from any point of the loop",
+            estimate.missing_packets(),
+            estimate.capture_frames(),
+            capture_secs
+        );
+    }
 
+    if args.json {
+        let summary = json_summary(
+            &estimate,
+            json!({
+                "missing_packets": estimate.missing_packets(),
+                "loop_secs": estimate.loop_secs(args.interval),
+                "capture_frames": estimate.capture_frames(),
+                "capture_secs": estimate.capture_secs(args.interval),
+            }),
+        );
+        println!("{}", summary);
+    }
+    Ok(())
+}
+
+fn run_pipe(input: &Path, args: &Cli, decoder: Decoder) -> Result<()> {
+    let written = match &args.output {
+        Some(output) => {
+            if !args.force && output.exists() {
+                return Err(FountainError::OutputExists(output.clone()).into());
+            }
+            let file = BufWriter::new(File::create(output)?);
+            let written = decoder.decode_pipe(input, file)?;
+            info!("Output file: {}", output.display());
+            written
+        }
+        None => decoder.decode_pipe(input, io::stdout().lock())?,
+    };
+    info!("Wrote {} bytes of stream", written);
     Ok(())
 }
@@ -1,34 +1,148 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::fs;
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use tracing::{error, info, warn};
 
+use fountain::cli::{
+    exit_code_for, init_logging, json_summary, parse_dimensions, parse_length, parse_size,
+    read_delta_base, read_dictionary, HelperCommand,
+};
+use fountain::config::{Config, Profile};
+use fountain::dictionary::{train_dictionary, MAX_DICTIONARY_SIZE};
+use fountain::eink::{DEFAULT_EINK_MARGIN, SLIDESHOW_MANIFEST};
+use fountain::pipe::DEFAULT_SEGMENT_SIZE;
+use fountain::qr::QUIET_ZONE_MODULES;
 use fountain::{
-    display_qr_carousel, display_qr_once, encode_file_for_terminal, encode_file_to_gif,
-    encode_file_to_images, DEFAULT_PAYLOAD_SIZE, MAX_PAYLOAD_SIZE,
+    display_pipe, display_qr_carousel, display_qr_endless, display_qr_once, gif_interval_ms,
+    gif_timing_warnings, CancelFlag, EinkDisplay, EncodeOptions, EncodeResult, Encoder,
+    PacketEncoding, PacketOrder, QrFrames, TerminalQrData, DEFAULT_PAYLOAD_SIZE, DEFAULT_PRINT_DPI,
+    MAX_PAYLOAD_SIZE, TEXT_FILENAME,
 };
 
 #[derive(Parser)]
 #[command(name = "fountain-encode")]
 #[command(author, version, about = "Encode files to QR codes using RaptorQ (Fountain Codes)", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input file to encode
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input file to encode. A directory is sent as a tar archive named `<dir>.tar`. Several
+    /// inputs travel in one transfer and are written out separately by the decoder
+    #[arg(required_unless_present_any = ["batch", "pipe", "text", "clipboard"])]
+    input: Vec<PathBuf>,
+
+    /// Send this text instead of a file, e.g. a password or an SSH key. It travels as
+    /// `text.txt`; `fountain-decode --print` shows it
+    #[arg(long, conflicts_with_all = ["input", "batch", "pipe", "clipboard", "endless", "split_size", "append", "dry_run"])]
+    text: Option<String>,
+
+    /// Like --text, sending the text currently on the clipboard
+    #[arg(long, conflicts_with_all = ["input", "batch", "pipe", "endless", "split_size", "append", "dry_run"])]
+    clipboard: bool,
+
+    /// Encode every file in a directory into its own GIF or image set, with shared options.
+    /// --gif-output-file and --image-output-dir then name directories that receive one
+    /// `<file>.gif` or `<file>/` per input.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["input", "terminal", "dry_run"])]
+    batch: Option<PathBuf>,
 
     /// Output directory for QR code images
-    #[arg(short = 'm', long = "image-output-dir", required_unless_present_any = ["terminal", "gif_output_file"])]
+    #[arg(short = 'm', long = "image-output-dir", required_unless_present_any = ["terminal", "gif_output_file", "dry_run", "pipe", "serve", "window", "framebuffer", "packets_output", "export_packets", "train_dict", "profile"])]
     image_output_dir: Option<PathBuf>,
 
     /// Output animated GIF file containing all QR codes
     #[arg(short = 'g', long)]
     gif_output_file: Option<PathBuf>,
 
+    /// Write the --image-output-dir images for an e-ink display: 1-bit PNGs of this size
+    /// (WIDTHxHEIGHT, or a panel such as waveshare-7.5 or kindle) plus a `slideshow.json`
+    /// listing them, for e-ink photo frames
+    #[arg(long, value_name = "DISPLAY", requires = "image_output_dir", conflicts_with_all = ["gif_output_file", "batch", "split_size", "append", "text", "clipboard"])]
+    eink: Option<EinkDisplay>,
+
+    /// Pixels kept clear around the code on the e-ink display
+    #[arg(long, value_name = "PX", default_value_t = DEFAULT_EINK_MARGIN, requires = "eink")]
+    eink_margin: u32,
+
     /// Display QR codes in terminal instead of saving to files
     #[arg(short, long)]
     terminal: bool,
 
-    /// Interval in milliseconds for auto-switching QR codes in terminal mode or GIF frame duration (default: 2000)
-    #[arg(short, long, default_value = "2000")]
-    interval: u64,
+    /// Write the packets as text, one per line, to this file (`-` for stdout) instead of
+    /// rendering QR codes, e.g. to render them with other QR tools or to archive them
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "serve", "window", "framebuffer", "batch", "pipe", "split_size", "append", "dry_run"])]
+    packets_output: Option<PathBuf>,
+
+    /// Write each packet into this directory as a `.pkt` file instead of rendering QR codes, for
+    /// transports that are not optical. `fountain-decode` reads the directory like one of images
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "serve", "window", "framebuffer", "packets_output", "batch", "pipe", "split_size", "append", "dry_run"])]
+    export_packets: Option<PathBuf>,
+
+    /// Text form of the --packets-output lines: base64 of each packet, or base45, the text the
+    /// QR codes hold
+    #[arg(long, value_enum, default_value_t = PacketFormat::Base64, requires = "packets_output")]
+    packet_encoding: PacketFormat,
+
+    /// Show the QR codes fullscreen in a native window, at the largest module size the screen
+    /// fits and with steady frame timing, until a key is pressed (needs the `window` feature
+    /// and an X11 display)
+    #[arg(long, conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "serve", "batch", "pipe", "split_size", "append", "dry_run", "json"])]
+    window: bool,
+
+    /// Show the QR codes on a Linux framebuffer device (e.g. /dev/fb0), for a headless board
+    /// with a small screen and no X server, at the largest module size the screen fits and
+    /// with steady frame timing, until Ctrl+C
+    #[arg(long, value_name = "DEVICE", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "serve", "window", "batch", "pipe", "split_size", "append", "dry_run", "json"])]
+    framebuffer: Option<PathBuf>,
+
+    /// Serve the QR codes as a web page cycling through them at this address (e.g.
+    /// 0.0.0.0:8080), so any device with a browser can act as the display. The carousel is
+    /// also streamed as MJPEG at `/stream.mjpg`, for signage players and OBS. Runs until Ctrl+C
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "batch", "pipe", "split_size", "append", "dry_run", "json"])]
+    serve: Option<String>,
+
+    /// Keep the carousel going with fresh repair packets on every loop instead of replaying the
+    /// same frames, so a receiver that keeps missing frames still converges (only with --terminal)
+    #[arg(long, requires = "terminal", conflicts_with_all = ["no_carousel", "pipe"])]
+    endless: bool,
+
+    /// Show two consecutive QR codes side by side on each terminal frame when the terminal is
+    /// wide enough, halving the frames to capture (only with --terminal)
+    #[arg(long, requires = "terminal", conflicts_with = "pipe")]
+    dual: bool,
+
+    /// Order of the packets across the frames: every source packet before the repair packets,
+    /// so a capture that misses nothing is done after that many frames, or the repair packets
+    /// spread among them
+    #[arg(long, value_enum, default_value_t = PacketOrderArg::Systematic, conflicts_with_all = ["pipe", "append", "transcode", "txqr"])]
+    packet_order: PacketOrderArg,
+
+    /// Send the manifest, the name, size and SHA-256 of every file, again after every N
+    /// packets, and for a single file too, so a receiver that starts part way through the loop
+    /// soon learns what is coming
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["pipe", "append", "transcode", "txqr", "endless"])]
+    beacon_interval: Option<u64>,
+
+    /// Pipe mode: read a continuous stream from stdin and show it in the terminal segment by
+    /// segment, until stdin is closed. Decode it with `fountain-decode --pipe`
+    #[arg(long, conflicts_with_all = ["input", "batch", "image_output_dir", "gif_output_file", "no_carousel", "target_frames", "dry_run", "json"])]
+    pipe: bool,
+
+    /// Bytes of the stream carried by each segment in pipe mode. A segment is shown once it is
+    /// full or stdin is closed
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_SEGMENT_SIZE, requires = "pipe")]
+    segment_size: usize,
+
+    /// Interval in milliseconds for auto-switching QR codes in terminal mode or GIF frame duration (default: 2000).
+    /// GIF frames are rounded to 10ms steps of at least 20ms
+    #[arg(short, long)]
+    interval: Option<u64>,
 
     /// Show all QR codes at once without carousel (only with --terminal)
     #[arg(long)]
@@ -39,41 +153,425 @@ struct Cli {
     #[arg(short = 's', long, alias = "payload-size")]
     chunk_size: Option<usize>,
 
+    /// Pick the payload size automatically so the output has at most N QR codes, using the
+    /// least dense codes that meet the budget
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["chunk_size", "terminal", "batch"])]
+    target_frames: Option<u64>,
+
+    /// Add parity QR codes, this many percent of the regular ones, for printed backups. They
+    /// hold repair packets no regular code carries, so pages lost beyond what the regular
+    /// codes' redundancy covers can be made up for. Images are named `<file>_parity_NNNN.png`
+    #[arg(long, value_name = "PERCENT", conflicts_with_all = ["terminal", "pipe", "append"])]
+    parity: Option<u32>,
+
+    /// Split the input into independent transfers of at most this many bytes (e.g. 50MB), each
+    /// with its own GIF (`<stem>.partN.gif`) or image directory (`<dir>/partN`). Decode them
+    /// together with `fountain-decode --stitch`
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["terminal", "batch", "pipe", "dry_run"])]
+    split_size: Option<usize>,
+
+    /// Add the input to the GIF or image directory written earlier at --gif-output-file or
+    /// --image-output-dir, as a new transfer with the same payload size and QR version. Decode
+    /// every transfer with `fountain-decode --all`
+    #[arg(long, conflicts_with_all = ["terminal", "batch", "pipe", "split_size", "chunk_size", "target_frames", "dry_run"])]
+    append: bool,
+
+    /// Re-render the QR codes of an output written earlier, given as the input (a GIF, or a
+    /// directory of images or packet files), with this run's --interval, --pixel-scale and
+    /// output, e.g. to slow down a GIF that is too fast to film. The original file is not needed
+    #[arg(long, conflicts_with_all = ["terminal", "batch", "pipe", "text", "clipboard", "packets_output", "export_packets", "eink", "split_size", "append", "chunk_size", "target_frames", "parity", "dry_run"])]
+    transcode: bool,
+
     /// Pixel scale for QR code modules (default: 4).
-    #[arg(long, default_value = "4")]
-    pixel_scale: u32,
+    #[arg(long)]
+    pixel_scale: Option<u32>,
+
+    /// Add a cover sheet to image and GIF output: a frame of text with each file's name, size
+    /// and SHA-256, the number of QR codes, the date and how to restore them. It opens the GIF,
+    /// or is written as `<file>_0000_cover.png` before the first image
+    #[arg(long, conflicts_with_all = ["terminal", "pipe", "serve", "window", "framebuffer", "eink", "packets_output", "export_packets", "append", "transcode"])]
+    cover: bool,
+
+    /// Write a small QR code of each file's name, size and SHA-256 in plain text next to image
+    /// and GIF output, as `<output>.receipt.png`, for checking the decoded file with any phone
+    /// app
+    #[arg(long, conflicts_with_all = ["terminal", "pipe", "serve", "window", "framebuffer", "eink", "packets_output", "export_packets", "append", "transcode", "txqr", "split_size"])]
+    receipt: bool,
+
+    /// Draw this text under every image and GIF frame, outside the quiet zone, e.g. a
+    /// classification label such as "CONFIDENTIAL"
+    #[arg(long, value_name = "TEXT", conflicts_with_all = ["terminal", "pipe", "window", "framebuffer", "eink", "packets_output", "export_packets", "txqr"])]
+    caption: Option<String>,
+
+    /// Draw an ArUco marker in each corner of every image and GIF frame, outside the quiet
+    /// zone, so fountain-decode can straighten photos of printed or projected frames taken at
+    /// an angle
+    #[arg(long, conflicts_with_all = ["terminal", "pipe", "window", "framebuffer", "eink", "packets_output", "export_packets", "txqr"])]
+    markers: bool,
+
+    /// Number the frames in the order they are shown, in 4 more bytes of each, so
+    /// fountain-decode can tell how many its capture missed and suggest a longer --interval
+    #[arg(long, conflicts_with_all = ["terminal", "pipe", "packets_output", "export_packets", "append", "transcode", "txqr"])]
+    sequence: bool,
+
+    /// Draw image, GIF, --serve, window and framebuffer frames as colour matrix symbols of this
+    /// many cells per side (odd, default 129) instead of QR codes. Each holds several times what
+    /// a QR code does, but only fountain-decode built with the `color` feature reads them
+    #[cfg(feature = "color")]
+    #[arg(long, value_name = "CELLS", num_args = 0..=1, default_missing_value = "129", conflicts_with_all = ["terminal", "pipe", "packets_output", "export_packets", "eink", "append", "transcode", "print_size", "caption", "markers", "strict_palette", "max_version"])]
+    color_matrix: Option<u32>,
+
+    /// Write --gif-output-file as frames of txqr, for its phone reader apps, instead of fountain
+    /// chunks: the input (or --text) in plain `<offset>/<total>|<data>` parts of --chunk-size
+    /// bytes (default 100), uncompressed and without its filename
+    #[arg(long, requires = "gif_output_file", conflicts_with_all = ["image_output_dir", "terminal", "serve", "window", "framebuffer", "packets_output", "export_packets", "eink", "batch", "pipe", "split_size", "append", "transcode", "target_frames", "parity", "cover", "print_size", "dict", "delta_base", "dry_run"])]
+    txqr: bool,
+
+    /// Blank margin around each QR code of image and GIF output, in modules (default: 4, the
+    /// least the QR standard asks for). Widen it to tile codes on a page or show them on a dark
+    /// background
+    #[arg(long, value_name = "MODULES", default_value_t = QUIET_ZONE_MODULES, conflicts_with_all = ["terminal", "pipe", "packets_output", "export_packets"])]
+    quiet_zone: u32,
+
+    /// Never make QR codes above this version (1-40), shrinking the payload of each until it
+    /// fits, e.g. 20 for cheap hardware scanners and phone apps that fail on the densest codes
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i16).range(1..=40), conflicts_with_all = ["pipe", "packets_output", "export_packets", "append", "transcode"])]
+    max_version: Option<i16>,
+
+    /// Size each QR code is to be printed at, quiet zone included (e.g. `60mm`, `6cm`, `2.5in`;
+    /// a bare number is millimetres). Picks the pixel scale for --dpi, so printing the images
+    /// at that resolution without scaling gives codes of at most this size
+    #[arg(long, value_name = "LENGTH", value_parser = parse_length, conflicts_with_all = ["pixel_scale", "terminal", "pipe", "serve", "window", "framebuffer", "eink", "packets_output", "export_packets", "append"])]
+    print_size: Option<f64>,
+
+    /// Centre every image and GIF frame, cover sheet included, on a white canvas of this many
+    /// pixels (e.g. `1920x1080`), so fullscreen playback scales them all alike and the code
+    /// does not move between frames. Fails if the codes are larger
+    #[arg(long, value_name = "WxH", value_parser = parse_dimensions, conflicts_with_all = ["terminal", "pipe", "window", "framebuffer", "eink", "packets_output", "export_packets", "append", "txqr"])]
+    canvas: Option<(u32, u32)>,
+
+    /// Snap every pixel of --gif-output-file to pure black or white, so each frame is written
+    /// with exactly those two colours. QR frames already are; this also covers anything drawn
+    /// on them, such as a caption or cover sheet
+    #[arg(long, requires = "gif_output_file")]
+    strict_palette: bool,
+
+    /// Write each frame of --gif-output-file this many times in a row. A camera filming at a
+    /// slow shutter speed then gets several exposures of every code, which reads more reliably
+    /// than a longer --interval
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), requires = "gif_output_file")]
+    repeat_frames: u32,
+
+    /// Printer resolution for --print-size, in dots per inch
+    #[arg(long, default_value_t = DEFAULT_PRINT_DPI, value_parser = clap::value_parser!(u32).range(1..), requires = "print_size")]
+    dpi: u32,
+
+    /// Fail early, before reading the input or once it is compressed, if encoding would take
+    /// more memory than this (e.g. 512MB). With --split-size, parts are made smaller to fit
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_memory: Option<usize>,
+
+    /// Fail before rendering anything if image or GIF output would take more than N QR codes,
+    /// parity codes included, instead of writing thousands of frames nobody can capture
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["terminal", "pipe", "packets_output", "export_packets"])]
+    max_chunks: Option<u64>,
+
+    /// Render the codes of image or GIF output on this many threads (default: one per core),
+    /// e.g. 1 to leave the other cores of a shared server alone
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+
+    /// Compress with this preset dictionary, shared with the receiver out of band (e.g. one
+    /// made with --train-dict). Small files that resemble it, such as configs or certificates,
+    /// shrink far more. Decode with `fountain-decode --dict` and the same file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pipe", "transcode"])]
+    dict: Option<PathBuf>,
+
+    /// Send only what changed against this file, which the receiver already has (e.g. the
+    /// previous version of the input). Decode with `fountain-decode --delta-base` and the
+    /// same file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pipe", "transcode", "split_size", "train_dict"])]
+    delta_base: Option<PathBuf>,
+
+    /// Build a dictionary for --dict from the inputs, samples of the files to send, write it
+    /// to this file and exit. Lines that recur across the samples are kept, up to 32 KiB
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "serve", "window", "framebuffer", "packets_output", "export_packets", "batch", "pipe", "text", "clipboard", "split_size", "append", "transcode", "dict", "dry_run", "json"])]
+    train_dict: Option<PathBuf>,
+
+    /// Run chunking and payload-size fitting and print an estimate, without writing anything
+    #[arg(long, conflicts_with = "terminal")]
+    dry_run: bool,
+
+    /// Tuned payload size, pixel scale, interval and parity for a common way of sending:
+    /// printed `paper`, a `screen-to-phone` read live, a screen recorded as `video`, or codes
+    /// in a `terminal` (shown there if no other output is given). Flags given override it, and
+    /// it overrides the config file
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    /// Config file with default options (default: ~/.config/fountain/config.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Print a machine-readable JSON summary on stdout; progress messages go to stderr
+    #[arg(long, conflicts_with = "terminal")]
+    json: bool,
+
+    /// Increase log verbosity (-v for debug, -vv for trace)
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Decrease log verbosity (-q for warnings only, -qq for errors only)
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PacketOrderArg {
+    Systematic,
+    Interleaved,
+}
+
+impl From<PacketOrderArg> for PacketOrder {
+    fn from(order: PacketOrderArg) -> Self {
+        match order {
+            PacketOrderArg::Systematic => PacketOrder::SystematicFirst,
+            PacketOrderArg::Interleaved => PacketOrder::Interleaved,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PacketFormat {
+    Base64,
+    Base45,
+}
+
+impl From<PacketFormat> for PacketEncoding {
+    fn from(format: PacketFormat) -> Self {
+        match format {
+            PacketFormat::Base64 => PacketEncoding::Base64,
+            PacketFormat::Base45 => PacketEncoding::Base45,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Helper(HelperCommand),
+    /// Encode and decode a random payload in memory and report frames per second and bytes per
+    /// second, to compare chunk sizes and pixel scales
+    Bench(BenchArgs),
+    /// Show the QR codes of a GIF or image directory written earlier as a terminal carousel,
+    /// for a sender that has the output but not the original file
+    Play(PlayArgs),
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// Size of the payload (e.g. `64KiB`, `1MB`)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "64KiB")]
+    size: usize,
+
+    /// Payload size per QR code to measure; repeat to compare several (default: ~1400)
+    #[arg(short = 's', long = "chunk-size", value_name = "BYTES")]
+    chunk_sizes: Vec<usize>,
+
+    /// Pixel scale for QR code modules (default: 4)
+    #[arg(long)]
+    pixel_scale: Option<u32>,
+
+    /// Pass each frame through a simulated camera (rescaled, blurred and noisy) before
+    /// decoding it
+    #[arg(long)]
+    camera: bool,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    /// GIF, or directory of QR images or packet files, to show
+    input: PathBuf,
+
+    /// Interval in milliseconds between codes (default: 2000)
+    #[arg(short, long)]
+    interval: Option<u64>,
+
+    /// Show all QR codes at once without carousel
+    #[arg(long)]
+    no_carousel: bool,
+
+    /// Show two consecutive QR codes side by side on each frame when the terminal is wide
+    /// enough
+    #[arg(long)]
+    dual: bool,
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Cli::parse();
+    match &args.command {
+        Some(Command::Helper(command)) => return exit_code_for(command.run::<Cli>()),
+        Some(Command::Bench(bench)) => {
+            // The table goes to stdout; only warnings from the runs themselves are logged.
+            init_logging(0, 1);
+            return exit_code_for(run_bench(bench));
+        }
+        Some(Command::Play(play)) => {
+            init_logging(args.verbose, args.quiet);
+            return exit_code_for(run_play(play));
+        }
+        None => {}
+    }
+    init_logging(args.verbose, args.quiet);
+    exit_code_for(run(&args))
+}
+
+fn run(args: &Cli) -> Result<()> {
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(profile) = args.profile {
+        config = profile.config().or(config);
+    }
+    let mut options = EncodeOptions::new();
+    options.chunk_size = args.chunk_size.or(config.chunk_size);
+    options.target_frames = args.target_frames.map(|n| n as usize);
+    options.max_memory = args.max_memory;
+    options.parity = args.parity.or(config.parity).unwrap_or_default();
+    options.max_chunks = args.max_chunks.map(|n| n as usize);
+    options.threads = args.threads.map(|n| n as usize);
+    if let Some(interval) = args.interval.or(config.interval) {
+        options = options.interval_ms(interval);
+    }
+    if let Some(pixel_scale) = args.pixel_scale.or(config.pixel_scale) {
+        options = options.pixel_scale(pixel_scale);
+    }
+    if args.quiet_zone < QUIET_ZONE_MODULES {
+        warn!(
+            "A quiet zone of {} module(s) is narrower than the QR standard's {}; some readers miss such codes",
+            args.quiet_zone, QUIET_ZONE_MODULES
+        );
+    }
+    options = options
+        .quiet_zone(args.quiet_zone)
+        .cover(args.cover)
+        .receipt(args.receipt)
+        .markers(args.markers)
+        .sequence(args.sequence)
+        .dual(args.dual)
+        .packet_order(args.packet_order.into())
+        .strict_palette(args.strict_palette)
+        .repeat_frames(args.repeat_frames);
+    if let Some(caption) = &args.caption {
+        options = options.caption(caption);
+    }
+    if let Some(version) = args.max_version {
+        options = options.max_version(version);
+    }
+    if let Some(packets) = args.beacon_interval {
+        options = options.beacon_interval(packets as usize);
+    }
+    if let Some((width, height)) = args.canvas {
+        options = options.canvas(width, height);
+    }
+    if let Some(side_mm) = args.print_size {
+        options = options.print_size(side_mm, args.dpi);
+    }
+    #[cfg(feature = "color")]
+    if let Some(cells) = args.color_matrix {
+        options = options.color_matrix(fountain::ColorMatrix::new(cells)?);
+    }
+    if let Some(path) = &args.dict {
+        options = options.dictionary(read_dictionary(path)?);
+    }
+    if let Some(path) = &args.delta_base {
+        options = options.delta_base(read_delta_base(path)?);
+    }
+
+    if args.json && args.packets_output.as_deref() == Some(Path::new("-")) {
+        anyhow::bail!("--json cannot be used when --packets-output writes to stdout.");
+    }
+
+    if let Some(batch_dir) = &args.batch {
+        return run_batch(batch_dir, args, options);
+    }
+    if args.pipe {
+        return run_pipe(&Encoder::new(options), args.segment_size);
+    }
+    if args.txqr {
+        return run_txqr(args, options);
+    }
 
-    println!("Encoding file: {}", args.input.display());
-    if let Some(size) = args.chunk_size {
-        println!("Max payload size: {} bytes", size);
+    if let Some(text) = input_text(args)? {
+        return run_text(&text, args, &Encoder::new(options));
     }
 
-    if args.terminal {
-        run_terminal(
-            &args.input,
-            args.chunk_size,
-            args.interval,
-            args.no_carousel,
+    let inputs: Vec<&Path> = args.input.iter().map(PathBuf::as_path).collect();
+    if let Some(output) = &args.train_dict {
+        return run_train_dict(&inputs, output);
+    }
+
+    for input in &inputs {
+        info!("Encoding file: {}", input.display());
+    }
+    if let (None, Some(size)) = (options.target_frames, options.chunk_size) {
+        info!("Max payload size: {} bytes", size);
+    }
+    let encoder = Encoder::new(options);
+
+    if args.transcode {
+        let [input] = inputs[..] else {
+            anyhow::bail!("--transcode takes a single input.");
+        };
+        run_transcode(input, args, &encoder)?;
+    } else if args.dry_run {
+        let [input] = inputs[..] else {
+            anyhow::bail!("--dry-run takes a single input file.");
+        };
+        run_dry_run(input, &encoder, args.json)?;
+    } else if let Some(split_size) = args.split_size {
+        let [input] = inputs[..] else {
+            anyhow::bail!("--split-size takes a single input file.");
+        };
+        run_split(input, split_size, args, &encoder)?;
+    } else if args.append {
+        run_append(&inputs, args, &encoder)?;
+    } else if let Some(addr) = &args.serve {
+        run_serve(encoder.frames_for_files(&inputs)?, addr, &encoder)?;
+    } else if args.window {
+        run_window(encoder.frames_for_files(&inputs)?, &encoder)?;
+    } else if let Some(device) = &args.framebuffer {
+        run_framebuffer(encoder.frames_for_files(&inputs)?, device, &encoder)?;
+    } else if let Some(output) = &args.packets_output {
+        let result = write_packets(output, args.packet_encoding.into(), |writer, encoding| {
+            encoder.encode_files_to_packets(&inputs, writer, encoding)
+        })?;
+        report_packets(&inputs, &result, encoder.options(), args.json);
+    } else if let Some(output_dir) = &args.export_packets {
+        info!("Output directory: {}", output_dir.display());
+        let result = encoder.export_packets(&inputs, output_dir)?;
+        let warnings = report_result(&result, encoder.options());
+        if args.json {
+            print_json_summary(&inputs, "packet-files", &result, &warnings);
+        }
+    } else if let (Some(panel), Some(output_dir)) = (args.eink, &args.image_output_dir) {
+        run_eink(
+            &inputs,
+            output_dir,
+            panel.margin(args.eink_margin),
+            &encoder,
+            args.json,
         )?;
+    } else if args.terminal {
+        run_terminal(&inputs, &encoder, args.no_carousel, args.endless)?;
     } else if let Some(gif_output) = &args.gif_output_file {
-        run_gif(
-            &args.input,
-            gif_output,
-            args.chunk_size,
-            args.interval,
-            args.pixel_scale,
-        )?;
+        run_gif(&inputs, gif_output, &encoder, args.json)?;
     } else if let Some(images_output) = &args.image_output_dir {
-        run_images(
-            &args.input,
-            images_output,
-            args.chunk_size,
-            args.pixel_scale,
-        )?;
+        run_images(&inputs, images_output, &encoder, args.json)?;
+    } else if args.profile == Some(Profile::Terminal) {
+        run_terminal(&inputs, &encoder, args.no_carousel, args.endless)?;
     } else {
         anyhow::bail!(
             "No output method specified. Use --terminal, --image-output-dir, or --gif-output-file."
@@ -84,82 +582,750 @@ fn main() -> Result<()> {
 }
 
 fn run_terminal(
-    input_file: &Path,
-    chunk_size: Option<usize>,
-    interval: u64,
+    inputs: &[&Path],
+    encoder: &Encoder,
     no_carousel: bool,
+    endless: bool,
 ) -> Result<()> {
-    let data = encode_file_for_terminal(input_file, chunk_size)?;
+    if endless {
+        return run_endless(inputs, encoder);
+    }
+    let data = encoder.encode_files_for_terminal(inputs)?;
+    show_terminal(&data, encoder, no_carousel)
+}
 
-    println!("Generated {} QR code(s)", data.total);
+fn show_terminal(data: &TerminalQrData, encoder: &Encoder, no_carousel: bool) -> Result<()> {
+    let interval = encoder.options().interval_ms;
 
-    let requested_size = chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE);
+    info!("Generated {} QR code(s)", data.total);
+
+    let requested_size = encoder.options().chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE);
     if data.effective_size < requested_size {
-        println!(
+        warn!(
             "WARNING! Automatically reduced payload size to {} bytes to fit terminal.",
             data.effective_size
         );
     }
-    println!();
 
     if no_carousel || data.total == 1 {
-        display_qr_once(&data);
+        display_qr_once(data);
     } else {
-        println!("Starting carousel mode ({}ms interval)...", interval);
-        println!("Press Ctrl+C to exit");
+        info!("Starting carousel mode ({}ms interval)...", interval);
+        info!("Press Ctrl+C to exit");
         std::thread::sleep(std::time::Duration::from_secs(1));
-        display_qr_carousel(&data, interval);
+        display_qr_carousel(data, interval);
+    }
+
+    Ok(())
+}
+
+/// Text to send with --text or --clipboard, if either was given.
+fn input_text(args: &Cli) -> Result<Option<String>> {
+    if let Some(text) = &args.text {
+        return Ok(Some(text.clone()));
+    }
+    if !args.clipboard {
+        return Ok(None);
+    }
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .context("Failed to read text from the clipboard")?;
+    if text.is_empty() {
+        anyhow::bail!("The clipboard holds no text.");
+    }
+    Ok(Some(text))
+}
+
+fn run_text(text: &str, args: &Cli, encoder: &Encoder) -> Result<()> {
+    info!("Encoding {} bytes of text", text.len());
+    let data = text.as_bytes();
+    let mut timing = Vec::new();
+    let (mode, result) = if args.terminal {
+        let qr_data = encoder.encode_bytes_for_terminal(data, TEXT_FILENAME)?;
+        return show_terminal(&qr_data, encoder, args.no_carousel);
+    } else if let Some(addr) = &args.serve {
+        return run_serve(
+            encoder.frames_from_bytes(data, TEXT_FILENAME)?,
+            addr,
+            encoder,
+        );
+    } else if args.window {
+        return run_window(encoder.frames_from_bytes(data, TEXT_FILENAME)?, encoder);
+    } else if let Some(device) = &args.framebuffer {
+        let frames = encoder.frames_from_bytes(data, TEXT_FILENAME)?;
+        return run_framebuffer(frames, device, encoder);
+    } else if let Some(output) = &args.packets_output {
+        let result = write_packets(output, args.packet_encoding.into(), |writer, encoding| {
+            encoder.encode_bytes_to_packets(data, TEXT_FILENAME, writer, encoding)
+        })?;
+        report_packets(
+            &[Path::new(TEXT_FILENAME)],
+            &result,
+            encoder.options(),
+            args.json,
+        );
+        return Ok(());
+    } else if let Some(gif_output) = &args.gif_output_file {
+        info!("Output GIF: {}", gif_output.display());
+        timing = check_gif_timing(encoder.options());
+        (
+            "gif",
+            encoder.encode_bytes_to_gif(data, TEXT_FILENAME, gif_output)?,
+        )
+    } else if let Some(images_output) = &args.image_output_dir {
+        info!("Output directory: {}", images_output.display());
+        (
+            "images",
+            encoder.encode_bytes_to_images(data, TEXT_FILENAME, images_output)?,
+        )
+    } else if let Some(output_dir) = &args.export_packets {
+        info!("Output directory: {}", output_dir.display());
+        (
+            "packet-files",
+            encoder.export_bytes_packets(data, TEXT_FILENAME, output_dir)?,
+        )
+    } else if args.profile == Some(Profile::Terminal) {
+        let qr_data = encoder.encode_bytes_for_terminal(data, TEXT_FILENAME)?;
+        return show_terminal(&qr_data, encoder, args.no_carousel);
+    } else {
+        anyhow::bail!(
+            "No output method specified. Use --terminal, --image-output-dir, or --gif-output-file."
+        );
+    };
+
+    let mut warnings = report_result(&result, encoder.options());
+    warnings.extend(timing);
+    if args.json {
+        print_json_summary(&[Path::new(TEXT_FILENAME)], mode, &result, &warnings);
+    }
+    Ok(())
+}
+
+/// Writes the text or the single input as a GIF of txqr frames.
+fn run_txqr(args: &Cli, mut options: EncodeOptions) -> Result<()> {
+    let Some(gif_output) = &args.gif_output_file else {
+        anyhow::bail!("--txqr needs --gif-output-file.");
+    };
+    // A configured chunk size is meant for fountain chunks, not the txqr apps.
+    options.chunk_size = args.chunk_size;
+    let (input, data) = match input_text(args)? {
+        Some(text) => (Path::new(TEXT_FILENAME), text.into_bytes()),
+        None => {
+            let [input] = &args.input[..] else {
+                anyhow::bail!("--txqr takes a single input file.");
+            };
+            let data =
+                fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+            (input.as_path(), data)
+        }
+    };
+    info!("Output GIF: {} (txqr frames)", gif_output.display());
+    let encoder = Encoder::new(options);
+    let timing = check_gif_timing(encoder.options());
+    let result = encoder.encode_bytes_to_txqr_gif(&data, gif_output)?;
+    info!(
+        "Successfully created {} txqr frame(s) of {} bytes",
+        result.num_chunks, result.effective_size
+    );
+    if args.json {
+        print_json_summary(&[input], "txqr-gif", &result, &timing);
+    }
+    Ok(())
+}
+
+fn run_endless(inputs: &[&Path], encoder: &Encoder) -> Result<()> {
+    let frames = encoder.endless_terminal_frames(inputs)?;
+    let interval = encoder.options().interval_ms;
+
+    info!("Generated {} QR code(s) per round", frames.round_len());
+
+    let requested_size = encoder.options().chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE);
+    if frames.effective_size() < requested_size {
+        warn!(
+            "WARNING! Automatically reduced payload size to {} bytes to fit terminal.",
+            frames.effective_size()
+        );
+    }
+
+    info!(
+        "Starting endless carousel mode ({}ms interval)...",
+        interval
+    );
+    info!("Press Ctrl+C to exit");
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    display_qr_endless(frames, interval)?;
+
+    Ok(())
+}
+
+fn run_pipe(encoder: &Encoder, segment_size: usize) -> Result<()> {
+    let pipe = encoder.terminal_pipe(std::io::stdin().lock(), segment_size)?;
+    let interval = encoder.options().interval_ms;
+
+    let requested_size = encoder.options().chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE);
+    if pipe.payload_size() < requested_size {
+        warn!(
+            "WARNING! Automatically reduced payload size to {} bytes to fit terminal.",
+            pipe.payload_size()
+        );
+    }
+
+    info!(
+        "Starting pipe mode ({} bytes per segment, {}ms interval)...",
+        segment_size, interval
+    );
+    info!("Press Ctrl+C to exit");
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let segments = display_pipe(pipe, interval)?;
+    info!("Sent {} segment(s)", segments);
+
+    Ok(())
+}
+
+fn run_serve(frames: QrFrames, addr: &str, encoder: &Encoder) -> Result<()> {
+    // Ctrl+C stops rendering, or the server once it runs.
+    let cancel = CancelFlag::default();
+    let flag = cancel.clone();
+    ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))?;
+    let encoder = Encoder::new(encoder.options().clone().cancel_flag(cancel));
+
+    let server = encoder
+        .serve(frames, addr)
+        .with_context(|| format!("Failed to serve on {}", addr))?;
+    let local = server.local_addr()?;
+    if local.ip().is_unspecified() {
+        info!(
+            "Open http://<this machine's address>:{}/ in a browser on the display device",
+            local.port()
+        );
+    } else {
+        info!("Open http://{}/ in a browser on the display device", local);
+    }
+    info!("Press Ctrl+C to exit");
+    server.run()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "window"))]
+fn run_window(_frames: QrFrames, _encoder: &Encoder) -> Result<()> {
+    anyhow::bail!("--window needs the window feature; build with `--features window`.");
+}
+
+#[cfg(feature = "window")]
+fn run_window(frames: QrFrames, encoder: &Encoder) -> Result<()> {
+    let interval = encoder.options().interval_ms;
+    info!("Generated {} QR code(s)", frames.total());
+    info!("Starting window mode ({}ms interval)...", interval);
+    info!("Press any key or Ctrl+C to exit");
+    fountain::display_qr_window(frames, interval)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_framebuffer(_frames: QrFrames, _device: &Path, _encoder: &Encoder) -> Result<()> {
+    anyhow::bail!("--framebuffer is only available on Linux.");
+}
+
+#[cfg(target_os = "linux")]
+fn run_framebuffer(frames: QrFrames, device: &Path, encoder: &Encoder) -> Result<()> {
+    let interval = encoder.options().interval_ms;
+    info!("Generated {} QR code(s)", frames.total());
+    info!("Starting framebuffer mode ({}ms interval)...", interval);
+    info!("Press Ctrl+C to exit");
+    fountain::display_qr_framebuffer(frames, device, interval)?;
+    Ok(())
+}
+
+/// Opens `output` (`-` for stdout) and lets `encode` write the packets to it.
+fn write_packets(
+    output: &Path,
+    encoding: PacketEncoding,
+    encode: impl FnOnce(&mut dyn Write, PacketEncoding) -> fountain::Result<EncodeResult>,
+) -> Result<EncodeResult> {
+    if output == Path::new("-") {
+        return Ok(encode(&mut io::stdout().lock(), encoding)?);
+    }
+    info!("Output packets: {}", output.display());
+    let mut writer = BufWriter::new(fs::File::create(output)?);
+    let mut result = encode(&mut writer, encoding)?;
+    result.output_files = vec![output.to_string_lossy().to_string()];
+    Ok(result)
+}
+
+fn report_packets(inputs: &[&Path], result: &EncodeResult, options: &EncodeOptions, json: bool) {
+    let warnings = report_result(result, options);
+    if json {
+        print_json_summary(inputs, "packets", result, &warnings);
     }
+}
+
+fn run_images(inputs: &[&Path], output_dir: &Path, encoder: &Encoder, json: bool) -> Result<()> {
+    info!("Output directory: {}", output_dir.display());
 
+    let result = encoder.encode_files_to_images(inputs, output_dir)?;
+    let warnings = report_result(&result, encoder.options());
+
+    if json {
+        print_json_summary(inputs, "images", &result, &warnings);
+    }
     Ok(())
 }
 
-fn run_images(
-    input_file: &Path,
+fn run_eink(
+    inputs: &[&Path],
     output_dir: &Path,
-    chunk_size: Option<usize>,
-    pixel_scale: u32,
+    panel: EinkDisplay,
+    encoder: &Encoder,
+    json: bool,
 ) -> Result<()> {
-    println!("Output directory: {}", output_dir.display());
+    info!("Output directory: {}", output_dir.display());
+    info!("E-ink display: {}x{}", panel.width, panel.height);
+
+    let result = encoder.encode_to_eink(inputs, output_dir, panel)?;
+    let warnings = report_result(&result, encoder.options());
+    info!(
+        "Slideshow manifest: {}",
+        output_dir.join(SLIDESHOW_MANIFEST).display()
+    );
+
+    if json {
+        print_json_summary(inputs, "eink", &result, &warnings);
+    }
+    Ok(())
+}
+
+fn run_gif(inputs: &[&Path], output_file: &Path, encoder: &Encoder, json: bool) -> Result<()> {
+    info!("Output GIF: {}", output_file.display());
+    info!(
+        "GIF frame interval: {}ms",
+        gif_interval_ms(encoder.options().interval_ms)
+    );
+    let timing = check_gif_timing(encoder.options());
+
+    let result = encoder.encode_files_to_gif(inputs, output_file)?;
+    let mut warnings = report_result(&result, encoder.options());
+    warnings.extend(timing);
+
+    if json {
+        print_json_summary(inputs, "gif", &result, &warnings);
+    }
+    Ok(())
+}
+
+fn run_split(input: &Path, split_size: usize, args: &Cli, encoder: &Encoder) -> Result<()> {
+    info!("Splitting into parts of at most {} bytes", split_size);
+    let mut timing = Vec::new();
+    let (mode, results) = if let Some(gif_output) = &args.gif_output_file {
+        timing = check_gif_timing(encoder.options());
+        let results = encoder.encode_split_to_gif(input, gif_output, split_size)?;
+        ("split-gif", results)
+    } else if let Some(images_output) = &args.image_output_dir {
+        let results = encoder.encode_split_to_images(input, images_output, split_size)?;
+        ("split-images", results)
+    } else {
+        anyhow::bail!("--split-size needs --image-output-dir or --gif-output-file.");
+    };
+
+    let mut parts = Vec::with_capacity(results.len());
+    for (i, result) in results.iter().enumerate() {
+        info!("Part {}/{}:", i + 1, results.len());
+        let mut warnings = report_result(result, encoder.options());
+        warnings.extend(timing.iter().cloned());
+        parts.push(json_summary(result, json!({ "warnings": warnings })));
+    }
+    info!("Split into {} part(s)", results.len());
+
+    if args.json {
+        let summary = json!({
+            "input": input.to_string_lossy(),
+            "mode": mode,
+            "split_size": split_size,
+            "parts": parts,
+        });
+        println!("{}", summary);
+    }
+    Ok(())
+}
 
-    let result = encode_file_to_images(input_file, output_dir, chunk_size, pixel_scale)?;
+#[cfg(not(feature = "decode"))]
+fn run_bench(_args: &BenchArgs) -> Result<()> {
+    anyhow::bail!("bench decodes what it encodes, which needs the decode feature.");
+}
 
-    let requested_size = chunk_size.unwrap_or(MAX_PAYLOAD_SIZE);
-    if result.effective_size < requested_size && result.effective_size > 0 {
-        println!();
+#[cfg(feature = "decode")]
+fn run_bench(args: &BenchArgs) -> Result<()> {
+    let chunk_sizes = match args.chunk_sizes.as_slice() {
+        [] => vec![None],
+        sizes => sizes.iter().copied().map(Some).collect(),
+    };
+    let mut results = Vec::with_capacity(chunk_sizes.len());
+    if !args.json {
         println!(
-            "WARNING! Automatically reduced payload size to {} bytes to fit QR code capacity.",
-            result.effective_size
+            "{:>8} {:>7} {:>8} {:>11} {:>11} {:>11}",
+            "Payload", "Frames", "Scanned", "Encode fps", "Decode fps", "Bytes/s"
         );
     }
+    for chunk_size in chunk_sizes {
+        let mut options = EncodeOptions::new();
+        options.chunk_size = chunk_size;
+        if let Some(pixel_scale) = args.pixel_scale {
+            options = options.pixel_scale(pixel_scale);
+        }
+        let result = Encoder::new(options).bench(args.size, args.camera)?;
+        if args.json {
+            results.push(json_summary(
+                &result,
+                json!({
+                    "encode_fps": result.encode_fps(),
+                    "decode_fps": result.decode_fps(),
+                    "throughput_bytes_per_sec": result.throughput(),
+                }),
+            ));
+        } else {
+            println!(
+                "{:>8} {:>7} {:>8} {:>11.1} {:>11.1} {:>11.0}",
+                result.effective_size,
+                result.frames,
+                result.frames_scanned,
+                result.encode_fps(),
+                result.decode_fps(),
+                result.throughput()
+            );
+        }
+    }
+    if args.json {
+        println!("{}", json!({ "size": args.size, "runs": results }));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "decode"))]
+fn run_play(_args: &PlayArgs) -> Result<()> {
+    anyhow::bail!("play reads the codes of its input, which needs the decode feature.");
+}
+
+#[cfg(feature = "decode")]
+fn run_play(args: &PlayArgs) -> Result<()> {
+    let mut options = EncodeOptions::new().dual(args.dual);
+    if let Some(interval) = args.interval {
+        options = options.interval_ms(interval);
+    }
+    let encoder = Encoder::new(options);
+    let data = encoder
+        .transcode_for_terminal(&args.input)
+        .map_err(|err| match err {
+            fountain::FountainError::TerminalTooSmall => anyhow::Error::new(err).context(format!(
+                "The codes of {} are shown at the size they were written; zoom out or enlarge the terminal",
+                args.input.display()
+            )),
+            err => err.into(),
+        })?;
+    // The payload size is the input's, so nothing was reduced to fit.
+    let encoder = Encoder::new(encoder.options().clone().chunk_size(data.effective_size));
+    show_terminal(&data, &encoder, args.no_carousel)
+}
 
-    println!();
-    println!("Successfully created {} QR code(s)", result.num_chunks);
+#[cfg(not(feature = "decode"))]
+fn run_append(_inputs: &[&Path], _args: &Cli, _encoder: &Encoder) -> Result<()> {
+    anyhow::bail!("--append reads the existing output, which needs the decode feature.");
+}
+
+#[cfg(feature = "decode")]
+fn run_append(inputs: &[&Path], args: &Cli, encoder: &Encoder) -> Result<()> {
+    let (mode, result) = if let Some(gif_output) = &args.gif_output_file {
+        info!("Appending to GIF: {}", gif_output.display());
+        ("append-gif", encoder.append_to_gif(inputs, gif_output)?)
+    } else if let Some(images_output) = &args.image_output_dir {
+        info!("Appending to directory: {}", images_output.display());
+        (
+            "append-images",
+            encoder.append_to_images(inputs, images_output)?,
+        )
+    } else {
+        anyhow::bail!("--append needs --image-output-dir or --gif-output-file.");
+    };
+
+    // The payload size comes from the existing output, so it is not a reduction.
+    let options = encoder.options().clone().chunk_size(result.effective_size);
+    let warnings = report_result(&result, &options);
+    if args.json {
+        print_json_summary(inputs, mode, &result, &warnings);
+    }
     Ok(())
 }
 
-fn run_gif(
-    input_file: &Path,
-    output_file: &Path,
-    chunk_size: Option<usize>,
-    interval: u64,
-    pixel_scale: u32,
-) -> Result<()> {
-    println!("Output GIF: {}", output_file.display());
-    println!("GIF frame interval: {}ms", interval);
+/// Writes a dictionary trained on the files at `inputs` to `output`.
+fn run_train_dict(inputs: &[&Path], output: &Path) -> Result<()> {
+    let samples = inputs
+        .iter()
+        .map(|input| {
+            fs::read(input).with_context(|| format!("Failed to read sample {}", input.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let samples: Vec<&[u8]> = samples.iter().map(Vec::as_slice).collect();
+    let dictionary = train_dictionary(&samples, MAX_DICTIONARY_SIZE);
+    if dictionary.is_empty() {
+        anyhow::bail!("The samples share no lines to build a dictionary from.");
+    }
+    fs::write(output, &dictionary)?;
+    info!(
+        "Wrote a {} byte dictionary from {} sample(s) to {}",
+        dictionary.len(),
+        samples.len(),
+        output.display()
+    );
+    Ok(())
+}
 
-    let result = encode_file_to_gif(input_file, output_file, chunk_size, interval, pixel_scale)?;
+#[cfg(not(feature = "decode"))]
+fn run_transcode(_input: &Path, _args: &Cli, _encoder: &Encoder) -> Result<()> {
+    anyhow::bail!("--transcode reads the existing output, which needs the decode feature.");
+}
 
-    let requested_size = chunk_size.unwrap_or(MAX_PAYLOAD_SIZE);
-    if result.effective_size < requested_size && result.effective_size > 0 {
-        println!();
-        println!(
-            "WARNING! Automatically reduced payload size to {} bytes to fit QR code capacity.",
-            result.effective_size
+#[cfg(feature = "decode")]
+fn run_transcode(input: &Path, args: &Cli, encoder: &Encoder) -> Result<()> {
+    let mut warnings = Vec::new();
+    let (mode, result) = if let Some(addr) = &args.serve {
+        return run_serve(encoder.transcode_frames(input)?, addr, encoder);
+    } else if args.window {
+        return run_window(encoder.transcode_frames(input)?, encoder);
+    } else if let Some(device) = &args.framebuffer {
+        return run_framebuffer(encoder.transcode_frames(input)?, device, encoder);
+    } else if let Some(gif_output) = &args.gif_output_file {
+        info!("Output GIF: {}", gif_output.display());
+        warnings = check_gif_timing(encoder.options());
+        (
+            "transcode-gif",
+            encoder.transcode_to_gif(input, gif_output)?,
+        )
+    } else if let Some(images_output) = &args.image_output_dir {
+        info!("Output directory: {}", images_output.display());
+        (
+            "transcode-images",
+            encoder.transcode_to_images(input, images_output)?,
+        )
+    } else {
+        anyhow::bail!("--transcode needs --image-output-dir or --gif-output-file.");
+    };
+
+    info!(
+        "Re-rendered {} QR code(s) in {:.2?}",
+        result.num_chunks, result.duration
+    );
+    if args.json {
+        print_json_summary(&[input], mode, &result, &warnings);
+    }
+    Ok(())
+}
+
+fn run_dry_run(input_file: &Path, encoder: &Encoder, json: bool) -> Result<()> {
+    info!("Dry run: nothing will be written");
+
+    let estimate = encoder.estimate(input_file)?;
+    let interval = encoder.options().interval_ms;
+
+    let mut warnings = Vec::new();
+    if estimate.effective_size < requested_size(encoder.options()) {
+        let warning = format!(
+            "Payload size would be reduced to {} bytes to fit QR code capacity.",
+            estimate.effective_size
         );
+        warn!("WARNING! {}", warning);
+        warnings.push(warning);
     }
 
-    println!();
-    println!("Successfully created {} QR code(s)", result.num_chunks);
+    info!("QR codes:            {}", estimate.num_chunks);
+    info!("Payload size:        {} bytes", estimate.effective_size);
+    info!("Bytes per frame:     {}", estimate.frame_bytes);
+    info!(
+        "QR version:          {} ({}x{} px)",
+        estimate.qr_version, estimate.frame_width, estimate.frame_height
+    );
+    info!(
+        "Estimated GIF size:  {:.1} KiB",
+        estimate.estimated_gif_bytes as f64 / 1024.0
+    );
+    info!(
+        "Loop duration:       {:.1} s at {}ms per frame",
+        estimate.duration_ms as f64 / 1000.0,
+        interval
+    );
+
+    if json {
+        let summary = json_summary(
+            &estimate,
+            json!({
+                "input": input_file.to_string_lossy(),
+                "mode": "dry-run",
+                "warnings": warnings,
+            }),
+        );
+        println!("{}", summary);
+    }
     Ok(())
 }
+
+fn run_batch(batch_dir: &Path, args: &Cli, options: EncodeOptions) -> Result<()> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(batch_dir)
+        .with_context(|| format!("Failed to read batch directory {}", batch_dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    inputs.sort();
+
+    if inputs.is_empty() {
+        anyhow::bail!("No files found in batch directory {}", batch_dir.display());
+    }
+    info!(
+        "Batch encoding {} file(s) from {}",
+        inputs.len(),
+        batch_dir.display()
+    );
+
+    // Once the first file settles on a payload size, reuse it so every output shares the
+    // same QR version and frame size.
+    let mut encoder = Encoder::new(options.clone());
+    let mut summaries = Vec::with_capacity(inputs.len());
+    let mut failures = 0;
+    let timing = if args.gif_output_file.is_some() {
+        check_gif_timing(&options)
+    } else {
+        Vec::new()
+    };
+
+    for input in &inputs {
+        let name = input
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        info!("Encoding file: {}", input.display());
+
+        let result = if let Some(gif_dir) = &args.gif_output_file {
+            let output = gif_dir.join(format!("{}.gif", name));
+            encoder.encode_to_gif(input, &output)
+        } else if let Some(images_dir) = &args.image_output_dir {
+            encoder.encode_to_images(input, &images_dir.join(&name))
+        } else {
+            anyhow::bail!("Batch mode needs --image-output-dir or --gif-output-file.");
+        };
+
+        match result {
+            Ok(result) => {
+                let mut warnings = report_result(&result, &options);
+                warnings.extend(timing.iter().cloned());
+                if encoder.options().chunk_size.is_none() {
+                    encoder = Encoder::new(options.clone().chunk_size(result.effective_size));
+                }
+                summaries.push(json_summary(
+                    &result,
+                    json!({
+                        "input": input.to_string_lossy(),
+                        "warnings": warnings,
+                    }),
+                ));
+            }
+            Err(e) => {
+                failures += 1;
+                error!("Failed to encode {}: {:#}", input.display(), e);
+                summaries.push(json!({
+                    "input": input.to_string_lossy(),
+                    "error": format!("{:#}", e),
+                }));
+            }
+        }
+    }
+
+    info!("");
+    info!("Batch summary:");
+    for summary in &summaries {
+        match summary.get("num_chunks") {
+            Some(chunks) => info!(
+                "  {}: {} QR code(s), payload {} bytes",
+                summary["input"].as_str().unwrap_or_default(),
+                chunks,
+                summary["effective_payload_size"]
+            ),
+            None => info!(
+                "  {}: FAILED",
+                summary["input"].as_str().unwrap_or_default()
+            ),
+        }
+    }
+    info!(
+        "Encoded {} of {} file(s)",
+        inputs.len() - failures,
+        inputs.len()
+    );
+
+    if args.json {
+        let mode = if args.gif_output_file.is_some() {
+            "batch-gif"
+        } else {
+            "batch-images"
+        };
+        println!("{}", json!({ "mode": mode, "files": summaries }));
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{} of {} file(s) failed to encode", failures, inputs.len());
+    }
+    Ok(())
+}
+
+/// Logs how GIF players will treat the frame interval and returns the warnings raised.
+fn check_gif_timing(options: &EncodeOptions) -> Vec<String> {
+    let warnings = gif_timing_warnings(options.interval_ms);
+    for warning in &warnings {
+        warn!("WARNING! {}", warning);
+    }
+    warnings
+}
+
+/// Logs the human-readable summary for file outputs and returns the warnings raised.
+fn report_result(result: &EncodeResult, options: &EncodeOptions) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if result.effective_size < requested_size(options) && result.effective_size > 0 {
+        let warning = format!(
+            "Automatically reduced payload size to {} bytes to fit QR code capacity.",
+            result.effective_size
+        );
+        warn!("WARNING! {}", warning);
+        warnings.push(warning);
+    }
+
+    info!("Successfully created {} QR code(s)", result.num_chunks);
+    info!(
+        "{} bytes compressed to {} bytes in {:.2?} ({:.0} bytes/s)",
+        result.total_bytes,
+        result.compressed_bytes,
+        result.duration,
+        result.throughput()
+    );
+    warnings
+}
+
+fn print_json_summary(inputs: &[&Path], mode: &str, result: &EncodeResult, warnings: &[String]) {
+    let inputs: Vec<_> = inputs.iter().map(|input| input.to_string_lossy()).collect();
+    let summary = json_summary(
+        result,
+        json!({
+            "input": inputs[0],
+            "inputs": inputs,
+            "mode": mode,
+            "throughput_bytes_per_sec": result.throughput(),
+            "warnings": warnings,
+        }),
+    );
+    println!("{}", summary);
+}
+
+/// Payload size the user asked for, used to warn when fitting had to shrink it.
+fn requested_size(options: &EncodeOptions) -> usize {
+    match options.target_frames {
+        // The frame budget already picks a size that fits.
+        Some(_) => 0,
+        None => options.chunk_size.unwrap_or(MAX_PAYLOAD_SIZE),
+    }
+}
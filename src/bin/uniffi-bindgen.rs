@@ -0,0 +1,6 @@
+//! Generates the Kotlin and Swift bindings of `fountain::mobile`; see
+//! `script/rust/gen-bindings.sh`.
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
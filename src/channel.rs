@@ -0,0 +1,113 @@
+//! How well a capture kept up with the sender, measured from the frame sequence numbers of
+//! `EncodeOptions::sequence`.
+//!
+//! Packet indices alone cannot tell a frame the camera missed from one the sender never
+//! showed, since their order depends on the encoding. Sequence numbers count the frames in
+//! the order they are shown, so the gaps between those captured are the frames missed.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Share of the frames shown a capture should catch; below it, a slower interval is suggested.
+const GOOD_CATCH_SHARE: f64 = 0.9;
+
+/// What the sequence numbers of the frames captured tell about the channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelStats {
+    /// Frames the sender showed from the first one captured to the last, each loop counted.
+    pub frames_shown: usize,
+    /// Frames among them that were captured.
+    pub frames_caught: usize,
+    /// Most frames missed in a row.
+    pub longest_gap: usize,
+    /// Captures per second, when the source tells when each frame was taken.
+    pub capture_fps: Option<f64>,
+}
+
+impl ChannelStats {
+    /// Share of the frames shown that were captured.
+    pub fn caught_share(&self) -> f64 {
+        self.frames_caught as f64 / self.frames_shown.max(1) as f64
+    }
+
+    /// How many times longer the sender should show each frame (its `--interval`) for the
+    /// capture to catch nearly all of them. `None` if it already does.
+    pub fn interval_factor(&self) -> Option<usize> {
+        (self.caught_share() < GOOD_CATCH_SHARE)
+            .then(|| self.frames_shown.div_ceil(self.frames_caught.max(1)).max(2))
+    }
+}
+
+/// Collects the sequence numbers of a scan in the order they were captured.
+#[derive(Debug, Default)]
+pub(crate) struct ChannelMeter {
+    /// Sequence numbers captured, with when the frame was taken if known.
+    captures: Vec<(u32, Option<Duration>)>,
+}
+
+impl ChannelMeter {
+    pub(crate) fn record(&mut self, sequence: u32, timestamp: Option<Duration>) {
+        // Codes of one frame, or a frame captured twice, show the sender standing still.
+        if self.captures.last().map(|&(last, _)| last) != Some(sequence) {
+            self.captures.push((sequence, timestamp));
+        }
+    }
+
+    /// The stats, once at least two frames were numbered.
+    pub(crate) fn stats(&self) -> Option<ChannelStats> {
+        if self.captures.len() < 2 {
+            return None;
+        }
+        // The loop is assumed to end at the highest number seen.
+        let loop_len = self.captures.iter().map(|&(seq, _)| seq).max()? as usize + 1;
+        let mut frames_shown = 1;
+        let mut longest_gap = 0;
+        for pair in self.captures.windows(2) {
+            let (from, to) = (pair[0].0 as usize, pair[1].0 as usize);
+            let step = (to + loop_len - from) % loop_len;
+            frames_shown += step;
+            longest_gap = longest_gap.max(step.saturating_sub(1));
+        }
+
+        let timestamps: Vec<Duration> = self.captures.iter().filter_map(|&(_, t)| t).collect();
+        let capture_fps = match (timestamps.first(), timestamps.last()) {
+            (Some(first), Some(last)) if last > first => {
+                Some((timestamps.len() - 1) as f64 / (*last - *first).as_secs_f64())
+            }
+            _ => None,
+        };
+        Some(ChannelStats {
+            frames_shown,
+            frames_caught: self.captures.len(),
+            longest_gap,
+            capture_fps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_stats() {
+        let mut meter = ChannelMeter::default();
+        // Every other frame of a loop of 10, the wrap included, one frame read twice.
+        for (i, sequence) in [1, 3, 3, 5, 7, 9, 1, 3].into_iter().enumerate() {
+            meter.record(sequence, Some(Duration::from_millis(i as u64 * 100)));
+        }
+        let stats = meter.stats().unwrap();
+        assert_eq!(stats.frames_shown, 13);
+        assert_eq!(stats.frames_caught, 7);
+        assert_eq!(stats.longest_gap, 1);
+        assert_eq!(stats.interval_factor(), Some(2));
+        assert!(stats.capture_fps.is_some());
+
+        let mut meter = ChannelMeter::default();
+        (0..10).for_each(|sequence| meter.record(sequence, None));
+        let stats = meter.stats().unwrap();
+        assert_eq!((stats.frames_shown, stats.frames_caught), (10, 10));
+        assert_eq!(stats.interval_factor(), None);
+        assert_eq!(stats.capture_fps, None);
+    }
+}
@@ -1,9 +1,19 @@
-use anyhow::{anyhow, Result};
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-use sha2::{Digest, Sha256};
-use std::io::{Read, Write};
+use flate2::write::{DeflateEncoder, ZlibEncoder};
+use flate2::Compression as Level;
+use std::ffi::OsStr;
+use std::io::Write;
+
+pub use fountain_core::chunk::{Chunk, ChunkHeader, HEADER_SIZE, SEQUENCE_SIZE};
+pub use fountain_core::is_source_packet;
+pub use fountain_core::manifest::{Manifest, ManifestEntry};
+pub use fountain_core::packed::{
+    calculate_checksum, clean_filename, pack_data, CHECKSUM_SIZE, MAX_FILENAME_LEN,
+};
+pub use fountain_core::packed::{
+    dictionary_id, Compression, COMPRESSION_FIELD_SIZE, STORED_TRAILER_SIZE, ZLIB_FDICT,
+};
+
+use crate::error::Result;
 
 // Default chunk size for QR code generation
 // Smaller = smaller QR codes but more of them
@@ -16,153 +26,159 @@ use std::io::{Read, Write};
 //   ~1400 bytes -> ~73x73 modules (original default)
 pub const DEFAULT_PAYLOAD_SIZE: usize = 100; // Small default for terminal display
 pub const MAX_PAYLOAD_SIZE: usize = 1400; // Max for file output
-pub const CHECKSUM_SIZE: usize = 8;
-pub const HEADER_SIZE: usize = 11; // 1 (version) + 4 (transfer len) + 4 (esi) + 2 (packet size)
-
-#[derive(Debug, Clone)]
-pub struct ChunkHeader {
-    pub version: u8,
-    pub total: u32,       // Transfer Length
-    pub index: u32,       // ESI
-    pub packet_size: u16, // Packet Size
-}
+/// Extension of the packet files written by `Encoder::export_packets`, each holding one
+/// serialized chunk.
+pub const PACKET_FILE_EXTENSION: &str = "pkt";
+/// Extension of text files holding one packet per line, as base64 or the base45 a QR code holds.
+pub const PACKET_LINES_EXTENSION: &str = "txt";
+/// Smallest payload size accepted: the longest chunk header (piped streams and appended
+/// transfers) plus a packet of 4 bytes.
+pub const MIN_PAYLOAD_SIZE: usize = fountain_core::STREAM_HEADER_SIZE + 4;
 
-#[derive(Debug, Clone)]
-pub struct Chunk {
-    pub header: ChunkHeader,
-    pub data: Vec<u8>,
-}
+/// Leading bytes, at an offset, of formats whose content is compressed already: zip (and the
+/// formats built on it, such as docx, jar and apk), gzip, zstd, xz, 7z, rar, JPEG, PNG, WebP,
+/// MP4 and its relatives (MOV, HEIC), Matroska and WebM, Ogg and FLAC.
+const COMPRESSED_SIGNATURES: &[(usize, &[u8])] = &[
+    (0, b"PK\x03\x04"),
+    (0, b"\x1f\x8b"),
+    (0, b"\x28\xb5\x2f\xfd"),
+    (0, b"\xfd7zXZ\x00"),
+    (0, b"7z\xbc\xaf\x27\x1c"),
+    (0, b"Rar!\x1a\x07"),
+    (0, b"\xff\xd8\xff"),
+    (0, b"\x89PNG\r\n\x1a\n"),
+    (8, b"WEBP"),
+    (4, b"ftyp"),
+    (0, b"\x1a\x45\xdf\xa3"),
+    (0, b"OggS"),
+    (0, b"fLaC"),
+];
+/// Smallest content whose compressibility is sampled; smaller content costs little to
+/// compress outright.
+const SAMPLE_THRESHOLD: usize = 1 << 20;
+/// Samples taken, spread over the content, and the bytes of each.
+const SAMPLES: usize = 4;
+const SAMPLE_LEN: usize = 64 * 1024;
+/// Share of their size the samples must shrink to, at zlib's fastest level, for the content to
+/// be worth compressing.
+const WORTH_COMPRESSING: f64 = 0.97;
 
-impl ChunkHeader {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![0u8; HEADER_SIZE];
-        bytes[0] = self.version;
-        bytes[1..5].copy_from_slice(&self.total.to_be_bytes());
-        bytes[5..9].copy_from_slice(&self.index.to_be_bytes());
-        bytes[9..11].copy_from_slice(&self.packet_size.to_be_bytes());
-        bytes
+/// Whether `content`, the bytes of a file, is compressed already, so zlib would only spend
+/// time on it: it starts like a compressed format, or samples of it hardly shrink.
+pub fn looks_compressed(content: &[u8]) -> bool {
+    let signed = COMPRESSED_SIGNATURES.iter().any(|&(offset, signature)| {
+        content
+            .get(offset..offset + signature.len())
+            .is_some_and(|bytes| bytes == signature)
+    });
+    if signed || content.len() < SAMPLE_THRESHOLD {
+        return signed;
     }
-
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize)> {
-        if bytes.is_empty() {
-            return Err(anyhow!("Invalid header: empty"));
-        }
-        let version = bytes[0];
-        if version != 1 {
-            return Err(anyhow!("Unsupported chunk version: {}. Only Version 1 (RaptorQ) is supported.", version));
-        }
-
-        if bytes.len() < HEADER_SIZE {
-            return Err(anyhow!("Invalid header: too short"));
+    let step = (content.len() - SAMPLE_LEN) / (SAMPLES - 1);
+    let mut encoder = DeflateEncoder::new(Vec::new(), Level::fast());
+    for i in 0..SAMPLES {
+        let start = i * step;
+        // A new block per sample, so one sample's matches do not reach into the next.
+        if encoder
+            .write_all(&content[start..start + SAMPLE_LEN])
+            .is_err()
+            || encoder.flush().is_err()
+        {
+            return false;
         }
-        let total = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-        let index = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
-        let packet_size = u16::from_be_bytes([bytes[9], bytes[10]]);
-        Ok((
-            ChunkHeader {
-                version,
-                total,
-                index,
-                packet_size,
-            },
-            HEADER_SIZE,
-        ))
-    }
-}
-
-impl Chunk {
-    pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        let header_bytes = self.header.to_bytes();
-        let mut result = Vec::with_capacity(header_bytes.len() + self.data.len());
-        result.extend_from_slice(&header_bytes);
-        result.extend_from_slice(&self.data);
-        Ok(result)
-    }
-
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let (header, header_len) = ChunkHeader::from_bytes(bytes)?;
-        let data = bytes[header_len..].to_vec();
-
-        Ok(Chunk { header, data })
     }
+    encoder.finish().is_ok_and(|deflated| {
+        deflated.len() as f64 >= (SAMPLES * SAMPLE_LEN) as f64 * WORTH_COMPRESSING
+    })
 }
 
+/// Compresses packed data for a transfer, led by the field naming its compression. Data that
+/// zlib does not shrink, such as a JPEG or an archive that is compressed already, is
+/// [`store`]d.
 pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    let level = Level::best();
+    let field = Compression::Zlib.field(level.level() as u8);
+    let mut encoder = ZlibEncoder::new(field.to_vec(), level);
     encoder.write_all(data)?;
-    Ok(encoder.finish()?)
+    let compressed = encoder.finish()?;
+    if compressed.len() < COMPRESSION_FIELD_SIZE + data.len() + STORED_TRAILER_SIZE {
+        return Ok(compressed);
+    }
+    Ok(store(data))
 }
 
-pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
-    let mut decoder = ZlibDecoder::new(data);
-    let mut result = Vec::new();
-    decoder.read_to_end(&mut result)?;
-    Ok(result)
+/// Packed data for a transfer as it is, led by the field naming no compression and followed by
+/// its Adler-32 as a zlib stream would be: the content's checksum does not cover the filename,
+/// so the trailer is what catches a corrupted name.
+pub fn store(data: &[u8]) -> Vec<u8> {
+    let mut stored = Compression::None.field(0).to_vec();
+    stored.extend_from_slice(data);
+    stored.extend_from_slice(&adler2::adler32_slice(data).to_be_bytes());
+    stored
 }
 
-pub fn calculate_checksum(data: &[u8]) -> Vec<u8> {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    result[..CHECKSUM_SIZE].to_vec()
+/// Like [`compress`], with a preset dictionary shared with the receiver out of band: matches
+/// may reach back into it, so small files that resemble it shrink far more. Only the
+/// dictionary's ID travels, in the zlib header.
+pub fn compress_with_dictionary(data: &[u8], dictionary: &[u8]) -> Result<Vec<u8>> {
+    // Deflating the dictionary first primes the window; a sync flush ends it on a byte
+    // boundary, so its output can be dropped and the rest inflated after the dictionary.
+    let level = Level::best();
+    let mut encoder = DeflateEncoder::new(Vec::new(), level);
+    encoder.write_all(dictionary)?;
+    encoder.flush()?;
+    let primed = encoder.get_ref().len();
+    encoder.write_all(data)?;
+    let deflated = encoder.finish()?;
+
+    let cmf = 0x78;
+    let flg = 0xc0 | ZLIB_FDICT;
+    let flg = flg + (31 - (u16::from_be_bytes([cmf, flg]) % 31) as u8) % 31;
+    let mut compressed = Vec::with_capacity(COMPRESSION_FIELD_SIZE + deflated.len() - primed + 10);
+    compressed.extend_from_slice(&Compression::Zlib.field(level.level() as u8));
+    compressed.extend_from_slice(&[cmf, flg]);
+    compressed.extend_from_slice(&dictionary_id(dictionary).to_be_bytes());
+    compressed.extend_from_slice(&deflated[primed..]);
+    compressed.extend_from_slice(&adler2::adler32_slice(data).to_be_bytes());
+    Ok(compressed)
 }
 
-// Pack data: [Checksum 8B] [Filename] [\0] [Content]
-pub fn pack_data(data: &[u8], filename: &str) -> Vec<u8> {
-    let checksum = calculate_checksum(data);
-    // Sanitize filename: remove null bytes
-    let clean_filename = filename.replace('\0', "");
-
-    let mut packed = Vec::with_capacity(CHECKSUM_SIZE + clean_filename.len() + 1 + data.len());
-    packed.extend_from_slice(&checksum);
-    packed.extend_from_slice(clean_filename.as_bytes());
-    packed.push(0); // Null terminator
-    packed.extend_from_slice(data);
-    packed
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(fountain_core::decompress(data)?)
 }
 
 // Unpack data: -> (Filename, Content)
 pub fn unpack_data(packed: &[u8]) -> Result<(String, Vec<u8>)> {
-    if packed.len() < CHECKSUM_SIZE + 2 {
-        // Min: Checksum + 1 char + \0
-        return Err(anyhow!("Invalid packed data: too short"));
-    }
+    Ok(fountain_core::unpack_data(packed)?)
+}
 
-    let expected_checksum = &packed[..CHECKSUM_SIZE];
+/// Name to embed for a file named `name` on the local system, cleaned by [`clean_filename`].
+/// Bytes that are not UTF-8 are escaped as `%XX` on Unix, so distinct names stay distinct, and
+/// replaced with U+FFFD elsewhere.
+pub fn embedded_filename(name: &OsStr) -> String {
+    #[cfg(unix)]
+    let name = {
+        use std::fmt::Write as _;
+        use std::os::unix::ffi::OsStrExt;
 
-    let mut null_pos = None;
-    for i in CHECKSUM_SIZE..packed.len() {
-        if packed[i] == 0 {
-            null_pos = Some(i);
-            break;
+        let mut escaped = String::new();
+        for chunk in name.as_bytes().utf8_chunks() {
+            escaped.push_str(chunk.valid());
+            for byte in chunk.invalid() {
+                let _ = write!(escaped, "%{:02X}", byte);
+            }
         }
-    }
-
-    let null_idx =
-        null_pos.ok_or_else(|| anyhow!("Invalid packed data: missing filename terminator"))?;
-
-    let filename_bytes = &packed[CHECKSUM_SIZE..null_idx];
-    let filename = std::str::from_utf8(filename_bytes)
-        .map_err(|_| anyhow!("Invalid filename: not valid UTF-8"))?
-        .to_string();
-
-    let content = packed[null_idx + 1..].to_vec();
-
-    let actual_checksum = calculate_checksum(&content);
-    if actual_checksum != expected_checksum {
-        return Err(anyhow!(
-            "Checksum mismatch: expected {:?}, got {:?}",
-            expected_checksum,
-            actual_checksum
-        ));
-    }
-
-    Ok((filename, content))
+        escaped
+    };
+    #[cfg(not(unix))]
+    let name = name.to_string_lossy();
+    clean_filename(&name)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::FountainError;
 
     #[test]
     fn test_pack_unpack() {
@@ -174,5 +190,136 @@ mod tests {
 
         assert_eq!(name, filename);
         assert_eq!(content, data);
+
+        let mut corrupted = packed.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            unpack_data(&corrupted),
+            Err(FountainError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_odd_filenames() {
+        for name in [
+            "报告.pdf",
+            "🎉 party.txt",
+            "../etc/passwd",
+            "tab\there",
+            "..",
+        ] {
+            let (unpacked, _) = unpack_data(&pack_data(b"x", name)).unwrap();
+            assert_eq!(unpacked, clean_filename(name));
+            assert!(!unpacked.contains('/') && !unpacked.chars().any(char::is_control));
+        }
+        assert_eq!(clean_filename("报告.pdf"), "报告.pdf");
+        assert_eq!(clean_filename("../etc/passwd"), ".._etc_passwd");
+        assert_eq!(clean_filename(".."), "file");
+
+        let long = format!("{}.tar", "é".repeat(200));
+        let short = clean_filename(&long);
+        assert!(short.len() <= MAX_FILENAME_LEN && short.ends_with("é.tar"));
+        assert_eq!(clean_filename(&short), short);
+        let exact = "a".repeat(MAX_FILENAME_LEN);
+        assert_eq!(clean_filename(&exact), exact);
+
+        // Another encoder may embed any bytes.
+        let mut packed = pack_data(b"x", "a");
+        packed[CHECKSUM_SIZE] = 0xff;
+        assert_eq!(unpack_data(&packed).unwrap().0, "\u{fffd}");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let name = OsStr::from_bytes(b"caf\xe9.txt");
+            assert_eq!(embedded_filename(name), "caf%E9.txt");
+        }
+    }
+
+    #[test]
+    fn test_compression_field() {
+        let text = b"text shrinks ".repeat(20);
+        let compressed = compress(&text).unwrap();
+        assert_eq!(
+            Compression::parse(&compressed).unwrap().0,
+            Compression::Zlib
+        );
+        assert_eq!(decompress(&compressed).unwrap(), text);
+
+        // Incompressible data is stored, and costs only the field and the trailer.
+        let noise: Vec<u8> = (0..500).map(|_| rand::random::<u8>()).collect();
+        let mut stored = compress(&noise).unwrap();
+        assert_eq!(
+            stored.len(),
+            COMPRESSION_FIELD_SIZE + noise.len() + STORED_TRAILER_SIZE
+        );
+        assert_eq!(decompress(&stored).unwrap(), noise);
+        stored[COMPRESSION_FIELD_SIZE] ^= 1;
+        assert!(decompress(&stored).is_err());
+
+        // Transfers made before the field are bare zlib streams.
+        let mut legacy = ZlibEncoder::new(Vec::new(), Level::best());
+        legacy.write_all(&text).unwrap();
+        assert_eq!(decompress(&legacy.finish().unwrap()).unwrap(), text);
+
+        let mut unknown = compressed.clone();
+        unknown[0] = 0xef;
+        assert!(matches!(
+            decompress(&unknown),
+            Err(FountainError::UnsupportedCompression(14))
+        ));
+    }
+
+    #[test]
+    fn test_looks_compressed() {
+        assert!(looks_compressed(b"PK\x03\x04\x14\x00\x00\x00"));
+        assert!(looks_compressed(b"\x00\x00\x00\x20ftypisom"));
+        assert!(!looks_compressed(b"PK"));
+
+        // Large content is sampled: text shrinks, noise does not.
+        let text = b"Plain text that zlib shrinks well. ".repeat(60_000);
+        assert!(!looks_compressed(&text));
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..2 << 20)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        assert!(looks_compressed(&noise));
+        assert_eq!(
+            Compression::parse(&store(&noise)).unwrap().0,
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn test_compress_with_dictionary() {
+        let dictionary =
+            b"[server]\nlisten = 0.0.0.0:443\ncertificate = /etc/ssl/server.pem\n".repeat(4);
+        let data = b"[server]\nlisten = 0.0.0.0:443\ncertificate = /etc/ssl/other.pem\n";
+
+        let compressed = compress_with_dictionary(data, &dictionary).unwrap();
+        // Plain zlib does not shrink data this small, so compress() would store it.
+        let mut plain = ZlibEncoder::new(Vec::new(), Level::best());
+        plain.write_all(data).unwrap();
+        let plain = plain.finish().unwrap();
+        assert!(compressed[COMPRESSION_FIELD_SIZE..].len() < plain.len() / 2);
+        let dictionaries = [b"unrelated".to_vec(), dictionary.clone()];
+        assert_eq!(
+            fountain_core::decompress_with_dictionaries(&compressed, &dictionaries).unwrap(),
+            data
+        );
+        assert!(matches!(
+            decompress(&compressed),
+            Err(FountainError::MissingDictionary(id)) if id == dictionary_id(&dictionary)
+        ));
+        assert_eq!(
+            fountain_core::decompress_with_dictionaries(&compress(data).unwrap(), &dictionaries)
+                .unwrap(),
+            data
+        );
     }
 }
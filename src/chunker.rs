@@ -0,0 +1,300 @@
+use fountain_core::encode_chunks;
+#[cfg(feature = "encode")]
+use fountain_core::{
+    encode_round_chunks, encode_segment_chunks, MULTI_FILE_HEADER_SIZE, STREAM_HEADER_SIZE,
+};
+#[cfg(all(feature = "encode", feature = "decode"))]
+use fountain_core::{encode_tagged_chunks, TAGGED_HEADER_SIZE};
+use tracing::debug;
+#[cfg(feature = "encode")]
+use tracing::warn;
+
+#[cfg(feature = "encode")]
+use crate::chunk::Manifest;
+use crate::chunk::{Chunk, HEADER_SIZE, MIN_PAYLOAD_SIZE};
+use crate::error::{FountainError, Result};
+use crate::qr::{fits_in_version, generate_qr_image};
+
+/// Smallest payload size tried when fitting chunks into QR code images.
+pub(crate) const IMG_MIN_PAYLOAD_SIZE: usize = 100;
+pub(crate) const IMG_REDUCTION_STEP: usize = 50;
+pub(crate) const IMG_REDUNDANCY_FACTOR: f64 = 1.5;
+/// Redundancy of terminal carousels, which a receiver may join at any frame.
+#[cfg(feature = "encode")]
+pub(crate) const TERMINAL_REDUNDANCY_FACTOR: f64 = 2.0;
+
+/// GIF frame delays are stored in hundredths of a second.
+pub(crate) const GIF_DELAY_UNIT_MS: u64 = 10;
+/// Shortest GIF frame delay that viewers show as written; most slow shorter ones down to 100ms.
+pub const MIN_GIF_INTERVAL_MS: u64 = 20;
+/// Longest frame delay a GIF can store.
+pub(crate) const MAX_GIF_INTERVAL_MS: u64 = u16::MAX as u64 * GIF_DELAY_UNIT_MS;
+
+/// The frame delay a GIF is written with for a requested interval: rounded to the 10ms steps
+/// GIFs store, and at least [`MIN_GIF_INTERVAL_MS`].
+pub fn gif_interval_ms(interval_ms: u64) -> u64 {
+    let rounded =
+        interval_ms.saturating_add(GIF_DELAY_UNIT_MS / 2) / GIF_DELAY_UNIT_MS * GIF_DELAY_UNIT_MS;
+    rounded.clamp(MIN_GIF_INTERVAL_MS, MAX_GIF_INTERVAL_MS)
+}
+
+/// Memory an encode takes for `input_bytes` of files that compress to `compressed_bytes`: the
+/// files, their compressed form, the RaptorQ encoder's copy of it and the packets it makes.
+#[cfg(feature = "encode")]
+pub(crate) fn encode_memory(
+    input_bytes: usize,
+    compressed_bytes: usize,
+    redundancy_factor: f64,
+) -> usize {
+    let packets = (compressed_bytes as f64 * redundancy_factor) as usize;
+    input_bytes + 2 * compressed_bytes + packets
+}
+
+/// RaptorQ packet size for a payload size. Packet size must be even for RaptorQ.
+pub(crate) fn packet_size_for(payload_size: usize) -> u16 {
+    packet_size_after_header(payload_size, HEADER_SIZE)
+}
+
+fn packet_size_after_header(payload_size: usize, header_size: usize) -> u16 {
+    let packet_size = (payload_size.saturating_sub(header_size)) as u16;
+    packet_size - (packet_size % 2)
+}
+
+/// Number of repair packets requested per source block for a given redundancy factor.
+pub(crate) fn repair_packets_for(data_len: usize, packet_size: u16, redundancy_factor: f64) -> u32 {
+    let source_packets = (data_len as f64 / packet_size as f64).ceil() as u32;
+    let total_packets = (source_packets as f64 * redundancy_factor).ceil() as u32;
+    total_packets.max(source_packets + 2)
+}
+
+/// Number of chunks `build_chunks` produces, computed without running the encoder.
+#[cfg(feature = "encode")]
+pub(crate) fn chunk_count(data_len: usize, packet_size: u16, redundancy_factor: f64) -> usize {
+    let config =
+        raptorq::ObjectTransmissionInformation::with_defaults(data_len as u64, packet_size);
+    let source_symbols = (data_len as u64).div_ceil(config.symbol_size() as u64) as usize;
+    let repair_packets = repair_packets_for(data_len, packet_size, redundancy_factor) as usize;
+    source_symbols + config.source_blocks() as usize * repair_packets
+}
+
+#[cfg(feature = "wasm")]
+pub(crate) fn build_chunks(
+    compressed: &[u8],
+    packet_size: u16,
+    redundancy_factor: f64,
+) -> Vec<Chunk> {
+    let repair_packets = repair_packets_for(compressed.len(), packet_size, redundancy_factor);
+    encode_chunks(compressed, packet_size, repair_packets)
+}
+
+/// Chunks of every file in a transfer, one file after another. A single file keeps the version 1
+/// header; several files carry their index in the longer version 2 header, which comes out of
+/// the packet so that frames keep the same payload size.
+#[cfg(feature = "encode")]
+pub(crate) fn build_transfer_chunks(
+    files: &[Vec<u8>],
+    payload_size: usize,
+    redundancy_factor: f64,
+) -> Vec<Chunk> {
+    build_round_chunks(files, payload_size, redundancy_factor, 0)
+}
+
+/// Like [`build_transfer_chunks`], for round `round` of an endless carousel: later rounds hold
+/// fresh repair packets only.
+#[cfg(feature = "encode")]
+pub(crate) fn build_round_chunks(
+    files: &[Vec<u8>],
+    payload_size: usize,
+    redundancy_factor: f64,
+    round: u32,
+) -> Vec<Chunk> {
+    let packet_size = if files.len() > 1 {
+        packet_size_after_header(payload_size, MULTI_FILE_HEADER_SIZE)
+    } else {
+        packet_size_for(payload_size)
+    };
+    files
+        .iter()
+        .enumerate()
+        .flat_map(|(file_id, compressed)| {
+            let repair_packets =
+                repair_packets_for(compressed.len(), packet_size, redundancy_factor);
+            encode_round_chunks(
+                compressed,
+                packet_size,
+                repair_packets,
+                file_id as u8,
+                files.len() as u8,
+                round,
+            )
+        })
+        .collect()
+}
+
+/// Chunks of the manifest of a transfer of several files, to send ahead of their packets. A
+/// single file gets none, as its name comes with its packets.
+#[cfg(feature = "encode")]
+pub(crate) fn build_manifest_chunks(files: &[(&[u8], &str)], payload_size: usize) -> Vec<Chunk> {
+    if files.len() < 2 {
+        return Vec::new();
+    }
+    build_beacon_chunks(files, payload_size)
+}
+
+/// Chunks of the manifest of any transfer, a single file included, to send as a beacon
+/// between its packets (see `EncodeOptions::beacon_interval`).
+#[cfg(feature = "encode")]
+pub(crate) fn build_beacon_chunks(files: &[(&[u8], &str)], payload_size: usize) -> Vec<Chunk> {
+    Manifest::new(files)
+        .to_chunks(0, payload_size)
+        .unwrap_or_else(|| {
+            warn!(
+                "Manifest of {} files takes too many frames; sending none",
+                files.len()
+            );
+            Vec::new()
+        })
+}
+
+/// `manifest` ahead of `chunks`, and again after every `every` of them.
+#[cfg(feature = "encode")]
+pub(crate) fn insert_beacons(chunks: Vec<Chunk>, manifest: &[Chunk], every: usize) -> Vec<Chunk> {
+    let beacons = chunks.len().div_ceil(every);
+    let mut with_beacons = Vec::with_capacity(chunks.len() + beacons * manifest.len());
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        if i % every == 0 {
+            with_beacons.extend_from_slice(manifest);
+        }
+        with_beacons.push(chunk);
+    }
+    with_beacons
+}
+
+/// `count` repair packets that none of the chunks of [`build_transfer_chunks`] carry, taken
+/// evenly from the later rounds so that every file and source block gets its share.
+#[cfg(feature = "encode")]
+pub(crate) fn build_parity_chunks(
+    files: &[Vec<u8>],
+    payload_size: usize,
+    redundancy_factor: f64,
+    count: usize,
+) -> Vec<Chunk> {
+    let mut chunks = Vec::with_capacity(count);
+    let mut round = 1;
+    while chunks.len() < count {
+        let round_chunks = build_round_chunks(files, payload_size, redundancy_factor, round);
+        let wanted = count - chunks.len();
+        if wanted >= round_chunks.len() {
+            chunks.extend(round_chunks);
+        } else {
+            let len = round_chunks.len();
+            chunks.extend((0..wanted).map(|i| round_chunks[i * len / wanted].clone()));
+        }
+        round += 1;
+    }
+    chunks
+}
+
+/// Chunks of one segment of a piped stream, in the version 3 header. Like the multi-file
+/// header, the extra bytes come out of the packet so frames keep the same payload size.
+#[cfg(feature = "encode")]
+pub(crate) fn build_segment_chunks(
+    compressed: &[u8],
+    payload_size: usize,
+    redundancy_factor: f64,
+    segment: u32,
+) -> Vec<Chunk> {
+    let packet_size = packet_size_after_header(payload_size, STREAM_HEADER_SIZE);
+    let repair_packets = repair_packets_for(compressed.len(), packet_size, redundancy_factor);
+    encode_segment_chunks(compressed, packet_size, repair_packets, segment)
+}
+
+/// Chunks of transfer `transfer` of an output that holds several, in the version 4 header
+/// and at the payload size of the transfers already in it.
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub(crate) fn build_tagged_chunks(
+    files: &[Vec<u8>],
+    payload_size: usize,
+    redundancy_factor: f64,
+    transfer: u16,
+) -> Vec<Chunk> {
+    let packet_size = packet_size_after_header(payload_size, TAGGED_HEADER_SIZE);
+    files
+        .iter()
+        .enumerate()
+        .flat_map(|(file_id, compressed)| {
+            let repair_packets =
+                repair_packets_for(compressed.len(), packet_size, redundancy_factor);
+            encode_tagged_chunks(
+                compressed,
+                packet_size,
+                repair_packets,
+                transfer,
+                file_id as u8,
+                files.len() as u8,
+            )
+        })
+        .collect()
+}
+
+/// Finds the largest payload size, starting from `start_size`, whose chunks pass `fit_check_fn`.
+pub(crate) fn fit_payload_size<F>(
+    compressed: &[u8],
+    start_size: usize,
+    min_size: usize,
+    reduction_step: usize,
+    fit_check_fn: F,
+) -> Result<usize>
+where
+    F: Fn(&[u8]) -> Result<bool>,
+{
+    // Below this, a chunk with a longer header would be left without room for its packet,
+    // which RaptorQ cannot encode.
+    if start_size < MIN_PAYLOAD_SIZE {
+        return Err(FountainError::PayloadTooSmall {
+            size: start_size,
+            min_size: MIN_PAYLOAD_SIZE,
+        });
+    }
+    let min_size = min_size.max(MIN_PAYLOAD_SIZE);
+    let mut current_size = start_size;
+
+    loop {
+        let packet_size = packet_size_for(current_size);
+
+        // Generate one packet to test fit
+        let test_chunks = encode_chunks(compressed, packet_size, 1);
+        if let Some(chunk) = test_chunks.first() {
+            let encoded = chunk.to_text()?;
+
+            if fit_check_fn(encoded.as_bytes())? {
+                return Ok(current_size);
+            }
+        }
+
+        if current_size > min_size {
+            debug!(
+                "Payload size {} bytes does not fit, trying a smaller size",
+                current_size
+            );
+            current_size = current_size.saturating_sub(reduction_step).max(min_size);
+        } else {
+            break;
+        }
+    }
+
+    Err(FountainError::QrCapacityExceeded { min_size })
+}
+
+/// Fit check for image output: whether the encoded chunk renders as a QR code at all, and
+/// with `max_version`, as one of at most that version.
+pub(crate) fn fits_in_image(
+    encoded: &[u8],
+    pixel_scale: u32,
+    max_version: Option<i16>,
+) -> Result<bool> {
+    if max_version.is_some_and(|max| !fits_in_version(encoded, max)) {
+        return Ok(false);
+    }
+    Ok(generate_qr_image(encoded, None, pixel_scale).is_ok())
+}
@@ -0,0 +1,374 @@
+//! Helpers shared by the `fountain-encode` and `fountain-decode` binaries.
+
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitCode};
+use std::time::Duration;
+use tracing::{debug, info, Level};
+
+use crate::chunk::dictionary_id;
+use crate::error::FountainError;
+
+/// Packaging helpers available as subcommands on every binary.
+#[derive(Subcommand)]
+pub enum HelperCommand {
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
+    /// Print the man page (roff) to stdout
+    Manpage,
+}
+
+impl HelperCommand {
+    /// Runs the helper against the CLI definition of the calling binary.
+    pub fn run<C: CommandFactory>(&self) -> Result<()> {
+        let mut cmd = C::command();
+        match self {
+            HelperCommand::Completions { shell } => {
+                let name = cmd.get_name().to_string();
+                clap_complete::generate(*shell, &mut cmd, name, &mut io::stdout());
+            }
+            HelperCommand::Manpage => {
+                clap_mangen::Man::new(cmd).render(&mut io::stdout())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a byte size such as `50MB`, `64KiB` or `1000`. `K`, `M` and `G` (optionally followed
+/// by `B`) are powers of 1000; `KiB`, `MiB` and `GiB` are powers of 1024.
+pub fn parse_size(s: &str) -> std::result::Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: usize = number.parse().map_err(|_| format!("invalid size: {}", s))?;
+    let multiplier: usize = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1_000,
+        "M" | "MB" => 1_000_000,
+        "G" | "GB" => 1_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        _ => return Err(format!("unknown size unit: {}", unit)),
+    };
+    match number.checked_mul(multiplier) {
+        Some(0) => Err("size must be greater than 0".to_string()),
+        Some(size) => Ok(size),
+        None => Err(format!("size too large: {}", s)),
+    }
+}
+
+/// Parses a duration such as `90`, `30s`, `500ms`, `5m` or `1h`. A bare number is seconds.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", s))?;
+    let millis: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "ms" => 1,
+        "" | "s" => 1_000,
+        "m" | "min" => 60_000,
+        "h" => 3_600_000,
+        _ => return Err(format!("unknown duration unit: {}", unit)),
+    };
+    number
+        .checked_mul(millis)
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("duration too long: {}", s))
+}
+
+/// Parses a printed length such as `60mm`, `6cm`, `2.5in` or `60` into millimetres. A bare
+/// number is millimetres.
+pub fn parse_length(s: &str) -> std::result::Result<f64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid length: {}", s))?;
+    let mm_per_unit = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "mm" => 1.0,
+        "cm" => 10.0,
+        "in" | "\"" => 25.4,
+        _ => return Err(format!("unknown length unit: {}", unit)),
+    };
+    match number * mm_per_unit {
+        mm if mm > 0.0 && mm.is_finite() => Ok(mm),
+        _ => Err("length must be greater than 0".to_string()),
+    }
+}
+
+/// Parses a frame size in pixels such as `1920x1080` into its width and height.
+pub fn parse_dimensions(s: &str) -> std::result::Result<(u32, u32), String> {
+    let (width, height) = s
+        .trim()
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, such as 1920x1080: {}", s))?;
+    let side = |side: &str| match side.trim().parse::<u32>() {
+        Ok(0) => Err("width and height must be greater than 0".to_string()),
+        Ok(side) => Ok(side),
+        Err(_) => Err(format!("invalid size: {}", s)),
+    };
+    Ok((side(width)?, side(height)?))
+}
+
+/// Runs a hook command through the shell with `{}` standing for `path`, e.g. `gpg --import {}`.
+/// Without `{}`, the path is appended. Fails if the command cannot start or does not succeed.
+pub fn run_exec_hook(command: &str, path: &Path) -> Result<()> {
+    info!("Running `{}` on {}", command, path.display());
+    let status = shell_command(command, path)
+        .status()
+        .with_context(|| format!("Failed to run `{}`", command))?;
+    if !status.success() {
+        anyhow::bail!("`{}` failed ({})", command, status);
+    }
+    Ok(())
+}
+
+/// The path goes in as a positional parameter, so the shell never parses it.
+#[cfg(not(windows))]
+fn shell_command(command: &str, path: &Path) -> Command {
+    let script = if command.contains("{}") {
+        command.replace("{}", "\"$1\"")
+    } else {
+        format!("{} \"$1\"", command)
+    };
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(script).arg("sh").arg(path);
+    cmd
+}
+
+/// The path goes in as an environment variable: cmd expands it once and does not expand the
+/// `%` or `^` of a filename again, and the quotes keep `&` and the like literal. The script is
+/// passed raw, as Rust's quoting of arguments is not what cmd understands.
+#[cfg(windows)]
+fn shell_command(command: &str, path: &Path) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let quoted = format!("\"%{}%\"", EXEC_PATH_VAR);
+    let script = if command.contains("{}") {
+        command.replace("{}", &quoted)
+    } else {
+        format!("{} {}", command, quoted)
+    };
+    let mut cmd = Command::new("cmd");
+    cmd.env(EXEC_PATH_VAR, path)
+        .raw_arg(format!("/S /C \"{}\"", script));
+    cmd
+}
+
+/// Environment variable that carries the path to a hook on Windows.
+#[cfg(windows)]
+const EXEC_PATH_VAR: &str = "FOUNTAIN_EXEC_PATH";
+
+/// How `--notify` tells that an unattended run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Notify {
+    /// Ring the terminal bell.
+    Bell,
+    /// Ring the bell and show a desktop notification.
+    Desktop,
+}
+
+/// Tells the user `message` the way `kind` asks. A desktop notification that cannot be shown,
+/// as on a headless machine, is only logged.
+pub fn notify(kind: Notify, message: &str) {
+    eprint!("\x07");
+    if kind == Notify::Desktop {
+        if let Err(e) = notification_command(message).status() {
+            debug!("No desktop notification: {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(message: &str) -> Command {
+    // The text goes in as an argument, so AppleScript never parses it.
+    let mut cmd = Command::new("osascript");
+    cmd.args([
+        "-e",
+        "on run argv",
+        "-e",
+        "display notification (item 1 of argv) with title \"fountain\"",
+        "-e",
+        "end run",
+        message,
+    ]);
+    cmd
+}
+
+#[cfg(not(target_os = "macos"))]
+fn notification_command(message: &str) -> Command {
+    let mut cmd = Command::new("notify-send");
+    cmd.arg("fountain").arg(message);
+    cmd
+}
+
+/// Reads a preset compression dictionary given with `--dict`.
+pub fn read_dictionary(path: &Path) -> Result<Vec<u8>> {
+    if !path.is_file() {
+        return Err(FountainError::InputNotFound(path.to_path_buf()).into());
+    }
+    let dictionary =
+        fs::read(path).with_context(|| format!("Failed to read dictionary {}", path.display()))?;
+    info!(
+        "Using dictionary {} ({:08x}, {} bytes)",
+        path.display(),
+        dictionary_id(&dictionary),
+        dictionary.len()
+    );
+    Ok(dictionary)
+}
+
+/// Reads the base file given with `--delta-base`.
+pub fn read_delta_base(path: &Path) -> Result<Vec<u8>> {
+    if !path.is_file() {
+        return Err(FountainError::InputNotFound(path.to_path_buf()).into());
+    }
+    let base =
+        fs::read(path).with_context(|| format!("Failed to read delta base {}", path.display()))?;
+    info!(
+        "Using {} ({} bytes) as the delta base",
+        path.display(),
+        base.len()
+    );
+    Ok(base)
+}
+
+/// The `--json` summary of a run: the fields of `result`, such as an
+/// [`EncodeResult`](crate::EncodeResult), with those of `extra` added.
+pub fn json_summary<T: Serialize>(result: &T, extra: Value) -> Value {
+    let mut summary = serde_json::to_value(result).unwrap_or_default();
+    if let (Value::Object(fields), Value::Object(extra)) = (&mut summary, extra) {
+        fields.extend(extra);
+    }
+    summary
+}
+
+/// Installs the stderr logger used by the binaries.
+///
+/// The default level is `info`; each `-v` raises it (debug, trace) and each `-q` lowers it
+/// (warn, error).
+pub fn init_logging(verbose: u8, quiet: u8) {
+    let level = match i16::from(verbose) - i16::from(quiet) {
+        i16::MIN..=-2 => Level::ERROR,
+        -1 => Level::WARN,
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .without_time()
+        .with_target(false)
+        .with_level(false)
+        .init();
+}
+
+/// Prints a failed run's error chain and maps it to the exit code of its [`FountainError`]
+/// category, or 1 for uncategorized failures.
+pub fn exit_code_for(result: Result<()>) -> ExitCode {
+    let err = match result {
+        Ok(()) => return ExitCode::SUCCESS,
+        Err(err) => err,
+    };
+
+    eprintln!("Error: {:#}", err);
+    match FountainError::find(&err) {
+        Some(kind) => {
+            match kind {
+                FountainError::OutputExists(_) => eprintln!("Use --force to overwrite it."),
+                FountainError::MemoryLimitExceeded { .. } => eprintln!(
+                    "Raise --max-memory, or send the file in parts with `fountain-encode --split-size`."
+                ),
+                FountainError::MissingDictionary(_) => eprintln!(
+                    "Pass the sender's dictionary with --dict."
+                ),
+                FountainError::DeltaBaseMismatch => eprintln!(
+                    "Pass the file the sender diffed against with --delta-base."
+                ),
+                FountainError::TooManyChunks { .. } => eprintln!(
+                    "Use a larger --chunk-size for denser codes, play the codes as video with --terminal or --window instead of writing them out, or send the file in parts with --split-size."
+                ),
+                _ => {}
+            }
+            ExitCode::from(kind.exit_code())
+        }
+        None => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("50MB"), Ok(50_000_000));
+        assert_eq!(parse_size("64KiB"), Ok(65_536));
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert_eq!(parse_size("2g"), Ok(2_000_000_000));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("5 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1H"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("2 fortnights").is_err());
+    }
+
+    #[test]
+    fn test_parse_length() {
+        assert_eq!(parse_length("60mm"), Ok(60.0));
+        assert_eq!(parse_length("60"), Ok(60.0));
+        assert_eq!(parse_length("6.5cm"), Ok(65.0));
+        assert_eq!(parse_length("2IN"), Ok(50.8));
+        assert!(parse_length("0mm").is_err());
+        assert!(parse_length("1.2.3mm").is_err());
+        assert!(parse_length("60 furlongs").is_err());
+    }
+
+    #[test]
+    fn test_parse_dimensions() {
+        assert_eq!(parse_dimensions("1920x1080"), Ok((1920, 1080)));
+        assert_eq!(parse_dimensions("800X600"), Ok((800, 600)));
+        assert!(parse_dimensions("1920").is_err());
+        assert!(parse_dimensions("0x1080").is_err());
+        assert!(parse_dimensions("wide x tall").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exec_hook_quotes_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("it's a \"file\"; echo");
+        std::fs::write(&path, "x").unwrap();
+
+        run_exec_hook("test -f {}", &path).unwrap();
+        run_exec_hook("test -f", &path).unwrap();
+        assert!(run_exec_hook("test -d {}", &path).is_err());
+    }
+}
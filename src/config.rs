@@ -0,0 +1,130 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{FountainError, Result};
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Defaults read from the user's config file. Command-line flags always take precedence.
+///
+/// ```toml
+/// pixel_scale = 6
+/// chunk_size = 800
+/// interval = 500
+/// parity = 20
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub pixel_scale: Option<u32>,
+    #[serde(alias = "payload_size")]
+    pub chunk_size: Option<usize>,
+    pub interval: Option<u64>,
+    pub parity: Option<u32>,
+}
+
+/// Tuned options for a common way of sending, picked with `fountain-encode --profile`, so a
+/// first transfer works without knowing how payload size, pixel scale, interval and parity
+/// interact. Codes always use the QR standard's error correction level M.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// Printed pages: large modules that survive printing and scanning, and parity codes
+    /// for pages lost or damaged.
+    Paper,
+    /// A screen read live by a phone camera: small, coarse codes and time for the phone to
+    /// focus on each.
+    ScreenToPhone,
+    /// A screen recorded as video and decoded later: denser codes, switched quickly.
+    Video,
+    /// Codes drawn as text in a terminal, read by a phone.
+    Terminal,
+}
+
+impl Profile {
+    /// The options this profile sets.
+    pub fn config(self) -> Config {
+        // Pixel scale and parity do not apply to terminal codes.
+        let (pixel_scale, chunk_size, interval, parity) = match self {
+            Profile::Paper => (Some(8), 700, 2000, Some(25)),
+            Profile::ScreenToPhone => (Some(6), 300, 1000, None),
+            Profile::Video => (Some(5), 500, 250, None),
+            Profile::Terminal => (None, 100, 1500, None),
+        };
+        Config {
+            pixel_scale,
+            chunk_size: Some(chunk_size),
+            interval: Some(interval),
+            parity,
+        }
+    }
+}
+
+impl Config {
+    /// This config with the options it leaves unset taken from `defaults`.
+    pub fn or(self, defaults: Config) -> Config {
+        Config {
+            pixel_scale: self.pixel_scale.or(defaults.pixel_scale),
+            chunk_size: self.chunk_size.or(defaults.chunk_size),
+            interval: self.interval.or(defaults.interval),
+            parity: self.parity.or(defaults.parity),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| FountainError::InvalidConfig(e.to_string()))
+    }
+
+    /// Loads the config from `path` if given, otherwise from the default location.
+    ///
+    /// An explicitly given file must exist; a missing default file yields an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => match default_config_path() {
+                Some(p) if p.is_file() => p,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let content = fs::read_to_string(&path).map_err(|e| {
+            FountainError::InvalidConfig(format!("failed to read {}: {}", path.display(), e))
+        })?;
+        toml::from_str(&content)
+            .map_err(|e| FountainError::InvalidConfig(format!("{} ({})", e, path.display())))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/fountain/config.toml`, falling back to `~/.config/fountain/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(config_dir.join("fountain").join(CONFIG_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::from_toml("pixel_scale = 6\npayload_size = 800\n").unwrap();
+        assert_eq!(config.pixel_scale, Some(6));
+        assert_eq!(config.chunk_size, Some(800));
+        assert_eq!(config.interval, None);
+
+        assert!(Config::from_toml("pixel_scael = 6\n").is_err());
+
+        // A profile outranks the config file, which fills in what the profile leaves unset.
+        let merged = Profile::Video
+            .config()
+            .or(Config::from_toml("parity = 10\n").unwrap());
+        assert_eq!(merged.interval, Some(250));
+        assert_eq!(merged.parity, Some(10));
+    }
+}
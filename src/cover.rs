@@ -0,0 +1,317 @@
+//! Cover sheets: a frame of plain text that tells what a set of QR codes holds and how to
+//! restore it, for whoever finds a printed backup or a GIF years later. Captions, a line of
+//! text under every frame, use the same font.
+//!
+//! The text is drawn with a built-in 5x8 pixel font at the largest whole scale that fits the
+//! frame, so the sheet needs no font files and prints as crisply as the codes. The font covers
+//! printable ASCII; dashes and curly quotes are drawn as their ASCII look-alikes, other
+//! characters as `?`.
+
+use image::{Rgb, RgbImage};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::qr::QR_FILE_EXTENSION;
+
+/// Glyph cell in font pixels: 5 columns and 8 rows of glyph, plus one of spacing each way.
+const CELL_WIDTH: u32 = 6;
+const CELL_HEIGHT: u32 = 9;
+/// Largest scale the text is drawn at, so a short sheet on a large frame stays readable.
+const MAX_TEXT_SCALE: u32 = 8;
+/// Rows a caption may wrap to before it is drawn smaller.
+const MAX_CAPTION_ROWS: usize = 2;
+
+/// What a cover sheet says about one file of the transfer.
+#[derive(Debug, Clone)]
+struct CoverFile {
+    name: String,
+    bytes: usize,
+    sha256: String,
+}
+
+/// The facts a cover sheet shows, taken from the files before they are compressed.
+#[derive(Debug, Clone)]
+pub(crate) struct CoverSheet {
+    files: Vec<CoverFile>,
+    created: String,
+}
+
+impl CoverSheet {
+    pub(crate) fn new(files: &[(&[u8], &str)]) -> Self {
+        Self {
+            files: files
+                .iter()
+                .map(|(data, name)| CoverFile {
+                    name: name.to_string(),
+                    bytes: data.len(),
+                    sha256: hex::encode(Sha256::digest(data)),
+                })
+                .collect(),
+            created: utc_date(SystemTime::now()),
+        }
+    }
+
+    /// Text of a receipt: the name, size and SHA-256 of each file, which any phone app shows
+    /// as it is when scanning the code.
+    pub(crate) fn receipt(&self) -> String {
+        self.files
+            .iter()
+            .map(|file| {
+                format!(
+                    "File: {}\nSize: {} bytes\nSHA-256: {}",
+                    file.name, file.bytes, file.sha256
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Text of the sheet for a transfer of `frames` codes, the last `parity` of them parity
+    /// codes, one entry per paragraph.
+    fn lines(&self, frames: usize, parity: usize) -> Vec<String> {
+        let mut lines = vec!["FOUNTAIN QR BACKUP".to_string(), String::new()];
+        for file in &self.files {
+            lines.push(format!("File: {}", file.name));
+            lines.push(format!("Size: {} bytes", file.bytes));
+            lines.push(format!("SHA-256: {}", file.sha256));
+        }
+        lines.push(match parity {
+            0 => format!("QR codes: {}", frames),
+            parity => format!("QR codes: {} ({} parity)", frames, parity),
+        });
+        lines.push(format!("Created: {}", self.created));
+        lines.push(String::new());
+        lines.push(
+            "To restore, scan or photograph the codes in any order into one directory and run \
+             `fountain-decode <dir>`. Not every code is needed."
+                .to_string(),
+        );
+        lines
+    }
+
+    /// Draws the sheet on a white `side` x `side` frame, keeping `margin` pixels clear.
+    pub(crate) fn render(&self, frames: usize, parity: usize, side: u32, margin: u32) -> RgbImage {
+        let mut img = RgbImage::from_pixel(side, side, Rgb([255, 255, 255]));
+        let paragraphs = self.lines(frames, parity);
+        let inner = side.saturating_sub(2 * margin);
+
+        // The largest scale whose wrapped text fits; at the smallest, whatever does not fit is
+        // cut off at the bottom.
+        let (scale, rows) = (1..=MAX_TEXT_SCALE)
+            .rev()
+            .map(|scale| (scale, wrap(&paragraphs, inner / (CELL_WIDTH * scale))))
+            .find(|(scale, rows)| rows.len() as u32 * CELL_HEIGHT * scale <= inner)
+            .unwrap_or_else(|| (1, wrap(&paragraphs, inner / CELL_WIDTH)));
+
+        for (i, row) in rows.iter().enumerate() {
+            let y = margin + i as u32 * CELL_HEIGHT * scale;
+            if y + CELL_HEIGHT * scale > side - margin {
+                break;
+            }
+            draw_text(&mut img, margin, y, row, scale);
+        }
+        img
+    }
+}
+
+/// `image` with `caption` drawn in a white band added below it, so the text stays clear of the
+/// code and its quiet zone. The text is at most `max_scale` pixels per font pixel, smaller if
+/// it would take more than two rows, and the band ends with `margin` blank pixels.
+pub(crate) fn add_caption(
+    image: &RgbImage,
+    caption: &str,
+    max_scale: u32,
+    margin: u32,
+) -> RgbImage {
+    let width = image.width();
+    let inner = width.saturating_sub(2 * margin);
+    let paragraphs = [caption.to_string()];
+    let (scale, rows) = (1..=max_scale.clamp(1, MAX_TEXT_SCALE))
+        .rev()
+        .map(|scale| (scale, wrap(&paragraphs, inner / (CELL_WIDTH * scale))))
+        .find(|(_, rows)| rows.len() <= MAX_CAPTION_ROWS)
+        .unwrap_or_else(|| (1, wrap(&paragraphs, inner / CELL_WIDTH)));
+
+    let band = rows.len() as u32 * CELL_HEIGHT * scale + margin;
+    let mut captioned = RgbImage::from_pixel(width, image.height() + band, Rgb([255, 255, 255]));
+    image::imageops::replace(&mut captioned, image, 0, 0);
+    for (i, row) in rows.iter().enumerate() {
+        let text_width = row.chars().count() as u32 * CELL_WIDTH * scale;
+        let x = width.saturating_sub(text_width) / 2;
+        let y = image.height() + i as u32 * CELL_HEIGHT * scale;
+        draw_text(&mut captioned, x, y, row, scale);
+    }
+    captioned
+}
+
+/// Name of the cover sheet image written with the frames named after `stem`. It sorts before
+/// the first frame.
+pub(crate) fn cover_file_name(stem: &str) -> String {
+    format!("{}_0000_cover.{}", stem, QR_FILE_EXTENSION)
+}
+
+/// Breaks `paragraphs` into rows of at most `columns` characters, at spaces where possible.
+fn wrap(paragraphs: &[String], columns: u32) -> Vec<String> {
+    let columns = columns.max(1) as usize;
+    let mut rows = Vec::new();
+    for paragraph in paragraphs {
+        let mut row = String::new();
+        for word in paragraph.split(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            let used = row.chars().count();
+            if used > 0 && used + 1 + word.len() > columns {
+                rows.push(std::mem::take(&mut row));
+            }
+            if !row.is_empty() {
+                row.push(' ');
+            }
+            // A word longer than a row, such as a checksum, is broken where the row ends.
+            while row.chars().count() + word.len() > columns {
+                let fits = columns - row.chars().count();
+                row.extend(word.drain(..fits));
+                rows.push(std::mem::take(&mut row));
+            }
+            row.extend(word);
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// Draws `text` in black with its top left corner at (`x`, `y`), each font pixel `scale`
+/// pixels square.
+fn draw_text(img: &mut RgbImage, x: u32, y: u32, text: &str, scale: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph = glyph(c);
+        let left = x + i as u32 * CELL_WIDTH * scale;
+        for (column, bits) in glyph.iter().enumerate() {
+            for row in 0..8 {
+                if bits & (1 << row) == 0 {
+                    continue;
+                }
+                let px = left + column as u32 * scale;
+                let py = y + row * scale;
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        if px + dx < img.width() && py + dy < img.height() {
+                            img.put_pixel(px + dx, py + dy, Rgb([0, 0, 0]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Columns of the glyph for `c`, left to right, with the top row in the lowest bit.
+fn glyph(c: char) -> [u8; 5] {
+    let c = match c {
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+        '\u{2018}' | '\u{2019}' => '\'',
+        '\u{201c}' | '\u{201d}' => '"',
+        c => c,
+    };
+    match c {
+        ' '..='~' => FONT[c as usize - ' ' as usize],
+        _ => FONT['?' as usize - ' ' as usize],
+    }
+}
+
+/// Calendar date of `time` in UTC, as `YYYY-MM-DD UTC`.
+fn utc_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+    // Days since 1970-01-01 to a proleptic Gregorian date, counting years from March so that
+    // the leap day comes last.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} UTC", year, month, day)
+}
+
+/// 5x8 glyphs of printable ASCII, from space to `~`.
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x08, 0x07, 0x03, 0x00], [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00], [0x2A, 0x1C, 0x7F, 0x1C, 0x2A], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x80, 0x70, 0x30, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x00, 0x60, 0x60, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x72, 0x49, 0x49, 0x49, 0x46], [0x21, 0x41, 0x49, 0x4D, 0x33], [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x31], [0x41, 0x21, 0x11, 0x09, 0x07],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x46, 0x49, 0x49, 0x29, 0x1E], [0x00, 0x00, 0x14, 0x00, 0x00],
+    [0x00, 0x40, 0x34, 0x00, 0x00], [0x00, 0x08, 0x14, 0x22, 0x41], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x59, 0x09, 0x06], [0x3E, 0x41, 0x5D, 0x59, 0x4E],
+    [0x7C, 0x12, 0x11, 0x12, 0x7C], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x41, 0x3E], [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x73], [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x26, 0x49, 0x49, 0x49, 0x32], [0x03, 0x01, 0x7F, 0x01, 0x03], [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x3F, 0x40, 0x38, 0x40, 0x3F], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03], [0x61, 0x59, 0x49, 0x4D, 0x43], [0x00, 0x7F, 0x41, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x41, 0x7F], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x03, 0x07, 0x08, 0x00], [0x20, 0x54, 0x54, 0x78, 0x40],
+    [0x7F, 0x28, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x28], [0x38, 0x44, 0x44, 0x28, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x00, 0x08, 0x7E, 0x09, 0x02], [0x18, 0xA4, 0xA4, 0x9C, 0x78],
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x40, 0x3D, 0x00],
+    [0x7F, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x78, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0xFC, 0x18, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xFC], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x24],
+    [0x04, 0x04, 0x3F, 0x44, 0x24], [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], [0x44, 0x28, 0x10, 0x28, 0x44], [0x4C, 0x90, 0x90, 0x90, 0x7C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x77, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(UNIX_EPOCH), "1970-01-01 UTC");
+        // 2024-02-29, a leap day, and 2000-03-01 after a century leap year.
+        let day = |days: u64| UNIX_EPOCH + Duration::from_secs(days * 86_400 + 3_600);
+        assert_eq!(utc_date(day(19_782)), "2024-02-29 UTC");
+        assert_eq!(utc_date(day(11_017)), "2000-03-01 UTC");
+    }
+
+    #[test]
+    fn test_add_caption() {
+        let code = RgbImage::from_pixel(200, 200, Rgb([0, 0, 0]));
+        let captioned = add_caption(&code, "TOP SECRET \u{2014} do not share", 4, 16);
+        assert_eq!(captioned.width(), 200);
+        // 25 characters wrap to two rows at scale 2, the largest that fits them in two.
+        assert_eq!(captioned.height(), 200 + 2 * CELL_HEIGHT * 2 + 16);
+        assert_eq!(glyph('\u{2014}'), glyph('-'));
+        assert!(captioned
+            .enumerate_pixels()
+            .any(|(_, y, pixel)| y >= 200 && pixel.0 == [0, 0, 0]));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let paragraphs = ["SHA-256: 0123456789abcdef".to_string(), String::new()];
+        assert_eq!(
+            wrap(&paragraphs, 10),
+            ["SHA-256:", "0123456789", "abcdef", ""]
+        );
+        assert_eq!(wrap(&["a b c".to_string()], 3), ["a b", "c"]);
+    }
+}
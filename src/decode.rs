@@ -1,195 +1,1535 @@
-use anyhow::{anyhow, Result};
-use image::codecs::gif::GifDecoder;
-use image::{AnimationDecoder, DynamicImage};
-use raptorq::{Decoder, EncodingPacket, ObjectTransmissionInformation};
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::BufReader;
-use std::path::Path;
-
-use crate::chunk::{decompress, unpack_data, Chunk};
-use crate::qr::{decode_qr_from_dynamic_image, QR_FILE_EXTENSION};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use image::{DynamicImage, GrayImage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, trace, warn};
 
+use crate::channel::{ChannelMeter, ChannelStats};
+use crate::chunk::{Chunk, PACKET_FILE_EXTENSION, PACKET_LINES_EXTENSION};
+use crate::delta::{apply_delta, patched_filename};
+use crate::error::{FountainError, Result};
+use crate::parallel::{map_parallel, thread_count};
+use crate::progress::{check_memory, is_cancelled, CancelFlag, Progress, ProgressCallback};
+use crate::qr::{
+    decode_qr_from_dynamic_image, decode_qrs_from_gray, FrameQuality, DECODE_IMAGE_EXTENSIONS,
+};
+use crate::salvage::write_salvage;
+use crate::screen::ScreenFilter;
+use crate::session::Session;
+use crate::source::{
+    open_source, ChainSource, DirSource, FrameSource, GifSource, ImageSource, InputFrame,
+    PacketLinesSource, SourceFrame,
+};
+use crate::stream::decode_qr_bytes_to_chunk;
+pub use crate::stream::{DecodedFile, FeedResult, StreamDecoder};
+use crate::txqr::{TxqrFeed, TxqrReceiver, TXQR_FILENAME};
+
+/// Summary of a decode. Serializes with the field names of `fountain-decode --json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DecodeResult {
+    /// Name of the decoded file; the first one of a multi-file transfer.
     pub original_filename: String,
+    /// Path of the decoded file; empty with [`DecodeOptions::check_only`].
     pub output_path: String,
+    /// Every file written, in the order they completed. Only `output_path` unless the transfer
+    /// carried several files; none with [`DecodeOptions::check_only`].
+    pub output_files: Vec<String>,
+    /// Unique packets consumed, including any resumed from a session.
     pub num_chunks: usize,
+    /// Size of the decoded files in bytes.
+    pub total_bytes: usize,
+    /// Size of the compressed transfer (filename and checksum included) in bytes.
+    pub compressed_bytes: usize,
+    /// Packets that arrived more than once and were skipped.
+    pub duplicate_packets: usize,
+    /// Frames or images scanned, including those without a QR code.
+    pub frames_scanned: usize,
+    /// Frames or images of the input left unread because the transfer completed before them;
+    /// 0 when the input's length is not known up front, as for a camera.
+    #[serde(default)]
+    pub frames_skipped: usize,
+    /// Wall-clock time spent decoding.
+    #[serde(rename = "duration_ms", with = "crate::millis")]
+    pub duration: Duration,
+    /// How well the capture kept up with the sender, if its frames were numbered (see
+    /// [`crate::channel`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<ChannelStats>,
+    /// Warnings logged while scanning, such as poor frame quality or packets of a different
+    /// encoding that were ignored.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
-struct RaptorQStreamDecoder {
-    chunks: HashMap<u32, Chunk>,
-    decoder: Option<Decoder>,
+impl DecodeResult {
+    /// Decoded bytes recovered per second.
+    pub fn throughput(&self) -> f64 {
+        self.total_bytes as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
 }
 
-impl RaptorQStreamDecoder {
-    fn new() -> Self {
-        Self {
-            chunks: HashMap::new(),
-            decoder: None,
+/// Callback receiving the number of unique packets collected after each new one arrives.
+pub type PacketCallback = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// Callback receiving what each scanned frame held.
+pub type ScannedFrameCallback = Arc<dyn Fn(&ScannedFrame) + Send + Sync>;
+
+/// What scanning one frame gave, in the order a frame gets through detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FrameOutcome {
+    /// The image could not be loaded.
+    Unreadable,
+    /// No QR code was detected.
+    NoCode,
+    /// A QR code was read, but it does not hold a fountain chunk.
+    NotChunk,
+    /// The chunk's packet had already been received.
+    Duplicate,
+    /// The chunk's packet was new.
+    New,
+}
+
+impl FrameOutcome {
+    /// Whether a QR code was detected in the frame.
+    pub fn detected(self) -> bool {
+        self > FrameOutcome::NoCode
+    }
+}
+
+/// One frame scanned by a [`Decoder`], reported to [`DecodeOptions::on_frame_scanned`].
+#[derive(Debug, Clone)]
+pub struct ScannedFrame {
+    /// Where the frame came from, such as `capture.gif: frame 12` or an image path.
+    pub label: String,
+    pub outcome: FrameOutcome,
+    /// Time spent looking for and reading the QR code. `None` for packet files, which need no
+    /// QR decoding, for images that could not be loaded, and for frames whose codes the
+    /// session file already held (see [`Session::detections`]).
+    pub detect_time: Option<Duration>,
+    /// When the frame was shown or captured, if its source knows (see
+    /// [`SourceFrame::timestamp`]).
+    pub timestamp: Option<Duration>,
+}
+
+/// Settings for a [`Decoder`]. Start from [`DecodeOptions::new`] and chain the setters.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// Output file path. `None` writes the original filename next to the input.
+    pub output: Option<PathBuf>,
+    /// Replace the output file if it already exists.
+    pub overwrite: bool,
+    /// Session file to resume from and to save unfinished progress to.
+    pub session_file: Option<PathBuf>,
+    /// Called whenever a new unique packet is received.
+    pub on_packet_received: Option<PacketCallback>,
+    /// Called with the estimated fraction of the file recovered so far.
+    pub on_progress: Option<ProgressCallback>,
+    /// Called after each frame is scanned with what it held and how long detection took, e.g.
+    /// to compare captures or preprocessing objectively.
+    pub on_frame_scanned: Option<ScannedFrameCallback>,
+    /// Checked before each frame; once set, decoding stops with [`FountainError::Cancelled`]
+    /// after saving the packets collected so far to the session file, if any.
+    pub cancel: Option<CancelFlag>,
+    /// Decode every transfer in the input (files appended to an output) instead of stopping at
+    /// the first one to complete. The whole input is scanned.
+    pub all_transfers: bool,
+    /// Give up once scanning has taken this long, as if the input had ended there.
+    pub timeout: Option<Duration>,
+    /// Give up after scanning this many frames, as if the input had ended there.
+    pub max_frames: Option<usize>,
+    /// Give up once no new packet has arrived for this long, from the start of the scan or the
+    /// last packet, with [`FountainError::Stalled`], e.g. for a camera left pointed at a screen
+    /// that stopped showing codes. Checked before each frame, or each batch of frames read at
+    /// once (see `threads`).
+    pub stall_timeout: Option<Duration>,
+    /// Memory a decode may take, in bytes. Checked as soon as the first packet of each file
+    /// tells its size; over the limit, decoding fails with
+    /// [`FountainError::MemoryLimitExceeded`] instead of running out of memory later.
+    pub max_memory: Option<usize>,
+    /// Preset dictionaries the sender may have compressed with, shared out of band. A transfer
+    /// names the one it needs; without it, decoding fails with
+    /// [`FountainError::MissingDictionary`].
+    pub dictionaries: Vec<Vec<u8>>,
+    /// Local copy of the base file a sender's deltas were made against (see [`crate::delta`]).
+    /// Each `<name>.fdelta` file decoded is applied to it and comes out as `<name>`; without
+    /// it, deltas are returned as they arrived.
+    pub delta_base: Option<Vec<u8>>,
+    /// Also read frames of a txqr sender (see [`crate::txqr`]). Its message is returned as
+    /// [`TXQR_FILENAME`](crate::txqr::TXQR_FILENAME) once every part has been seen.
+    pub txqr: bool,
+    /// Directory a decode that fails writes what it collected to, marked as partial by a
+    /// `PARTIAL.txt` note: the packets as `.pkt` files and as a session file, and a txqr
+    /// message as far as it was received, its missing bytes zeroed.
+    pub salvage_dir: Option<PathBuf>,
+    /// The frames are a camera's view of a screen, such as a phone video of a monitor: a frame
+    /// in which no code is found is tried again with rolling-shutter bands levelled and moiré
+    /// smoothed out, then averaged with the frame before it.
+    pub filmed_screen: bool,
+    /// Threads that read the codes of a batch of frames at once; frames are still fed in
+    /// order. `None` uses one per core, 1 reads them one by one.
+    pub threads: Option<usize>,
+    /// Rebuild and verify the files, but write none of them: the result tells what was decoded
+    /// and its `output_files` are empty, e.g. to check that an archived GIF still decodes.
+    /// Output and overwrite options do not apply.
+    pub check_only: bool,
+}
+
+impl fmt::Debug for DecodeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecodeOptions")
+            .field("output", &self.output)
+            .field("overwrite", &self.overwrite)
+            .field("session_file", &self.session_file)
+            .field("on_packet_received", &self.on_packet_received.is_some())
+            .field("on_progress", &self.on_progress.is_some())
+            .field("on_frame_scanned", &self.on_frame_scanned.is_some())
+            .field("cancel", &self.cancel)
+            .field("all_transfers", &self.all_transfers)
+            .field("timeout", &self.timeout)
+            .field("max_frames", &self.max_frames)
+            .field("stall_timeout", &self.stall_timeout)
+            .field("max_memory", &self.max_memory)
+            .field("dictionaries", &self.dictionaries.len())
+            .field("delta_base", &self.delta_base.as_ref().map(Vec::len))
+            .field("txqr", &self.txqr)
+            .field("salvage_dir", &self.salvage_dir)
+            .field("filmed_screen", &self.filmed_screen)
+            .field("threads", &self.threads)
+            .field("check_only", &self.check_only)
+            .finish()
+    }
+}
+
+impl DecodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn session_file(mut self, session_file: impl Into<PathBuf>) -> Self {
+        self.session_file = Some(session_file.into());
+        self
+    }
+
+    pub fn on_packet_received(mut self, callback: impl Fn(usize) + Send + Sync + 'static) -> Self {
+        self.on_packet_received = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn on_progress(mut self, callback: impl Fn(f32) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn on_frame_scanned(
+        mut self,
+        callback: impl Fn(&ScannedFrame) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_frame_scanned = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn cancel_flag(mut self, flag: CancelFlag) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    pub fn all_transfers(mut self, all_transfers: bool) -> Self {
+        self.all_transfers = all_transfers;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn stall_timeout(mut self, stall_timeout: Duration) -> Self {
+        self.stall_timeout = Some(stall_timeout);
+        self
+    }
+
+    pub fn max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Adds a preset dictionary to pick from; see [`DecodeOptions::dictionaries`].
+    pub fn dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.dictionaries.push(dictionary);
+        self
+    }
+
+    pub fn delta_base(mut self, base: Vec<u8>) -> Self {
+        self.delta_base = Some(base);
+        self
+    }
+
+    pub fn txqr(mut self, txqr: bool) -> Self {
+        self.txqr = txqr;
+        self
+    }
+
+    pub fn salvage_dir(mut self, salvage_dir: impl Into<PathBuf>) -> Self {
+        self.salvage_dir = Some(salvage_dir.into());
+        self
+    }
+
+    pub fn filmed_screen(mut self, filmed_screen: bool) -> Self {
+        self.filmed_screen = filmed_screen;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    pub fn check_only(mut self, check_only: bool) -> Self {
+        self.check_only = check_only;
+        self
+    }
+
+    /// Applies each delta among `files` to the base, if one is configured.
+    pub(crate) fn apply_deltas(&self, files: Vec<DecodedFile>) -> Result<Vec<DecodedFile>> {
+        files
+            .into_iter()
+            .map(|file| {
+                let Some(filename) = patched_filename(&file.filename) else {
+                    return Ok(file);
+                };
+                let Some(base) = &self.delta_base else {
+                    warn!(
+                        "{} is a delta; decode with the base it was made against to apply it",
+                        file.filename
+                    );
+                    return Ok(file);
+                };
+                let data = apply_delta(base, &file.data)?;
+                info!("Applied {} to the base", file.filename);
+                Ok(DecodedFile {
+                    filename: filename.to_string(),
+                    data,
+                })
+            })
+            .collect()
+    }
+
+    /// Which limit, if any, a scan that started at `started` and has scanned `scanned` frames
+    /// has reached, described for the error it ends with.
+    pub(crate) fn limit_reached(&self, scanned: usize, started: Instant) -> Option<String> {
+        if let Some(max_frames) = self.max_frames.filter(|&max| scanned >= max) {
+            return Some(format!("{} frame(s)", max_frames));
         }
+        self.timeout
+            .filter(|&timeout| started.elapsed() >= timeout)
+            .map(|timeout| format!("{:?}", timeout))
     }
 
-    fn add_chunk(&mut self, chunk: Chunk) -> Result<Option<(String, Vec<u8>)>> {
-        if self.decoder.is_none() {
-            let config = ObjectTransmissionInformation::with_defaults(
-                chunk.header.total as u64,
-                chunk.header.packet_size,
-            );
-            self.decoder = Some(Decoder::new(config));
-        }
-
-        if !self.chunks.contains_key(&chunk.header.index) {
-            let index = chunk.header.index;
-            let total_len = chunk.header.total as usize;
-            let packet_data = chunk.data.clone();
-            self.chunks.insert(index, chunk);
-
-            if let Some(dec) = &mut self.decoder {
-                let packet = EncodingPacket::deserialize(&packet_data);
-                if let Some(result_data) = dec.decode(packet) {
-                    let mut final_data = result_data;
-                    final_data.truncate(total_len);
-                    let packed = decompress(&final_data)?;
-                    return Ok(Some(unpack_data(&packed)?));
-                }
-            }
+    fn report_packet(&self, stream: &StreamDecoder) {
+        if let Some(callback) = &self.on_packet_received {
+            callback(stream.num_packets());
+        }
+        if let (Some(callback), Some(expected)) = (&self.on_progress, stream.expected_packets()) {
+            // Decoding usually needs a few packets more than the source count, so stay
+            // below 100% until it actually completes.
+            callback((stream.num_packets() as f32 / expected as f32).min(0.99));
         }
-        Ok(None)
     }
 
-    fn num_chunks(&self) -> usize {
-        self.chunks.len()
+    fn report_frame(
+        &self,
+        label: &str,
+        outcome: FrameOutcome,
+        detect_time: Option<Duration>,
+        timestamp: Option<Duration>,
+    ) {
+        if let Some(callback) = &self.on_frame_scanned {
+            callback(&ScannedFrame {
+                label: label.to_string(),
+                outcome,
+                detect_time,
+                timestamp,
+            });
+        }
+    }
+
+    pub(crate) fn report_complete(&self) {
+        if let Some(callback) = &self.on_progress {
+            callback(1.0);
+        }
     }
 }
 
-fn decode_qr_bytes_to_chunk(qr_bytes: &[u8]) -> Option<Chunk> {
-    let qr_string = std::str::from_utf8(qr_bytes).ok()?;
-    let chunk_bytes = base45::decode(qr_string).ok()?;
-    Chunk::from_bytes(&chunk_bytes).ok()
+/// Reconstructs files from QR codes with a fixed set of [`DecodeOptions`].
+///
+/// ```no_run
+/// use fountain::{DecodeOptions, Decoder};
+/// use std::path::Path;
+///
+/// let decoder = Decoder::new(DecodeOptions::new().output("notes.txt").overwrite(true));
+/// let result = decoder.decode(Path::new("notes.gif"))?;
+/// println!("{} -> {}", result.original_filename, result.output_path);
+/// # Ok::<(), fountain::FountainError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    options: DecodeOptions,
 }
 
-fn save_decoded_file(
-    original_filename: String,
-    data: Vec<u8>,
-    num_chunks: usize,
-    output_path: Option<&Path>,
+/// Where each decoded file goes. A single file goes to the configured output path, or its
+/// original name in `default_dir`; the files of a multi-file transfer keep their names, inside
+/// the configured output directory or `default_dir`. Fails if a file exists and overwriting is
+/// not allowed.
+pub(crate) fn output_paths_for(
+    files: &[DecodedFile],
     default_dir: &Path,
-) -> Result<DecodeResult> {
-    let final_output_path = match output_path {
-        Some(p) => p.to_path_buf(),
-        None => default_dir.join(&original_filename),
-    };
+    options: &DecodeOptions,
+) -> Result<Vec<PathBuf>> {
+    files
+        .iter()
+        .map(|file| {
+            let final_output_path = match &options.output {
+                Some(p) if files.len() == 1 => p.clone(),
+                Some(dir) => dir.join(&file.filename),
+                None => default_dir.join(&file.filename),
+            };
 
-    fs::write(&final_output_path, &data)?;
+            if !options.overwrite && final_output_path.exists() {
+                return Err(FountainError::OutputExists(final_output_path));
+            }
+            Ok(final_output_path)
+        })
+        .collect()
+}
 
-    Ok(DecodeResult {
-        original_filename,
-        output_path: final_output_path.to_string_lossy().to_string(),
-        num_chunks,
-    })
+/// Frames in a row that seem to show a QR code but yield no chunk before the scan warns about
+/// their quality.
+const MISSED_CODES_BEFORE_WARNING: usize = 5;
+
+/// Memory a transfer takes to decode, in multiples of its compressed size: the packets kept,
+/// the RaptorQ decoder's working copy, and the decoded and decompressed files.
+const DECODE_MEMORY_FACTOR: usize = 4;
+
+/// Drives a [`StreamDecoder`] over a sequence of images, with progress, logging and session
+/// handling shared by every decode entry point.
+pub(crate) struct Scan<'a> {
+    stream: StreamDecoder,
+    files: Vec<DecodedFile>,
+    scanned: usize,
+    duplicates: usize,
+    /// Frames that loaded but held no fountain chunk.
+    no_chunk: usize,
+    /// Frames that could not be loaded.
+    unreadable: usize,
+    /// Frames without a chunk that seemed to show a QR code.
+    missed_codes: usize,
+    /// Such frames since the last chunk was read.
+    missed_in_row: usize,
+    quality_warned: bool,
+    /// Warnings logged so far, for [`DecodeResult::warnings`].
+    warnings: Vec<String>,
+    /// Parts of a txqr message, with [`DecodeOptions::txqr`].
+    txqr: Option<TxqrReceiver>,
+    /// Whether the frames turned out to be txqr fountain blocks, which are not decoded.
+    txqr_fountain_warned: bool,
+    /// Clean-up of frames filmed off a screen, with [`DecodeOptions::filmed_screen`].
+    screen: Option<ScreenFilter>,
+    /// Sequence numbers of the frames captured, for [`DecodeResult::channel`].
+    channel: ChannelMeter,
+    /// The codes read off each frame by frame hash, saved with the session so a later run
+    /// skips their detection. Only kept with a session file.
+    detections: Option<DetectionCache>,
+    /// Frames whose codes came from `detections`.
+    cached_frames: usize,
+    /// The limit that ended the scan, if one did.
+    stopped: Option<String>,
+    /// When the last new packet, or part of a txqr message, arrived; the start of the scan
+    /// before any did.
+    last_progress: Instant,
+    /// How long no packet had arrived when the scan gave up as stalled.
+    stalled: Option<Duration>,
+    /// Timestamp of the frame being scanned.
+    timestamp: Option<Duration>,
+    /// Frames in the input, if known up front.
+    total: Option<u64>,
+    started: Instant,
+    progress: Progress,
+    options: &'a DecodeOptions,
 }
 
-fn decode_core<I>(
-    images: I,
-    output_file: Option<&Path>,
-    default_dir: &Path,
-) -> Result<DecodeResult>
-where
-    I: Iterator<Item = (Result<DynamicImage>, String)>,
-{
-    let mut rq_decoder = RaptorQStreamDecoder::new();
-    let mut count = 0;
+/// The codes read off frames, by [`frame_hash`].
+type DetectionCache = BTreeMap<u64, Vec<Vec<u8>>>;
+
+/// The codes read off an image, apart from the [`Scan`] so a batch of frames can be read on
+/// several threads.
+pub(crate) struct Detection {
+    gray: GrayImage,
+    codes: Vec<Vec<u8>>,
+    time: Duration,
+    /// The frame's hash, when its codes are cached.
+    hash: Option<u64>,
+    /// Whether the codes came from the cache rather than detection.
+    cached: bool,
+}
+
+impl Detection {
+    /// Reads the codes off `img`, or takes them from `cache` if it holds the frame.
+    fn of(img: &DynamicImage, cache: Option<&DetectionCache>) -> Self {
+        let gray = img.to_luma8();
+        let started = Instant::now();
+        let hash = cache.map(|_| frame_hash(&gray));
+        if let Some(codes) = cache.zip(hash).and_then(|(cache, hash)| cache.get(&hash)) {
+            return Self {
+                gray,
+                codes: codes.clone(),
+                time: started.elapsed(),
+                hash,
+                cached: true,
+            };
+        }
+        // A frame may hold several codes side by side (see `EncodeOptions::dual`).
+        let codes = decode_qrs_from_gray(&gray).unwrap_or_default();
+        Self {
+            gray,
+            codes,
+            time: started.elapsed(),
+            hash,
+            cached: false,
+        }
+    }
+}
+
+/// Identifies a frame by its pixels, for the detection cache: the first 8 bytes of the
+/// SHA-256 of its size and grayscale pixels.
+fn frame_hash(gray: &GrayImage) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(gray.width().to_be_bytes());
+    hasher.update(gray.height().to_be_bytes());
+    hasher.update(gray.as_raw());
+    let digest = hasher.finalize();
+    u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 is 32 bytes"))
+}
+
+/// Counters of a finished [`Scan`], turned into a [`DecodeResult`] once the files are written.
+pub(crate) struct ScanStats {
+    num_chunks: usize,
+    compressed_bytes: usize,
+    duplicates: usize,
+    scanned: usize,
+    skipped: usize,
+    started: Instant,
+    channel: Option<ChannelStats>,
+    warnings: Vec<String>,
+}
 
-    for (img_result, label) in images {
-        count += 1;
+impl ScanStats {
+    /// Adds the counters of another scan, for a result that spans several inputs.
+    pub(crate) fn merge(&mut self, other: ScanStats) {
+        self.num_chunks += other.num_chunks;
+        self.compressed_bytes += other.compressed_bytes;
+        self.duplicates += other.duplicates;
+        self.scanned += other.scanned;
+        self.skipped += other.skipped;
+        self.started = self.started.min(other.started);
+        self.channel = self.channel.take().or(other.channel);
+        self.warnings.extend(other.warnings);
+    }
+
+    pub(crate) fn result(self, files: Vec<DecodedFile>, output_paths: &[PathBuf]) -> DecodeResult {
+        let output_files: Vec<String> = output_paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        DecodeResult {
+            total_bytes: files.iter().map(|file| file.data.len()).sum(),
+            original_filename: files[0].filename.clone(),
+            output_path: output_files.first().cloned().unwrap_or_default(),
+            output_files,
+            num_chunks: self.num_chunks,
+            compressed_bytes: self.compressed_bytes,
+            duplicate_packets: self.duplicates,
+            frames_scanned: self.scanned,
+            frames_skipped: self.skipped,
+            duration: self.started.elapsed(),
+            channel: self.channel,
+            warnings: self.warnings,
+        }
+    }
+}
+
+impl<'a> Scan<'a> {
+    pub(crate) fn new(total: Option<u64>, options: &'a DecodeOptions) -> Self {
+        Self {
+            stream: StreamDecoder::with_dictionaries(options.dictionaries.clone()),
+            files: Vec::new(),
+            scanned: 0,
+            duplicates: 0,
+            no_chunk: 0,
+            unreadable: 0,
+            missed_codes: 0,
+            missed_in_row: 0,
+            quality_warned: false,
+            warnings: Vec::new(),
+            txqr: options.txqr.then(TxqrReceiver::new),
+            txqr_fountain_warned: false,
+            screen: options.filmed_screen.then(ScreenFilter::default),
+            channel: ChannelMeter::default(),
+            detections: None,
+            cached_frames: 0,
+            stopped: None,
+            last_progress: Instant::now(),
+            stalled: None,
+            timestamp: None,
+            total,
+            started: Instant::now(),
+            progress: Progress::new(total, "frames scanned"),
+            options,
+        }
+    }
+
+    /// Feeds the packets saved in the session file, if any. Returns the files if they alone
+    /// complete the transfer.
+    pub(crate) fn resume(&mut self) -> Result<Option<Vec<DecodedFile>>> {
+        let Some(session_path) = self.options.session_file.as_deref() else {
+            return Ok(None);
+        };
+
+        let session = Session::load_or_default(session_path)?;
+        if !session.chunks.is_empty() || !session.detections.is_empty() {
+            self.progress.suspend(|| {
+                info!(
+                    "Resuming session {} with {} packet(s) and the codes of {} frame(s)",
+                    session_path.display(),
+                    session.chunks.len(),
+                    session.detections.len()
+                )
+            });
+        }
+        self.detections = Some(session.detections);
+        for chunk in session.chunks {
+            let fed = self.stream.feed_chunk(chunk)?;
+            self.check_memory()?;
+            match fed {
+                FeedResult::FileComplete(file) => self.files.push(file),
+                FeedResult::Complete(file) => {
+                    self.progress
+                        .suspend(|| info!("RaptorQ decoding successful from the saved session!"));
+                    self.files.push(file);
+                    if let Some(files) = self.complete() {
+                        return Ok(Some(files));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Scans one image, whose codes may already be read. Returns the files once enough packets
+    /// have arrived for all of them.
+    fn feed(
+        &mut self,
+        img_result: Result<DynamicImage>,
+        detection: Option<Detection>,
+        label: &str,
+    ) -> Result<Option<Vec<DecodedFile>>> {
+        self.scanned += 1;
+        self.progress.inc(|| None);
         let img = match img_result {
             Ok(img) => img,
             Err(e) => {
-                println!("    Failed to load {}: {}", label, e);
-                continue;
+                self.unreadable += 1;
+                self.progress
+                    .suspend(|| warn!("    Failed to load {}: {}", label, e));
+                self.options
+                    .report_frame(label, FrameOutcome::Unreadable, None, self.timestamp);
+                return Ok(None);
             }
         };
 
-        if let Ok(qr_bytes) = decode_qr_from_dynamic_image(&img) {
-            if let Some(chunk) = decode_qr_bytes_to_chunk(&qr_bytes) {
-                if let Some((original_filename, data)) = rq_decoder.add_chunk(chunk)? {
-                    println!("RaptorQ decoding successful at {}!", label);
-                    return save_decoded_file(
-                        original_filename,
+        let Detection {
+            gray,
+            mut codes,
+            time,
+            hash,
+            cached,
+        } = detection.unwrap_or_else(|| Detection::of(&img, self.detections.as_ref()));
+        if cached {
+            self.cached_frames += 1;
+            trace!("    Codes of {} taken from the session", label);
+        } else if let (Some(cache), Some(hash)) = (&mut self.detections, hash) {
+            cache.insert(hash, codes.clone());
+        }
+        let detect_started = Instant::now();
+        if let Some(screen) = &mut self.screen {
+            codes = screen.codes(&gray, codes);
+        }
+        let chunks: Vec<Chunk> = codes
+            .iter()
+            .filter_map(|bytes| decode_qr_bytes_to_chunk(bytes))
+            .collect();
+        // Frames drawn as colour matrix symbols (see `EncodeOptions::color_matrix`).
+        #[cfg(feature = "color")]
+        let chunks = if chunks.is_empty() {
+            crate::matrix::decode_color_matrix(&img.to_rgb8())
+                .and_then(|bytes| Chunk::from_bytes(&bytes).ok())
+                .into_iter()
+                .collect()
+        } else {
+            chunks
+        };
+        let detect_time = (!cached).then(|| time + detect_started.elapsed());
+        if chunks.is_empty() && self.txqr.is_some() && !codes.is_empty() {
+            if let Some(fed) = self.feed_txqr(&codes, label, detect_time) {
+                return fed;
+            }
+        }
+        if chunks.is_empty() {
+            let outcome = if codes.is_empty() {
+                FrameOutcome::NoCode
+            } else {
+                FrameOutcome::NotChunk
+            };
+            self.options
+                .report_frame(label, outcome, detect_time, self.timestamp);
+            self.check_quality(&gray, label);
+            return self.accept(FeedResult::NoChunk, label);
+        }
+
+        for sequence in chunks.iter().filter_map(|chunk| chunk.header.sequence) {
+            self.channel.record(sequence, self.timestamp);
+        }
+        let mut fed = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let result = self.stream.feed_chunk(chunk)?;
+            let complete = matches!(result, FeedResult::Complete(_));
+            fed.push(result);
+            if complete {
+                break;
+            }
+        }
+        let outcome = fed
+            .iter()
+            .map(packet_outcome)
+            .max()
+            .unwrap_or(FrameOutcome::New);
+        self.options
+            .report_frame(label, outcome, detect_time, self.timestamp);
+        for result in fed {
+            if let Some(files) = self.accept(result, label)? {
+                return Ok(Some(files));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Feeds the codes of a frame that held no fountain chunk to the txqr receiver. `None` if
+    /// none of them is a txqr frame either.
+    fn feed_txqr(
+        &mut self,
+        codes: &[Vec<u8>],
+        label: &str,
+        detect_time: Option<Duration>,
+    ) -> Option<Result<Option<Vec<DecodedFile>>>> {
+        let receiver = self.txqr.as_mut()?;
+        let mut outcome = None;
+        for code in codes {
+            match receiver.feed(code) {
+                TxqrFeed::NotTxqr => {}
+                TxqrFeed::Fountain => {
+                    if !self.txqr_fountain_warned {
+                        self.txqr_fountain_warned = true;
+                        self.progress.suspend(|| {
+                            warn!("The frames are txqr fountain blocks, which cannot be decoded; send with txqr's plain frames instead")
+                        });
+                    }
+                }
+                TxqrFeed::Duplicate => {
+                    outcome = outcome.max(Some(FrameOutcome::Duplicate));
+                    self.duplicates += 1;
+                }
+                TxqrFeed::Accepted => {
+                    outcome = Some(FrameOutcome::New);
+                    self.last_progress = Instant::now();
+                    let progress = receiver.progress();
+                    self.progress
+                        .set_message(format!("({:.0}% of the txqr message)", progress * 100.0));
+                    if let Some(callback) = &self.options.on_progress {
+                        callback(progress);
+                    }
+                }
+                TxqrFeed::Complete(data) => {
+                    self.options.report_frame(
+                        label,
+                        FrameOutcome::New,
+                        detect_time,
+                        self.timestamp,
+                    );
+                    self.missed_in_row = 0;
+                    self.progress
+                        .suspend(|| info!("Received the txqr message at {}", label));
+                    self.files.push(DecodedFile {
+                        filename: TXQR_FILENAME.to_string(),
                         data,
-                        rq_decoder.num_chunks(),
-                        output_file,
-                        default_dir,
+                    });
+                    return Some(Ok(self.complete()));
+                }
+            }
+        }
+        let outcome = outcome?;
+        self.missed_in_row = 0;
+        self.options
+            .report_frame(label, outcome, detect_time, self.timestamp);
+        Some(Ok(None))
+    }
+
+    /// Like [`Scan::feed`], for a frame of any source: an image, whose codes may already be
+    /// read, or a packet that needs no QR decoding.
+    pub(crate) fn feed_frame(
+        &mut self,
+        frame: SourceFrame,
+        detection: Option<Detection>,
+    ) -> Result<Option<Vec<DecodedFile>>> {
+        let SourceFrame {
+            frame,
+            label,
+            timestamp,
+        } = frame;
+        self.timestamp = timestamp;
+        match frame {
+            Ok(InputFrame::Image(img)) => self.feed(Ok(img), detection, &label),
+            Ok(InputFrame::Packet(chunk)) => {
+                self.scanned += 1;
+                self.progress.inc(|| None);
+                let fed = self.stream.feed_chunk(chunk)?;
+                self.options
+                    .report_frame(&label, packet_outcome(&fed), None, timestamp);
+                self.accept(fed, &label)
+            }
+            Err(e) => self.feed(Err(e), None, &label),
+        }
+    }
+
+    /// Scans `source` until the transfer is complete, resuming from the session file first.
+    /// The codes of each batch of frames are read on [`DecodeOptions::threads`] threads.
+    pub(crate) fn run(
+        mut self,
+        source: &mut dyn FrameSource,
+    ) -> Result<(Vec<DecodedFile>, ScanStats)> {
+        let threads = thread_count(self.options.threads);
+        let mut files = self.resume()?;
+        'scan: while files.is_none() && !self.should_stop() {
+            // No frame past the frame limit is read.
+            let left = self
+                .options
+                .max_frames
+                .map_or(usize::MAX, |max| max.saturating_sub(self.scanned));
+            let batch: Vec<SourceFrame> = std::iter::from_fn(|| source.next_frame())
+                .take(threads.min(left))
+                .collect();
+            if batch.is_empty() {
+                break;
+            }
+            let images: Vec<Option<&DynamicImage>> = batch
+                .iter()
+                .map(|frame| match &frame.frame {
+                    Ok(InputFrame::Image(img)) => Some(img),
+                    _ => None,
+                })
+                .collect();
+            let cache = self.detections.as_ref();
+            let detections = map_parallel(&images, threads, |img| {
+                img.map(|img| Detection::of(img, cache))
+            });
+            for (i, (frame, detection)) in batch.into_iter().zip(detections).enumerate() {
+                if i > 0 && self.reached_limit() {
+                    break 'scan;
+                }
+                files = self.feed_frame(frame, detection)?;
+                if files.is_some() {
+                    break 'scan;
+                }
+            }
+        }
+        self.conclude(files)
+    }
+
+    /// Ends the scan once the frames stop, with the files they completed, if any: the files,
+    /// with any deltas applied, or the error to report.
+    pub(crate) fn conclude(
+        mut self,
+        files: Option<Vec<DecodedFile>>,
+    ) -> Result<(Vec<DecodedFile>, ScanStats)> {
+        let Some(files) = files.or_else(|| self.end_of_input()) else {
+            return Err(self.fail());
+        };
+        let files = self.options.apply_deltas(files)?;
+        Ok((files, self.finish()))
+    }
+
+    /// Records what feeding one frame gave. Returns the files once enough packets have
+    /// arrived for all of them.
+    fn accept(&mut self, fed: FeedResult, label: &str) -> Result<Option<Vec<DecodedFile>>> {
+        if !matches!(fed, FeedResult::NoChunk) {
+            self.missed_in_row = 0;
+        }
+        match fed {
+            FeedResult::NoChunk => {
+                self.no_chunk += 1;
+                debug!("    No fountain chunk found in {}", label);
+            }
+            FeedResult::Duplicate => {
+                self.duplicates += 1;
+                debug!("    Duplicate packet in {}", label)
+            }
+            FeedResult::Accepted => {
+                self.last_progress = Instant::now();
+                self.check_memory()?;
+                self.progress
+                    .set_message(format!("({} unique packets)", self.stream.num_packets()));
+                self.options.report_packet(&self.stream);
+            }
+            FeedResult::FileComplete(file) => {
+                self.last_progress = Instant::now();
+                self.options.report_packet(&self.stream);
+                self.progress
+                    .suspend(|| info!("Decoded {} at {}", file.filename, label));
+                self.files.push(file);
+            }
+            FeedResult::Complete(file) => {
+                self.options.report_packet(&self.stream);
+                self.progress
+                    .suspend(|| info!("RaptorQ decoding successful at {}!", label));
+                self.files.push(file);
+                return Ok(self.complete());
+            }
+            FeedResult::Manifest(manifest) => self.progress.suspend(|| {
+                info!(
+                    "Manifest at {}: {} file(s) coming",
+                    label,
+                    manifest.files.len()
+                );
+                for file in &manifest.files {
+                    info!(
+                        "    {} ({} bytes, SHA-256 {})",
+                        file.name,
+                        file.size,
+                        hex::encode(file.sha256)
                     );
                 }
+            }),
+        }
+        Ok(None)
+    }
+
+    /// Measures a frame without a chunk. Once several in a row seem to show a QR code, warns
+    /// with the likely cause, as a scan that reads nothing is otherwise hard to troubleshoot.
+    fn check_quality(&mut self, gray: &GrayImage, label: &str) {
+        let quality = FrameQuality::measure(gray);
+        trace!("    Quality of {}: {:?}", label, quality);
+        if !quality.has_code_region {
+            return;
+        }
+        self.missed_codes += 1;
+        self.missed_in_row += 1;
+        if self.quality_warned || self.missed_in_row < MISSED_CODES_BEFORE_WARNING {
+            return;
+        }
+        self.quality_warned = true;
+        let hint = if quality.is_low_contrast() && !quality.is_blurry() {
+            "Frames appear low-contrast: avoid glare and reflections on the screen"
+        } else {
+            "Frames appear blurry or low-resolution: move the camera closer or encode with a larger pixel scale"
+        };
+        self.warn(format!(
+            "{} ({} frames in a row show no readable QR code; sharpness {:.2}, contrast {:.2})",
+            hint, self.missed_in_row, quality.sharpness, quality.contrast
+        ));
+    }
+
+    /// Logs `warning` and keeps it for [`DecodeResult::warnings`].
+    fn warn(&mut self, warning: String) {
+        self.progress.suspend(|| warn!("{}", warning));
+        self.warnings.push(warning);
+    }
+
+    /// Called when a transfer completes. Returns the files, unless every transfer is wanted
+    /// and scanning goes on.
+    fn complete(&mut self) -> Option<Vec<DecodedFile>> {
+        if self.options.all_transfers {
+            return None;
+        }
+        if self.stream.num_transfers() > 1 {
+            self.progress.suspend(|| {
+                info!("The input holds more than one transfer; only the first to complete was decoded")
+            });
+        }
+        Some(std::mem::take(&mut self.files))
+    }
+
+    /// Called once the input is exhausted. When every transfer is wanted, returns the files
+    /// completed so far, if any; `None` means the scan failed.
+    fn end_of_input(&mut self) -> Option<Vec<DecodedFile>> {
+        if !self.options.all_transfers || self.files.is_empty() || self.is_cancelled() {
+            return None;
+        }
+        let pending = self.stream.num_transfers() - self.stream.num_complete_transfers();
+        if pending > 0 {
+            self.warn(format!(
+                "{} transfer(s) did not receive enough packets and were skipped",
+                pending
+            ));
+        }
+        Some(std::mem::take(&mut self.files))
+    }
+
+    /// Ends a scan that produced its files, keeping its counters for the result.
+    fn finish(mut self) -> ScanStats {
+        let ignored = self.stream.mismatched_packets();
+        if ignored > 0 {
+            self.warn(format!(
+                "Ignored {} packet(s) from a different encoding of the same file",
+                ignored
+            ));
+        }
+        let transfer_lengths: BTreeMap<(u16, u8), usize> = self
+            .stream
+            .chunks()
+            .map(|chunk| {
+                let key = (chunk.header.transfer, chunk.header.file_id);
+                (key, chunk.header.total as usize)
+            })
+            .collect();
+        let channel = self.channel.stats();
+        if let Some(stats) = &channel {
+            self.progress.suspend(|| log_channel(stats));
+            if let Some(warning) = channel_warning(stats) {
+                self.warn(warning);
             }
         }
+        self.log_cached_frames();
+        let skipped = self.skipped_frames();
+        ScanStats {
+            num_chunks: self.stream.num_packets(),
+            compressed_bytes: transfer_lengths.values().sum(),
+            duplicates: self.duplicates,
+            scanned: self.scanned,
+            skipped,
+            started: self.started,
+            channel,
+            warnings: self.warnings,
+        }
+    }
+
+    /// Frames of the input the scan never reached, logged when there are any: once the
+    /// transfer completes, the rest of a directory or GIF goes unread.
+    fn skipped_frames(&self) -> usize {
+        let skipped = self
+            .total
+            .filter(|_| self.stopped.is_none())
+            .map_or(0, |total| (total as usize).saturating_sub(self.scanned));
+        if skipped > 0 {
+            self.progress.suspend(|| {
+                info!(
+                    "Transfer complete after {} of {} frame(s); skipped the remaining {}",
+                    self.scanned,
+                    self.scanned + skipped,
+                    skipped
+                )
+            });
+        }
+        skipped
+    }
+
+    /// Fails if decoding the files seen so far would take more memory than allowed.
+    fn check_memory(&self) -> Result<()> {
+        check_memory(
+            self.options.max_memory,
+            DECODE_MEMORY_FACTOR * self.stream.transfer_bytes(),
+        )
+    }
+
+    /// Whether the caller asked to stop.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        is_cancelled(&self.options.cancel)
+    }
+
+    /// Whether to stop before the next frame: the caller asked to, or the scan reached its
+    /// timeout or frame limit, or stalled.
+    pub(crate) fn should_stop(&mut self) -> bool {
+        if self.reached_limit() {
+            return true;
+        }
+        let idle = self.last_progress.elapsed();
+        if self
+            .options
+            .stall_timeout
+            .is_some_and(|stall| idle >= stall)
+        {
+            self.progress
+                .suspend(|| warn!("Giving up: no new packet for {:.0?}", idle));
+            self.stalled = Some(idle);
+            return true;
+        }
+        false
     }
 
-    if rq_decoder.num_chunks() == 0 {
-        return Err(anyhow!("No valid QR chunks found"));
+    /// Like [`Scan::should_stop`], without the stall check: for the frames of a batch, read
+    /// before any of them was fed.
+    fn reached_limit(&mut self) -> bool {
+        if self.is_cancelled() {
+            return true;
+        }
+        let Some(reason) = self.options.limit_reached(self.scanned, self.started) else {
+            return false;
+        };
+        self.progress
+            .suspend(|| warn!("Giving up after {}", reason));
+        self.stopped = Some(reason);
+        true
     }
 
-    Err(anyhow!(
-        "Could not decode with RaptorQ (insufficient packets after {} items)",
-        count
-    ))
+    /// Logs what the frames of a failed scan held, to tell a bad capture from a short one.
+    fn log_diagnostics(&self) {
+        self.progress.suspend(|| info!(
+            "Scanned {} frame(s) in {:.2?}: {} without a fountain chunk ({} seeming to show a QR code), {} unreadable, {} duplicate packet(s), {} unique packet(s) of at least {} needed",
+            self.scanned,
+            self.started.elapsed(),
+            self.no_chunk,
+            self.missed_codes,
+            self.unreadable,
+            self.duplicates,
+            self.stream.num_packets(),
+            self.stream.expected_packets().unwrap_or_default()
+        ));
+        if let Some(stats) = self.channel.stats() {
+            self.progress.suspend(|| {
+                log_channel(&stats);
+                if let Some(warning) = channel_warning(&stats) {
+                    warn!("{}", warning);
+                }
+            });
+        }
+        self.log_cached_frames();
+    }
+
+    fn log_cached_frames(&self) {
+        if self.cached_frames > 0 {
+            self.progress.suspend(|| {
+                info!(
+                    "Took the codes of {} frame(s) from the session instead of detecting them",
+                    self.cached_frames
+                )
+            });
+        }
+    }
+
+    /// Ends a scan that ran out of input or was cancelled: saves the session and the salvage,
+    /// if any, and returns the error to report.
+    fn fail(self) -> FountainError {
+        let cancelled = self.is_cancelled();
+        self.log_diagnostics();
+        drop(self.progress);
+        if let Some(salvage_dir) = self.options.salvage_dir.as_deref() {
+            if let Err(e) = write_salvage(salvage_dir, &self.stream, self.txqr.as_ref()) {
+                return e;
+            }
+        }
+        let limit_error = |stream: &StreamDecoder, reason: String| FountainError::LimitReached {
+            reason,
+            received: stream.num_packets(),
+            needed: stream.expected_packets().unwrap_or_default(),
+            scanned: self.scanned,
+        };
+        let stall_error = |stream: &StreamDecoder, idle: Duration| FountainError::Stalled {
+            idle,
+            received: stream.num_packets(),
+            needed: stream.expected_packets().unwrap_or_default(),
+            scanned: self.scanned,
+        };
+
+        // Frames without a chunk are worth saving too, so they need no detection next time.
+        let detections = self.detections.unwrap_or_default();
+        if let Some(session_path) = self
+            .options
+            .session_file
+            .as_deref()
+            .filter(|_| self.stream.num_packets() > 0 || !detections.is_empty())
+        {
+            let session = Session {
+                chunks: self.stream.chunks().cloned().collect(),
+                detections,
+            };
+            if let Err(e) = session.save(session_path) {
+                return e;
+            }
+            info!(
+                "Saved {} packet(s) and the codes of {} frame(s) to session {}; resume with more input to finish",
+                session.chunks.len(),
+                session.detections.len(),
+                session_path.display()
+            );
+        }
+
+        if self.stream.num_packets() == 0 {
+            return match (self.stopped, self.stalled) {
+                _ if cancelled => FountainError::Cancelled,
+                (Some(reason), _) => limit_error(&self.stream, reason),
+                (None, Some(idle)) => stall_error(&self.stream, idle),
+                (None, None) => FountainError::NoChunksFound,
+            };
+        }
+
+        if cancelled {
+            return FountainError::Cancelled;
+        }
+        if let Some(reason) = self.stopped {
+            return limit_error(&self.stream, reason);
+        }
+        if let Some(idle) = self.stalled {
+            return stall_error(&self.stream, idle);
+        }
+        let ignored = self.stream.mismatched_packets();
+        if ignored > 0 {
+            return FountainError::MismatchedPackets {
+                ignored,
+                received: self.stream.num_packets() - ignored,
+                needed: self.stream.expected_packets().unwrap_or_default(),
+            };
+        }
+        FountainError::NotEnoughPackets {
+            received: self.stream.num_packets(),
+            needed: self.stream.expected_packets().unwrap_or_default(),
+            scanned: self.scanned,
+        }
+    }
 }
 
-pub fn decode_from_gif(input_file: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
-    let file = File::open(input_file)?;
-    let reader = BufReader::new(file);
-    let gif_decoder = GifDecoder::new(reader)?;
-    let frames = gif_decoder.into_frames();
+/// Tells how many of the frames shown the capture caught.
+fn log_channel(stats: &ChannelStats) {
+    let fps = stats
+        .capture_fps
+        .map(|fps| format!(" at {:.1} captures per second", fps))
+        .unwrap_or_default();
+    info!(
+        "Caught {} of {} frames shown ({:.0}%){}, missing at most {} in a row",
+        stats.frames_caught,
+        stats.frames_shown,
+        stats.caught_share() * 100.0,
+        fps,
+        stats.longest_gap
+    );
+}
 
-    println!("Decoding QR codes from GIF: {}", input_file.display());
+/// How much longer to show the frames, if the capture missed many.
+fn channel_warning(stats: &ChannelStats) -> Option<String> {
+    stats.interval_factor().map(|factor| {
+        format!(
+            "The capture misses frames; send with about {}x the --interval to catch them",
+            factor
+        )
+    })
+}
 
-    let images = frames.enumerate().map(|(i, frame_result)| {
-        let label = format!("frame {}", i + 1);
-        let res = frame_result
-            .map(|frame| DynamicImage::ImageRgba8(frame.buffer().clone()))
-            .map_err(anyhow::Error::from);
-        (res, label)
-    });
+/// What feeding a chunk to the stream gave, for [`ScannedFrame::outcome`].
+fn packet_outcome(fed: &FeedResult) -> FrameOutcome {
+    match fed {
+        FeedResult::Duplicate => FrameOutcome::Duplicate,
+        _ => FrameOutcome::New,
+    }
+}
 
-    decode_core(images, output_file, Path::new("."))
+/// Deletes the session file of a decode that has finished.
+pub(crate) fn remove_session(options: &DecodeOptions) -> Result<()> {
+    if let Some(session_path) = options.session_file.as_deref().filter(|p| p.exists()) {
+        fs::remove_file(session_path)?;
+        debug!("Removed finished session {}", session_path.display());
+    }
+    Ok(())
 }
 
-pub fn decode_from_images(input_dir: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
-    let images_files: Vec<_> = fs::read_dir(input_dir)?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry
-                .path()
-                .extension()
-                .map(|ext| ext.to_ascii_lowercase() == QR_FILE_EXTENSION)
-                .unwrap_or(false)
-        })
-        .map(|entry| entry.path())
-        .collect();
+fn decode_core(
+    source: &mut dyn FrameSource,
+    default_dir: &Path,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
+    let (files, stats) = scan_frames(source, options)?;
+    let output_paths = write_files(&files, default_dir, options)?;
+    options.report_complete();
+    remove_session(options)?;
 
-    if images_files.is_empty() {
-        return Err(anyhow!(
-            "No image ({}) files found in directory",
-            QR_FILE_EXTENSION
-        ));
+    Ok(stats.result(files, &output_paths))
+}
+
+/// Scans `source` until the transfer is complete, resuming from the session file if one is
+/// configured. Nothing is written, except the session file of a scan that fails.
+pub(crate) fn scan_frames(
+    source: &mut dyn FrameSource,
+    options: &DecodeOptions,
+) -> Result<(Vec<DecodedFile>, ScanStats)> {
+    Scan::new(source.len_hint(), options).run(source)
+}
+
+/// Writes decoded files to the paths picked by [`output_paths_for`]. With
+/// [`DecodeOptions::check_only`], only logs what was verified and writes nothing.
+pub(crate) fn write_files(
+    files: &[DecodedFile],
+    default_dir: &Path,
+    options: &DecodeOptions,
+) -> Result<Vec<PathBuf>> {
+    if options.check_only {
+        for file in files {
+            info!(
+                "Verified {} ({} bytes, SHA-256 {}); not written",
+                file.filename,
+                file.data.len(),
+                hex::encode(Sha256::digest(&file.data))
+            );
+        }
+        return Ok(Vec::new());
     }
+    let output_paths = output_paths_for(files, default_dir, options)?;
+    for (file, output_path) in files.iter().zip(&output_paths) {
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(output_path, &file.data)?;
+    }
+    Ok(output_paths)
+}
 
-    println!("Found {} QR code image(s)", images_files.len());
+impl Decoder {
+    pub fn new(options: DecodeOptions) -> Self {
+        Self { options }
+    }
 
-    let images = images_files.into_iter().map(|path| {
-        let label = path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-        let res = image::open(path).map_err(anyhow::Error::from);
-        (res, label)
-    });
+    pub fn options(&self) -> &DecodeOptions {
+        &self.options
+    }
+
+    /// Decodes a directory of QR images, a GIF file or a text file of packet lines, picking the
+    /// reader from the input type. A directory may also hold packets written by
+    /// [`crate::Encoder::export_packets`].
+    pub fn decode(&self, input: &Path) -> Result<DecodeResult> {
+        if !input.exists() {
+            return Err(FountainError::InputNotFound(input.to_path_buf()));
+        }
+
+        if input.is_dir() {
+            info!("Decoding QR codes from directory: {}", input.display());
+            return self.decode_images(input);
+        }
+
+        let is_gif = input
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("gif"))
+            .unwrap_or(false);
+        if is_gif {
+            self.decode_gif(input)
+        } else if is_packet_lines(input) {
+            self.decode_packet_lines(input)
+        } else {
+            Err(FountainError::UnsupportedInput(input.to_path_buf()))
+        }
+    }
+
+    /// Decodes several inputs, such as partial recordings of one transfer, as if they were one:
+    /// the packets of all of them go to a single decoder, so none needs enough on its own. The
+    /// inputs are read in order until the transfer completes, and files are written to the
+    /// current directory unless an output is configured.
+    pub fn decode_inputs(&self, inputs: &[&Path]) -> Result<DecodeResult> {
+        if let [input] = inputs {
+            return self.decode(input);
+        }
+        info!("Pooling the packets of {} inputs", inputs.len());
+        let sources = inputs
+            .iter()
+            .map(|input| Ok((input.display().to_string(), open_source(input)?)))
+            .collect::<Result<_>>()?;
+        decode_core(
+            &mut ChainSource::new(sources),
+            Path::new("."),
+            &self.options,
+        )
+    }
+
+    /// Decodes the frames of any [`FrameSource`], such as a camera or a screen capture, until
+    /// the transfer completes, and writes the files to the current directory unless an output
+    /// is configured.
+    pub fn decode_source(&self, source: &mut dyn FrameSource) -> Result<DecodeResult> {
+        decode_core(source, Path::new("."), &self.options)
+    }
+
+    /// Decodes a text file with one packet per line: the output of
+    /// [`crate::Encoder::encode_files_to_packets`], or payloads collected with another scanner
+    /// such as `zbarimg`. Lines that are not packets are counted as unreadable.
+    pub fn decode_packet_lines(&self, input_file: &Path) -> Result<DecodeResult> {
+        let mut source = PacketLinesSource::open(input_file)?;
+        decode_core(&mut source, Path::new("."), &self.options)
+    }
+
+    pub fn decode_gif(&self, input_file: &Path) -> Result<DecodeResult> {
+        let mut source = GifSource::open(input_file)?;
+        decode_core(&mut source, Path::new("."), &self.options)
+    }
+
+    pub fn decode_images(&self, input_dir: &Path) -> Result<DecodeResult> {
+        let mut source = DirSource::open(input_dir)?;
+        decode_core(
+            &mut source,
+            input_dir.parent().unwrap_or(Path::new(".")),
+            &self.options,
+        )
+    }
 
-    decode_core(
-        images,
-        output_file,
-        input_dir.parent().unwrap_or(Path::new(".")),
-    )
+    /// Like [`Decoder::decode`], returning the files instead of writing them, e.g. to print a
+    /// text transfer. Output and overwrite options do not apply; the session file does.
+    pub fn decode_files(&self, input: &Path) -> Result<Vec<DecodedFile>> {
+        let (files, _) = scan_frames(open_source(input)?.as_mut(), &self.options)?;
+        self.options.report_complete();
+        remove_session(&self.options)?;
+        Ok(files)
+    }
+
+    /// Decodes frames that are already in memory, such as camera captures or an upload.
+    /// Nothing is read or written, except the session file if one is configured.
+    /// Returns one file, or every file of a multi-file transfer in the order they completed.
+    pub fn decode_frames<I>(&self, frames: I) -> Result<Vec<DecodedFile>>
+    where
+        I: IntoIterator<Item = DynamicImage>,
+    {
+        let (files, _) = scan_frames(&mut ImageSource::new(frames), &self.options)?;
+        self.options.report_complete();
+        remove_session(&self.options)?;
+        Ok(files)
+    }
+}
+
+/// Distinct chunks of `input`, in the order they first appear. Frames without a readable QR
+/// code are skipped with a warning; fails if none holds a chunk.
+pub(crate) fn input_chunks(input: &Path, cancel: &Option<CancelFlag>) -> Result<Vec<Chunk>> {
+    let mut source = open_source(input)?;
+    let mut seen = HashSet::new();
+    let mut chunks = Vec::new();
+    let mut unreadable = 0;
+    for SourceFrame { frame, label, .. } in source.frames() {
+        if is_cancelled(cancel) {
+            return Err(FountainError::Cancelled);
+        }
+        let chunk = match frame {
+            Ok(InputFrame::Image(img)) => decode_qr_from_dynamic_image(&img)
+                .ok()
+                .and_then(|qr_bytes| decode_qr_bytes_to_chunk(&qr_bytes)),
+            Ok(InputFrame::Packet(chunk)) => Some(chunk),
+            Err(e) => {
+                debug!("Failed to load {}: {}", label, e);
+                None
+            }
+        };
+        let Some(chunk) = chunk else {
+            debug!("No packet read from {}", label);
+            unreadable += 1;
+            continue;
+        };
+        if seen.insert(chunk.to_bytes()?) {
+            chunks.push(chunk);
+        }
+    }
+    if chunks.is_empty() {
+        return Err(FountainError::NoChunksFound);
+    }
+    if unreadable > 0 {
+        warn!(
+            "Skipped {} frame(s) of {} without a readable packet",
+            unreadable,
+            input.display()
+        );
+    }
+    info!(
+        "Read {} distinct packet(s) from {}",
+        chunks.len(),
+        input.display()
+    );
+    Ok(chunks)
+}
+
+/// Parses one line of a packet text file: base64 of a serialized chunk, or the base45 text of a
+/// QR code. The `QR-Code:` prefix `zbarimg` puts before each result is ignored.
+pub(crate) fn parse_packet_line(line: &str) -> Result<Chunk> {
+    let payload = line.strip_prefix("QR-Code:").unwrap_or(line);
+    let from_base64 = BASE64_STANDARD
+        .decode(payload)
+        .ok()
+        .and_then(|bytes| Chunk::from_bytes(&bytes).ok());
+    match from_base64 {
+        Some(chunk) => Ok(chunk),
+        None => Ok(Chunk::from_text(payload)?),
+    }
+}
+
+/// Whether `path` is a packet file written by [`crate::Encoder::export_packets`].
+pub(crate) fn is_packet_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(PACKET_FILE_EXTENSION))
+}
+
+/// Whether `path` is a text file of packet lines, read by [`Decoder::decode_packet_lines`].
+pub(crate) fn is_packet_lines(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(PACKET_LINES_EXTENSION))
+}
+
+/// Whether `path` looks like an image of a QR code: one written by the encoder, or a photo.
+pub(crate) fn is_qr_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        DECODE_IMAGE_EXTENSIONS
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    })
+}
+
+/// Replaces an existing output, as these free functions always have; refuse to, or resume a
+/// session, with [`DecodeOptions`] on a [`Decoder`] instead.
+fn decoder_for(output_file: Option<&Path>) -> Decoder {
+    let mut options = DecodeOptions::new().overwrite(true);
+    options.output = output_file.map(Path::to_path_buf);
+    Decoder::new(options)
+}
+
+pub fn decode_from_gif(input_file: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
+    decoder_for(output_file).decode_gif(input_file)
+}
+
+pub fn decode_from_images(input_dir: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
+    decoder_for(output_file).decode_images(input_dir)
+}
+
+/// Free-function form of [`Decoder::decode_frames`] with default options.
+pub fn decode_frames<I>(frames: I) -> Result<Vec<DecodedFile>>
+where
+    I: IntoIterator<Item = DynamicImage>,
+{
+    Decoder::new(DecodeOptions::new()).decode_frames(frames)
 }
@@ -0,0 +1,239 @@
+//! Binary deltas against a base file both sides already hold, so a slightly changed large file
+//! costs only its changes over the channel.
+//!
+//! A delta is `FDLT`, the size and SHA-256 of the base, the size of the target, then a run of
+//! operations: `[0] [Length]` followed by bytes to insert, or `[1] [Offset] [Length]` to copy
+//! from the base. Numbers are LEB128 varints. It travels as an ordinary file named
+//! `<name>.fdelta`, which the decoder applies to its copy of the base.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::error::{FountainError, Result};
+
+/// Extension of the files that carry a delta instead of the file itself.
+pub const DELTA_EXTENSION: &str = "fdelta";
+
+const MAGIC: &[u8; 4] = b"FDLT";
+const OP_INSERT: u8 = 0;
+const OP_COPY: u8 = 1;
+
+/// Bytes of the base indexed at a time; changes are found with this granularity, and shorter
+/// matches are sent as inserts.
+const BLOCK_SIZE: usize = 32;
+const HASH_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Name a delta of the file `filename` travels under.
+pub fn delta_filename(filename: &str) -> String {
+    format!("{}.{}", filename, DELTA_EXTENSION)
+}
+
+/// Name of the file a delta named `filename` rebuilds. `None` if it does not name a delta, or
+/// the name left is only dots, such as `..`, which is no file to patch.
+pub fn patched_filename(filename: &str) -> Option<&str> {
+    filename
+        .strip_suffix(DELTA_EXTENSION)?
+        .strip_suffix('.')
+        .filter(|name| !name.trim_matches('.').is_empty())
+}
+
+/// Delta that turns `base` into `target`.
+///
+/// The base is indexed block by block; a rolling hash over the target finds blocks it shares
+/// with the base, and each match is extended both ways byte by byte. Whatever no match covers
+/// is inserted as is.
+pub fn make_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    delta.extend_from_slice(MAGIC);
+    write_varint(&mut delta, base.len() as u64);
+    delta.extend_from_slice(&Sha256::digest(base));
+    write_varint(&mut delta, target.len() as u64);
+
+    let mut index: HashMap<u64, usize> = HashMap::new();
+    for (block, offset) in base.chunks_exact(BLOCK_SIZE).zip((0..).step_by(BLOCK_SIZE)) {
+        index.entry(block_hash(block)).or_insert(offset);
+    }
+    let drop_factor = HASH_PRIME.wrapping_pow(BLOCK_SIZE as u32 - 1);
+
+    // Target bytes before `pending` are covered by the operations written so far.
+    let mut pending = 0;
+    let mut pos = 0;
+    let mut hash = None;
+    while pos + BLOCK_SIZE <= target.len() {
+        let current = hash.unwrap_or_else(|| block_hash(&target[pos..pos + BLOCK_SIZE]));
+        let matched = index
+            .get(&current)
+            .filter(|&&offset| base[offset..offset + BLOCK_SIZE] == target[pos..pos + BLOCK_SIZE]);
+        if let Some(&offset) = matched {
+            let (mut start, mut base_start) = (pos, offset);
+            while start > pending && base_start > 0 && target[start - 1] == base[base_start - 1] {
+                start -= 1;
+                base_start -= 1;
+            }
+            let (mut end, mut base_end) = (pos + BLOCK_SIZE, offset + BLOCK_SIZE);
+            while end < target.len() && base_end < base.len() && target[end] == base[base_end] {
+                end += 1;
+                base_end += 1;
+            }
+            write_insert(&mut delta, &target[pending..start]);
+            delta.push(OP_COPY);
+            write_varint(&mut delta, base_start as u64);
+            write_varint(&mut delta, (end - start) as u64);
+            pending = end;
+            pos = end;
+            hash = None;
+            continue;
+        }
+
+        hash = target.get(pos + BLOCK_SIZE).map(|&next| {
+            let dropped = current.wrapping_sub(byte_value(target[pos]).wrapping_mul(drop_factor));
+            dropped
+                .wrapping_mul(HASH_PRIME)
+                .wrapping_add(byte_value(next))
+        });
+        pos += 1;
+    }
+    write_insert(&mut delta, &target[pending..]);
+    delta
+}
+
+/// Rebuilds the target of `delta` from `base`. Fails with [`FountainError::DeltaBaseMismatch`]
+/// if the delta was made against a different base.
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || FountainError::InvalidDelta("truncated".to_string());
+    let mut rest = delta
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| FountainError::InvalidDelta("not a delta".to_string()))?;
+    let base_size = read_varint(&mut rest).ok_or_else(invalid)?;
+    let (sha256, after) = rest.split_at_checked(32).ok_or_else(invalid)?;
+    rest = after;
+    if base_size != base.len() as u64 || sha256 != Sha256::digest(base).as_slice() {
+        return Err(FountainError::DeltaBaseMismatch);
+    }
+    let target_size = read_varint(&mut rest).ok_or_else(invalid)?;
+
+    let mut target = Vec::with_capacity(target_size.min(delta.len() as u64 * 64) as usize);
+    while let Some((&op, after)) = rest.split_first() {
+        rest = after;
+        match op {
+            OP_INSERT => {
+                let len = read_varint(&mut rest).ok_or_else(invalid)?;
+                let (bytes, after) = usize::try_from(len)
+                    .ok()
+                    .and_then(|len| rest.split_at_checked(len))
+                    .ok_or_else(invalid)?;
+                target.extend_from_slice(bytes);
+                rest = after;
+            }
+            OP_COPY => {
+                let offset = read_varint(&mut rest).ok_or_else(invalid)?;
+                let len = read_varint(&mut rest).ok_or_else(invalid)?;
+                let bytes = offset
+                    .checked_add(len)
+                    .and_then(|end| base.get(offset as usize..end as usize))
+                    .ok_or_else(|| {
+                        FountainError::InvalidDelta("copy past the end of the base".to_string())
+                    })?;
+                target.extend_from_slice(bytes);
+            }
+            op => {
+                return Err(FountainError::InvalidDelta(format!(
+                    "unknown operation {}",
+                    op
+                )))
+            }
+        }
+        if target.len() as u64 > target_size {
+            break;
+        }
+    }
+    if target.len() as u64 != target_size {
+        return Err(FountainError::InvalidDelta(format!(
+            "rebuilt {} bytes, expected {}",
+            target.len(),
+            target_size
+        )));
+    }
+    Ok(target)
+}
+
+fn byte_value(byte: u8) -> u64 {
+    u64::from(byte) + 1
+}
+
+fn block_hash(block: &[u8]) -> u64 {
+    block.iter().fold(0, |hash: u64, &byte| {
+        hash.wrapping_mul(HASH_PRIME).wrapping_add(byte_value(byte))
+    })
+}
+
+fn write_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        delta.push(OP_INSERT);
+        write_varint(delta, bytes.len() as u64);
+        delta.extend_from_slice(bytes);
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        value |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let base: Vec<u8> = (0..20_000u32)
+            .flat_map(|i| (i * 7919).to_le_bytes())
+            .collect();
+        let mut target = base.clone();
+        target[1000..1010].copy_from_slice(b"0123456789");
+        target.splice(40_000..40_000, b"inserted".iter().copied());
+        target.drain(60_000..60_500);
+        target.extend_from_slice(b"appended");
+
+        let delta = make_delta(&base, &target);
+        assert!(delta.len() < 200, "delta is {} bytes", delta.len());
+        assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+
+        // Nothing in common, or an empty base: the whole target is inserted.
+        assert_eq!(apply_delta(b"", &make_delta(b"", &target)).unwrap(), target);
+        assert_eq!(apply_delta(&base, &make_delta(&base, b"")).unwrap(), b"");
+
+        let mut other = base.clone();
+        other[0] ^= 1;
+        assert!(matches!(
+            apply_delta(&other, &delta),
+            Err(FountainError::DeltaBaseMismatch)
+        ));
+        assert!(matches!(
+            apply_delta(&base, &delta[..delta.len() - 1]),
+            Err(FountainError::InvalidDelta(_))
+        ));
+
+        assert_eq!(patched_filename(&delta_filename("a.bin")), Some("a.bin"));
+        assert_eq!(patched_filename("a.bin"), None);
+        assert_eq!(patched_filename(".fdelta"), None);
+        assert_eq!(patched_filename("..fdelta"), None);
+        assert_eq!(patched_filename("...fdelta"), None);
+    }
+}
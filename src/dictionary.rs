@@ -0,0 +1,52 @@
+//! Preset compression dictionaries, shared out of band between sender and receiver.
+//!
+//! A transfer of a small file that resembles many others, such as a config or a certificate,
+//! spends most of its bytes on what they all have in common. Deflate matches may reach back
+//! into a dictionary both sides already hold, so that common part costs next to nothing; the
+//! zlib header carries the dictionary's ID, from which the decoder picks among those it has.
+
+use std::collections::{HashMap, HashSet};
+
+/// Most bytes of a dictionary that deflate matches can reach: the size of its window.
+pub const MAX_DICTIONARY_SIZE: usize = 32 * 1024;
+
+/// Shortest line worth keeping in a dictionary; deflate matches are at least 3 bytes.
+const MIN_LINE_LEN: usize = 4;
+
+/// Builds a dictionary of at most `max_size` bytes from `samples` of the files it is for.
+///
+/// Lines found in at least two samples are kept (every line, from a single sample), those
+/// that save the most bytes across the samples first. They are written with the most
+/// valuable last, as deflate reaches nearer bytes with shorter codes.
+pub fn train_dictionary(samples: &[&[u8]], max_size: usize) -> Vec<u8> {
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for sample in samples {
+        let lines: HashSet<&[u8]> = sample.split_inclusive(|&byte| byte == b'\n').collect();
+        for line in lines {
+            *counts.entry(line).or_default() += 1;
+        }
+    }
+
+    let min_count = samples.len().min(2);
+    let mut lines: Vec<(&[u8], usize)> = counts
+        .into_iter()
+        .filter(|&(line, count)| count >= min_count && line.len() >= MIN_LINE_LEN)
+        .map(|(line, count)| (line, count * line.len()))
+        .collect();
+    lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let mut size = 0;
+    lines.retain(|(line, _)| {
+        let fits = size + line.len() <= max_size;
+        if fits {
+            size += line.len();
+        }
+        fits
+    });
+    lines
+        .iter()
+        .rev()
+        .flat_map(|(line, _)| *line)
+        .copied()
+        .collect()
+}
@@ -0,0 +1,224 @@
+//! Comparing the packets of two inputs, such as two recordings of one transfer, to tell whether
+//! decoding them together is worth it before doing so, and sizing up how much of a transfer a
+//! partial capture still lacks.
+//!
+//! Packets are matched by their encoding parameters and ESI (encoding symbol ID), so the same
+//! packet read from a GIF in one input and a photo in the other counts once.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::chunk::Chunk;
+use crate::decode::{input_chunks, Decoder};
+use crate::error::{FountainError, Result};
+use crate::session::Session;
+use crate::stream::StreamDecoder;
+
+/// What two inputs hold, from [`Decoder::diff`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputDiff {
+    /// Distinct packets read from the first input.
+    pub packets_a: usize,
+    /// Distinct packets read from the second input.
+    pub packets_b: usize,
+    /// ESIs of the packets found in both inputs, in ascending order. In a transfer of several
+    /// files, an ESI can be listed once per file.
+    pub common_esis: Vec<u32>,
+    /// Packets only the first input holds.
+    pub only_a: usize,
+    /// Packets only the second input holds.
+    pub only_b: usize,
+    /// Source packets of the transfers seen, the fewest that can decode them.
+    pub expected_packets: usize,
+    /// Whether the first input alone decodes every transfer it holds.
+    pub complete_a: bool,
+    /// Whether the second input alone decodes every transfer it holds.
+    pub complete_b: bool,
+    /// Whether both inputs together decode every transfer they hold.
+    pub complete_combined: bool,
+}
+
+impl InputDiff {
+    /// Distinct packets of both inputs together.
+    pub fn combined_packets(&self) -> usize {
+        self.packets_a + self.only_b
+    }
+
+    /// Rough number of packets still missing once both inputs are combined: 0 if they decode,
+    /// and at least 1 otherwise, as decoding sometimes needs a few more than the source count.
+    pub fn missing_packets(&self) -> usize {
+        if self.complete_combined {
+            return 0;
+        }
+        self.expected_packets
+            .saturating_sub(self.combined_packets())
+            .max(1)
+    }
+}
+
+/// How much of a transfer a partial capture holds, from [`Decoder::estimate`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureEstimate {
+    /// Distinct packets held that can be decoded together.
+    pub packets: usize,
+    /// Source packets of the transfers seen, the fewest that can decode them.
+    pub expected_packets: usize,
+    /// Frames in the sender's loop, going by the highest ESI seen, and at least enough to hold
+    /// the packets still missing.
+    pub loop_frames: usize,
+    /// Whether the packets held decode every transfer seen.
+    pub complete: bool,
+}
+
+impl CaptureEstimate {
+    /// Rough number of packets still missing: 0 if they decode, and at least 1 otherwise, as
+    /// decoding sometimes needs a few more than the source count.
+    pub fn missing_packets(&self) -> usize {
+        if self.complete {
+            return 0;
+        }
+        self.expected_packets.saturating_sub(self.packets).max(1)
+    }
+
+    /// Frames to capture from any point of the loop to collect the missing packets, as some of
+    /// them repeat packets already held.
+    pub fn capture_frames(&self) -> usize {
+        let missing = self.missing_packets();
+        if missing == 0 {
+            return 0;
+        }
+        let unseen = self.loop_frames.saturating_sub(self.packets).max(missing);
+        (missing * self.loop_frames).div_ceil(unseen)
+    }
+
+    /// Seconds of capture [`CaptureEstimate::capture_frames`] takes at a frame interval of
+    /// `interval_ms`.
+    pub fn capture_secs(&self, interval_ms: u64) -> f64 {
+        self.capture_frames() as f64 * interval_ms as f64 / 1000.0
+    }
+
+    /// Seconds one pass of the sender's loop takes at a frame interval of `interval_ms`.
+    pub fn loop_secs(&self, interval_ms: u64) -> f64 {
+        self.loop_frames as f64 * interval_ms as f64 / 1000.0
+    }
+}
+
+/// Identifies a packet across inputs: packets decode together only if these all agree.
+type PacketKey = (u16, u32, u8, u32, u16, u32);
+
+fn packet_key(chunk: &Chunk) -> PacketKey {
+    let header = &chunk.header;
+    (
+        header.transfer,
+        header.segment,
+        header.file_id,
+        header.total,
+        header.packet_size,
+        header.index,
+    )
+}
+
+/// `chunks` without the manifest of a multi-file transfer, which holds no packets.
+fn packet_chunks(mut chunks: Vec<Chunk>) -> Vec<Chunk> {
+    chunks.retain(|chunk| !chunk.is_manifest());
+    chunks
+}
+
+/// Feeds `chunks` to `stream` and tells whether every transfer it has seen is now complete.
+fn feed_all<'a>(
+    stream: &mut StreamDecoder,
+    chunks: impl IntoIterator<Item = &'a Chunk>,
+) -> Result<bool> {
+    for chunk in chunks {
+        stream.feed_chunk(chunk.clone())?;
+    }
+    Ok(stream.num_transfers() > 0 && stream.num_complete_transfers() == stream.num_transfers())
+}
+
+impl Decoder {
+    /// Reads the packets of inputs `a` and `b`, each a directory of QR images or packet files,
+    /// a GIF or a text file of packet lines, and compares them: which packets they share, which
+    /// only one holds, and whether each alone or both together decode. Nothing is written.
+    pub fn diff(&self, a: &Path, b: &Path) -> Result<InputDiff> {
+        let chunks_a = packet_chunks(input_chunks(a, &self.options().cancel)?);
+        let chunks_b = packet_chunks(input_chunks(b, &self.options().cancel)?);
+        let keys_a: BTreeSet<PacketKey> = chunks_a.iter().map(packet_key).collect();
+        let keys_b: BTreeSet<PacketKey> = chunks_b.iter().map(packet_key).collect();
+
+        let mut common_esis: Vec<u32> = keys_a
+            .intersection(&keys_b)
+            .map(|&(.., index)| index)
+            .collect();
+        common_esis.sort_unstable();
+        let only_b: Vec<&Chunk> = chunks_b
+            .iter()
+            .filter(|chunk| !keys_a.contains(&packet_key(chunk)))
+            .collect();
+
+        let dictionaries = &self.options().dictionaries;
+        let mut combined = StreamDecoder::with_dictionaries(dictionaries.clone());
+        let complete_a = feed_all(&mut combined, &chunks_a)?;
+        let complete_combined = feed_all(&mut combined, only_b.iter().copied())?;
+        let complete_b = feed_all(
+            &mut StreamDecoder::with_dictionaries(dictionaries.clone()),
+            &chunks_b,
+        )?;
+
+        Ok(InputDiff {
+            packets_a: keys_a.len(),
+            packets_b: keys_b.len(),
+            only_a: keys_a.difference(&keys_b).count(),
+            only_b: keys_b.difference(&keys_a).count(),
+            common_esis,
+            expected_packets: combined.expected_packets().unwrap_or_default(),
+            complete_a,
+            complete_b,
+            complete_combined,
+        })
+    }
+
+    /// Tells how much the packets in the session file, if one is set, and in `inputs` lack to
+    /// decode, from which [`CaptureEstimate`] works out how long to keep capturing. Nothing is
+    /// written, and the session is left as it is.
+    pub fn estimate(&self, inputs: &[&Path]) -> Result<CaptureEstimate> {
+        let mut chunks = Vec::new();
+        if let Some(session_path) = self.options().session_file.as_deref() {
+            if !session_path.exists() {
+                return Err(FountainError::InputNotFound(session_path.to_path_buf()));
+            }
+            chunks.extend(Session::load_or_default(session_path)?.chunks);
+        }
+        for input in inputs {
+            chunks.extend(input_chunks(input, &self.options().cancel)?);
+        }
+        let chunks = packet_chunks(chunks);
+        if chunks.is_empty() {
+            return Err(FountainError::NoChunksFound);
+        }
+
+        // The loop holds each file's packets up to the highest ESI sent.
+        let mut highest: BTreeMap<(u16, u32, u8), u32> = BTreeMap::new();
+        for chunk in &chunks {
+            let header = &chunk.header;
+            let esi = highest
+                .entry((header.transfer, header.segment, header.file_id))
+                .or_default();
+            *esi = (*esi).max(header.index);
+        }
+
+        let keys: BTreeSet<PacketKey> = chunks.iter().map(packet_key).collect();
+        let mut stream = StreamDecoder::with_dictionaries(self.options().dictionaries.clone());
+        let complete = feed_all(&mut stream, &chunks)?;
+        let mut estimate = CaptureEstimate {
+            packets: keys.len() - stream.mismatched_packets(),
+            expected_packets: stream.expected_packets().unwrap_or_default(),
+            loop_frames: highest.values().map(|&esi| esi as usize + 1).sum(),
+            complete,
+        };
+        estimate.loop_frames = estimate
+            .loop_frames
+            .max(estimate.packets + estimate.missing_packets());
+        Ok(estimate)
+    }
+}
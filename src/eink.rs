@@ -0,0 +1,244 @@
+//! Output for e-ink displays: strictly black-and-white PNGs the size of the panel.
+//!
+//! E-ink photo frames make good passive senders, but they dither, smooth or reject the RGB
+//! frames of the regular image output. Here every frame is a 1-bit PNG as large as the panel,
+//! with the code centred at a whole number of pixels per module, so no pixel is grey and the
+//! frame has nothing left to dither. A [`SLIDESHOW_MANIFEST`] lists the frames in order with
+//! their interval, for frames and scripts that play a playlist.
+
+use image::RgbImage;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::encode::{frame_file_name, image_stem, EncodeResult, Encoder};
+use crate::error::{FountainError, Result};
+use crate::progress::Progress;
+
+/// Name of the slideshow manifest written next to the frames.
+pub const SLIDESHOW_MANIFEST: &str = "slideshow.json";
+
+/// Pixels kept clear around the code unless another margin is set.
+pub const DEFAULT_EINK_MARGIN: u32 = 16;
+
+/// Common e-ink panels by name, with their resolution in landscape.
+pub const EINK_DISPLAYS: &[(&str, u32, u32)] = &[
+    ("waveshare-4.2", 400, 300),
+    ("waveshare-7.5", 800, 480),
+    ("inkplate-6", 800, 600),
+    ("inkplate-10", 1200, 825),
+    ("kindle", 1448, 1072),
+    ("remarkable", 1872, 1404),
+];
+
+/// Fewest pixels per module that a camera reliably resolves on a panel.
+const MIN_READABLE_MODULE_PIXELS: u32 = 3;
+
+/// Panel that [`Encoder::encode_to_eink`] renders for: its resolution, and the margin kept
+/// clear around the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EinkDisplay {
+    pub width: u32,
+    pub height: u32,
+    pub margin: u32,
+}
+
+impl EinkDisplay {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            margin: DEFAULT_EINK_MARGIN,
+        }
+    }
+
+    pub fn margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Pixels per module for a code `modules` wide, quiet zone included; 0 if it does not fit.
+    fn module_pixels(&self, modules: u32) -> u32 {
+        let side = self.width.min(self.height).saturating_sub(2 * self.margin);
+        side / modules
+    }
+}
+
+impl FromStr for EinkDisplay {
+    type Err = FountainError;
+
+    /// Parses a panel name from [`EINK_DISPLAYS`] or a `WIDTHxHEIGHT` resolution.
+    fn from_str(spec: &str) -> Result<Self> {
+        if let Some(&(_, width, height)) = EINK_DISPLAYS
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(spec))
+        {
+            return Ok(Self::new(width, height));
+        }
+        spec.split_once(['x', 'X'])
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .map(|(width, height)| Self::new(width, height))
+            .ok_or_else(|| {
+                let names: Vec<_> = EINK_DISPLAYS.iter().map(|(name, _, _)| *name).collect();
+                FountainError::InvalidConfig(format!(
+                    "Unknown e-ink display {}: use WIDTHxHEIGHT or one of {}",
+                    spec,
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+impl Encoder {
+    /// Writes one 1-bit PNG per QR code into `output_dir`, each the size of `display`, and a
+    /// [`SLIDESHOW_MANIFEST`] that lists them at the encoder's interval. Fails before writing
+    /// anything if the codes do not fit the display at one pixel per module.
+    pub fn encode_to_eink(
+        &self,
+        input_paths: &[&Path],
+        output_dir: &Path,
+        display: EinkDisplay,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_for_files(input_paths)?;
+        // The display picks the module size, so read the codes at their smallest, bare.
+        frames.pixel_scale = 1;
+        frames.caption = None;
+        frames.markers = false;
+        let version = frames
+            .fixed_version
+            .expect("RaptorQ always produces at least one packet");
+        let modules = version.width() as u32 + 2 * frames.quiet_zone;
+        let scale = display.module_pixels(modules);
+        if scale == 0 {
+            return Err(FountainError::InvalidConfig(format!(
+                "QR codes of {} modules do not fit a {}x{} display with {}px margins; use a smaller chunk size or margin",
+                modules, display.width, display.height, display.margin
+            )));
+        }
+        if scale < MIN_READABLE_MODULE_PIXELS {
+            warn!(
+                "QR modules are only {}px on this display; a smaller chunk size makes them larger",
+                scale
+            );
+        }
+        let (width, height) = (display.width, display.height);
+        info!(
+            "Drawing {}x{} frames at {}px per module",
+            width, height, scale
+        );
+
+        fs::create_dir_all(output_dir)?;
+        let stem = image_stem(frames.filename());
+        let total = frames.total();
+        let regular = total - frames.parity();
+        let progress = Progress::new(Some(total as u64), "QR codes");
+
+        let mut output_files = Vec::with_capacity(total);
+        for (i, qr_image) in frames.by_ref().enumerate() {
+            self.options().check_cancelled()?;
+            let output_filename = frame_file_name(&stem, i, regular);
+            let bits = panel_bits(&qr_image?, modules, display, scale);
+            save_bilevel_png(&bits, display, &output_dir.join(&output_filename))?;
+
+            self.image_written(&progress, i, total, &output_filename);
+            output_files.push(output_filename);
+        }
+
+        let manifest = json!({
+            "filename": frames.filename(),
+            "width": display.width,
+            "height": display.height,
+            "interval_ms": self.options().interval_ms,
+            "frames": output_files,
+        });
+        fs::write(
+            output_dir.join(SLIDESHOW_MANIFEST),
+            serde_json::to_string_pretty(&manifest).map_err(io::Error::from)?,
+        )?;
+        Ok(frames.result(output_files, started))
+    }
+}
+
+/// Draws `qr_image`, a code `modules` wide, centred on a white panel at `scale` pixels per
+/// module. Returns the panel as rows of packed 1-bit pixels, where 1 is white.
+fn panel_bits(qr_image: &RgbImage, modules: u32, display: EinkDisplay, scale: u32) -> Vec<u8> {
+    let source_scale = qr_image.width() / modules;
+    let side = modules * scale;
+    let x0 = (display.width - side) / 2;
+    let y0 = (display.height - side) / 2;
+    let row_bytes = display.width.div_ceil(8) as usize;
+
+    let mut bits = vec![0xff; row_bytes * display.height as usize];
+    for y in 0..side {
+        for x in 0..side {
+            let pixel = qr_image.get_pixel(
+                x / scale * source_scale + source_scale / 2,
+                y / scale * source_scale + source_scale / 2,
+            );
+            if pixel[0] < 128 {
+                let (px, py) = ((x0 + x) as usize, (y0 + y) as usize);
+                bits[py * row_bytes + px / 8] &= !(0x80 >> (px % 8));
+            }
+        }
+    }
+    bits
+}
+
+fn save_bilevel_png(bits: &[u8], display: EinkDisplay, path: &Path) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, display.width, display.height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    writer.write_image_data(bits).map_err(io::Error::from)?;
+    writer.finish().map_err(io::Error::from)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_parse_display() {
+        let display: EinkDisplay = "waveshare-7.5".parse().unwrap();
+        assert_eq!((display.width, display.height), (800, 480));
+        assert_eq!(display.margin, DEFAULT_EINK_MARGIN);
+        let display: EinkDisplay = "640x384".parse().unwrap();
+        assert_eq!((display.width, display.height), (640, 384));
+        assert!("0x384".parse::<EinkDisplay>().is_err());
+        assert!("large".parse::<EinkDisplay>().is_err());
+    }
+
+    #[test]
+    fn test_panel_bits() {
+        // Two modules at 2 source pixels each, the left one dark.
+        let mut img = RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]));
+        for (x, y) in [
+            (0, 0),
+            (1, 0),
+            (0, 1),
+            (1, 1),
+            (0, 2),
+            (1, 2),
+            (0, 3),
+            (1, 3),
+        ] {
+            img.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+        let display = EinkDisplay::new(12, 4).margin(0);
+        let bits = panel_bits(&img, 2, display, 2);
+        // Centred at x 4..8: pixels 4 and 5 of every row are black.
+        assert_eq!(bits.len(), 2 * 4);
+        for row in bits.chunks(2) {
+            assert_eq!(row, [0b1111_0011, 0xff]);
+        }
+    }
+}
@@ -1,19 +1,80 @@
-use anyhow::{anyhow, Result};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
 use image::codecs::gif::GifEncoder;
-use image::{Delay, Frame, RgbaImage};
+use image::{Delay, Frame, Rgb, RgbImage, RgbaImage};
 use qrcode::Version;
-use raptorq::Encoder as RQEncoder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
-use crate::chunk::{compress, pack_data, Chunk, ChunkHeader, DEFAULT_PAYLOAD_SIZE, HEADER_SIZE};
-use crate::qr::{generate_qr_image, render_qr_to_terminal, save_qr_image, QR_FILE_EXTENSION};
+use crate::archive::archive_directory;
+use crate::chunk::{
+    compress, compress_with_dictionary, embedded_filename, is_source_packet, looks_compressed,
+    pack_data, store, Chunk, DEFAULT_PAYLOAD_SIZE, MAX_FILENAME_LEN, PACKET_FILE_EXTENSION,
+    SEQUENCE_SIZE,
+};
+use crate::chunker::{
+    build_beacon_chunks, build_manifest_chunks, build_parity_chunks, build_round_chunks,
+    build_transfer_chunks, chunk_count, encode_memory, fit_payload_size, fits_in_image,
+    insert_beacons, packet_size_for, GIF_DELAY_UNIT_MS, IMG_MIN_PAYLOAD_SIZE, IMG_REDUCTION_STEP,
+    IMG_REDUNDANCY_FACTOR, MAX_GIF_INTERVAL_MS, TERMINAL_REDUNDANCY_FACTOR,
+};
+pub use crate::chunker::{gif_interval_ms, MIN_GIF_INTERVAL_MS};
+use crate::cover::{add_caption, CoverSheet};
+use crate::delta::{delta_filename, make_delta};
+use crate::error::{FountainError, Result};
+use crate::fiducial::{add_markers, MARKER_MARGIN_MODULES};
+#[cfg(feature = "color")]
+use crate::matrix::{ColorMatrix, MATRIX_QUIET_ZONE};
+use crate::parallel::{map_parallel, thread_count};
+use crate::progress::{check_memory, is_cancelled, CancelFlag, Progress, ProgressCallback};
+use crate::qr::{
+    fits_in_version, generate_qr_image, generate_qr_image_with_quiet_zone, qr_version_for,
+    render_qrs_to_terminal, save_qr_image, QR_FILE_EXTENSION, QUIET_ZONE_MODULES,
+};
+use crate::sink::{GifSink, ImageDirSink, OutputSink, SinkFrame, SinkInfo};
 
+/// Summary of an encode. Serializes with the field names of `fountain-encode --json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncodeResult {
+    /// Packets generated, one per QR code.
     pub num_chunks: usize,
     pub output_files: Vec<String>,
+    #[serde(rename = "effective_payload_size")]
     pub effective_size: usize,
+    /// Size of the input file in bytes.
+    pub total_bytes: usize,
+    /// Size of the compressed transfer (filename and checksum included) in bytes.
+    pub compressed_bytes: usize,
+    /// Wall-clock time spent encoding.
+    #[serde(rename = "duration_ms", with = "crate::millis")]
+    pub duration: Duration,
+}
+
+impl EncodeResult {
+    /// Input bytes encoded per second.
+    pub fn throughput(&self) -> f64 {
+        self.total_bytes as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Outcome of a dry run: what encoding would produce, without writing anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncodeEstimate {
+    pub num_chunks: usize,
+    #[serde(rename = "effective_payload_size")]
+    pub effective_size: usize,
+    pub qr_version: i16,
+    /// Serialized chunk size (header + packet) carried by each QR code.
+    pub frame_bytes: usize,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub estimated_gif_bytes: u64,
+    pub duration_ms: u64,
 }
 
 pub struct TerminalQrData {
@@ -23,145 +84,1750 @@ pub struct TerminalQrData {
     pub effective_size: usize,
 }
 
-/// Internal helper to handle the common logic of reading, compressing, and finding the optimal
-/// packet size for RaptorQ encoding while ensuring it fits via a provided check.
-fn prepare_chunks<F>(
-    input_path: &Path,
-    chunk_size: Option<usize>,
-    default_size: usize,
-    min_size: usize,
-    reduction_step: usize,
-    redundancy_factor: f64,
-    fit_check_fn: F,
-) -> Result<(Vec<Chunk>, usize, String)>
-where
-    F: Fn(&[u8]) -> Result<bool>,
-{
-    let data = fs::read(input_path)?;
-    let filename = input_path
-        .file_name()
-        .and_then(|s| s.to_str())
-        .ok_or_else(|| anyhow!("Invalid filename"))?
-        .to_string();
+/// Most files one transfer can carry; the chunk header stores the file index in one byte.
+pub const MAX_FILES: usize = u8::MAX as usize;
 
-    let packed = pack_data(&data, &filename);
-    let compressed = compress(&packed)?;
+/// Extension of the receipt written with [`EncodeOptions::receipt`].
+pub const RECEIPT_EXTENSION: &str = "receipt.png";
 
-    let mut current_size = chunk_size.unwrap_or(default_size);
+/// Filename under which a literal text (`fountain-encode --text`) is sent.
+pub const TEXT_FILENAME: &str = "text.txt";
 
-    loop {
-        // Ensure packet size is even for RaptorQ
-        let packet_size = (current_size.saturating_sub(HEADER_SIZE)) as u16;
-        let packet_size = packet_size - (packet_size % 2);
+/// Below this, some players skip frames to keep up, and every skipped frame loses its packet.
+const FAST_GIF_INTERVAL_MS: u64 = 50;
 
-        if packet_size < 4 {
-            if current_size <= min_size {
-                break;
+/// Problems GIF players have with frames `interval_ms` apart, as messages for the user. Empty
+/// if the interval is shown as requested.
+pub fn gif_timing_warnings(interval_ms: u64) -> Vec<String> {
+    let delay = gif_interval_ms(interval_ms);
+    let mut warnings = Vec::new();
+    if interval_ms < MIN_GIF_INTERVAL_MS {
+        warnings.push(format!(
+            "GIF players show frames shorter than {}ms for 100ms or more; using {}ms instead of {}ms.",
+            MIN_GIF_INTERVAL_MS, delay, interval_ms
+        ));
+    } else if delay != interval_ms {
+        warnings.push(format!(
+            "GIF frame delays are stored in steps of {}ms (at most {}ms); using {}ms instead of {}ms.",
+            GIF_DELAY_UNIT_MS, MAX_GIF_INTERVAL_MS, delay, interval_ms
+        ));
+    }
+    if delay < FAST_GIF_INTERVAL_MS {
+        warnings.push(format!(
+            "At {}ms per frame some GIF players skip frames, losing their packets; {}ms or more is safer.",
+            delay, FAST_GIF_INTERVAL_MS
+        ));
+    }
+    warnings
+}
+
+/// Order of the packets of a transfer across its frames, see [`EncodeOptions::packet_order`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PacketOrder {
+    /// The source packets of every file and source block first, then the repair packets, so a
+    /// receiver that catches every frame is done after as many frames as there are source
+    /// packets.
+    #[default]
+    SystematicFirst,
+    /// The repair packets spread evenly among the source packets, so a capture that starts or
+    /// stops part way through, or misses a run of frames, still has a share of both.
+    Interleaved,
+}
+
+impl PacketOrder {
+    /// `chunks` in this order. Source and repair packets each keep their order.
+    pub(crate) fn arrange(self, chunks: Vec<Chunk>) -> Vec<Chunk> {
+        let (sources, repairs): (Vec<Chunk>, Vec<Chunk>) =
+            chunks.into_iter().partition(is_source_packet);
+        match self {
+            PacketOrder::SystematicFirst => sources.into_iter().chain(repairs).collect(),
+            PacketOrder::Interleaved => {
+                let total = sources.len() + repairs.len();
+                let spread = repairs.len();
+                let (mut sources, mut repairs) = (sources.into_iter(), repairs.into_iter());
+                (0..total)
+                    .filter_map(|i| {
+                        if (i + 1) * spread / total > i * spread / total {
+                            repairs.next()
+                        } else {
+                            sources.next()
+                        }
+                    })
+                    .collect()
             }
-            current_size = current_size.saturating_sub(reduction_step).max(min_size);
-            continue;
-        }
-
-        let rq_encoder = RQEncoder::with_defaults(&compressed, packet_size);
-
-        // Generate one packet to test fit
-        let test_packets = rq_encoder.get_encoded_packets(1);
-        if let Some(first_packet) = test_packets.first() {
-            let chunk = Chunk {
-                header: ChunkHeader {
-                    version: 1,
-                    total: compressed.len() as u32,
-                    index: 0,
-                    packet_size,
-                },
-                data: first_packet.serialize(),
-            };
+        }
+    }
+}
 
-            let chunk_bytes = chunk.to_bytes()?;
-            let encoded = base45::encode(&chunk_bytes);
-
-            if fit_check_fn(encoded.as_bytes())? {
-                // Fits. Generate all packets.
-                let source_packets = (compressed.len() as f64 / packet_size as f64).ceil() as u32;
-                let total_packets = (source_packets as f64 * redundancy_factor).ceil() as u32;
-                let total_packets = total_packets.max(source_packets + 2);
-
-                let packets_data = rq_encoder.get_encoded_packets(total_packets);
-                let mut chunks = Vec::with_capacity(packets_data.len());
-
-                for (i, packet) in packets_data.into_iter().enumerate() {
-                    chunks.push(Chunk {
-                        header: ChunkHeader {
-                            version: 1,
-                            total: compressed.len() as u32,
-                            index: i as u32,
-                            packet_size,
-                        },
-                        data: packet.serialize(),
-                    });
-                }
+/// Text form of the packets written by [`Encoder::encode_files_to_packets`], one per line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PacketEncoding {
+    /// Base64 of each serialized chunk.
+    #[default]
+    Base64,
+    /// Base45, the text each QR code holds, so other QR tools render codes the decoder reads.
+    Base45,
+}
+
+impl PacketEncoding {
+    pub fn encode(self, chunk: &Chunk) -> Result<String> {
+        Ok(match self {
+            PacketEncoding::Base64 => BASE64_STANDARD.encode(chunk.to_bytes()?),
+            PacketEncoding::Base45 => chunk.to_text()?,
+        })
+    }
+}
+
+/// Printer resolution assumed unless another is set.
+pub const DEFAULT_PRINT_DPI: u32 = 300;
+
+/// Smallest printed module, in millimetres, that phone cameras and scanners reliably resolve.
+const MIN_PRINT_MODULE_MM: f64 = 0.3;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Size each code of image/GIF output is printed at, see [`EncodeOptions::print_size`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintSize {
+    /// Side of a printed code, quiet zone included, in millimetres.
+    pub side_mm: f64,
+    /// Printer resolution in dots per inch; one image pixel is printed as one dot.
+    pub dpi: u32,
+}
+
+impl PrintSize {
+    pub fn new(side_mm: f64, dpi: u32) -> Self {
+        Self { side_mm, dpi }
+    }
+
+    /// Largest whole number of pixels per module that keeps a code of `version`, with a quiet
+    /// zone of `quiet_zone` modules, within the printed side; 0 if even one pixel per module is
+    /// too large.
+    pub fn pixel_scale(&self, version: Version, quiet_zone: u32) -> u32 {
+        let modules = version.width() as u32 + 2 * quiet_zone;
+        let side_pixels = self.side_mm / MM_PER_INCH * self.dpi as f64;
+        (side_pixels / modules as f64) as u32
+    }
+
+    /// Printed size of a module of `pixel_scale` pixels, in millimetres.
+    pub fn module_mm(&self, pixel_scale: u32) -> f64 {
+        pixel_scale as f64 * MM_PER_INCH / self.dpi as f64
+    }
+}
+
+/// Fixed size of the frames of image/GIF output, see [`EncodeOptions::canvas`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// `image` centred on a white canvas of this size. Fails if it does not fit.
+    pub fn place(&self, image: &RgbImage) -> Result<RgbImage> {
+        if image.width() > self.width || image.height() > self.height {
+            return Err(FountainError::InvalidConfig(format!(
+                "frames of {}x{} pixels do not fit a {}x{} canvas; use a larger canvas or a smaller pixel scale or chunk size",
+                image.width(),
+                image.height(),
+                self.width,
+                self.height
+            )));
+        }
+        let mut canvas = RgbImage::from_pixel(self.width, self.height, Rgb([255, 255, 255]));
+        let x = (self.width - image.width()) / 2;
+        let y = (self.height - image.height()) / 2;
+        image::imageops::replace(&mut canvas, image, i64::from(x), i64::from(y));
+        Ok(canvas)
+    }
+}
+
+/// Callback receiving the 0-based index of a frame that was just written, and the frame total.
+pub type FrameCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Settings for an [`Encoder`]. Start from [`EncodeOptions::new`] and chain the setters;
+/// fields left alone keep the CLI defaults.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct EncodeOptions {
+    /// Maximum payload size (bytes) per QR code. `None` picks the largest size that fits.
+    pub chunk_size: Option<usize>,
+    /// Frame budget for image/GIF output; overrides `chunk_size` when set.
+    pub target_frames: Option<usize>,
+    /// GIF frame duration in milliseconds, written as [`gif_interval_ms`] gives it.
+    pub interval_ms: u64,
+    /// Pixel scale for QR code modules.
+    pub pixel_scale: u32,
+    /// Modules of blank margin around each code of image/GIF output, e.g. wider to tile codes
+    /// on a page or show them on a dark background.
+    pub quiet_zone: u32,
+    /// Highest QR version (1 to 40) a code may take; the payload size shrinks until every
+    /// chunk fits. Cheap scanners and some phone apps fail on the densest codes.
+    pub max_version: Option<i16>,
+    /// Add a cover sheet to image/GIF output: a frame of text with the name, size and SHA-256
+    /// of each file, the number of codes, the date and how to restore the files. It opens a
+    /// GIF, and is written as `<file>_0000_cover.png` next to images.
+    pub cover: bool,
+    /// Write a receipt next to image/GIF output: a small QR code of the name, size and
+    /// SHA-256 of each file in plain text, which a stock phone app can scan to check a decoded
+    /// file without this tool. It is `<output>.receipt.png` beside a GIF, and
+    /// `<file>.receipt.png` among images.
+    pub receipt: bool,
+    /// Text drawn under every frame of image/GIF output, cover sheet included, outside the
+    /// quiet zone, e.g. a classification label. It is part of the rendering, so the same
+    /// input and options still give the same files. Window, framebuffer and e-ink frames go
+    /// without it.
+    pub caption: Option<String>,
+    /// Draw an ArUco marker in each corner of every frame of image/GIF output, cover sheet
+    /// included, outside the quiet zone (and caption), adding 7 modules on each side. Where a
+    /// photo taken at an angle defeats the code's own finder patterns, the decoder finds the
+    /// markers and warps the frame flat and upright by them. Window, framebuffer and e-ink
+    /// frames go without them. Cannot be combined with `color_matrix`.
+    pub markers: bool,
+    /// Number the codes of image/GIF output in the order they are shown, in 4 more bytes of
+    /// each header, so the receiver can tell how many frames its capture missed and whether
+    /// to show them longer (its `ChannelStats`).
+    pub sequence: bool,
+    /// Show two consecutive codes side by side on each terminal frame when the terminal is
+    /// wide enough, halving the number of frames; the receiver reads both from one capture.
+    pub dual: bool,
+    /// Order of the packets across the frames of image/GIF and terminal output: source
+    /// packets first (the default), or repair packets interleaved with them.
+    pub packet_order: PacketOrder,
+    /// Send the manifest (the name, size and SHA-256 of every file) again after every this
+    /// many packets of image/GIF and terminal output, and for a single file too, so a
+    /// receiver that starts part way through the loop learns what is coming without waiting
+    /// for it to wrap around.
+    pub beacon_interval: Option<usize>,
+    /// Size to print the codes of image/GIF output at. When set, it picks the pixel scale
+    /// instead of `pixel_scale`, once the payload size has fixed the QR version.
+    pub print_size: Option<PrintSize>,
+    /// Called after each frame is written to an image or GIF.
+    pub on_frame_encoded: Option<FrameCallback>,
+    /// Called with the fraction of frames written so far.
+    pub on_progress: Option<ProgressCallback>,
+    /// Checked before each frame; once set, encoding stops with [`FountainError::Cancelled`].
+    pub cancel: Option<CancelFlag>,
+    /// Memory an encode may take, in bytes. The input is checked against it before it is read,
+    /// and the whole encode once it is compressed; over the limit, encoding fails with
+    /// [`FountainError::MemoryLimitExceeded`]. Split encodes lower their part size to fit.
+    pub max_memory: Option<usize>,
+    /// Parity frames added to image/GIF output, as a percentage of its regular frames. They
+    /// hold repair packets that no regular frame carries, so a printed backup survives losing
+    /// more pages than the regular frames' redundancy covers. 0 adds none.
+    pub parity: u32,
+    /// Most QR codes image/GIF output may take, parity codes included. A file that needs more
+    /// fails with [`FountainError::TooManyChunks`] before any code is rendered.
+    pub max_chunks: Option<usize>,
+    /// Preset dictionary to compress with, shared with the receiver out of band (see
+    /// [`crate::dictionary`]). The receiver needs the same one to decode.
+    pub dictionary: Option<Vec<u8>>,
+    /// Base file the receiver already holds (see [`crate::delta`]). Each file is sent as a
+    /// delta against it, named `<name>.fdelta`; the receiver applies it to its copy of the base.
+    pub delta_base: Option<Vec<u8>>,
+    /// Draw the frames of image/GIF output as colour matrix symbols of this layout instead of
+    /// QR codes (see [`crate::matrix`]), `pixel_scale` pixels per cell. They hold several times
+    /// as much, but only this crate's decoder reads them.
+    #[cfg(feature = "color")]
+    pub color_matrix: Option<ColorMatrix>,
+    /// Threads that render the frames of image/GIF output, a batch at a time; frames are still
+    /// written in order. `None` uses one per core, 1 renders them one by one.
+    pub threads: Option<usize>,
+    /// Size of every frame of image/GIF output, the cover sheet included: each is centred on a
+    /// white canvas of this size, so a player showing them fullscreen scales them all alike and
+    /// the code stays put. Frames larger than the canvas fail with
+    /// [`FountainError::InvalidConfig`].
+    pub canvas: Option<Canvas>,
+    /// Snap every pixel of GIF frames to black or white, so each is written with exactly those
+    /// two colours and no gray that low-end cameras misread. QR frames already are; this also
+    /// holds for anything else drawn on them. Cannot be combined with `color_matrix`.
+    pub strict_palette: bool,
+    /// Times each GIF frame is written in a row, so a code stays up for several intervals and
+    /// a camera with a slow shutter gets more than one exposure of it. 1 writes each once.
+    pub repeat_frames: u32,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: None,
+            target_frames: None,
+            interval_ms: 2000,
+            pixel_scale: 4,
+            quiet_zone: QUIET_ZONE_MODULES,
+            max_version: None,
+            cover: false,
+            receipt: false,
+            caption: None,
+            markers: false,
+            sequence: false,
+            dual: false,
+            packet_order: PacketOrder::default(),
+            beacon_interval: None,
+            print_size: None,
+            on_frame_encoded: None,
+            on_progress: None,
+            cancel: None,
+            max_memory: None,
+            parity: 0,
+            max_chunks: None,
+            dictionary: None,
+            delta_base: None,
+            #[cfg(feature = "color")]
+            color_matrix: None,
+            threads: None,
+            canvas: None,
+            strict_palette: false,
+            repeat_frames: 1,
+        }
+    }
+}
+
+impl fmt::Debug for EncodeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("EncodeOptions");
+        debug
+            .field("chunk_size", &self.chunk_size)
+            .field("target_frames", &self.target_frames)
+            .field("interval_ms", &self.interval_ms)
+            .field("pixel_scale", &self.pixel_scale)
+            .field("quiet_zone", &self.quiet_zone)
+            .field("max_version", &self.max_version)
+            .field("cover", &self.cover)
+            .field("receipt", &self.receipt)
+            .field("caption", &self.caption)
+            .field("markers", &self.markers)
+            .field("sequence", &self.sequence)
+            .field("dual", &self.dual)
+            .field("packet_order", &self.packet_order)
+            .field("beacon_interval", &self.beacon_interval)
+            .field("print_size", &self.print_size)
+            .field("on_frame_encoded", &self.on_frame_encoded.is_some())
+            .field("on_progress", &self.on_progress.is_some())
+            .field("cancel", &self.cancel)
+            .field("max_memory", &self.max_memory)
+            .field("parity", &self.parity)
+            .field("max_chunks", &self.max_chunks)
+            .field("dictionary", &self.dictionary.as_ref().map(Vec::len))
+            .field("delta_base", &self.delta_base.as_ref().map(Vec::len));
+        #[cfg(feature = "color")]
+        debug.field("color_matrix", &self.color_matrix);
+        debug
+            .field("threads", &self.threads)
+            .field("canvas", &self.canvas)
+            .field("strict_palette", &self.strict_palette)
+            .field("repeat_frames", &self.repeat_frames)
+            .finish()
+    }
+}
+
+impl EncodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub fn target_frames(mut self, target_frames: usize) -> Self {
+        self.target_frames = Some(target_frames);
+        self
+    }
+
+    pub fn interval_ms(mut self, interval_ms: u64) -> Self {
+        self.interval_ms = interval_ms;
+        self
+    }
+
+    pub fn pixel_scale(mut self, pixel_scale: u32) -> Self {
+        self.pixel_scale = pixel_scale;
+        self
+    }
+
+    pub fn quiet_zone(mut self, modules: u32) -> Self {
+        self.quiet_zone = modules;
+        self
+    }
+
+    pub fn max_version(mut self, version: i16) -> Self {
+        self.max_version = Some(version);
+        self
+    }
+
+    pub fn cover(mut self, cover: bool) -> Self {
+        self.cover = cover;
+        self
+    }
+
+    pub fn receipt(mut self, receipt: bool) -> Self {
+        self.receipt = receipt;
+        self
+    }
+
+    pub fn caption(mut self, caption: impl Into<String>) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+
+    pub fn markers(mut self, markers: bool) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    pub fn sequence(mut self, sequence: bool) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn dual(mut self, dual: bool) -> Self {
+        self.dual = dual;
+        self
+    }
+
+    pub fn packet_order(mut self, order: PacketOrder) -> Self {
+        self.packet_order = order;
+        self
+    }
+
+    pub fn beacon_interval(mut self, packets: usize) -> Self {
+        self.beacon_interval = Some(packets);
+        self
+    }
+
+    #[cfg(feature = "color")]
+    pub fn color_matrix(mut self, matrix: ColorMatrix) -> Self {
+        self.color_matrix = Some(matrix);
+        self
+    }
+
+    pub fn print_size(mut self, side_mm: f64, dpi: u32) -> Self {
+        self.print_size = Some(PrintSize::new(side_mm, dpi));
+        self
+    }
+
+    pub fn on_frame_encoded(
+        mut self,
+        callback: impl Fn(usize, usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_frame_encoded = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn on_progress(mut self, callback: impl Fn(f32) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn cancel_flag(mut self, flag: CancelFlag) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    pub fn parity(mut self, percent: u32) -> Self {
+        self.parity = percent;
+        self
+    }
+
+    pub fn max_chunks(mut self, max_chunks: usize) -> Self {
+        self.max_chunks = Some(max_chunks);
+        self
+    }
+
+    pub fn dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    pub fn delta_base(mut self, base: Vec<u8>) -> Self {
+        self.delta_base = Some(base);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    pub fn canvas(mut self, width: u32, height: u32) -> Self {
+        self.canvas = Some(Canvas::new(width, height));
+        self
+    }
+
+    pub fn strict_palette(mut self, strict: bool) -> Self {
+        self.strict_palette = strict;
+        self
+    }
+
+    pub fn repeat_frames(mut self, times: u32) -> Self {
+        self.repeat_frames = times;
+        self
+    }
+
+    /// Modules around the code on each side of an image/GIF frame: the quiet zone, and the
+    /// corner markers' margin if they are drawn.
+    pub(crate) fn frame_margin(&self) -> u32 {
+        match self.markers {
+            true => self.quiet_zone + MARKER_MARGIN_MODULES,
+            false => self.quiet_zone,
+        }
+    }
+
+    /// Pixel scale for image/GIF frames of QR `version`: the one that fits the print size if
+    /// one is set, or `pixel_scale`.
+    pub(crate) fn pixel_scale_for(&self, version: Version) -> Result<u32> {
+        let Some(print) = self.print_size else {
+            return Ok(self.pixel_scale);
+        };
+        let scale = print.pixel_scale(version, self.frame_margin());
+        if scale == 0 {
+            return Err(FountainError::InvalidConfig(format!(
+                "QR codes of {} modules do not fit {}mm at {} dpi; use a larger print size, a higher dpi or a smaller chunk size",
+                version.width() as u32 + 2 * self.frame_margin(),
+                print.side_mm,
+                print.dpi
+            )));
+        }
+        let module_mm = print.module_mm(scale);
+        if module_mm < MIN_PRINT_MODULE_MM {
+            warn!(
+                "Printed modules are only {:.2}mm; a larger print size or a smaller chunk size makes them easier to scan",
+                module_mm
+            );
+        }
+        info!(
+            "Printing codes at {} px per module ({:.2}mm at {} dpi)",
+            scale, module_mm, print.dpi
+        );
+        Ok(scale)
+    }
 
-                return Ok((chunks, current_size, filename));
+    /// `max_version`, once checked to be a QR version.
+    pub(crate) fn version_cap(&self) -> Result<Option<i16>> {
+        match self.max_version {
+            Some(version) if !(1..=40).contains(&version) => {
+                Err(FountainError::InvalidConfig(format!(
+                    "there is no QR version {}; versions go from 1 to 40",
+                    version
+                )))
             }
+            cap => Ok(cap),
         }
+    }
 
-        if current_size > min_size {
-            current_size = current_size.saturating_sub(reduction_step).max(min_size);
-        } else {
-            break;
+    /// `chunks` behind the manifest of `files`, which is sent again every
+    /// [`EncodeOptions::beacon_interval`] packets if set.
+    pub(crate) fn with_manifest(
+        &self,
+        files: &[(&[u8], &str)],
+        chunks: Vec<Chunk>,
+        payload_size: usize,
+    ) -> Result<Vec<Chunk>> {
+        let Some(every) = self.beacon_interval else {
+            return Ok(build_manifest_chunks(files, payload_size)
+                .into_iter()
+                .chain(chunks)
+                .collect());
+        };
+        if every == 0 {
+            return Err(FountainError::InvalidConfig(
+                "the beacon interval must be at least 1 packet".to_string(),
+            ));
+        }
+        Ok(insert_beacons(
+            chunks,
+            &build_beacon_chunks(files, payload_size),
+            every,
+        ))
+    }
+
+    /// Whether image/GIF output is drawn as colour matrix symbols rather than QR codes.
+    fn uses_color_matrix(&self) -> bool {
+        #[cfg(feature = "color")]
+        return self.color_matrix.is_some();
+        #[cfg(not(feature = "color"))]
+        false
+    }
+
+    /// Number of parity frames added to `regular` frames of image/GIF output.
+    pub(crate) fn parity_frames(&self, regular: usize) -> usize {
+        (regular * self.parity as usize).div_ceil(100)
+    }
+
+    /// Fails if `chunks` QR codes of `payload_size` bytes are more than `max_chunks` allows.
+    pub(crate) fn check_chunk_count(&self, chunks: usize, payload_size: usize) -> Result<()> {
+        match self.max_chunks {
+            Some(limit) if chunks > limit => Err(FountainError::TooManyChunks {
+                chunks,
+                limit,
+                payload_size,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if files of `input_bytes` that compress to `compressed` exceed the memory limit.
+    pub(crate) fn check_memory(
+        &self,
+        input_bytes: usize,
+        compressed: &[Vec<u8>],
+        redundancy_factor: f64,
+    ) -> Result<()> {
+        let compressed_bytes = compressed.iter().map(Vec::len).sum();
+        check_memory(
+            self.max_memory,
+            encode_memory(input_bytes, compressed_bytes, redundancy_factor),
+        )
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        if is_cancelled(&self.cancel) {
+            return Err(FountainError::Cancelled);
         }
+        Ok(())
+    }
+
+    pub(crate) fn report_frame(&self, index: usize, total: usize) {
+        if let Some(callback) = &self.on_frame_encoded {
+            callback(index, total);
+        }
+        if let Some(callback) = &self.on_progress {
+            callback((index + 1) as f32 / total as f32);
+        }
+    }
+}
+
+/// Encodes files into QR codes with a fixed set of [`EncodeOptions`].
+///
+/// ```no_run
+/// use fountain::{EncodeOptions, Encoder};
+/// use std::path::Path;
+///
+/// let encoder = Encoder::new(EncodeOptions::new().chunk_size(500).interval_ms(200));
+/// let result = encoder.encode_to_gif(Path::new("notes.txt"), Path::new("notes.gif"))?;
+/// println!("{} frames", result.num_chunks);
+/// # Ok::<(), fountain::FountainError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    options: EncodeOptions,
+}
+
+/// Reads the input file and returns its contents together with the filename.
+/// A directory is read as a tar archive of its contents.
+pub(crate) fn read_input(input_path: &Path) -> Result<(Vec<u8>, String)> {
+    if !input_path.exists() {
+        return Err(FountainError::InputNotFound(input_path.to_path_buf()));
+    }
+    if input_path.is_dir() {
+        return archive_directory(input_path);
     }
+    let data = fs::read(input_path)?;
+    Ok((data, input_filename(input_path)?))
+}
 
-    Err(anyhow!(
-        "Data too large to fit in QR code even at minimum payload size ({} bytes).",
-        min_size
-    ))
+/// Name recorded in the chunks for the file at `input_path`, see [`embedded_filename`].
+pub(crate) fn input_filename(input_path: &Path) -> Result<String> {
+    let name = input_path
+        .file_name()
+        .ok_or_else(|| FountainError::InvalidFilename(input_path.to_path_buf()))?;
+    let filename = embedded_filename(name);
+    if name.to_str() != Some(filename.as_str()) {
+        warn!(
+            "Filename {} is sent as {}",
+            name.to_string_lossy(),
+            filename
+        );
+    }
+    Ok(filename)
+}
+
+/// Reads every input of a multi-file transfer. Filenames must be unique, and a transfer holds
+/// at most [`MAX_FILES`] files. Fails before reading anything if the files alone are larger
+/// than `max_memory`.
+pub(crate) fn read_inputs(
+    input_paths: &[&Path],
+    max_memory: Option<usize>,
+) -> Result<Vec<(Vec<u8>, String)>> {
+    if input_paths.len() > MAX_FILES {
+        return Err(FountainError::TooManyFiles(input_paths.len()));
+    }
+    if max_memory.is_some() {
+        let input_bytes = input_paths
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len() as usize)
+            .sum();
+        check_memory(max_memory, input_bytes)?;
+    }
+    let mut inputs: Vec<(Vec<u8>, String)> = Vec::with_capacity(input_paths.len());
+    for input_path in input_paths {
+        let (data, filename) = read_input(input_path)?;
+        if inputs.iter().any(|(_, name)| *name == filename) {
+            return Err(FountainError::InvalidFilename(input_path.to_path_buf()));
+        }
+        inputs.push((data, filename));
+    }
+    Ok(inputs)
+}
+
+/// Reads the input file and returns its packed, compressed form together with the filename.
+fn read_and_compress(input_path: &Path, options: &EncodeOptions) -> Result<(Vec<u8>, String)> {
+    let (data, filename) = read_input(input_path)?;
+    let compressed = compress_files(&[(&data, &filename)], options)?.remove(0);
+    Ok((compressed, filename))
+}
+
+/// Packed, compressed form of each file in a transfer, as a delta against the base and with
+/// the preset dictionary if the options set them.
+pub(crate) fn compress_files(
+    files: &[(&[u8], &str)],
+    options: &EncodeOptions,
+) -> Result<Vec<Vec<u8>>> {
+    files
+        .iter()
+        .map(|&(data, filename)| {
+            let packed = match &options.delta_base {
+                Some(base) => {
+                    let delta = make_delta(base, data);
+                    info!(
+                        "Sending {} ({} bytes) as a {} byte delta against the base",
+                        filename,
+                        data.len(),
+                        delta.len()
+                    );
+                    pack_data(&delta, &delta_filename(filename))
+                }
+                None => pack_data(data, filename),
+            };
+            // A delta of a compressed file is mostly copies of the base, and shrinks.
+            if options.delta_base.is_none() && looks_compressed(data) {
+                info!(
+                    "Storing {} uncompressed: it is compressed already",
+                    filename
+                );
+                return Ok(store(&packed));
+            }
+            match options.dictionary.as_deref() {
+                Some(dictionary) => compress_with_dictionary(&packed, dictionary),
+                None => compress(&packed),
+            }
+        })
+        .collect()
+}
+
+/// Internal helper to handle the common logic of finding the optimal packet size for RaptorQ
+/// encoding of compressed data while ensuring it fits via a provided check. With several
+/// files, every file uses the payload size that fits the largest one.
+fn prepare_chunks<F>(
+    files: &[Vec<u8>],
+    chunk_size: Option<usize>,
+    default_size: usize,
+    min_size: usize,
+    reduction_step: usize,
+    redundancy_factor: f64,
+    fit_check_fn: F,
+) -> Result<(Vec<Chunk>, usize)>
+where
+    F: Fn(&[u8]) -> Result<bool>,
+{
+    let largest = files.iter().max_by_key(|compressed| compressed.len());
+    let effective_size = fit_payload_size(
+        largest.map_or(&[][..], Vec::as_slice),
+        chunk_size.unwrap_or(default_size),
+        min_size,
+        reduction_step,
+        fit_check_fn,
+    )?;
+    let chunks = build_transfer_chunks(files, effective_size, redundancy_factor);
+
+    Ok((chunks, effective_size))
 }
 
 /// Helper function to split data into chunks using RaptorQ and ensure they fit into QR codes.
-/// Returns the chunks, the effective payload size used, and the filename string.
+/// Returns the chunks and the effective payload size used.
 fn prepare_chunks_for_img(
-    input_path: &Path,
+    files: &[Vec<u8>],
     chunk_size: Option<usize>,
     pixel_scale: u32,
+    max_version: Option<i16>,
     redundancy_factor: f64,
-) -> Result<(Vec<Chunk>, usize, String)> {
+) -> Result<(Vec<Chunk>, usize)> {
     prepare_chunks(
-        input_path,
+        files,
         chunk_size,
         crate::chunk::MAX_PAYLOAD_SIZE,
-        100, // min_size
-        50,  // reduction_step
+        IMG_MIN_PAYLOAD_SIZE,
+        IMG_REDUCTION_STEP,
         redundancy_factor,
-        |encoded| Ok(generate_qr_image(encoded, None, pixel_scale).is_ok()),
+        |encoded| fits_in_image(encoded, pixel_scale, max_version),
     )
-    .map_err(|e| anyhow!("Failed to generate QR codes: {}", e))
 }
 
-pub fn encode_file_for_terminal(
-    input_path: &Path,
-    chunk_size: Option<usize>,
-) -> Result<TerminalQrData> {
-    let (chunks, effective_size, filename) = prepare_chunks(
-        input_path,
-        chunk_size,
-        DEFAULT_PAYLOAD_SIZE,
-        50, // min_size
-        20, // reduction_step
-        2.0, // redundancy_factor
-        |encoded| crate::qr::fits_in_terminal(encoded),
-    )
-    .map_err(|e| anyhow!("Terminal too small to display QR codes even at minimum payload size. Please increase terminal size. Underlying error: {}", e))?;
+/// Smallest QR version that holds every chunk. How densely a version holds a chunk depends on
+/// its text, so one chunk's version may be too small for another of the same size.
+pub(crate) fn common_version(chunks: &[Chunk]) -> Result<Option<Version>> {
+    let mut common: Option<Version> = None;
+    for chunk in chunks {
+        let version = qr_version_for(chunk.to_text()?.as_bytes())?;
+        if common.is_none_or(|common| version.width() > common.width()) {
+            common = Some(version);
+        }
+    }
+    Ok(common)
+}
 
-    let total = chunks.len();
-    let mut qr_strings = Vec::with_capacity(total);
+/// Lazily rendered QR code images, one per chunk, returned by [`Encoder::frames`].
+///
+/// Chunks are prepared up front, but each image is only rendered when the iterator is advanced.
+/// All frames share the QR version of the chunk that needs the largest one, so they have the
+/// same size.
+pub struct QrFrames {
+    pub(crate) chunks: std::vec::IntoIter<Chunk>,
+    pub(crate) total: usize,
+    /// Parity frames at the end of `chunks`, see [`EncodeOptions::parity`].
+    pub(crate) parity: usize,
+    pub(crate) effective_size: usize,
+    pub(crate) filename: String,
+    pub(crate) pixel_scale: u32,
+    /// Modules of quiet zone on each side, see [`EncodeOptions::quiet_zone`].
+    pub(crate) quiet_zone: u32,
+    pub(crate) fixed_version: Option<Version>,
+    pub(crate) total_bytes: usize,
+    pub(crate) compressed_bytes: usize,
+    /// Cover sheet to put before the frames, see [`EncodeOptions::cover`].
+    pub(crate) cover: Option<CoverSheet>,
+    /// Text of the receipt to write next to the frames, see [`EncodeOptions::receipt`].
+    pub(crate) receipt: Option<String>,
+    /// Text drawn under each frame, see [`EncodeOptions::caption`].
+    pub(crate) caption: Option<String>,
+    /// Whether to draw corner markers, see [`EncodeOptions::markers`].
+    pub(crate) markers: bool,
+    /// Symbol layout drawn instead of QR codes, see [`EncodeOptions::color_matrix`].
+    #[cfg(feature = "color")]
+    pub(crate) color_matrix: Option<ColorMatrix>,
+    /// Size every frame is centred on, see [`EncodeOptions::canvas`].
+    pub(crate) canvas: Option<Canvas>,
+}
 
-    for chunk in chunks {
-        let chunk_bytes = chunk.to_bytes()?;
+impl QrFrames {
+    fn render(&self, chunk: &Chunk) -> Result<RgbImage> {
+        #[cfg(feature = "color")]
+        if let Some(matrix) = &self.color_matrix {
+            return self.on_canvas(matrix.render(&chunk.to_bytes()?, self.pixel_scale)?);
+        }
+        let encoded = chunk.to_text()?;
+
+        let (qr_image, _) = generate_qr_image_with_quiet_zone(
+            encoded.as_bytes(),
+            self.fixed_version,
+            self.pixel_scale,
+            self.quiet_zone,
+        )?;
+        self.on_canvas(self.marked(self.captioned(qr_image)))
+    }
+
+    /// `image` centred on the canvas, if one was asked for.
+    fn on_canvas(&self, image: RgbImage) -> Result<RgbImage> {
+        match &self.canvas {
+            Some(canvas) => canvas.place(&image),
+            None => Ok(image),
+        }
+    }
+
+    /// `image` with the caption, if any, drawn under it, the text no larger than a module.
+    fn captioned(&self, image: RgbImage) -> RgbImage {
+        match &self.caption {
+            Some(caption) => {
+                let margin = self.quiet_zone.max(1) * self.pixel_scale;
+                add_caption(&image, caption, self.pixel_scale, margin)
+            }
+            None => image,
+        }
+    }
+
+    /// `image` with the corner markers around it, if they were asked for.
+    fn marked(&self, image: RgbImage) -> RgbImage {
+        match self.markers {
+            true => add_markers(&image, self.pixel_scale),
+            false => image,
+        }
+    }
+
+    /// The cover sheet, if one was asked for, drawn the size of a frame.
+    pub(crate) fn cover_image(&self) -> Result<Option<RgbImage>> {
+        let (Some(cover), Some((side, quiet_zone))) = (&self.cover, self.frame_modules()) else {
+            return Ok(None);
+        };
+        let margin = quiet_zone.max(1) * self.pixel_scale;
+        let image = cover.render(self.total, self.parity, side * self.pixel_scale, margin);
+        self.on_canvas(self.marked(self.captioned(image))).map(Some)
+    }
+
+    /// The receipt, if one was asked for, as a QR code at the frames' pixel scale.
+    pub(crate) fn receipt_image(&self) -> Result<Option<RgbImage>> {
+        let Some(receipt) = &self.receipt else {
+            return Ok(None);
+        };
+        let (image, _) = generate_qr_image(receipt.as_bytes(), None, self.pixel_scale)?;
+        Ok(Some(image))
+    }
+
+    /// Writes the receipt, if one was asked for, to `path`. Whether it was written.
+    pub(crate) fn write_receipt(&self, path: &Path) -> Result<bool> {
+        let Some(image) = self.receipt_image()? else {
+            return Ok(false);
+        };
+        save_qr_image(&image, path)?;
+        info!("Wrote the receipt to {}", path.display());
+        Ok(true)
+    }
+
+    /// Modules (or colour matrix cells) across a frame, quiet zone included, and those of the
+    /// quiet zone on each side. `None` before the QR version is known.
+    pub(crate) fn frame_modules(&self) -> Option<(u32, u32)> {
+        #[cfg(feature = "color")]
+        if let Some(matrix) = &self.color_matrix {
+            return Some((matrix.side_px(1), MATRIX_QUIET_ZONE));
+        }
+        let version = self.fixed_version?;
+        Some((
+            version.width() as u32 + 2 * self.quiet_zone,
+            self.quiet_zone,
+        ))
+    }
+
+    /// Total number of frames, including those already yielded.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Number of parity frames, which come after the regular ones.
+    pub fn parity(&self) -> usize {
+        self.parity
+    }
+
+    pub fn effective_size(&self) -> usize {
+        self.effective_size
+    }
+
+    /// Name of the encoded file, as embedded in the transfer; the first one of a multi-file
+    /// transfer.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Result of an encode that started at `started` and wrote these frames to `output_files`.
+    pub(crate) fn result(&self, output_files: Vec<String>, started: Instant) -> EncodeResult {
+        EncodeResult {
+            num_chunks: self.total,
+            output_files,
+            effective_size: self.effective_size,
+            total_bytes: self.total_bytes,
+            compressed_bytes: self.compressed_bytes,
+            duration: started.elapsed(),
+        }
+    }
+}
+
+impl Iterator for QrFrames {
+    type Item = Result<RgbImage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunks.next()?;
+        Some(self.render(&chunk))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl ExactSizeIterator for QrFrames {}
+
+/// Lazily rendered terminal QR codes, returned by [`Encoder::terminal_frames`]: one chunk per
+/// frame, or two side by side with [`EncodeOptions::dual`].
+pub struct TerminalFrames {
+    pub(crate) chunks: std::vec::IntoIter<Chunk>,
+    pub(crate) per_frame: usize,
+    pub(crate) total: usize,
+    pub(crate) effective_size: usize,
+    pub(crate) filename: String,
+}
+
+impl TerminalFrames {
+    /// Total number of frames, including those already yielded.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn effective_size(&self) -> usize {
+        self.effective_size
+    }
+
+    /// Name of the encoded file, as embedded in the transfer.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Codes shown side by side on each frame.
+    pub fn codes_per_frame(&self) -> usize {
+        self.per_frame
+    }
+}
+
+impl Iterator for TerminalFrames {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunks: Vec<Chunk> = self.chunks.by_ref().take(self.per_frame).collect();
+        if chunks.is_empty() {
+            return None;
+        }
+        let render = || {
+            let texts = chunks
+                .iter()
+                .map(|chunk| chunk.to_text().map_err(FountainError::from))
+                .collect::<Result<Vec<_>>>()?;
+            let codes: Vec<&[u8]> = texts.iter().map(|text| text.as_bytes()).collect();
+            render_qrs_to_terminal(&codes)
+        };
+        Some(render())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.chunks.len().div_ceil(self.per_frame);
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for TerminalFrames {}
+
+/// Endless terminal carousel returned by [`Encoder::endless_terminal_frames`].
+///
+/// The first round yields the same frames as [`TerminalFrames`]. Each later round renders as
+/// many repair packets that were never shown before, so the iterator never ends.
+pub struct EndlessFrames {
+    frames: TerminalFrames,
+    files: Vec<Vec<u8>>,
+    round: u32,
+}
+
+impl EndlessFrames {
+    /// Number of frames in each round.
+    pub fn round_len(&self) -> usize {
+        self.frames.total
+    }
+
+    /// Round that the last yielded frame belongs to, starting at 0.
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn effective_size(&self) -> usize {
+        self.frames.effective_size
+    }
+
+    /// Name of the encoded file, as embedded in the transfer.
+    pub fn filename(&self) -> &str {
+        &self.frames.filename
+    }
+}
+
+impl Iterator for EndlessFrames {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frames.chunks.len() == 0 {
+            self.round = self.round.checked_add(1).unwrap_or(1);
+            self.frames.chunks = build_round_chunks(
+                &self.files,
+                self.frames.effective_size,
+                TERMINAL_REDUNDANCY_FACTOR,
+                self.round,
+            )
+            .into_iter();
+        }
+        self.frames.next()
+    }
+}
+
+impl Encoder {
+    pub fn new(options: EncodeOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &EncodeOptions {
+        &self.options
+    }
+
+    /// Payload size for image/GIF output, resolving `target_frames` against the input.
+    fn img_chunk_size(&self, files: &[Vec<u8>]) -> Result<Option<usize>> {
+        match self.options.target_frames {
+            Some(_) if files.len() > 1 => Err(FountainError::InvalidConfig(
+                "target_frames needs a single input file".to_string(),
+            )),
+            Some(target_frames) => {
+                let size = frame_budget_payload_size(
+                    &files[0],
+                    target_frames,
+                    self.options.pixel_scale,
+                    self.options.version_cap()?,
+                )?;
+                info!(
+                    "Payload size {} bytes keeps the output within {} frames",
+                    size, target_frames
+                );
+                Ok(Some(size))
+            }
+            None => Ok(self.options.chunk_size),
+        }
+    }
+
+    /// Prepares the chunks for terminal display and returns an iterator that renders them
+    /// as QR strings one at a time. The payload size shrinks as needed to fit the terminal.
+    pub fn terminal_frames(&self, input_path: &Path) -> Result<TerminalFrames> {
+        self.terminal_frames_for_files(&[input_path])
+    }
+
+    /// Like [`Encoder::terminal_frames`], for a carousel that carries several files.
+    pub fn terminal_frames_for_files(&self, input_paths: &[&Path]) -> Result<TerminalFrames> {
+        Ok(self.prepare_terminal(input_paths)?.0)
+    }
+
+    /// Like [`Encoder::terminal_frames_for_files`], for an endless carousel: after the last
+    /// frame, it goes on with fresh repair packets instead of starting over, so a receiver that
+    /// keeps missing frames gets new information on every loop.
+    pub fn endless_terminal_frames(&self, input_paths: &[&Path]) -> Result<EndlessFrames> {
+        let (frames, files) = self.prepare_terminal(input_paths)?;
+        Ok(EndlessFrames {
+            frames,
+            files,
+            round: 0,
+        })
+    }
+
+    /// Like [`Encoder::terminal_frames`], for file contents that are already in memory.
+    pub fn terminal_frames_from_bytes(
+        &self,
+        data: &[u8],
+        filename: &str,
+    ) -> Result<TerminalFrames> {
+        let (frames, _) = self.prepare_terminal_files(&[(data, filename)])?;
+        Ok(frames)
+    }
+
+    /// Terminal frames of the first round, and the compressed files they were built from.
+    fn prepare_terminal(&self, input_paths: &[&Path]) -> Result<(TerminalFrames, Vec<Vec<u8>>)> {
+        let inputs = read_inputs(input_paths, self.options.max_memory)?;
+        let files: Vec<(&[u8], &str)> = inputs
+            .iter()
+            .map(|(data, filename)| (data.as_slice(), filename.as_str()))
+            .collect();
+        self.prepare_terminal_files(&files)
+    }
+
+    fn prepare_terminal_files(
+        &self,
+        files: &[(&[u8], &str)],
+    ) -> Result<(TerminalFrames, Vec<Vec<u8>>)> {
+        let compressed = compress_files(files, &self.options)?;
+        self.options.check_memory(
+            files.iter().map(|(data, _)| data.len()).sum(),
+            &compressed,
+            TERMINAL_REDUNDANCY_FACTOR,
+        )?;
+        let max_version = self.options.version_cap()?;
+        let (chunks, effective_size) = prepare_chunks(
+            &compressed,
+            self.options.chunk_size,
+            DEFAULT_PAYLOAD_SIZE,
+            50, // min_size
+            20, // reduction_step
+            TERMINAL_REDUNDANCY_FACTOR,
+            |encoded| {
+                Ok(max_version.is_none_or(|max| fits_in_version(encoded, max))
+                    && crate::qr::fits_in_terminal(encoded)?)
+            },
+        )
+        .map_err(|e| match e {
+            FountainError::QrCapacityExceeded { .. } => FountainError::TerminalTooSmall,
+            e => e,
+        })?;
+        let chunks = self.options.with_manifest(
+            files,
+            self.options.packet_order.arrange(chunks),
+            effective_size,
+        )?;
+
+        let per_frame = self.terminal_codes_per_frame(&chunks)?;
+        let frames = TerminalFrames {
+            total: chunks.len().div_ceil(per_frame),
+            chunks: chunks.into_iter(),
+            per_frame,
+            effective_size,
+            filename: files[0].1.to_string(),
+        };
+        Ok((frames, compressed))
+    }
+
+    /// Codes of `chunks` to show side by side on each terminal frame: two with
+    /// [`EncodeOptions::dual`] if the terminal is wide enough, else one.
+    pub(crate) fn terminal_codes_per_frame(&self, chunks: &[Chunk]) -> Result<usize> {
+        if !self.options.dual {
+            return Ok(1);
+        }
+        let widest = chunks
+            .iter()
+            .map(Chunk::to_text)
+            .max_by_key(|text| text.as_ref().map(String::len).unwrap_or_default());
+        if let Some(text) = widest {
+            if crate::qr::terminal_codes_across(text?.as_bytes())? >= 2 {
+                return Ok(2);
+            }
+            info!("The terminal is too narrow for two codes side by side; showing one");
+        }
+        Ok(1)
+    }
+
+    /// Renders every chunk as a QR string for display with [`crate::display_qr_carousel`].
+    /// The payload size shrinks as needed to fit the current terminal.
+    pub fn encode_for_terminal(&self, input_path: &Path) -> Result<TerminalQrData> {
+        self.encode_files_for_terminal(&[input_path])
+    }
+
+    /// Like [`Encoder::encode_for_terminal`], for a carousel that carries several files.
+    pub fn encode_files_for_terminal(&self, input_paths: &[&Path]) -> Result<TerminalQrData> {
+        collect_terminal(self.terminal_frames_for_files(input_paths)?)
+    }
+
+    /// Like [`Encoder::encode_for_terminal`], for file contents that are already in memory,
+    /// such as a short text.
+    pub fn encode_bytes_for_terminal(&self, data: &[u8], filename: &str) -> Result<TerminalQrData> {
+        collect_terminal(self.terminal_frames_from_bytes(data, filename)?)
+    }
+
+    /// Prepares the chunks for image/GIF output and returns an iterator that renders them
+    /// as QR code images one at a time, for callers that feed frames to their own sink.
+    pub fn frames(&self, input_path: &Path) -> Result<QrFrames> {
+        self.frames_for_files(&[input_path])
+    }
+
+    /// Like [`Encoder::frames`], for one transfer that carries several files. The decoder
+    /// writes each file out separately.
+    pub fn frames_for_files(&self, input_paths: &[&Path]) -> Result<QrFrames> {
+        let inputs = read_inputs(input_paths, self.options.max_memory)?;
+        let files: Vec<(&[u8], &str)> = inputs
+            .iter()
+            .map(|(data, filename)| (data.as_slice(), filename.as_str()))
+            .collect();
+        self.frames_from_files(&files)
+    }
+
+    /// Like [`Encoder::frames`], for file contents that are already in memory.
+    pub fn frames_from_bytes(&self, data: &[u8], filename: &str) -> Result<QrFrames> {
+        self.frames_from_files(&[(data, filename)])
+    }
+
+    fn frames_from_files(&self, files: &[(&[u8], &str)]) -> Result<QrFrames> {
+        // The colour matrix decoder finds a symbol by its outermost dark cells.
+        if self.options.uses_color_matrix() && self.options.caption.is_some() {
+            return Err(FountainError::InvalidConfig(
+                "a caption cannot be drawn under colour matrix frames".to_string(),
+            ));
+        }
+        if self.options.uses_color_matrix() && self.options.markers {
+            return Err(FountainError::InvalidConfig(
+                "corner markers cannot be drawn around colour matrix frames".to_string(),
+            ));
+        }
+        if self.options.uses_color_matrix() && self.options.strict_palette {
+            return Err(FountainError::InvalidConfig(
+                "colour matrix frames cannot be written in black and white only".to_string(),
+            ));
+        }
+        let pixel_scale = self.options.pixel_scale;
+        let compressed = compress_files(files, &self.options)?;
+        self.options.check_memory(
+            files.iter().map(|(data, _)| data.len()).sum(),
+            &compressed,
+            IMG_REDUNDANCY_FACTOR * (1.0 + self.options.parity as f64 / 100.0),
+        )?;
+        let (chunks, effective_size) = self.prepare_img_chunks(&compressed)?;
+        let parity = self.options.parity_frames(chunks.len());
+        let mut chunks = self.options.with_manifest(
+            files,
+            self.options.packet_order.arrange(chunks),
+            effective_size,
+        )?;
+        self.options
+            .check_chunk_count(chunks.len() + parity, effective_size)?;
+        if parity > 0 {
+            info!(
+                "Adding {} parity frame(s) to {} regular frame(s)",
+                parity,
+                chunks.len()
+            );
+            chunks.extend(build_parity_chunks(
+                &compressed,
+                effective_size,
+                IMG_REDUNDANCY_FACTOR,
+                parity,
+            ));
+        }
+        if self.options.sequence {
+            for (sequence, chunk) in chunks.iter_mut().enumerate() {
+                chunk.header.sequence = Some(sequence as u32);
+            }
+        }
+
+        let fixed_version = if self.options.uses_color_matrix() {
+            None
+        } else {
+            common_version(&chunks)?
+        };
+        Ok(QrFrames {
+            pixel_scale: match fixed_version {
+                Some(version) => self.options.pixel_scale_for(version)?,
+                None => pixel_scale,
+            },
+            quiet_zone: self.options.quiet_zone,
+            fixed_version,
+            total: chunks.len(),
+            parity,
+            chunks: chunks.into_iter(),
+            effective_size,
+            filename: files[0].1.to_string(),
+            total_bytes: files.iter().map(|(data, _)| data.len()).sum(),
+            compressed_bytes: compressed.iter().map(Vec::len).sum(),
+            cover: self.options.cover.then(|| CoverSheet::new(files)),
+            receipt: self
+                .options
+                .receipt
+                .then(|| CoverSheet::new(files).receipt()),
+            caption: self.options.caption.clone(),
+            markers: self.options.markers,
+            #[cfg(feature = "color")]
+            color_matrix: self.options.color_matrix,
+            canvas: self.options.canvas,
+        })
+    }
+
+    /// Chunks for image/GIF output and their payload size, which fits a QR code or, with
+    /// [`EncodeOptions::color_matrix`], a colour matrix symbol.
+    fn prepare_img_chunks(&self, compressed: &[Vec<u8>]) -> Result<(Vec<Chunk>, usize)> {
+        let chunk_size = self.img_chunk_size(compressed)?;
+        // A sequence number lengthens every chunk alike; base45 takes 3 characters per 2 bytes.
+        let stamp_size = if self.options.sequence {
+            SEQUENCE_SIZE
+        } else {
+            0
+        };
+        let stamp = "0".repeat(stamp_size / 2 * 3);
+        #[cfg(feature = "color")]
+        if let Some(matrix) = &self.options.color_matrix {
+            let capacity = matrix.capacity() - stamp_size;
+            return prepare_chunks(
+                compressed,
+                Some(chunk_size.map_or(capacity, |size| size.min(capacity))),
+                capacity,
+                IMG_MIN_PAYLOAD_SIZE,
+                IMG_REDUCTION_STEP,
+                IMG_REDUNDANCY_FACTOR,
+                |_| Ok(true),
+            );
+        }
+        let pixel_scale = self.options.pixel_scale;
+        let max_version = self.options.version_cap()?;
+        prepare_chunks(
+            compressed,
+            chunk_size,
+            crate::chunk::MAX_PAYLOAD_SIZE,
+            IMG_MIN_PAYLOAD_SIZE,
+            IMG_REDUCTION_STEP,
+            IMG_REDUNDANCY_FACTOR,
+            |encoded| {
+                fits_in_image(
+                    &[encoded, stamp.as_bytes()].concat(),
+                    pixel_scale,
+                    max_version,
+                )
+            },
+        )
+    }
+
+    /// Renders every QR code for file contents that are already in memory.
+    /// Nothing is read or written.
+    pub fn encode_bytes(&self, data: &[u8], filename: &str) -> Result<Vec<RgbImage>> {
+        let frames = self.frames_from_bytes(data, filename)?;
+        let total = frames.total();
+        frames
+            .enumerate()
+            .map(|(i, qr_image)| {
+                self.options.check_cancelled()?;
+                self.options.report_frame(i, total);
+                qr_image
+            })
+            .collect()
+    }
+
+    /// Writes one PNG per QR code into `output_dir`.
+    pub fn encode_to_images(&self, input_path: &Path, output_dir: &Path) -> Result<EncodeResult> {
+        self.encode_files_to_images(&[input_path], output_dir)
+    }
+
+    /// Like [`Encoder::encode_to_images`], for one transfer that carries several files.
+    pub fn encode_files_to_images(
+        &self,
+        input_paths: &[&Path],
+        output_dir: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_for_files(input_paths)?;
+        let output_files = self.write_images(&mut frames, output_dir)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Like [`Encoder::encode_to_images`], for file contents that are already in memory, such
+    /// as a short text.
+    pub fn encode_bytes_to_images(
+        &self,
+        data: &[u8],
+        filename: &str,
+        output_dir: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_from_bytes(data, filename)?;
+        let output_files = self.write_images(&mut frames, output_dir)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Sends every QR code of one transfer to `sink`, such as a display of your own (see
+    /// [`crate::sink`]).
+    pub fn encode_to_sink(
+        &self,
+        input_paths: &[&Path],
+        sink: &mut dyn OutputSink,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_for_files(input_paths)?;
+        let output_files = self.write_to_sink(&mut frames, sink)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Like [`Encoder::encode_to_sink`], for file contents that are already in memory.
+    pub fn encode_bytes_to_sink(
+        &self,
+        data: &[u8],
+        filename: &str,
+        sink: &mut dyn OutputSink,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_from_bytes(data, filename)?;
+        let output_files = self.write_to_sink(&mut frames, sink)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Renders the rest of `frames` into `sink` and returns the files it wrote.
+    pub(crate) fn write_to_sink(
+        &self,
+        frames: &mut QrFrames,
+        sink: &mut dyn OutputSink,
+    ) -> Result<Vec<String>> {
+        let total = frames.total();
+        let regular = total - frames.parity();
+        sink.begin(&SinkInfo {
+            filename: frames.filename().to_string(),
+            total,
+            parity: frames.parity(),
+            effective_size: frames.effective_size(),
+        })?;
+        if let Some(cover) = frames.cover_image()? {
+            sink.cover(&cover)?;
+        }
+        let threads = thread_count(self.options.threads);
+        let mut index = total - frames.chunks.len();
+        loop {
+            self.options.check_cancelled()?;
+            let batch: Vec<Chunk> = frames.chunks.by_ref().take(threads).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let images = map_parallel(&batch, threads, |chunk| frames.render(chunk));
+            for (chunk, image) in batch.iter().zip(images) {
+                self.options.check_cancelled()?;
+                sink.write_frame(&SinkFrame {
+                    index,
+                    total,
+                    is_parity: index >= regular,
+                    chunk,
+                    image: &image?,
+                })?;
+                self.options.report_frame(index, total);
+                index += 1;
+            }
+        }
+        sink.finish()
+    }
+
+    /// Saves `frames` as numbered PNG files in `output_dir` and returns their names.
+    pub(crate) fn write_images(
+        &self,
+        frames: &mut QrFrames,
+        output_dir: &Path,
+    ) -> Result<Vec<String>> {
+        let mut output_files = self.write_to_sink(frames, &mut ImageDirSink::new(output_dir))?;
+        let receipt = format!("{}.{}", image_stem(frames.filename()), RECEIPT_EXTENSION);
+        if frames.write_receipt(&output_dir.join(&receipt))? {
+            output_files.push(receipt);
+        }
+        Ok(output_files)
+    }
+
+    /// Writes every packet into `output_dir` as a `.pkt` file holding its serialized chunk,
+    /// without rendering QR codes, for transports that are not optical. The decoder reads the
+    /// directory like one of QR images.
+    pub fn export_packets(&self, input_paths: &[&Path], output_dir: &Path) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_for_files(input_paths)?;
+        let output_files = self.write_packet_files(&mut frames, output_dir)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Like [`Encoder::export_packets`], for file contents that are already in memory.
+    pub fn export_bytes_packets(
+        &self,
+        data: &[u8],
+        filename: &str,
+        output_dir: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_from_bytes(data, filename)?;
+        let output_files = self.write_packet_files(&mut frames, output_dir)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    fn write_packet_files(&self, frames: &mut QrFrames, output_dir: &Path) -> Result<Vec<String>> {
+        fs::create_dir_all(output_dir)?;
+        let stem = image_stem(frames.filename());
+        let total = frames.total();
+
+        let mut output_files = Vec::with_capacity(total);
+        let progress = Progress::new(Some(total as u64), "packets");
+
+        for (i, chunk) in frames.chunks.by_ref().enumerate() {
+            self.options.check_cancelled()?;
+            let output_filename = packet_file_name(&stem, i);
+            fs::write(output_dir.join(&output_filename), chunk.to_bytes()?)?;
+
+            progress.inc(|| {
+                Some(format!(
+                    "  Wrote packet {}/{}: {}",
+                    i + 1,
+                    total,
+                    output_filename
+                ))
+            });
+            self.options.report_frame(i, total);
+            output_files.push(output_filename);
+        }
+        Ok(output_files)
+    }
+
+    /// Writes all QR codes as frames of a looping animated GIF.
+    pub fn encode_to_gif(&self, input_path: &Path, output_gif: &Path) -> Result<EncodeResult> {
+        self.encode_files_to_gif(&[input_path], output_gif)
+    }
+
+    /// Like [`Encoder::encode_to_gif`], for one transfer that carries several files.
+    pub fn encode_files_to_gif(
+        &self,
+        input_paths: &[&Path],
+        output_gif: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let frames = self.frames_for_files(input_paths)?;
+        self.save_gif(frames, output_gif, started)
+    }
+
+    /// Like [`Encoder::encode_to_gif`], for file contents that are already in memory, such as
+    /// a short text.
+    pub fn encode_bytes_to_gif(
+        &self,
+        data: &[u8],
+        filename: &str,
+        output_gif: &Path,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let frames = self.frames_from_bytes(data, filename)?;
+        self.save_gif(frames, output_gif, started)
+    }
+
+    pub(crate) fn save_gif(
+        &self,
+        mut frames: QrFrames,
+        output_gif: &Path,
+        started: Instant,
+    ) -> Result<EncodeResult> {
+        let mut sink = GifSink::create(output_gif, self.options.interval_ms)?
+            .strict_palette(self.options.strict_palette)
+            .repeat_frames(self.options.repeat_frames);
+        let mut output_files = self.write_to_sink(&mut frames, &mut sink)?;
+        let receipt = output_gif.with_extension(RECEIPT_EXTENSION);
+        if frames.write_receipt(&receipt)? {
+            output_files.push(receipt.to_string_lossy().to_string());
+        }
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Writes the packets of one transfer to `writer`, one line each in `encoding`, without
+    /// rendering QR codes. Each line holds what one frame of the GIF or image output would.
+    pub fn encode_files_to_packets<W: Write>(
+        &self,
+        input_paths: &[&Path],
+        writer: W,
+        encoding: PacketEncoding,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_for_files(input_paths)?;
+        self.write_packets(&mut frames, writer, encoding)?;
+        Ok(frames.result(Vec::new(), started))
+    }
+
+    /// Like [`Encoder::encode_files_to_packets`], for file contents that are already in memory.
+    pub fn encode_bytes_to_packets<W: Write>(
+        &self,
+        data: &[u8],
+        filename: &str,
+        writer: W,
+        encoding: PacketEncoding,
+    ) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.frames_from_bytes(data, filename)?;
+        self.write_packets(&mut frames, writer, encoding)?;
+        Ok(frames.result(Vec::new(), started))
+    }
+
+    fn write_packets<W: Write>(
+        &self,
+        frames: &mut QrFrames,
+        mut writer: W,
+        encoding: PacketEncoding,
+    ) -> Result<()> {
+        let total = frames.total();
+        for (i, chunk) in frames.chunks.by_ref().enumerate() {
+            self.options.check_cancelled()?;
+            writeln!(writer, "{}", encoding.encode(&chunk)?)?;
+            self.options.report_frame(i, total);
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Logs and reports one image written by `encode_to_images` (or its async variant).
+    pub(crate) fn image_written(
+        &self,
+        progress: &Progress,
+        index: usize,
+        total: usize,
+        output_filename: &str,
+    ) {
+        progress.inc(|| {
+            Some(format!(
+                "  Generated QR code {}/{}: {}",
+                index + 1,
+                total,
+                output_filename
+            ))
+        });
+        self.options.report_frame(index, total);
+    }
+
+    /// Encodes `frames` as a looping animated GIF into `writer`.
+    pub(crate) fn write_gif<W: Write>(&self, frames: &mut QrFrames, writer: W) -> Result<()> {
+        let mut sink = GifSink::new(writer, self.options.interval_ms)?
+            .strict_palette(self.options.strict_palette)
+            .repeat_frames(self.options.repeat_frames);
+        self.write_to_sink(frames, &mut sink)?;
+        Ok(())
+    }
+
+    /// Runs chunking and payload-size fitting for image/GIF output and estimates the result,
+    /// without writing any files. The GIF size is extrapolated from one encoded sample frame.
+    pub fn estimate(&self, input_path: &Path) -> Result<EncodeEstimate> {
+        let pixel_scale = self.options.pixel_scale;
+        let interval_ms = gif_interval_ms(self.options.interval_ms);
+
+        let (compressed, _filename) = read_and_compress(input_path, &self.options)?;
+        let compressed = [compressed];
+        let (chunks, effective_size) = prepare_chunks_for_img(
+            &compressed,
+            self.img_chunk_size(&compressed)?,
+            pixel_scale,
+            self.options.version_cap()?,
+            IMG_REDUNDANCY_FACTOR,
+        )?;
+
+        let num_chunks = chunks.len() + self.options.parity_frames(chunks.len());
+        let shown_frames = num_chunks as u64 * u64::from(self.options.repeat_frames.max(1));
+        let first = chunks
+            .first()
+            .expect("RaptorQ always produces at least one packet");
+        let chunk_bytes = first.to_bytes()?;
         let encoded = base45::encode(&chunk_bytes);
-        let qr_string = render_qr_to_terminal(encoded.as_bytes())?;
-        qr_strings.push(qr_string);
+        let pixel_scale = self
+            .options
+            .pixel_scale_for(qr_version_for(encoded.as_bytes())?)?;
+        let (qr_image, version) = generate_qr_image_with_quiet_zone(
+            encoded.as_bytes(),
+            None,
+            pixel_scale,
+            self.options.quiet_zone,
+        )?;
+        let (frame_width, frame_height) = qr_image.dimensions();
+
+        let mut sample = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut sample);
+            let rgba_image: RgbaImage = image::DynamicImage::ImageRgb8(qr_image).into_rgba8();
+            let delay = Delay::from_saturating_duration(Duration::from_millis(interval_ms));
+            encoder.encode_frame(Frame::from_parts(rgba_image, 0, 0, delay))?;
+        }
+
+        let qr_version = match version {
+            Version::Normal(v) | Version::Micro(v) => v,
+        };
+
+        Ok(EncodeEstimate {
+            num_chunks,
+            effective_size,
+            qr_version,
+            frame_bytes: chunk_bytes.len(),
+            frame_width,
+            frame_height,
+            estimated_gif_bytes: sample.len() as u64 * shown_frames,
+            duration_ms: interval_ms * shown_frames,
+        })
     }
+}
+
+/// Renders every terminal frame up front, for display with [`crate::display_qr_carousel`].
+pub(crate) fn collect_terminal(frames: TerminalFrames) -> Result<TerminalQrData> {
+    let filename = frames.filename().to_string();
+    let effective_size = frames.effective_size();
+    let total = frames.total();
+    let qr_strings = frames.collect::<Result<Vec<_>>>()?;
 
     Ok(TerminalQrData {
         filename,
@@ -171,33 +1837,50 @@ pub fn encode_file_for_terminal(
     })
 }
 
-/// Internal helper to process a sequence of chunks as QR images with a consistent version.
-fn process_chunks_as_qr_images<F>(
-    chunks: &[Chunk],
-    pixel_scale: u32,
-    mut processor: F,
-) -> Result<()>
-where
-    F: FnMut(&Chunk, image::RgbImage, usize, usize) -> Result<()>,
-{
-    let mut fixed_version: Option<Version> = None;
-    let total = chunks.len();
-
-    for (i, chunk) in chunks.iter().enumerate() {
-        let chunk_bytes = chunk.to_bytes()?;
-        let encoded = base45::encode(&chunk_bytes);
+/// Longest stem of the image files written for a transfer, in bytes, leaving room for the
+/// transfer and frame suffixes within [`MAX_FILENAME_LEN`].
+const MAX_IMAGE_STEM_LEN: usize = MAX_FILENAME_LEN - 32;
 
-        let (qr_image, version) =
-            generate_qr_image(encoded.as_bytes(), fixed_version, pixel_scale)?;
+/// Stem of the image files written for `filename`: dots become `_`, and a long name is cut.
+pub(crate) fn image_stem(filename: &str) -> String {
+    let mut end = filename.len().min(MAX_IMAGE_STEM_LEN);
+    while !filename.is_char_boundary(end) {
+        end -= 1;
+    }
+    filename[..end].replace('.', "_")
+}
 
-        if fixed_version.is_none() {
-            fixed_version = Some(version);
-        }
+/// File name of the QR code image at `index` for an input whose name became `stem`.
+pub(crate) fn image_file_name(stem: &str, index: usize) -> String {
+    format!("{}_{:04}.{}", stem, index + 1, QR_FILE_EXTENSION)
+}
 
-        processor(chunk, qr_image, i, total)?;
+/// File name of the image at `index` of frames whose first `regular` are the regular frames and
+/// the rest parity frames, which are numbered on their own as `<stem>_parity_NNNN`.
+pub(crate) fn frame_file_name(stem: &str, index: usize, regular: usize) -> String {
+    match index.checked_sub(regular) {
+        Some(parity_index) => format!(
+            "{}_parity_{:04}.{}",
+            stem,
+            parity_index + 1,
+            QR_FILE_EXTENSION
+        ),
+        None => image_file_name(stem, index),
     }
+}
+
+/// File name of the packet at `index` for an input whose name became `stem`.
+fn packet_file_name(stem: &str, index: usize) -> String {
+    format!("{}_{:04}.{}", stem, index + 1, PACKET_FILE_EXTENSION)
+}
 
-    Ok(())
+pub fn encode_file_for_terminal(
+    input_path: &Path,
+    chunk_size: Option<usize>,
+) -> Result<TerminalQrData> {
+    let mut options = EncodeOptions::new();
+    options.chunk_size = chunk_size;
+    Encoder::new(options).encode_for_terminal(input_path)
 }
 
 pub fn encode_file_to_images(
@@ -206,39 +1889,9 @@ pub fn encode_file_to_images(
     chunk_size: Option<usize>,
     pixel_scale: u32,
 ) -> Result<EncodeResult> {
-    fs::create_dir_all(output_dir)?;
-
-    let (chunks, effective_size, filename) =
-        prepare_chunks_for_img(input_path, chunk_size, pixel_scale, 1.5)?;
-
-    let mut output_files = Vec::with_capacity(chunks.len());
-
-    process_chunks_as_qr_images(&chunks, pixel_scale, |chunk, qr_image, i, total| {
-        let output_filename = format!(
-            "{}_{:04}.{}",
-            filename.replace('.', "_"),
-            chunk.header.index + 1,
-            QR_FILE_EXTENSION
-        );
-        let output_path = output_dir.join(&output_filename);
-        save_qr_image(&qr_image, &output_path)?;
-
-        println!(
-            "  Generated QR code {}/{}: {}",
-            i + 1,
-            total,
-            &output_filename
-        );
-
-        output_files.push(output_filename);
-        Ok(())
-    })?;
-
-    Ok(EncodeResult {
-        num_chunks: chunks.len(),
-        output_files,
-        effective_size,
-    })
+    let mut options = EncodeOptions::new().pixel_scale(pixel_scale);
+    options.chunk_size = chunk_size;
+    Encoder::new(options).encode_to_images(input_path, output_dir)
 }
 
 pub fn encode_file_to_gif(
@@ -248,34 +1901,93 @@ pub fn encode_file_to_gif(
     interval_ms: u64,
     pixel_scale: u32,
 ) -> Result<EncodeResult> {
-    let (chunks, effective_size, _filename) =
-        prepare_chunks_for_img(input_path, chunk_size, pixel_scale, 1.5)?;
+    let mut options = EncodeOptions::new()
+        .interval_ms(interval_ms)
+        .pixel_scale(pixel_scale);
+    options.chunk_size = chunk_size;
+    Encoder::new(options).encode_to_gif(input_path, output_gif)
+}
 
-    if let Some(parent) = output_gif.parent() {
-        fs::create_dir_all(parent)?;
-    }
+/// Free-function form of [`Encoder::encode_bytes`].
+pub fn encode_bytes(data: &[u8], filename: &str, options: EncodeOptions) -> Result<Vec<RgbImage>> {
+    Encoder::new(options).encode_bytes(data, filename)
+}
 
-    let file = fs::File::create(output_gif)?;
-    let mut encoder = GifEncoder::new(file);
-    encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+/// Free-function form of [`Encoder::estimate`].
+pub fn estimate_encoding(
+    input_path: &Path,
+    chunk_size: Option<usize>,
+    interval_ms: u64,
+    pixel_scale: u32,
+) -> Result<EncodeEstimate> {
+    let mut options = EncodeOptions::new()
+        .interval_ms(interval_ms)
+        .pixel_scale(pixel_scale);
+    options.chunk_size = chunk_size;
+    Encoder::new(options).estimate(input_path)
+}
 
-    process_chunks_as_qr_images(&chunks, pixel_scale, |_, qr_image, i, total| {
-        let rgba_image: RgbaImage = image::DynamicImage::ImageRgb8(qr_image).into_rgba8();
+/// Picks the payload size for image/GIF output that keeps the transfer within `max_frames`
+/// QR codes while making them as sparse (easy to scan) as possible.
+///
+/// Fails if even the densest payload that fits a QR code needs more than `max_frames` codes.
+pub fn payload_size_for_frame_budget(
+    input_path: &Path,
+    max_frames: usize,
+    pixel_scale: u32,
+) -> Result<usize> {
+    let (compressed, _filename) = read_and_compress(input_path, &EncodeOptions::default())?;
+    frame_budget_payload_size(&compressed, max_frames, pixel_scale, None)
+}
 
-        let delay = Delay::from_saturating_duration(Duration::from_millis(interval_ms));
-        let frame = Frame::from_parts(rgba_image, 0, 0, delay);
+fn frame_budget_payload_size(
+    compressed: &[u8],
+    max_frames: usize,
+    pixel_scale: u32,
+    max_version: Option<i16>,
+) -> Result<usize> {
+    let frames_at = |size: usize| {
+        chunk_count(
+            compressed.len(),
+            packet_size_for(size),
+            IMG_REDUNDANCY_FACTOR,
+        )
+    };
 
-        encoder.encode_frame(frame)?;
+    let max_size = fit_payload_size(
+        compressed,
+        crate::chunk::MAX_PAYLOAD_SIZE,
+        IMG_MIN_PAYLOAD_SIZE,
+        IMG_REDUCTION_STEP,
+        |encoded| fits_in_image(encoded, pixel_scale, max_version),
+    )?;
 
-        if total <= 10 || ((i + 1) % 10 == 0 || i + 1 == total) {
-            println!("  Processed frame {}/{}", i + 1, total);
+    let fewest_frames = frames_at(max_size);
+    if fewest_frames > max_frames {
+        return Err(FountainError::FrameBudgetExceeded {
+            max_frames,
+            max_size,
+            min_frames: fewest_frames,
+        });
+    }
+
+    // Frame count only shrinks as the payload grows, so binary search the smallest size
+    // that still meets the budget.
+    let (mut low, mut high) = (IMG_MIN_PAYLOAD_SIZE, max_size);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if frames_at(mid) <= max_frames {
+            high = mid;
+        } else {
+            low = mid + 1;
         }
-        Ok(())
-    })?;
+    }
 
-    Ok(EncodeResult {
-        num_chunks: chunks.len(),
-        output_files: vec![output_gif.to_string_lossy().to_string()],
-        effective_size,
-    })
+    debug!(
+        "Payload size {} bytes gives {} frames (budget {})",
+        high,
+        frames_at(high),
+        max_frames
+    );
+    Ok(high)
 }
@@ -0,0 +1,205 @@
+use fountain_core::WireError;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::chunk::{PACKET_FILE_EXTENSION, PACKET_LINES_EXTENSION};
+use crate::qr::DECODE_IMAGE_EXTENSIONS;
+
+/// Result type of the library API.
+pub type Result<T, E = FountainError> = std::result::Result<T, E>;
+
+/// Failures raised by the library, so callers can branch on the cause.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum FountainError {
+    #[error("Input path does not exist: {}", .0.display())]
+    InputNotFound(PathBuf),
+    #[error(
+        "Unsupported input file type: {}. Only directories (containing {} or {} files), GIF files or .{} files of packet lines are supported.",
+        .0.display(),
+        DECODE_IMAGE_EXTENSIONS.join("/"),
+        PACKET_FILE_EXTENSION,
+        PACKET_LINES_EXTENSION
+    )]
+    UnsupportedInput(PathBuf),
+    #[error(
+        "No image ({}) or packet ({}) files found in directory {}",
+        DECODE_IMAGE_EXTENSIONS.join("/"),
+        PACKET_FILE_EXTENSION,
+        .0.display()
+    )]
+    NoImagesFound(PathBuf),
+    #[error("No valid QR chunks found")]
+    NoChunksFound,
+    #[error(
+        "Could not decode with RaptorQ (insufficient packets: {received} unique of at least {needed} needed, after {scanned} items)"
+    )]
+    NotEnoughPackets {
+        received: usize,
+        needed: usize,
+        scanned: usize,
+    },
+    #[error(
+        "Gave up after {reason} with {received} unique packet(s) of at least {needed} needed, from {scanned} frame(s)"
+    )]
+    LimitReached {
+        reason: String,
+        received: usize,
+        needed: usize,
+        scanned: usize,
+    },
+    #[error(
+        "Stalled: no new packet for {idle:?}, with {received} unique packet(s) of at least {needed} needed, from {scanned} frame(s)"
+    )]
+    Stalled {
+        idle: Duration,
+        received: usize,
+        needed: usize,
+        scanned: usize,
+    },
+    #[error(
+        "Could not decode with RaptorQ: {ignored} packet(s) come from a different encoding (another file size or payload size) and cannot be combined with the rest, which has {received} unique of at least {needed} needed. Decode the frames of each encoding separately."
+    )]
+    MismatchedPackets {
+        ignored: usize,
+        received: usize,
+        needed: usize,
+    },
+    #[error("Checksum mismatch: expected {expected:?}, got {actual:?}")]
+    ChecksumMismatch { expected: Vec<u8>, actual: Vec<u8> },
+    #[error("Unsupported chunk version: {0}. Only versions 1 to 5 are supported.")]
+    UnsupportedVersion(u8),
+    #[error(
+        "Transfer uses compression algorithm {0}, which this decoder does not support. Update fountain-decode to decode it."
+    )]
+    UnsupportedCompression(u8),
+    #[error("Invalid chunk: {0}")]
+    InvalidChunk(WireError),
+    #[error("Output file already exists: {}", .0.display())]
+    OutputExists(PathBuf),
+    #[error(
+        "Terminal too small to display QR codes even at minimum payload size. Please increase terminal size."
+    )]
+    TerminalTooSmall,
+    #[error("Data too large to fit in QR code even at minimum payload size ({min_size} bytes).")]
+    QrCapacityExceeded { min_size: usize },
+    #[error(
+        "Payload size of {size} bytes is too small: each QR code needs at least {min_size} bytes for its header and packet."
+    )]
+    PayloadTooSmall { size: usize, min_size: usize },
+    #[error(
+        "Cannot fit into {max_frames} frames: even the largest payload size ({max_size} bytes) needs {min_frames} frames"
+    )]
+    FrameBudgetExceeded {
+        max_frames: usize,
+        max_size: usize,
+        min_frames: usize,
+    },
+    #[error(
+        "Would take {chunks} QR codes at a payload size of {payload_size} bytes, more than the limit of {limit}"
+    )]
+    TooManyChunks {
+        chunks: usize,
+        limit: usize,
+        payload_size: usize,
+    },
+    #[error("Needs about {needed} bytes of memory, more than the limit of {limit} bytes")]
+    MemoryLimitExceeded { needed: usize, limit: usize },
+    #[error("QR code error: {0}")]
+    Qr(String),
+    #[error("Invalid filename: {}", .0.display())]
+    InvalidFilename(PathBuf),
+    #[error("Too many files for one transfer: {0} (at most 255)")]
+    TooManyFiles(usize),
+    #[error("Invalid split transfer: {0}")]
+    InvalidSplit(String),
+    #[error("QR codes carry a piped stream; decode them in pipe mode")]
+    PipedStream,
+    #[error("Transfer was compressed with dictionary {0:08x}, which was not supplied")]
+    MissingDictionary(u32),
+    #[error("Delta was made against a different base file")]
+    DeltaBaseMismatch,
+    #[error("Invalid delta: {0}")]
+    InvalidDelta(String),
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Invalid session file: {0}")]
+    InvalidSession(String),
+    #[error("Window error: {0}")]
+    Window(String),
+    #[error("Framebuffer error: {0}")]
+    Framebuffer(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+}
+
+impl FountainError {
+    /// Process exit code used by the binaries. 1 is any other failure, 2 is a usage error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            FountainError::InputNotFound(_) => 3,
+            FountainError::UnsupportedInput(_) => 4,
+            FountainError::NoChunksFound => 5,
+            FountainError::NotEnoughPackets { .. } | FountainError::MismatchedPackets { .. } => 6,
+            FountainError::ChecksumMismatch { .. } => 7,
+            FountainError::OutputExists(_) => 8,
+            FountainError::TerminalTooSmall => 9,
+            FountainError::QrCapacityExceeded { .. } => 10,
+            FountainError::LimitReached { .. } => 11,
+            FountainError::MemoryLimitExceeded { .. } => 12,
+            FountainError::TooManyChunks { .. } => 13,
+            FountainError::MissingDictionary(_) => 14,
+            FountainError::DeltaBaseMismatch => 15,
+            FountainError::Stalled { .. } => 16,
+            FountainError::PayloadTooSmall { .. } => 2,
+            FountainError::Cancelled => 130,
+            _ => 1,
+        }
+    }
+
+    /// Finds the first `FountainError` in the chain of causes of an `anyhow::Error`.
+    pub fn find(err: &anyhow::Error) -> Option<&FountainError> {
+        err.chain().find_map(|e| e.downcast_ref::<FountainError>())
+    }
+}
+
+#[cfg(feature = "window")]
+macro_rules! window_errors {
+    ($($error:ty),*) => {$(
+        impl From<$error> for FountainError {
+            fn from(err: $error) -> Self {
+                FountainError::Window(err.to_string())
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "window")]
+window_errors!(
+    x11rb::errors::ConnectError,
+    x11rb::errors::ConnectionError,
+    x11rb::errors::ReplyError,
+    x11rb::errors::ReplyOrIdError
+);
+
+impl From<WireError> for FountainError {
+    fn from(err: WireError) -> Self {
+        match err {
+            WireError::ChecksumMismatch { expected, actual } => {
+                FountainError::ChecksumMismatch { expected, actual }
+            }
+            WireError::UnsupportedVersion(version) => FountainError::UnsupportedVersion(version),
+            WireError::MissingDictionary(id) => FountainError::MissingDictionary(id),
+            WireError::UnsupportedCompression(algorithm) => {
+                FountainError::UnsupportedCompression(algorithm)
+            }
+            err => FountainError::InvalidChunk(err),
+        }
+    }
+}
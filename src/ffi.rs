@@ -0,0 +1,413 @@
+//! C interface for embedding the fountain QR protocol in other languages.
+//!
+//! The matching header is `include/fountain.h`, generated with `./script/rust/gen-header.sh`.
+//! Functions that can fail return NULL or `FOUNTAIN_FEED_STATUS_ERROR`; the message is then
+//! available from `fountain_last_error` on the same thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::io::Cursor;
+use std::ptr;
+use std::slice;
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+
+use crate::encode::{EncodeOptions, Encoder};
+use crate::stream::{DecodedFile, FeedResult, StreamDecoder};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: anyhow::Error) {
+    let message = CString::new(format!("{:#}", err).replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Message of the last error raised on this thread, or NULL if there was none.
+/// The string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn fountain_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// QR code frames produced by `fountain_encode`, each stored as PNG bytes.
+pub struct FountainFrames {
+    frames: Vec<Vec<u8>>,
+    effective_size: usize,
+}
+
+/// Encodes `len` bytes at `data` into QR code frames.
+///
+/// `chunk_size` of 0 picks the largest payload that fits. Returns NULL on failure.
+/// Free the result with `fountain_frames_free`.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `filename` to a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_encode(
+    data: *const u8,
+    len: usize,
+    filename: *const c_char,
+    chunk_size: usize,
+    pixel_scale: u32,
+) -> *mut FountainFrames {
+    if (data.is_null() && len > 0) || filename.is_null() {
+        set_last_error(anyhow::anyhow!("Null argument"));
+        return ptr::null_mut();
+    }
+    let data = if len == 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(data, len)
+    };
+
+    let result = CStr::from_ptr(filename)
+        .to_str()
+        .map_err(anyhow::Error::from)
+        .and_then(|filename| encode_png_frames(data, filename, chunk_size, pixel_scale));
+
+    match result {
+        Ok(frames) => Box::into_raw(Box::new(frames)),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn encode_png_frames(
+    data: &[u8],
+    filename: &str,
+    chunk_size: usize,
+    pixel_scale: u32,
+) -> anyhow::Result<FountainFrames> {
+    let mut options = EncodeOptions::new().pixel_scale(pixel_scale);
+    if chunk_size > 0 {
+        options = options.chunk_size(chunk_size);
+    }
+
+    let qr_frames = Encoder::new(options).frames_from_bytes(data, filename)?;
+    let effective_size = qr_frames.effective_size();
+
+    let mut frames = Vec::with_capacity(qr_frames.len());
+    for qr_image in qr_frames {
+        let mut png = Vec::new();
+        qr_image?.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        frames.push(png);
+    }
+
+    Ok(FountainFrames {
+        frames,
+        effective_size,
+    })
+}
+
+/// Number of frames in `frames`.
+///
+/// # Safety
+/// `frames` must be a pointer returned by `fountain_encode` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_frames_count(frames: *const FountainFrames) -> usize {
+    frames.as_ref().map_or(0, |frames| frames.frames.len())
+}
+
+/// Payload size (bytes) per QR code that the encoder settled on.
+///
+/// # Safety
+/// `frames` must be a pointer returned by `fountain_encode` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_frames_payload_size(frames: *const FountainFrames) -> usize {
+    frames.as_ref().map_or(0, |frames| frames.effective_size)
+}
+
+/// PNG bytes of frame `index`, with the length written to `out_len`. Returns NULL if `index`
+/// is out of range. The data is owned by `frames` and valid until it is freed.
+///
+/// # Safety
+/// `frames` must be a pointer returned by `fountain_encode` and not yet freed, and `out_len`
+/// must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_frames_png(
+    frames: *const FountainFrames,
+    index: usize,
+    out_len: *mut usize,
+) -> *const u8 {
+    match frames.as_ref().and_then(|frames| frames.frames.get(index)) {
+        Some(png) => {
+            if !out_len.is_null() {
+                *out_len = png.len();
+            }
+            png.as_ptr()
+        }
+        None => ptr::null(),
+    }
+}
+
+/// Releases frames returned by `fountain_encode`. Passing NULL is a no-op.
+///
+/// # Safety
+/// `frames` must be NULL or a pointer returned by `fountain_encode` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_frames_free(frames: *mut FountainFrames) {
+    if !frames.is_null() {
+        drop(Box::from_raw(frames));
+    }
+}
+
+/// Outcome of feeding one frame to a `FountainDecoder`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FountainFeedStatus {
+    Error = -1,
+    NoChunk = 0,
+    Accepted = 1,
+    Duplicate = 2,
+    Complete = 3,
+}
+
+/// Streaming decoder that accumulates packets from camera or screen frames.
+pub struct FountainDecoder {
+    stream: StreamDecoder,
+    files: Vec<(CString, Vec<u8>)>,
+    complete: bool,
+}
+
+impl FountainDecoder {
+    fn push_file(&mut self, file: DecodedFile) {
+        let filename = CString::new(file.filename.replace('\0', "_")).unwrap_or_default();
+        self.files.push((filename, file.data));
+    }
+}
+
+/// Creates a decoder. Free it with `fountain_decoder_free`.
+#[no_mangle]
+pub extern "C" fn fountain_decoder_new() -> *mut FountainDecoder {
+    Box::into_raw(Box::new(FountainDecoder {
+        stream: StreamDecoder::new(),
+        files: Vec::new(),
+        complete: false,
+    }))
+}
+
+/// Feeds one RGBA frame of `width` x `height` pixels (`width * height * 4` bytes).
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new` and `rgba` must point to
+/// `width * height * 4` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_feed_rgba(
+    decoder: *mut FountainDecoder,
+    rgba: *const u8,
+    width: u32,
+    height: u32,
+) -> FountainFeedStatus {
+    let Some(decoder) = decoder.as_mut() else {
+        set_last_error(anyhow::anyhow!("Null decoder"));
+        return FountainFeedStatus::Error;
+    };
+    if decoder.complete {
+        return FountainFeedStatus::Complete;
+    }
+    if rgba.is_null() {
+        set_last_error(anyhow::anyhow!("Null frame data"));
+        return FountainFeedStatus::Error;
+    }
+
+    let len = width as usize * height as usize * 4;
+    let pixels = slice::from_raw_parts(rgba, len).to_vec();
+    let Some(img) = RgbaImage::from_raw(width, height, pixels) else {
+        set_last_error(anyhow::anyhow!("Invalid frame dimensions"));
+        return FountainFeedStatus::Error;
+    };
+
+    match decoder.stream.feed_image(&DynamicImage::ImageRgba8(img)) {
+        Ok(FeedResult::NoChunk) => FountainFeedStatus::NoChunk,
+        Ok(FeedResult::Accepted | FeedResult::Manifest(_)) => FountainFeedStatus::Accepted,
+        Ok(FeedResult::Duplicate) => FountainFeedStatus::Duplicate,
+        Ok(FeedResult::FileComplete(file)) => {
+            decoder.push_file(file);
+            FountainFeedStatus::Accepted
+        }
+        Ok(FeedResult::Complete(file)) => {
+            decoder.push_file(file);
+            decoder.complete = true;
+            FountainFeedStatus::Complete
+        }
+        Err(e) => {
+            set_last_error(e.into());
+            FountainFeedStatus::Error
+        }
+    }
+}
+
+/// Unique packets received so far.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_packets_received(
+    decoder: *const FountainDecoder,
+) -> usize {
+    decoder
+        .as_ref()
+        .map_or(0, |decoder| decoder.stream.num_packets())
+}
+
+/// Minimum number of packets needed to decode, or 0 until the first packet has arrived.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_packets_expected(
+    decoder: *const FountainDecoder,
+) -> usize {
+    decoder
+        .as_ref()
+        .and_then(|decoder| decoder.stream.expected_packets())
+        .unwrap_or(0)
+}
+
+/// Original filename of the decoded file, or NULL until decoding is complete. In a multi-file
+/// transfer this is the first file. The string is owned by `decoder`.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_filename(
+    decoder: *const FountainDecoder,
+) -> *const c_char {
+    match decoder.as_ref().filter(|decoder| decoder.complete) {
+        Some(_) => fountain_decoder_file_name_at(decoder, 0),
+        None => ptr::null(),
+    }
+}
+
+/// Contents of the decoded file, with the length written to `out_len`, or NULL until decoding
+/// is complete. In a multi-file transfer this is the first file. The data is owned by `decoder`.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new` and `out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_data(
+    decoder: *const FountainDecoder,
+    out_len: *mut usize,
+) -> *const u8 {
+    match decoder.as_ref().filter(|decoder| decoder.complete) {
+        Some(_) => fountain_decoder_file_data_at(decoder, 0, out_len),
+        None => ptr::null(),
+    }
+}
+
+/// Number of files decoded so far. A multi-file transfer reports each file as it completes,
+/// while `fountain_decoder_feed_rgba` returns `FOUNTAIN_FEED_STATUS_ACCEPTED` until the last.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_file_count(decoder: *const FountainDecoder) -> usize {
+    decoder.as_ref().map_or(0, |decoder| decoder.files.len())
+}
+
+/// Original filename of decoded file `index`, or NULL if `index` is out of range.
+/// The string is owned by `decoder`.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_file_name_at(
+    decoder: *const FountainDecoder,
+    index: usize,
+) -> *const c_char {
+    decoder
+        .as_ref()
+        .and_then(|decoder| decoder.files.get(index))
+        .map_or(ptr::null(), |(filename, _)| filename.as_ptr())
+}
+
+/// Contents of decoded file `index`, with the length written to `out_len`, or NULL if `index`
+/// is out of range. The data is owned by `decoder`.
+///
+/// # Safety
+/// `decoder` must be a live pointer from `fountain_decoder_new` and `out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_file_data_at(
+    decoder: *const FountainDecoder,
+    index: usize,
+    out_len: *mut usize,
+) -> *const u8 {
+    match decoder
+        .as_ref()
+        .and_then(|decoder| decoder.files.get(index))
+    {
+        Some((_, data)) => {
+            if !out_len.is_null() {
+                *out_len = data.len();
+            }
+            data.as_ptr()
+        }
+        None => ptr::null(),
+    }
+}
+
+/// Releases a decoder. Passing NULL is a no-op.
+///
+/// # Safety
+/// `decoder` must be NULL or a pointer from `fountain_decoder_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_free(decoder: *mut FountainDecoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_roundtrip() {
+        let content = b"FFI roundtrip test. ".repeat(20);
+        let filename = CString::new("ffi.txt").unwrap();
+
+        unsafe {
+            let frames =
+                fountain_encode(content.as_ptr(), content.len(), filename.as_ptr(), 300, 4);
+            assert!(!frames.is_null());
+
+            let decoder = fountain_decoder_new();
+            let mut status = FountainFeedStatus::NoChunk;
+            for i in 0..fountain_frames_count(frames) {
+                let mut len = 0;
+                let png = fountain_frames_png(frames, i, &mut len);
+                let img = image::load_from_memory(slice::from_raw_parts(png, len))
+                    .unwrap()
+                    .into_rgba8();
+                status =
+                    fountain_decoder_feed_rgba(decoder, img.as_ptr(), img.width(), img.height());
+                if status == FountainFeedStatus::Complete {
+                    break;
+                }
+            }
+            assert_eq!(status, FountainFeedStatus::Complete);
+
+            let name = CStr::from_ptr(fountain_decoder_filename(decoder));
+            assert_eq!(name.to_str().unwrap(), "ffi.txt");
+            let mut len = 0;
+            let data = fountain_decoder_data(decoder, &mut len);
+            assert_eq!(slice::from_raw_parts(data, len), &content[..]);
+            assert_eq!(fountain_decoder_file_count(decoder), 1);
+            assert!(fountain_decoder_file_name_at(decoder, 1).is_null());
+
+            fountain_decoder_free(decoder);
+            fountain_frames_free(frames);
+
+            assert!(fountain_encode(ptr::null(), 10, filename.as_ptr(), 0, 4).is_null());
+            assert!(!fountain_last_error().is_null());
+        }
+    }
+}
@@ -0,0 +1,581 @@
+//! Corner markers for photographing frames, see `EncodeOptions::markers`.
+//!
+//! Each frame gets an ArUco marker (from the 4x4 dictionary, ids 0 to 3) in each corner,
+//! outside the code's quiet zone. A handheld photo of a printed sheet or a projected slide
+//! sees the code at an angle, which the finder patterns alone do not always survive. The
+//! markers are large and blocky, so they are still found; their corners give the frame's
+//! perspective, and the frame is warped back to a flat, upright image before the code is read.
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+use image::imageops::{resize, FilterType};
+#[cfg(any(feature = "decode", feature = "wasm"))]
+use image::GrayImage;
+#[cfg(feature = "encode")]
+use image::{Rgb, RgbImage};
+
+/// Cells across a marker: a 4x4 grid of bits inside a black border one cell wide.
+pub(crate) const MARKER_CELLS: u32 = 6;
+/// Modules added on each side of a frame: a marker, and a cell of white around it.
+#[cfg(feature = "encode")]
+pub(crate) const MARKER_MARGIN_MODULES: u32 = MARKER_CELLS + 1;
+
+/// Bits of ArUco `DICT_4X4_50` markers 0 to 3, row by row from the top left, 1 for white.
+/// Marker `i` goes in corner `i`, clockwise from the top left.
+const MARKER_BITS: [u16; 4] = [0xB532, 0x0F9A, 0x332D, 0x9946];
+
+/// Whether cell (`row`, `col`) of marker `id` is black.
+fn is_dark_cell(id: usize, row: u32, col: u32) -> bool {
+    let last = MARKER_CELLS - 1;
+    if row == 0 || col == 0 || row == last || col == last {
+        return true;
+    }
+    let bit = (row - 1) * 4 + (col - 1);
+    MARKER_BITS[id] >> (15 - bit) & 1 == 0
+}
+
+/// `image` with a margin of [`MARKER_MARGIN_MODULES`] modules of `module` pixels on each side,
+/// and a marker in each corner of it.
+#[cfg(feature = "encode")]
+pub(crate) fn add_markers(image: &RgbImage, module: u32) -> RgbImage {
+    let margin = MARKER_MARGIN_MODULES * module;
+    let (width, height) = (image.width() + 2 * margin, image.height() + 2 * margin);
+    let mut framed = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+    image::imageops::replace(&mut framed, image, i64::from(margin), i64::from(margin));
+
+    let (near, side) = (module, MARKER_CELLS * module);
+    let (right, bottom) = (width - near - side, height - near - side);
+    let corners = [(near, near), (right, near), (right, bottom), (near, bottom)];
+    for (id, (x, y)) in corners.into_iter().enumerate() {
+        for row in 0..MARKER_CELLS {
+            for col in 0..MARKER_CELLS {
+                if !is_dark_cell(id, row, col) {
+                    continue;
+                }
+                for dy in 0..module {
+                    for dx in 0..module {
+                        let (px, py) = (x + col * module + dx, y + row * module + dy);
+                        framed.put_pixel(px, py, Rgb([0, 0, 0]));
+                    }
+                }
+            }
+        }
+    }
+    framed
+}
+
+/// Frames are searched for markers at most this large, scaled down if needed.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const MAX_SEARCH_SIDE: u32 = 1600;
+/// Dark blobs smaller than this many pixels across cannot be a marker whose cells are read.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const MIN_MARKER_SIDE: f64 = 8.0;
+/// How much darker than its surroundings a pixel must be to count as dark.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const DARK_OFFSET: u32 = 8;
+/// Pixels per module of a rectified frame, in the order tried: rqrr misreads some codes at
+/// one scale and not at another.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const RECTIFIED_MODULE_PX: [f64; 3] = [6.0, 5.0, 8.0];
+/// Longest side of a rectified frame.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const MAX_RECTIFIED_SIDE: f64 = 3000.0;
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+type Point = (f64, f64);
+
+/// A marker found in a frame: its id and its corners clockwise from its own top left.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+#[derive(Debug, Clone, Copy)]
+struct Marker {
+    id: usize,
+    corners: [Point; 4],
+    /// Cells read differently from the marker's pattern; fewer is a better match.
+    misread: u32,
+}
+
+/// The frame in `gray` warped flat and upright by its corner markers and cropped to their
+/// outer corners, at each of [`RECTIFIED_MODULE_PX`] in turn; nothing if fewer than three
+/// markers are found.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub(crate) fn rectified(gray: &GrayImage) -> impl Iterator<Item = GrayImage> + '_ {
+    let frame = frame_corners(gray);
+    RECTIFIED_MODULE_PX
+        .into_iter()
+        .filter_map(move |module_px| warp(gray, frame.as_ref()?, module_px))
+}
+
+/// Where a frame found by its markers lies in a photo.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+struct FrameCorners {
+    /// Outer corners of the markers, clockwise from the frame's top left.
+    corners: [Point; 4],
+    /// Mean size of a marker cell, in pixels of the photo.
+    module: f64,
+}
+
+/// The frame in `gray` by its corner markers, or `None` if fewer than three are found.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn frame_corners(gray: &GrayImage) -> Option<FrameCorners> {
+    let (width, height) = gray.dimensions();
+    let factor = width.max(height).div_ceil(MAX_SEARCH_SIDE).max(1);
+    let markers = if factor > 1 {
+        let small = resize(gray, width / factor, height / factor, FilterType::Triangle);
+        let scale = |(x, y): Point| (x * factor as f64, y * factor as f64);
+        find_markers(&small)
+            .into_iter()
+            .map(|marker| Marker {
+                corners: marker.corners.map(scale),
+                ..marker
+            })
+            .collect()
+    } else {
+        find_markers(gray)
+    };
+
+    // The outer corner of each marker is a corner of the frame.
+    let mut best: [Option<Marker>; 4] = [None; 4];
+    for marker in markers {
+        let slot = &mut best[marker.id];
+        if slot.is_none_or(|kept| marker.misread < kept.misread) {
+            *slot = Some(marker);
+        }
+    }
+    let mut corners: [Option<Point>; 4] = [0, 1, 2, 3].map(|i| best[i].map(|m| m.corners[i]));
+    let found = corners.iter().flatten().count();
+    if found < 3 {
+        return None;
+    }
+    if found == 3 {
+        // A covered corner lies where it would on a parallelogram of the other three.
+        let missing = corners.iter().position(Option::is_none)?;
+        let (prev, next, opposite) = (
+            corners[(missing + 3) % 4]?,
+            corners[(missing + 1) % 4]?,
+            corners[(missing + 2) % 4]?,
+        );
+        corners[missing] = Some((prev.0 + next.0 - opposite.0, prev.1 + next.1 - opposite.1));
+    }
+    let corners = corners.map(|corner| corner.expect("all four corners are known"));
+    if !is_convex_clockwise(&corners) {
+        return None;
+    }
+
+    let module = best
+        .iter()
+        .flatten()
+        .map(|marker| mean_side(&marker.corners))
+        .sum::<f64>()
+        / best.iter().flatten().count() as f64
+        / MARKER_CELLS as f64;
+    Some(FrameCorners { corners, module })
+}
+
+/// The frame at `corners` of `gray` warped flat, at `module_px` pixels per module.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn warp(gray: &GrayImage, frame: &FrameCorners, module_px: f64) -> Option<GrayImage> {
+    let corners = frame.corners;
+    let across = (distance(corners[0], corners[1]) + distance(corners[3], corners[2])) / 2.0;
+    let down = (distance(corners[0], corners[3]) + distance(corners[1], corners[2])) / 2.0;
+    let scale = (module_px / frame.module).min(MAX_RECTIFIED_SIDE / across.max(down));
+    let (out_width, out_height) = ((across * scale).round(), (down * scale).round());
+    if out_width < 1.0 || out_height < 1.0 {
+        return None;
+    }
+    let to_frame = homography(
+        [
+            (0.0, 0.0),
+            (out_width, 0.0),
+            (out_width, out_height),
+            (0.0, out_height),
+        ],
+        corners,
+    )?;
+    // rqrr takes parts of a marker for a finder pattern now and then, so the markers, in
+    // the corners now, are left out; the quiet zone keeps the code well clear of them.
+    let marker = (MARKER_CELLS as f64 + 0.5) * module_px;
+    let in_marker = |x: f64, y: f64| {
+        (x < marker || x > out_width - marker) && (y < marker || y > out_height - marker)
+    };
+    Some(GrayImage::from_fn(
+        out_width as u32,
+        out_height as u32,
+        |x, y| {
+            let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+            if in_marker(x, y) {
+                return image::Luma([255]);
+            }
+            let (fx, fy) = project(&to_frame, (x, y));
+            image::Luma([sample(gray, fx - 0.5, fy - 0.5)])
+        },
+    ))
+}
+
+/// Markers among the dark blobs of `gray`.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn find_markers(gray: &GrayImage) -> Vec<Marker> {
+    let dark = threshold(gray);
+    let (width, height) = gray.dimensions();
+    let max_side = width.max(height) as f64 / 3.0;
+    blobs(&dark, width, height)
+        .into_iter()
+        .filter_map(|pixels| {
+            let corners = quad_corners(&pixels)?;
+            let area = quad_area(&corners);
+            let sides = [0, 1, 2, 3].map(|i| distance(corners[i], corners[(i + 1) % 4]));
+            let (shortest, longest) = sides
+                .iter()
+                .fold((f64::MAX, 0.0f64), |(lo, hi), &s| (lo.min(s), hi.max(s)));
+            // The black border alone covers 20 of the 36 cells.
+            let fill = pixels.len() as f64 / area.max(1.0);
+            (shortest >= MIN_MARKER_SIDE
+                && longest <= max_side
+                && shortest >= 0.2 * longest
+                && (0.45..=1.4).contains(&fill))
+            .then_some(corners)
+        })
+        .filter_map(|corners| read_marker(gray, corners))
+        .collect()
+}
+
+/// Dark pixels of `gray`: those darker than the mean of a window around them.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn threshold(gray: &GrayImage) -> Vec<bool> {
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let mut integral = vec![0u64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row = 0u64;
+        for x in 0..width {
+            row += u64::from(gray.get_pixel(x as u32, y as u32).0[0]);
+            integral[(y + 1) * (width + 1) + x + 1] = integral[y * (width + 1) + x + 1] + row;
+        }
+    }
+    let radius = (width.max(height) / 16).max(8);
+    let mut dark = vec![false; width * height];
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            let sum = integral[y1 * (width + 1) + x1] + integral[y0 * (width + 1) + x0]
+                - integral[y0 * (width + 1) + x1]
+                - integral[y1 * (width + 1) + x0];
+            let mean = sum / ((x1 - x0) * (y1 - y0)) as u64;
+            let pixel = u64::from(gray.get_pixel(x as u32, y as u32).0[0]);
+            dark[y * width + x] = pixel + u64::from(DARK_OFFSET) < mean;
+        }
+    }
+    dark
+}
+
+/// The pixels of each 4-connected blob of `dark` big enough to be a marker.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn blobs(dark: &[bool], width: u32, height: u32) -> Vec<Vec<Point>> {
+    let (width, height) = (width as usize, height as usize);
+    let min_pixels = (MIN_MARKER_SIDE * MIN_MARKER_SIDE * 0.45) as usize;
+    let mut seen = vec![false; dark.len()];
+    let mut found = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..dark.len() {
+        if !dark[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        stack.push(start);
+        let mut pixels = Vec::new();
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % width, i / width);
+            pixels.push((x as f64, y as f64));
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then(|| i + width),
+            ];
+            for j in neighbours.into_iter().flatten() {
+                if dark[j] && !seen[j] {
+                    seen[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        if pixels.len() >= min_pixels {
+            found.push(pixels);
+        }
+    }
+    found
+}
+
+/// Corners of the quadrilateral a blob fills, clockwise: the pixel farthest from its centre,
+/// the one farthest from that, and the farthest on either side of the diagonal between them.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn quad_corners(pixels: &[Point]) -> Option<[Point; 4]> {
+    let n = pixels.len() as f64;
+    let centre = pixels
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), &(x, y)| (sx + x / n, sy + y / n));
+    let farthest_from = |from: Point| {
+        pixels
+            .iter()
+            .copied()
+            .max_by(|&a, &b| distance(from, a).total_cmp(&distance(from, b)))
+    };
+    let first = farthest_from(centre)?;
+    let third = farthest_from(first)?;
+    let side =
+        |p: Point| (third.0 - first.0) * (p.1 - first.1) - (third.1 - first.1) * (p.0 - first.0);
+    let second = pixels
+        .iter()
+        .copied()
+        .max_by(|&a, &b| side(a).total_cmp(&side(b)))?;
+    let fourth = pixels
+        .iter()
+        .copied()
+        .min_by(|&a, &b| side(a).total_cmp(&side(b)))?;
+    let diagonal = distance(first, third);
+    if side(second) < 0.3 * diagonal * diagonal / 2.0
+        || -side(fourth) < 0.3 * diagonal * diagonal / 2.0
+    {
+        return None;
+    }
+    // Extreme pixels are the centres of the corner pixels; the corners are half a pixel out.
+    let corners = [first, second, third, fourth].map(|(x, y)| {
+        let (dx, dy) = (x - centre.0, y - centre.1);
+        let len = (dx * dx + dy * dy).sqrt().max(1.0);
+        (x + 0.5 + 0.7 * dx / len, y + 0.5 + 0.7 * dy / len)
+    });
+    Some(if is_convex_clockwise(&corners) {
+        corners
+    } else {
+        [corners[0], corners[3], corners[2], corners[1]]
+    })
+}
+
+/// The marker whose outline is `corners`, if its cells read as one: tries each of its corners
+/// as its top left, and keeps the id and turn that match best.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn read_marker(gray: &GrayImage, corners: [Point; 4]) -> Option<Marker> {
+    let cells = MARKER_CELLS as f64;
+    let grid = [(0.0, 0.0), (cells, 0.0), (cells, cells), (0.0, cells)];
+    (0..4)
+        .filter_map(|turn| {
+            let turned = [0, 1, 2, 3].map(|i| corners[(i + turn) % 4]);
+            let to_frame = homography(grid, turned)?;
+            let levels: Vec<f64> = (0..MARKER_CELLS * MARKER_CELLS)
+                .map(|cell| {
+                    let (row, col) = (cell / MARKER_CELLS, cell % MARKER_CELLS);
+                    cell_level(gray, &to_frame, row, col)
+                })
+                .collect();
+            let cut = otsu(&levels)?;
+            (0..MARKER_BITS.len())
+                .map(|id| {
+                    let misread = (0..MARKER_CELLS * MARKER_CELLS)
+                        .filter(|&cell| {
+                            let (row, col) = (cell / MARKER_CELLS, cell % MARKER_CELLS);
+                            (levels[cell as usize] < cut) != is_dark_cell(id, row, col)
+                        })
+                        .count() as u32;
+                    Marker {
+                        id,
+                        corners: turned,
+                        misread,
+                    }
+                })
+                .min_by_key(|marker| marker.misread)
+        })
+        .min_by_key(|marker| marker.misread)
+        .filter(|marker| marker.misread <= 1)
+}
+
+/// Mean brightness of the middle of a marker cell.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn cell_level(gray: &GrayImage, to_frame: &[f64; 9], row: u32, col: u32) -> f64 {
+    const OFFSETS: [(f64, f64); 5] = [(0.5, 0.5), (0.3, 0.3), (0.7, 0.3), (0.3, 0.7), (0.7, 0.7)];
+    OFFSETS
+        .iter()
+        .map(|&(dx, dy)| {
+            let (x, y) = project(to_frame, (col as f64 + dx, row as f64 + dy));
+            f64::from(sample(gray, x - 0.5, y - 0.5))
+        })
+        .sum::<f64>()
+        / OFFSETS.len() as f64
+}
+
+/// Level that best splits `levels` into dark and light, or `None` if they hardly differ.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn otsu(levels: &[f64]) -> Option<f64> {
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let (darkest, lightest) = (*sorted.first()?, *sorted.last()?);
+    if lightest - darkest < 30.0 {
+        return None;
+    }
+    let total: f64 = sorted.iter().sum();
+    let mut below = 0.0;
+    let mut best = (0.0, (darkest + lightest) / 2.0);
+    for i in 1..sorted.len() {
+        below += sorted[i - 1];
+        let (n0, n1) = (i as f64, (sorted.len() - i) as f64);
+        let (mean0, mean1) = (below / n0, (total - below) / n1);
+        let between = n0 * n1 * (mean1 - mean0) * (mean1 - mean0);
+        if between > best.0 {
+            best = (between, (sorted[i - 1] + sorted[i]) / 2.0);
+        }
+    }
+    Some(best.1)
+}
+
+/// Brightness of `gray` at (`x`, `y`), between pixel centres; white outside it.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn sample(gray: &GrayImage, x: f64, y: f64) -> u8 {
+    let (width, height) = gray.dimensions();
+    if x < 0.0 || y < 0.0 || x > (width - 1) as f64 || y > (height - 1) as f64 {
+        return 255;
+    }
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let at = |x, y| f64::from(gray.get_pixel(x, y).0[0]);
+    let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+    let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+    (top * (1.0 - fy) + bottom * fy).round() as u8
+}
+
+/// The projective transform taking each of `from` to the matching `to`, as a row-major 3x3
+/// matrix, or `None` if three of the points are in line.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn homography(from: [Point; 4], to: [Point; 4]) -> Option<[f64; 9]> {
+    // Eight equations in the eight unknowns h0..h7, with h8 = 1.
+    let mut rows = [[0.0; 9]; 8];
+    for (i, (&(x, y), &(u, v))) in from.iter().zip(&to).enumerate() {
+        rows[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+        rows[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+    }
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&a, &b| rows[a][col].abs().total_cmp(&rows[b][col].abs()))?;
+        if rows[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        rows.swap(col, pivot);
+        let pivot_row = rows[col];
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot_row[col];
+                for (cell, pivot_cell) in row.iter_mut().zip(&pivot_row).skip(col) {
+                    *cell -= factor * pivot_cell;
+                }
+            }
+        }
+    }
+    let h: Vec<f64> = (0..8).map(|i| rows[i][8] / rows[i][i]).collect();
+    Some([h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0])
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn project(h: &[f64; 9], (x, y): Point) -> Point {
+    let w = h[6] * x + h[7] * y + h[8];
+    (
+        (h[0] * x + h[1] * y + h[2]) / w,
+        (h[3] * x + h[4] * y + h[5]) / w,
+    )
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn mean_side(corners: &[Point; 4]) -> f64 {
+    (0..4)
+        .map(|i| distance(corners[i], corners[(i + 1) % 4]))
+        .sum::<f64>()
+        / 4.0
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn quad_area(corners: &[Point; 4]) -> f64 {
+    (0..4)
+        .map(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>()
+        .abs()
+        / 2.0
+}
+
+/// Whether `corners` turn the same way, clockwise on screen, at every corner.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn is_convex_clockwise(corners: &[Point; 4]) -> bool {
+    (0..4).all(|i| {
+        let (a, b, c) = (corners[i], corners[(i + 1) % 4], corners[(i + 2) % 4]);
+        (b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0) > 0.0
+    })
+}
+
+#[cfg(all(test, feature = "encode", feature = "decode"))]
+mod tests {
+    use super::*;
+    use crate::qr::{decode_qr_from_gray, generate_qr_image};
+    use image::imageops::rotate90;
+
+    #[test]
+    fn test_markers_are_distinct() {
+        // No marker reads as another, or as itself turned, with fewer than 3 cells wrong.
+        let turned = |id: usize, turn: u32| {
+            (0..MARKER_CELLS * MARKER_CELLS).map(move |cell| {
+                let (mut row, mut col) = (cell / MARKER_CELLS, cell % MARKER_CELLS);
+                for _ in 0..turn {
+                    (row, col) = (col, MARKER_CELLS - 1 - row);
+                }
+                is_dark_cell(id, row, col)
+            })
+        };
+        for a in 0..4 {
+            for b in 0..4 {
+                for turn in 0..4 {
+                    if a == b && turn == 0 {
+                        continue;
+                    }
+                    let differ = turned(a, 0)
+                        .zip(turned(b, turn))
+                        .filter(|(x, y)| x != y)
+                        .count();
+                    assert!(differ >= 3, "markers {} and {} turned {}", a, b, turn);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rectify_photo_at_an_angle() {
+        let text = "FOUNTAIN MARKER TEST 0123456789";
+        let (code, _) = generate_qr_image(text.as_bytes(), None, 4).unwrap();
+        let frame = image::DynamicImage::ImageRgb8(add_markers(&code, 4)).to_luma8();
+        let (w, h) = (frame.width() as f64, frame.height() as f64);
+
+        // The frame seen from below and to the side, on a grey table, turned a quarter.
+        let seen = [
+            (260.0, 140.0),
+            (900.0, 230.0),
+            (820.0, 760.0),
+            (180.0, 880.0),
+        ];
+        let to_frame = homography(seen, [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)]).unwrap();
+        let photo = GrayImage::from_fn(1100, 1000, |x, y| {
+            let (fx, fy) = project(&to_frame, (x as f64 + 0.5, y as f64 + 0.5));
+            if fx < 0.0 || fy < 0.0 || fx >= w || fy >= h {
+                image::Luma([90])
+            } else {
+                image::Luma([sample(&frame, fx - 0.5, fy - 0.5)])
+            }
+        });
+        let photo = rotate90(&photo);
+
+        let flat = rectified(&photo).next().expect("markers not found");
+        assert_eq!(decode_qr_from_gray(&flat).unwrap(), text.as_bytes());
+        assert_eq!(rectified(&frame).count(), RECTIFIED_MODULE_PX.len());
+        let bare = image::DynamicImage::ImageRgb8(code).to_luma8();
+        assert_eq!(rectified(&bare).count(), 0);
+    }
+}
@@ -0,0 +1,234 @@
+//! Showing QR frames on a Linux framebuffer device such as `/dev/fb0`, for a headless board
+//! with a small screen and no X server or terminal emulator.
+//!
+//! The screen geometry is read from sysfs, and each frame is drawn into a buffer the size of
+//! the screen and written to the device in one go. Codes are black and white only, which are
+//! all-zero and all-one pixels in every packed RGB layout, so the channel order does not
+//! matter. As in the window, frames are due at fixed offsets from the start.
+
+use image::RgbImage;
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::encode::QrFrames;
+use crate::error::{FountainError, Result};
+
+/// Longest sleep between checks for Ctrl+C.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// An open framebuffer device. What the screen showed before is put back when it is dropped.
+struct Framebuffer {
+    file: File,
+    width: u32,
+    height: u32,
+    /// Bytes per row, which may be more than the visible pixels take.
+    stride: usize,
+    bytes_per_pixel: usize,
+    saved: Vec<u8>,
+}
+
+impl Framebuffer {
+    fn open(device: &Path) -> Result<Self> {
+        let name = device
+            .file_name()
+            .ok_or_else(|| framebuffer_error(device, "not a device path"))?;
+        let sysfs = Path::new("/sys/class/graphics").join(name);
+        let attribute = |attribute: &str| -> Result<String> {
+            fs::read_to_string(sysfs.join(attribute))
+                .map(|value| value.trim().to_string())
+                .map_err(|e| framebuffer_error(device, &format!("reading {}: {}", attribute, e)))
+        };
+
+        let bits_per_pixel: usize = parse(device, &attribute("bits_per_pixel")?)?;
+        let stride: usize = parse(device, &attribute("stride")?)?;
+        let virtual_size = attribute("virtual_size")?;
+        // The visible mode, e.g. `U:800x480p-0`; the virtual size may be taller for panning.
+        let mode = attribute("modes").ok().and_then(|modes| {
+            let (_, size) = modes.lines().next()?.split_once(':')?;
+            let (width, rest) = size.split_once('x')?;
+            let height: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            Some((width.parse().ok()?, height.parse().ok()?))
+        });
+        let (width, height) = match mode {
+            Some(size) => size,
+            None => {
+                let (width, height) = virtual_size
+                    .split_once(',')
+                    .ok_or_else(|| framebuffer_error(device, "unreadable virtual_size"))?;
+                (parse(device, width)?, parse(device, height)?)
+            }
+        };
+        if !matches!(bits_per_pixel, 16 | 24 | 32) {
+            return Err(framebuffer_error(
+                device,
+                &format!("{} bits per pixel is not supported", bits_per_pixel),
+            ));
+        }
+
+        let file = OpenOptions::new().read(true).write(true).open(device)?;
+        let mut saved = vec![0; stride * height as usize];
+        file.read_exact_at(&mut saved, 0)?;
+        info!(
+            "Framebuffer {}: {}x{}, {} bits per pixel",
+            device.display(),
+            width,
+            height,
+            bits_per_pixel
+        );
+        Ok(Self {
+            file,
+            width,
+            height,
+            stride,
+            bytes_per_pixel: bits_per_pixel / 8,
+            saved,
+        })
+    }
+
+    /// Draws a frame centred on a white screen at `scale` pixels per module and shows it.
+    fn show(&self, modules: &Modules, scale: u32, buffer: &mut [u8]) -> Result<()> {
+        let side = modules.size * scale;
+        let x0 = ((self.width - side) / 2) as usize;
+        let y0 = ((self.height - side) / 2) as usize;
+        let (scale, bpp) = (scale as usize, self.bytes_per_pixel);
+
+        buffer.fill(0xff);
+        for &(row, column) in &modules.dark {
+            let x = (x0 + column as usize * scale) * bpp;
+            for y in y0 + row as usize * scale..y0 + (row as usize + 1) * scale {
+                let start = y * self.stride + x;
+                buffer[start..start + scale * bpp].fill(0);
+            }
+        }
+        self.file.write_all_at(buffer, 0)?;
+        Ok(())
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        let _ = self.file.write_all_at(&self.saved, 0);
+    }
+}
+
+/// Dark modules of one frame, as (row, column).
+struct Modules {
+    size: u32,
+    dark: Vec<(u16, u16)>,
+}
+
+impl Modules {
+    /// Reads the modules of a frame whose side is `size` modules.
+    fn from_image(img: &RgbImage, size: u32) -> Self {
+        let scale = img.width() / size;
+        let mut dark = Vec::new();
+        for row in 0..size {
+            for column in 0..size {
+                let pixel = img.get_pixel(column * scale + scale / 2, row * scale + scale / 2);
+                if pixel[0] < 128 {
+                    dark.push((row as u16, column as u16));
+                }
+            }
+        }
+        Self { size, dark }
+    }
+}
+
+fn framebuffer_error(device: &Path, message: &str) -> FountainError {
+    FountainError::Framebuffer(format!("{}: {}", device.display(), message))
+}
+
+fn parse<T: std::str::FromStr>(device: &Path, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| framebuffer_error(device, &format!("unreadable value {:?}", value)))
+}
+
+/// Shows the frames on the framebuffer `device`, one every `interval_ms`, until Ctrl+C, at the
+/// largest module size the screen fits. The frames are read up front, so preparing them never
+/// delays one; the previous screen contents are restored on exit.
+pub fn display_qr_framebuffer(mut frames: QrFrames, device: &Path, interval_ms: u64) -> Result<()> {
+    // The screen picks the module size, so read the codes at their smallest, bare.
+    frames.pixel_scale = 1;
+    frames.caption = None;
+    frames.markers = false;
+    let (size, _) = frames
+        .frame_modules()
+        .expect("RaptorQ always produces at least one packet");
+    let framebuffer = Framebuffer::open(device)?;
+    let scale = framebuffer.width.min(framebuffer.height) / size;
+    if scale == 0 {
+        return Err(framebuffer_error(
+            device,
+            &format!(
+                "QR codes of {} modules do not fit the {}x{} screen; use a smaller chunk size",
+                size, framebuffer.width, framebuffer.height
+            ),
+        ));
+    }
+
+    let modules = frames
+        .by_ref()
+        .map(|img| img.map(|img| Modules::from_image(&img, size)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    let mut buffer = vec![0xff; framebuffer.saved.len()];
+    let interval = Duration::from_millis(interval_ms.max(1));
+    let started = Instant::now();
+    for shown in 1.. {
+        framebuffer.show(&modules[(shown - 1) % modules.len()], scale, &mut buffer)?;
+
+        let due = started + interval * shown as u32;
+        if Instant::now() > due {
+            debug!("Frame {} shown late", shown);
+        }
+        while Instant::now() < due {
+            if !running.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL.min(due.saturating_duration_since(Instant::now())));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_modules_from_image() {
+        // Two modules per side at 2 pixels each; the top left and bottom right are dark.
+        let mut img = RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]));
+        for (x, y) in [
+            (0, 0),
+            (1, 0),
+            (0, 1),
+            (1, 1),
+            (2, 2),
+            (3, 2),
+            (2, 3),
+            (3, 3),
+        ] {
+            img.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+        let modules = Modules::from_image(&img, 2);
+        assert_eq!(modules.size, 2);
+        assert_eq!(modules.dark, [(0, 0), (1, 1)]);
+    }
+}
@@ -0,0 +1,89 @@
+//! HEIF photos (`.heic`, `.heif`), the format iPhones save camera shots in by default, read
+//! through libheif so photos of the codes decode without converting them first. Needs a build
+//! with the `heif` feature and libheif installed.
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, RgbImage};
+use libheif_rs::{ColorSpace, HeifContext, HeifError, LibHeif, RgbChroma};
+use std::path::Path;
+
+use crate::error::{FountainError, Result};
+
+/// Extensions of HEIF photos.
+pub const HEIF_EXTENSIONS: &[&str] = &["heic", "heif"];
+
+/// Brands of the `ftyp` box that open a HEIF file: HEVC stills and sequences, and the
+/// generic image and sequence brands.
+const HEIF_BRANDS: &[&[u8; 4]] = &[
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1",
+];
+
+/// Whether `path` has a HEIF extension.
+pub(crate) fn is_heif_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        HEIF_EXTENSIONS
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    })
+}
+
+/// Whether `bytes` start like a HEIF file, with an `ftyp` box of a HEIF brand.
+pub(crate) fn is_heif(bytes: &[u8]) -> bool {
+    bytes.get(4..8) == Some(b"ftyp")
+        && bytes
+            .get(8..12)
+            .is_some_and(|brand| HEIF_BRANDS.iter().any(|known| &known[..] == brand))
+}
+
+/// Decodes the primary image of a HEIF file. libheif applies its rotation and mirroring, so
+/// the image comes out the right way up, as [`crate::qr::open_image`] does for JPEGs.
+pub fn decode_heif(bytes: &[u8]) -> Result<DynamicImage> {
+    let context = HeifContext::read_from_bytes(bytes).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(heif_error)?;
+    let Some(plane) = image.planes().interleaved else {
+        return Err(decoding_error(
+            "libheif returned no interleaved RGB plane".into(),
+        ));
+    };
+
+    let row_bytes = plane.width as usize * 3;
+    let mut rgb = Vec::with_capacity(row_bytes * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        rgb.extend_from_slice(&row[..row_bytes]);
+    }
+    let rgb = RgbImage::from_raw(plane.width, plane.height, rgb)
+        .ok_or_else(|| decoding_error("the RGB plane is shorter than the image".into()))?;
+    Ok(DynamicImage::ImageRgb8(rgb))
+}
+
+fn heif_error(err: HeifError) -> FountainError {
+    decoding_error(Box::new(err))
+}
+
+fn decoding_error(err: Box<dyn std::error::Error + Send + Sync>) -> FountainError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Name("HEIF".into()),
+        err,
+    ))
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_heif() {
+        let mut header = vec![0, 0, 0, 24];
+        header.extend_from_slice(b"ftypheic");
+        assert!(is_heif(&header));
+        header[8..12].copy_from_slice(b"avif");
+        assert!(!is_heif(&header));
+        assert!(!is_heif(b"\x89PNG\r\n\x1a\n"));
+        assert!(is_heif_path(Path::new("IMG_0001.HEIC")));
+        assert!(!is_heif_path(Path::new("frame.png")));
+    }
+}
@@ -1,31 +1,194 @@
+#[cfg(all(feature = "encode", feature = "decode"))]
+mod append;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub mod archive;
+
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub mod bench;
+
+#[cfg(feature = "async")]
+mod async_io;
+
 pub mod chunk;
 
+#[cfg(any(feature = "encode", feature = "wasm"))]
+mod chunker;
+
+#[cfg(feature = "decode")]
+pub mod channel;
+
+#[cfg(feature = "cli")]
+pub mod cli;
+
+#[cfg(feature = "cli")]
+pub mod config;
+
+#[cfg(feature = "encode")]
+mod cover;
+
 #[cfg(feature = "decode")]
 pub mod decode;
 
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub mod delta;
+
+#[cfg(feature = "encode")]
+pub mod dictionary;
+
+#[cfg(feature = "decode")]
+mod diff;
+
+#[cfg(feature = "encode")]
+pub mod eink;
+
 #[cfg(feature = "encode")]
 pub mod encode;
 
+#[cfg(any(feature = "encode", feature = "decode", feature = "wasm"))]
+mod fiducial;
+
+pub mod error;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(all(feature = "encode", target_os = "linux"))]
+pub mod framebuffer;
+
+#[cfg(feature = "heif")]
+pub mod heif;
+
+#[cfg(feature = "color")]
+pub mod matrix;
+
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+mod locate;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+mod millis;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+mod parallel;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub mod pipe;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+mod progress;
+
 pub mod qr;
 
+#[cfg(feature = "decode")]
+pub mod receive;
+
+#[cfg(feature = "decode")]
+mod salvage;
+
+#[cfg(feature = "decode")]
+mod screen;
+
+#[cfg(feature = "decode")]
+pub mod session;
+
+#[cfg(feature = "encode")]
+pub mod serve;
+
+#[cfg(feature = "encode")]
+pub mod sink;
+
+#[cfg(feature = "decode")]
+pub mod source;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub mod split;
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+mod stream;
+
 #[cfg(feature = "encode")]
 pub mod terminal;
 
+#[cfg(all(feature = "encode", feature = "decode"))]
+mod transcode;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub mod txqr;
+
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
+#[cfg(feature = "window")]
+pub mod window;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
 pub use chunk::{
-    Chunk, ChunkHeader, DEFAULT_PAYLOAD_SIZE, MAX_PAYLOAD_SIZE,
+    Chunk, ChunkHeader, Manifest, ManifestEntry, DEFAULT_PAYLOAD_SIZE, MAX_PAYLOAD_SIZE,
+    MIN_PAYLOAD_SIZE,
+};
+
+pub use error::{FountainError, Result};
+
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub use bench::BenchResult;
+
+#[cfg(feature = "decode")]
+pub use decode::{
+    decode_frames, decode_from_gif, decode_from_images, DecodeOptions, DecodeResult, Decoder,
+    FrameOutcome, PacketCallback, ScannedFrame, ScannedFrameCallback,
 };
 
 #[cfg(feature = "decode")]
-pub use decode::{decode_from_gif, decode_from_images, DecodeResult};
+pub use channel::ChannelStats;
+
+#[cfg(feature = "decode")]
+pub use diff::{CaptureEstimate, InputDiff};
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub use stream::{DecodedFile, FeedResult, StreamDecoder};
 
 #[cfg(feature = "encode")]
 pub use encode::{
-    encode_file_for_terminal, encode_file_to_gif,
-    encode_file_to_images, EncodeResult, TerminalQrData,
+    encode_bytes, encode_file_for_terminal, encode_file_to_gif, encode_file_to_images,
+    estimate_encoding, gif_interval_ms, gif_timing_warnings, payload_size_for_frame_budget, Canvas,
+    EncodeEstimate, EncodeOptions, EncodeResult, Encoder, EndlessFrames, FrameCallback,
+    PacketEncoding, PacketOrder, PrintSize, QrFrames, TerminalFrames, TerminalQrData,
+    DEFAULT_PRINT_DPI, MAX_FILES, MIN_GIF_INTERVAL_MS, RECEIPT_EXTENSION, TEXT_FILENAME,
+};
+
+#[cfg(feature = "encode")]
+pub use eink::EinkDisplay;
+
+#[cfg(feature = "color")]
+pub use matrix::{decode_color_matrix, ColorMatrix};
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub use progress::{CancelFlag, ProgressCallback};
+
+#[cfg(feature = "decode")]
+pub use receive::UploadServer;
+
+#[cfg(feature = "encode")]
+pub use serve::FrameServer;
+
+#[cfg(feature = "encode")]
+pub use sink::{GifSink, ImageDirSink, OutputSink, SinkFrame, SinkInfo, TerminalSink};
+
+#[cfg(feature = "decode")]
+pub use source::{
+    DirSource, FrameSource, GifSource, ImageSource, InputFrame, PacketLinesSource, SourceFrame,
 };
 
 #[cfg(feature = "encode")]
-pub use terminal::{display_qr_carousel, display_qr_once};
+pub use terminal::{display_pipe, display_qr_carousel, display_qr_endless, display_qr_once};
+
+#[cfg(all(feature = "encode", target_os = "linux"))]
+pub use framebuffer::display_qr_framebuffer;
+
+#[cfg(feature = "window")]
+pub use window::display_qr_window;
@@ -0,0 +1,211 @@
+//! A quick search for the finder patterns of QR codes, so a large photo in which the codes take
+//! up a small part can be cropped to them before rqrr reads it.
+//!
+//! Rows of the frame are scanned for the 1:1:3:1:1 dark/light runs that cross the middle of a
+//! finder pattern, each hit is checked along its column, and hits at the same spot are merged.
+//! The codes lie within a few modules of the finders found.
+
+use image::GrayImage;
+
+/// Rows skipped between the rows scanned; a finder pattern is at least 7 pixels tall.
+const ROW_STEP: u32 = 2;
+/// Smallest module size looked for, in pixels. Noise around the threshold makes many tiny
+/// matches, and rqrr cannot read codes this fine at full size anyway.
+const MIN_MODULE: f32 = 2.0;
+/// Rows a finder pattern must be hit on before it counts, so stray matches in texture or in
+/// a code's data are dropped.
+const MIN_HITS: usize = 2;
+/// Modules kept around the finders: 3.5 from a finder's centre to the code's edge, then the
+/// quiet zone.
+const MARGIN_MODULES: f32 = 8.0;
+/// Share of the frame above which a crop is not worth it.
+const MAX_CROP_SHARE: f32 = 0.6;
+
+/// Part of a frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A finder pattern, merged from the hits on its rows.
+#[derive(Debug, Clone, Copy)]
+struct Finder {
+    x: f32,
+    y: f32,
+    module: f32,
+    hits: usize,
+}
+
+/// The part of `gray` that holds its QR codes: the finder patterns found and their margins.
+/// `None` if fewer than three finders are found, or they spread over most of the frame.
+pub(crate) fn locate_codes(gray: &GrayImage) -> Option<Region> {
+    let threshold = threshold(gray);
+    let dark = |x: u32, y: u32| gray.get_pixel(x, y).0[0] < threshold;
+
+    let mut finders: Vec<Finder> = Vec::new();
+    for y in (0..gray.height()).step_by(ROW_STEP as usize) {
+        for (x, module) in row_hits(gray.width(), |x| dark(x, y)) {
+            let Some((y, module)) = column_hit(gray.height(), y, module, |y| dark(x, y)) else {
+                continue;
+            };
+            add_hit(&mut finders, x as f32, y, module);
+        }
+    }
+    finders.retain(|finder| finder.hits >= MIN_HITS);
+    if finders.len() < 3 {
+        return None;
+    }
+
+    let margin = |finder: &Finder| finder.module * MARGIN_MODULES;
+    let left = finders
+        .iter()
+        .map(|f| f.x - margin(f))
+        .fold(f32::MAX, f32::min);
+    let top = finders
+        .iter()
+        .map(|f| f.y - margin(f))
+        .fold(f32::MAX, f32::min);
+    let right = finders.iter().map(|f| f.x + margin(f)).fold(0.0, f32::max);
+    let bottom = finders.iter().map(|f| f.y + margin(f)).fold(0.0, f32::max);
+    let x = left.max(0.0) as u32;
+    let y = top.max(0.0) as u32;
+    let width = (right.min(gray.width() as f32) as u32).saturating_sub(x);
+    let height = (bottom.min(gray.height() as f32) as u32).saturating_sub(y);
+
+    let share = (width * height) as f32 / (gray.width() * gray.height()) as f32;
+    (width > 0 && height > 0 && share <= MAX_CROP_SHARE).then_some(Region {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Brightness halfway between the dark and light ends of the frame, from a sample of pixels.
+fn threshold(gray: &GrayImage) -> u8 {
+    let mut levels = [0u32; 256];
+    for pixel in gray.pixels().step_by(7) {
+        levels[pixel.0[0] as usize] += 1;
+    }
+    let total: u32 = levels.iter().sum();
+    let level_at = |rank: u32| {
+        let mut seen = 0;
+        levels
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen > rank
+            })
+            .unwrap_or(255) as u32
+    };
+    ((level_at(total / 20) + level_at(total - total / 20)) / 2) as u8
+}
+
+/// Whether five run lengths are in the 1:1:3:1:1 proportions of a finder pattern. Returns
+/// the module size.
+fn finder_runs(runs: &[u32; 5]) -> Option<f32> {
+    let module = runs.iter().sum::<u32>() as f32 / 7.0;
+    if module < MIN_MODULE {
+        return None;
+    }
+    let tolerance = module / 2.0;
+    let fits = runs
+        .iter()
+        .zip([1.0, 1.0, 3.0, 1.0, 1.0])
+        .all(|(&run, modules)| (run as f32 - module * modules).abs() < tolerance * modules);
+    fits.then_some(module)
+}
+
+/// Centres and module sizes of the finder patterns crossed by a row of `width` pixels.
+fn row_hits(width: u32, dark: impl Fn(u32) -> bool) -> Vec<(u32, f32)> {
+    let mut hits = Vec::new();
+    // Lengths of the last five runs, the newest last, and whether the newest is dark.
+    let mut runs = [0u32; 5];
+    let mut current_dark = false;
+    for x in 0..=width {
+        let is_dark = x < width && dark(x);
+        if x < width && is_dark == current_dark {
+            runs[4] += 1;
+            continue;
+        }
+        // A run ended at `x`; a dark one may close a finder pattern.
+        if current_dark {
+            if let Some(module) = finder_runs(&runs) {
+                let centre = x - runs[4] - runs[3] - runs[2] / 2 - 1;
+                hits.push((centre, module));
+            }
+        }
+        runs.rotate_left(1);
+        runs[4] = 1;
+        current_dark = is_dark;
+    }
+    hits
+}
+
+/// Checks a row hit along its column, whose pixels `dark` tells: the runs through row `y`
+/// must also be a finder pattern of about the same size. Returns the centre row and module
+/// size.
+fn column_hit(height: u32, y: u32, module: f32, dark: impl Fn(u32) -> bool) -> Option<(f32, f32)> {
+    let limit = (module * 5.0) as u32 + 2;
+    // The run containing `y` and the two on each side of it.
+    let run_up = |from: u32, want_dark: bool| {
+        (0..=from.min(limit))
+            .take_while(|&i| dark(from - i) == want_dark)
+            .count() as u32
+    };
+    let run_down = |from: u32, want_dark: bool| {
+        (from..height.min(from + limit))
+            .take_while(|&i| dark(i) == want_dark)
+            .count() as u32
+    };
+    if !dark(y) {
+        return None;
+    }
+    let above = run_up(y, true);
+    let below = run_down(y, true);
+    let top = y + 1 - above;
+    let bottom = y + below;
+    let light_above = run_up(top.checked_sub(1)?, false);
+    let dark_above = run_up(top.checked_sub(1 + light_above)?, true);
+    let light_below = run_down(bottom, false);
+    let dark_below = run_down(bottom + light_below, true);
+
+    let runs = [
+        dark_above,
+        light_above,
+        above + below - 1,
+        light_below,
+        dark_below,
+    ];
+    let vertical = finder_runs(&runs)?;
+    if (vertical - module).abs() > module / 2.0 {
+        return None;
+    }
+    let centre = top as f32 + (above + below - 1) as f32 / 2.0;
+    Some((centre, (module + vertical) / 2.0))
+}
+
+/// Merges a hit into the finder at the same spot, or starts a new one.
+fn add_hit(finders: &mut Vec<Finder>, x: f32, y: f32, module: f32) {
+    let near = finders.iter_mut().find(|finder| {
+        (finder.x - x).abs() < finder.module * 2.0 && (finder.y - y).abs() < finder.module * 2.0
+    });
+    match near {
+        Some(finder) => {
+            let hits = finder.hits as f32;
+            finder.x = (finder.x * hits + x) / (hits + 1.0);
+            finder.y = (finder.y * hits + y) / (hits + 1.0);
+            finder.module = (finder.module * hits + module) / (hits + 1.0);
+            finder.hits += 1;
+        }
+        None => finders.push(Finder {
+            x,
+            y,
+            module,
+            hits: 1,
+        }),
+    }
+}
@@ -0,0 +1,423 @@
+//! Colour matrix symbology, in the spirit of libcimbar, for screen-to-camera transfers where
+//! both ends run this crate. Each cell holds 3 bits as one of eight colours, so a frame carries
+//! several times what a QR code does; QR codes stay the default, as any reader can scan them.
+//!
+//! A symbol is a square of `cells` cells on a light margin. Its top row and left column
+//! alternate dark and light cells, from which a reader counts the cells and learns the dark and
+//! light levels of each colour channel; its bottom row and right column are dark. Every other
+//! cell holds data: a 2-byte length, an Adler-32 checksum and the payload, 3 bits per cell.
+//! There is no error correction within a symbol; one that fails its checksum is dropped and
+//! the fountain code makes up for it.
+
+use image::{Rgb, RgbImage};
+
+use crate::error::{FountainError, Result};
+
+/// Cells per side used when none is given.
+pub const DEFAULT_MATRIX_CELLS: u32 = 129;
+/// Fewest cells per side a symbol may have.
+pub const MIN_MATRIX_CELLS: u32 = 21;
+/// Most cells per side a symbol may have.
+pub const MAX_MATRIX_CELLS: u32 = 255;
+/// Cells of light margin around a symbol.
+pub const MATRIX_QUIET_ZONE: u32 = 2;
+/// Length and checksum in front of the payload.
+const MATRIX_HEADER_SIZE: usize = 6;
+
+const DARK: Rgb<u8> = Rgb([0, 0, 0]);
+const LIGHT: Rgb<u8> = Rgb([255, 255, 255]);
+
+/// Layout of a colour matrix symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorMatrix {
+    cells: u32,
+}
+
+impl ColorMatrix {
+    /// A symbol of `cells` cells per side: an odd number from [`MIN_MATRIX_CELLS`] to
+    /// [`MAX_MATRIX_CELLS`], so the timing row starts and ends on a dark cell.
+    pub fn new(cells: u32) -> Result<Self> {
+        if !(MIN_MATRIX_CELLS..=MAX_MATRIX_CELLS).contains(&cells) || cells.is_multiple_of(2) {
+            return Err(FountainError::InvalidConfig(format!(
+                "a colour matrix needs an odd number of cells per side from {} to {}, not {}",
+                MIN_MATRIX_CELLS, MAX_MATRIX_CELLS, cells
+            )));
+        }
+        Ok(Self { cells })
+    }
+
+    pub fn cells(&self) -> u32 {
+        self.cells
+    }
+
+    /// Most payload bytes one symbol holds.
+    pub fn capacity(&self) -> usize {
+        let data_cells = (self.cells as usize - 2).pow(2);
+        data_cells * 3 / 8 - MATRIX_HEADER_SIZE
+    }
+
+    /// Width and height of a symbol drawn `cell_px` pixels per cell, margin included.
+    pub fn side_px(&self, cell_px: u32) -> u32 {
+        (self.cells + 2 * MATRIX_QUIET_ZONE) * cell_px
+    }
+
+    /// Draws a symbol holding `data`, `cell_px` pixels per cell.
+    pub fn render(&self, data: &[u8], cell_px: u32) -> Result<RgbImage> {
+        if data.len() > self.capacity() {
+            return Err(FountainError::InvalidConfig(format!(
+                "{} bytes do not fit a colour matrix of {} cells, which holds {}",
+                data.len(),
+                self.cells,
+                self.capacity()
+            )));
+        }
+        let mut bytes = Vec::with_capacity(MATRIX_HEADER_SIZE + data.len());
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&adler2::adler32_slice(data).to_be_bytes());
+        bytes.extend_from_slice(data);
+
+        let side = self.side_px(cell_px);
+        let mut image = RgbImage::from_pixel(side, side, LIGHT);
+        let last = self.cells - 1;
+        let mut bit = 0;
+        for row in 0..self.cells {
+            for col in 0..self.cells {
+                let color = if row == 0 {
+                    timing_color(col)
+                } else if col == 0 {
+                    timing_color(row)
+                } else if row == last || col == last {
+                    DARK
+                } else {
+                    let index = (0..3).fold(0, |index, i| (index << 1) | read_bit(&bytes, bit + i));
+                    bit += 3;
+                    palette_color(index)
+                };
+                let (x0, y0) = (
+                    (col + MATRIX_QUIET_ZONE) * cell_px,
+                    (row + MATRIX_QUIET_ZONE) * cell_px,
+                );
+                for y in y0..y0 + cell_px {
+                    for x in x0..x0 + cell_px {
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+        Ok(image)
+    }
+}
+
+/// Cells of the timing row and column alternate, dark at even positions.
+fn timing_color(position: u32) -> Rgb<u8> {
+    if position.is_multiple_of(2) {
+        DARK
+    } else {
+        LIGHT
+    }
+}
+
+/// The colour for 3 bits: red, green and blue on or off.
+fn palette_color(index: u8) -> Rgb<u8> {
+    let channel = |bit: u8| if index & bit != 0 { 255 } else { 0 };
+    Rgb([channel(4), channel(2), channel(1)])
+}
+
+fn read_bit(bytes: &[u8], bit: usize) -> u8 {
+    bytes
+        .get(bit / 8)
+        .map_or(0, |byte| (byte >> (7 - bit % 8)) & 1)
+}
+
+fn luma(pixel: &Rgb<u8>) -> u32 {
+    let [r, g, b] = pixel.0.map(u32::from);
+    (r * 299 + g * 587 + b * 114) / 1000
+}
+
+/// Reads the payload of a colour matrix symbol that fills most of `image` on a light
+/// background, such as a screen capture or a photo of a screen. Tilt and mild perspective are
+/// tolerated; `None` if no symbol is found or it fails its checksum.
+pub fn decode_color_matrix(image: &RgbImage) -> Option<Vec<u8>> {
+    let mut histogram = [0u32; 256];
+    for pixel in image.pixels() {
+        histogram[luma(pixel) as usize] += 1;
+    }
+    let pixels = image.width() as u64 * image.height() as u64;
+    let percentile = |fraction: f64| {
+        let target = (pixels as f64 * fraction) as u64;
+        let mut seen = 0;
+        (0..256u32)
+            .find(|&level| {
+                seen += u64::from(histogram[level as usize]);
+                seen > target
+            })
+            .unwrap_or(255)
+    };
+    let (dark, light) = (percentile(0.02), percentile(0.98));
+    if light < dark + 64 {
+        return None;
+    }
+    let threshold = (dark + light) / 2;
+
+    let grid = Grid::locate(image, threshold)?;
+    let cells = grid.count_cells(image, threshold)?;
+    let matrix = ColorMatrix::new(cells).ok()?;
+    let grid = Grid { cells, ..grid };
+
+    // Dark and light levels of each channel, from the timing cells.
+    let mut sums = [[0u32; 3]; 2];
+    let mut counts = [0u32; 2];
+    for position in 0..cells {
+        let class = (position % 2) as usize;
+        for (row, col) in [(0, position), (position, 0)] {
+            let color = grid.sample(image, row, col);
+            for (sum, value) in sums[class].iter_mut().zip(color) {
+                *sum += value;
+            }
+            counts[class] += 1;
+        }
+    }
+    let mut thresholds = [0u32; 3];
+    for channel in 0..3 {
+        let dark = sums[0][channel] / counts[0];
+        let light = sums[1][channel] / counts[1];
+        if light < dark + 40 {
+            return None;
+        }
+        thresholds[channel] = (dark + light) / 2;
+    }
+
+    let data_cells = (cells as usize - 2).pow(2);
+    let mut bytes = vec![0u8; (data_cells * 3).div_ceil(8)];
+    let mut bit = 0;
+    for row in 1..cells - 1 {
+        for col in 1..cells - 1 {
+            let color = grid.sample(image, row, col);
+            for (value, threshold) in color.iter().zip(thresholds) {
+                if *value > threshold {
+                    bytes[bit / 8] |= 0x80 >> (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+    }
+
+    let len = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+    if len > matrix.capacity() {
+        return None;
+    }
+    let checksum = u32::from_be_bytes(bytes[2..MATRIX_HEADER_SIZE].try_into().ok()?);
+    let payload = &bytes[MATRIX_HEADER_SIZE..MATRIX_HEADER_SIZE + len];
+    (adler2::adler32_slice(payload) == checksum).then(|| payload.to_vec())
+}
+
+/// Where a symbol lies in an image: the outer corners of its cells, and how many cells it has
+/// per side once they are counted.
+struct Grid {
+    top_left: (f32, f32),
+    top_right: (f32, f32),
+    bottom_left: (f32, f32),
+    bottom_right: (f32, f32),
+    cells: u32,
+}
+
+impl Grid {
+    /// Finds the corners of the symbol: its corner cells are dark and the outermost dark
+    /// pixels of the image.
+    fn locate(image: &RgbImage, threshold: u32) -> Option<Self> {
+        let (mut tl, mut tr, mut bl, mut br) = (None, None, None, None);
+        let better = |best: &mut Option<(i64, u32, u32)>, score: i64, x: u32, y: u32| {
+            if best.is_none_or(|(top, _, _)| score > top) {
+                *best = Some((score, x, y));
+            }
+        };
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if luma(pixel) >= threshold {
+                continue;
+            }
+            let (sx, sy) = (i64::from(x), i64::from(y));
+            better(&mut tl, -(sx + sy), x, y);
+            better(&mut tr, sx - sy, x, y);
+            better(&mut bl, sy - sx, x, y);
+            better(&mut br, sx + sy, x, y);
+        }
+        let corner = |best: Option<(i64, u32, u32)>, dx: u32, dy: u32| {
+            best.map(|(_, x, y)| ((x + dx) as f32, (y + dy) as f32))
+        };
+        let grid = Self {
+            top_left: corner(tl, 0, 0)?,
+            top_right: corner(tr, 1, 0)?,
+            bottom_left: corner(bl, 0, 1)?,
+            bottom_right: corner(br, 1, 1)?,
+            cells: 0,
+        };
+        let (width, height) = grid.edges();
+        (width >= MIN_MATRIX_CELLS as f32 && height >= MIN_MATRIX_CELLS as f32).then_some(grid)
+    }
+
+    /// Lengths of the top and left edges, in pixels.
+    fn edges(&self) -> (f32, f32) {
+        let distance = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).hypot(a.1 - b.1);
+        (
+            distance(self.top_left, self.top_right),
+            distance(self.top_left, self.bottom_left),
+        )
+    }
+
+    /// The image position at `u` across and `v` down the symbol, each from 0 to 1.
+    fn point(&self, u: f32, v: f32) -> (f32, f32) {
+        let mix = |tl: f32, tr: f32, bl: f32, br: f32| {
+            (1.0 - u) * (1.0 - v) * tl + u * (1.0 - v) * tr + (1.0 - u) * v * bl + u * v * br
+        };
+        (
+            mix(
+                self.top_left.0,
+                self.top_right.0,
+                self.bottom_left.0,
+                self.bottom_right.0,
+            ),
+            mix(
+                self.top_left.1,
+                self.top_right.1,
+                self.bottom_left.1,
+                self.bottom_right.1,
+            ),
+        )
+    }
+
+    /// Counts the cells along the timing row and column, which must agree. The first timing
+    /// cell, read just inside the top edge, gives the size of a cell, so the row can then be
+    /// read through its middle.
+    fn count_cells(&self, image: &RgbImage, threshold: u32) -> Option<u32> {
+        let (width, height) = self.edges();
+        let steps = (width.max(height) * 2.0) as usize;
+        let dark_at = |u: f32, v: f32| {
+            let (x, y) = self.point(u, v);
+            luma(&pixel_at(image, x, y)) < threshold
+        };
+
+        let inside = (1.5 / height).min(0.5);
+        let first_cell = (0..steps)
+            .take_while(|&i| dark_at((i as f32 + 0.5) / steps as f32, inside))
+            .count() as f32
+            / steps as f32;
+        if first_cell == 0.0 {
+            return None;
+        }
+        // Middle of the timing row, as a fraction of the height, and of the timing column.
+        let (row_middle, column_middle) = (first_cell * width / height / 2.0, first_cell / 2.0);
+
+        let min_run = (steps as f32 * first_cell / 3.0).max(1.0) as usize;
+        let row: Vec<bool> = (0..steps)
+            .map(|i| dark_at((i as f32 + 0.5) / steps as f32, row_middle))
+            .collect();
+        let column: Vec<bool> = (0..steps)
+            .map(|i| dark_at(column_middle, (i as f32 + 0.5) / steps as f32))
+            .collect();
+        let cells = count_runs(&row, min_run);
+        (cells == count_runs(&column, min_run)).then_some(cells as u32)
+    }
+
+    /// Mean colour around the centre of cell (`row`, `col`).
+    fn sample(&self, image: &RgbImage, row: u32, col: u32) -> [u32; 3] {
+        let cells = self.cells as f32;
+        let (x, y) = self.point((col as f32 + 0.5) / cells, (row as f32 + 0.5) / cells);
+        let mut sum = [0u32; 3];
+        for (dx, dy) in [(0.0, 0.0), (-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0)] {
+            let pixel = pixel_at(image, x + dx, y + dy);
+            for (sum, value) in sum.iter_mut().zip(pixel.0) {
+                *sum += u32::from(value);
+            }
+        }
+        sum.map(|sum| sum / 5)
+    }
+}
+
+fn pixel_at(image: &RgbImage, x: f32, y: f32) -> Rgb<u8> {
+    let x = (x.max(0.0) as u32).min(image.width() - 1);
+    let y = (y.max(0.0) as u32).min(image.height() - 1);
+    *image.get_pixel(x, y)
+}
+
+/// Number of alternating runs in `samples`, ignoring flickers shorter than `min_run`.
+fn count_runs(samples: &[bool], min_run: usize) -> usize {
+    let mut runs = 0;
+    let mut state = None;
+    let mut candidate = false;
+    let mut streak = 0;
+    for &sample in samples {
+        if Some(sample) == state {
+            streak = 0;
+            continue;
+        }
+        if sample == candidate {
+            streak += 1;
+        } else {
+            candidate = sample;
+            streak = 1;
+        }
+        if streak >= min_run {
+            state = Some(sample);
+            runs += 1;
+            streak = 0;
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops::{blur, resize, FilterType};
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 131 + i / 7) as u8).collect()
+    }
+
+    #[test]
+    fn test_color_matrix_roundtrip() {
+        let matrix = ColorMatrix::new(DEFAULT_MATRIX_CELLS).unwrap();
+        let data = payload(matrix.capacity());
+        let image = matrix.render(&data, 4).unwrap();
+        assert_eq!(image.width(), matrix.side_px(4));
+        assert_eq!(decode_color_matrix(&image).unwrap(), data);
+
+        // As a camera sees it: larger, soft and slightly off-colour.
+        let (side, scaled) = (image.width() * 3 / 2, image.width() * 3 / 2);
+        let mut photo = blur(&resize(&image, side, scaled, FilterType::Triangle), 0.6);
+        for pixel in photo.pixels_mut() {
+            pixel.0 = pixel.0.map(|value| (u32::from(value) * 3 / 4 + 30) as u8);
+        }
+        assert_eq!(decode_color_matrix(&photo).unwrap(), data);
+
+        let short = b"a few bytes";
+        let image = ColorMatrix::new(MIN_MATRIX_CELLS)
+            .unwrap()
+            .render(short, 3)
+            .unwrap();
+        assert_eq!(decode_color_matrix(&image).unwrap(), short);
+    }
+
+    #[test]
+    fn test_color_matrix_rejects() {
+        assert!(ColorMatrix::new(100).is_err());
+        assert!(ColorMatrix::new(MAX_MATRIX_CELLS + 2).is_err());
+
+        let matrix = ColorMatrix::new(MIN_MATRIX_CELLS).unwrap();
+        assert!(matrix.render(&payload(matrix.capacity() + 1), 4).is_err());
+
+        let blank = RgbImage::from_pixel(200, 200, LIGHT);
+        assert!(decode_color_matrix(&blank).is_none());
+
+        // A damaged cell fails the checksum.
+        let mut image = matrix.render(b"checked", 4).unwrap();
+        let cell = (MATRIX_QUIET_ZONE + 1) * 4;
+        let flipped = Rgb(image.get_pixel(cell, cell).0.map(|value| 255 - value));
+        for y in cell..cell + 4 {
+            for x in cell..cell + 4 {
+                image.put_pixel(x, y, flipped);
+            }
+        }
+        assert!(decode_color_matrix(&image).is_none());
+    }
+}
@@ -0,0 +1,304 @@
+//! UniFFI bindings, so Android and iOS apps can both show and receive transfers with this
+//! crate's exact protocol.
+//!
+//! [`FrameEncoder`] renders the QR frames of a file one at a time as they are shown, and
+//! [`FrameDecoder`] takes camera frames and returns the files once enough packets have
+//! arrived. Generate the Kotlin and Swift sources with `./script/rust/gen-bindings.sh`.
+
+use image::{DynamicImage, GrayImage, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+use crate::encode::{EncodeOptions, Encoder, QrFrames};
+use crate::error::FountainError;
+use crate::stream::{FeedResult, StreamDecoder};
+
+/// Error raised to the app; the bindings see its message.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum MobileError {
+    #[error(transparent)]
+    Fountain(#[from] FountainError),
+    #[error("{0}")]
+    InvalidArgument(String),
+}
+
+/// The QR frames of one file, rendered as PNGs on first use. A carousel shows frame
+/// `n % total()` for ever larger `n`; each frame is only rendered once.
+#[derive(uniffi::Object)]
+pub struct FrameEncoder {
+    filename: String,
+    total: u32,
+    payload_size: u32,
+    frames: Mutex<RenderedFrames>,
+}
+
+struct RenderedFrames {
+    pending: QrFrames,
+    pngs: Vec<Vec<u8>>,
+}
+
+#[uniffi::export]
+impl FrameEncoder {
+    /// Encodes `data` as `filename`. A `chunk_size` of 0 picks the largest payload that fits
+    /// a QR code; `pixel_scale` is the size of a module in pixels.
+    #[uniffi::constructor]
+    pub fn new(
+        data: Vec<u8>,
+        filename: String,
+        chunk_size: u32,
+        pixel_scale: u32,
+    ) -> Result<Arc<Self>, MobileError> {
+        let mut options = EncodeOptions::new().pixel_scale(pixel_scale);
+        if chunk_size > 0 {
+            options = options.chunk_size(chunk_size as usize);
+        }
+        let frames = Encoder::new(options).frames_from_bytes(&data, &filename)?;
+        Ok(Arc::new(Self {
+            filename: frames.filename().to_string(),
+            total: frames.total() as u32,
+            payload_size: frames.effective_size() as u32,
+            frames: Mutex::new(RenderedFrames {
+                pngs: Vec::with_capacity(frames.total()),
+                pending: frames,
+            }),
+        }))
+    }
+
+    /// Name of the file, as embedded in the transfer.
+    pub fn filename(&self) -> String {
+        self.filename.clone()
+    }
+
+    /// Number of frames in the loop.
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    /// Payload size in bytes per QR code that the encoder settled on.
+    pub fn payload_size(&self) -> u32 {
+        self.payload_size
+    }
+
+    /// PNG bytes of frame `index`, counted from 0, rendering the frames up to it if needed.
+    pub fn frame_png(&self, index: u32) -> Result<Vec<u8>, MobileError> {
+        let index = index as usize;
+        let mut frames = self.frames.lock().unwrap();
+        while frames.pngs.len() <= index {
+            let Some(qr_image) = frames.pending.next() else {
+                return Err(MobileError::InvalidArgument(format!(
+                    "frame {} of {} does not exist",
+                    index, self.total
+                )));
+            };
+            let mut png = Vec::new();
+            qr_image?
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .map_err(FountainError::from)?;
+            frames.pngs.push(png);
+        }
+        Ok(frames.pngs[index].clone())
+    }
+}
+
+/// Outcome of feeding one frame to a [`FrameDecoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum FeedStatus {
+    /// The frame holds no packet of this protocol.
+    NoChunk,
+    /// A new packet was added, but more are needed.
+    Accepted,
+    /// The packet had already been received.
+    Duplicate,
+    /// The manifest of a multi-file transfer is complete; see [`FrameDecoder::manifest`].
+    Manifest,
+    /// One file of a multi-file transfer is decoded; others still need packets.
+    FileComplete,
+    /// Every file is decoded; see [`FrameDecoder::files`].
+    Complete,
+}
+
+/// A file of the transfer, as the manifest announces it.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ManifestFile {
+    pub name: String,
+    pub size: u64,
+    pub sha256: Vec<u8>,
+}
+
+/// A decoded file.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ReceivedFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Feed-based decoder of camera or screen frames, in any order and with any repeats.
+#[derive(uniffi::Object)]
+pub struct FrameDecoder {
+    state: Mutex<DecoderState>,
+}
+
+#[derive(Default)]
+struct DecoderState {
+    stream: StreamDecoder,
+    manifest: Vec<ManifestFile>,
+    files: Vec<ReceivedFile>,
+    complete: bool,
+}
+
+#[uniffi::export]
+impl FrameDecoder {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(DecoderState::default()),
+        })
+    }
+
+    /// Feeds an RGBA frame of `width` x `height` pixels (`width * height * 4` bytes), such as
+    /// an Android `Bitmap` or the contents of a `CGImage`.
+    pub fn feed_rgba(
+        &self,
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    ) -> Result<FeedStatus, MobileError> {
+        let image = RgbaImage::from_raw(width, height, rgba)
+            .ok_or_else(|| invalid_frame(width, height, 4))?;
+        self.feed(|stream| stream.feed_image(&DynamicImage::ImageRgba8(image)))
+    }
+
+    /// Feeds the luminance plane of a camera frame (`width * height` bytes), which is all
+    /// QR detection needs: the Y plane of Android's `YUV_420_888` or of iOS's bi-planar
+    /// `420YpCbCr8` buffers, with any row padding removed.
+    pub fn feed_luma(
+        &self,
+        width: u32,
+        height: u32,
+        luma: Vec<u8>,
+    ) -> Result<FeedStatus, MobileError> {
+        let image = GrayImage::from_raw(width, height, luma)
+            .ok_or_else(|| invalid_frame(width, height, 1))?;
+        self.feed(|stream| stream.feed_luma(&image))
+    }
+
+    /// Unique packets received so far.
+    pub fn packets_received(&self) -> u64 {
+        self.state.lock().unwrap().stream.num_packets() as u64
+    }
+
+    /// Minimum number of packets needed to decode, once the first packet has arrived.
+    pub fn packets_expected(&self) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        state.stream.expected_packets().map(|n| n as u64)
+    }
+
+    /// The files announced by the transfer's manifest, once it is complete.
+    pub fn manifest(&self) -> Vec<ManifestFile> {
+        self.state.lock().unwrap().manifest.clone()
+    }
+
+    /// Files decoded so far, in the order they completed.
+    pub fn files(&self) -> Vec<ReceivedFile> {
+        self.state.lock().unwrap().files.clone()
+    }
+
+    /// Whether every file is decoded.
+    pub fn is_complete(&self) -> bool {
+        self.state.lock().unwrap().complete
+    }
+}
+
+impl FrameDecoder {
+    fn feed(
+        &self,
+        feed: impl FnOnce(&mut StreamDecoder) -> crate::error::Result<FeedResult>,
+    ) -> Result<FeedStatus, MobileError> {
+        let mut state = self.state.lock().unwrap();
+        if state.complete {
+            return Ok(FeedStatus::Complete);
+        }
+        Ok(match feed(&mut state.stream)? {
+            FeedResult::NoChunk => FeedStatus::NoChunk,
+            FeedResult::Accepted => FeedStatus::Accepted,
+            FeedResult::Duplicate => FeedStatus::Duplicate,
+            FeedResult::Manifest(manifest) => {
+                state.manifest = manifest
+                    .files
+                    .into_iter()
+                    .map(|entry| ManifestFile {
+                        name: entry.name,
+                        size: entry.size,
+                        sha256: entry.sha256.to_vec(),
+                    })
+                    .collect();
+                FeedStatus::Manifest
+            }
+            FeedResult::FileComplete(file) => {
+                state.files.push(ReceivedFile {
+                    name: file.filename,
+                    data: file.data,
+                });
+                FeedStatus::FileComplete
+            }
+            FeedResult::Complete(file) => {
+                state.files.push(ReceivedFile {
+                    name: file.filename,
+                    data: file.data,
+                });
+                state.complete = true;
+                FeedStatus::Complete
+            }
+        })
+    }
+}
+
+fn invalid_frame(width: u32, height: u32, bytes_per_pixel: u32) -> MobileError {
+    MobileError::InvalidArgument(format!(
+        "a {}x{} frame needs {} bytes per pixel",
+        width, height, bytes_per_pixel
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mobile_roundtrip() {
+        let content = b"UniFFI roundtrip test. ".repeat(20);
+        let encoder = FrameEncoder::new(content.clone(), "mobile.txt".into(), 300, 4).unwrap();
+        assert_eq!(encoder.filename(), "mobile.txt");
+
+        let decoder = FrameDecoder::new();
+        let mut status = FeedStatus::NoChunk;
+        for i in 0..encoder.total() {
+            let img = image::load_from_memory(&encoder.frame_png(i).unwrap()).unwrap();
+            status = if i % 2 == 0 {
+                let rgba = img.into_rgba8();
+                decoder
+                    .feed_rgba(rgba.width(), rgba.height(), rgba.into_raw())
+                    .unwrap()
+            } else {
+                let luma = img.into_luma8();
+                decoder
+                    .feed_luma(luma.width(), luma.height(), luma.into_raw())
+                    .unwrap()
+            };
+            if status == FeedStatus::Complete {
+                break;
+            }
+        }
+        assert_eq!(status, FeedStatus::Complete);
+        assert!(decoder.is_complete());
+        let files = decoder.files();
+        assert_eq!(files[0].name, "mobile.txt");
+        assert_eq!(files[0].data, content);
+
+        // Frames already rendered are served again, and the loop has an end.
+        assert_eq!(encoder.frame_png(0).unwrap(), encoder.frame_png(0).unwrap());
+        assert!(encoder.frame_png(encoder.total()).is_err());
+        assert!(decoder.feed_rgba(10, 10, vec![0; 12]).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+//! Work spread over worker threads, as many as `EncodeOptions::threads` or
+//! `DecodeOptions::threads` allow: rendering the QR codes of an encode, and reading the codes
+//! of a decode's frames, a batch at a time. The frames are still written and fed in order.
+
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// Threads to use for `threads`, an option left unset meaning one per core.
+pub(crate) fn thread_count(threads: Option<usize>) -> usize {
+    threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+        .max(1)
+}
+
+/// `f` of each item, in order, computed on up to `threads` threads. With one thread, or one
+/// item, no thread is started.
+pub(crate) fn map_parallel<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let per_thread = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(per_thread)
+            .map(|part| scope.spawn(move || part.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_parallel_keeps_order() {
+        let items: Vec<u32> = (0..10).collect();
+        for threads in [1, 3, 16] {
+            let squares = map_parallel(&items, threads, |n| n * n);
+            assert_eq!(squares, items.iter().map(|n| n * n).collect::<Vec<_>>());
+        }
+        assert_eq!(thread_count(Some(0)), 1);
+        assert!(thread_count(None) >= 1);
+    }
+}
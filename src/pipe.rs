@@ -0,0 +1,368 @@
+//! Pipe mode: an endless byte stream, such as stdin, sent as a sequence of segments.
+//!
+//! The stream is cut into segments of a fixed size, and each segment is compressed and encoded
+//! as its own RaptorQ object whose chunks carry the segment number (chunk header version 3).
+//! The receiver reassembles segments in any order and releases the stream bytes in order. A
+//! final empty segment marks the end of the stream.
+
+#[cfg(feature = "decode")]
+use fountain_core::{AssembleResult, ChunkAssembler, STREAM_VERSION};
+#[cfg(feature = "decode")]
+use image::{DynamicImage, GrayImage};
+#[cfg(feature = "decode")]
+use std::collections::BTreeMap;
+#[cfg(feature = "encode")]
+use std::io::Read;
+#[cfg(feature = "decode")]
+use std::io::Write;
+#[cfg(feature = "decode")]
+use std::path::Path;
+#[cfg(feature = "decode")]
+use std::time::Instant;
+#[cfg(feature = "decode")]
+use tracing::{debug, info, warn};
+
+use crate::chunk::Chunk;
+#[cfg(feature = "encode")]
+use crate::chunk::{compress, pack_data, DEFAULT_PAYLOAD_SIZE};
+#[cfg(feature = "encode")]
+use crate::chunker::{build_segment_chunks, fit_payload_size, TERMINAL_REDUNDANCY_FACTOR};
+#[cfg(feature = "decode")]
+use crate::decode::Decoder;
+#[cfg(feature = "encode")]
+use crate::encode::Encoder;
+use crate::error::{FountainError, Result};
+#[cfg(feature = "decode")]
+use crate::progress::is_cancelled;
+#[cfg(feature = "decode")]
+use crate::qr::decode_qr_from_gray;
+#[cfg(feature = "decode")]
+use crate::source::{open_source, InputFrame, SourceFrame};
+#[cfg(feature = "decode")]
+use crate::stream::decode_qr_bytes_to_chunk;
+
+/// Default number of stream bytes carried by one segment.
+pub const DEFAULT_SEGMENT_SIZE: usize = 1024;
+
+/// Name under which segments are packed; the stream itself has no filename.
+#[cfg(feature = "encode")]
+const SEGMENT_FILENAME: &str = "-";
+
+/// Reads a stream segment by segment and encodes each one, returned by
+/// [`Encoder::terminal_pipe`].
+///
+/// Each item holds the chunks of one segment. A segment is only sent once it is full, so a
+/// slow writer is shown in bursts. When the reader is exhausted, the last item is the empty
+/// segment that ends the stream.
+#[cfg(feature = "encode")]
+pub struct PipeEncoder<R> {
+    reader: R,
+    segment_size: usize,
+    payload_size: usize,
+    next_segment: u32,
+    finished: bool,
+}
+
+#[cfg(feature = "encode")]
+impl<R: Read> PipeEncoder<R> {
+    /// Encodes `reader` in segments of `segment_size` bytes, with QR payloads of
+    /// `payload_size` bytes.
+    pub fn new(reader: R, segment_size: usize, payload_size: usize) -> Self {
+        Self {
+            reader,
+            segment_size: segment_size.max(1),
+            payload_size,
+            next_segment: 0,
+            finished: false,
+        }
+    }
+
+    /// Payload size (bytes) of every QR code in the stream.
+    pub fn payload_size(&self) -> usize {
+        self.payload_size
+    }
+
+    /// Number of the segment the next item carries.
+    pub fn next_segment(&self) -> u32 {
+        self.next_segment
+    }
+
+    fn read_segment(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.segment_size);
+        self.reader
+            .by_ref()
+            .take(self.segment_size as u64)
+            .read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "encode")]
+impl<R: Read> Iterator for PipeEncoder<R> {
+    type Item = Result<Vec<Chunk>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let data = match self.read_segment() {
+            Ok(data) => data,
+            Err(e) => {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        };
+        self.finished = data.is_empty();
+
+        let segment = self.next_segment;
+        self.next_segment += 1;
+        Some(
+            compress(&pack_data(&data, SEGMENT_FILENAME)).map(|compressed| {
+                build_segment_chunks(
+                    &compressed,
+                    self.payload_size,
+                    TERMINAL_REDUNDANCY_FACTOR,
+                    segment,
+                )
+            }),
+        )
+    }
+}
+
+#[cfg(feature = "encode")]
+impl Encoder {
+    /// Prepares pipe mode for terminal display: segments of `reader` are encoded as they are
+    /// read, with the payload size shrunk as needed to fit the terminal. Show them with
+    /// [`crate::display_pipe`].
+    pub fn terminal_pipe<R: Read>(&self, reader: R, segment_size: usize) -> Result<PipeEncoder<R>> {
+        let start_size = self.options().chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE);
+        // Frames have the same size whatever they carry, so any data long enough to fill a
+        // packet will do for the fit check.
+        let payload_size = fit_payload_size(
+            &vec![0; start_size],
+            start_size,
+            50, // min_size
+            20, // reduction_step
+            crate::qr::fits_in_terminal,
+        )
+        .map_err(|e| match e {
+            FountainError::QrCapacityExceeded { .. } => FountainError::TerminalTooSmall,
+            e => e,
+        })?;
+
+        Ok(PipeEncoder::new(reader, segment_size, payload_size))
+    }
+}
+
+/// Outcome of feeding one frame or chunk to a [`PipeDecoder`].
+#[cfg(feature = "decode")]
+#[derive(Debug)]
+pub enum PipeFeed {
+    /// The frame holds no QR code, or the QR code is not part of a piped stream.
+    NoChunk,
+    /// A new packet was added, but no more stream bytes are available yet.
+    Accepted,
+    /// The packet had already been received, or belongs to a segment that is already complete.
+    Duplicate,
+    /// Stream bytes that follow the ones returned so far, from one or more segments.
+    Data(Vec<u8>),
+    /// The end of the stream was reached. Holds the last bytes, if any were still pending.
+    End(Vec<u8>),
+}
+
+/// Push-based decoder for a piped stream: feed it frames from any source and it returns the
+/// stream bytes in order as segments complete.
+///
+/// ```no_run
+/// use fountain::pipe::{PipeDecoder, PipeFeed};
+/// use std::io::Write;
+///
+/// let mut decoder = PipeDecoder::new();
+/// let mut out = std::io::stdout();
+/// for path in ["frame1.png", "frame2.png"] {
+///     match decoder.feed_image(&image::open(path)?)? {
+///         PipeFeed::Data(data) => out.write_all(&data)?,
+///         PipeFeed::End(data) => {
+///             out.write_all(&data)?;
+///             break;
+///         }
+///         _ => {}
+///     }
+/// }
+/// # Ok::<(), fountain::FountainError>(())
+/// ```
+#[cfg(feature = "decode")]
+#[derive(Default)]
+pub struct PipeDecoder {
+    next_segment: u32,
+    assemblers: BTreeMap<u32, ChunkAssembler>,
+    ready: BTreeMap<u32, Vec<u8>>,
+    finished: bool,
+}
+
+#[cfg(feature = "decode")]
+impl PipeDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scans an image for a QR code and feeds the chunk it carries, if any.
+    pub fn feed_image(&mut self, img: &DynamicImage) -> Result<PipeFeed> {
+        self.feed_luma(&img.to_luma8())
+    }
+
+    /// Like [`PipeDecoder::feed_image`], for frames already converted to grayscale.
+    pub fn feed_luma(&mut self, img: &GrayImage) -> Result<PipeFeed> {
+        let chunk = decode_qr_from_gray(img)
+            .ok()
+            .and_then(|qr_bytes| decode_qr_bytes_to_chunk(&qr_bytes));
+        match chunk {
+            Some(chunk) => self.feed_chunk(chunk),
+            None => Ok(PipeFeed::NoChunk),
+        }
+    }
+
+    /// Feeds a chunk that was already extracted from its QR code.
+    pub fn feed_chunk(&mut self, chunk: Chunk) -> Result<PipeFeed> {
+        if chunk.header.version != STREAM_VERSION {
+            return Ok(PipeFeed::NoChunk);
+        }
+        let segment = chunk.header.segment;
+        if self.finished || segment < self.next_segment || self.ready.contains_key(&segment) {
+            return Ok(PipeFeed::Duplicate);
+        }
+
+        match self.assemblers.entry(segment).or_default().push(chunk)? {
+            AssembleResult::Accepted => Ok(PipeFeed::Accepted),
+            AssembleResult::Duplicate => Ok(PipeFeed::Duplicate),
+            AssembleResult::Complete { data, .. } => {
+                self.assemblers.remove(&segment);
+                self.ready.insert(segment, data);
+                Ok(self.release())
+            }
+        }
+    }
+
+    /// Whether the end of the stream was reached.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Number of the first segment still missing; all segments before it were returned.
+    pub fn next_segment(&self) -> u32 {
+        self.next_segment
+    }
+
+    /// Unique packets received for the first missing segment, and the minimum it needs
+    /// (unknown until one of its packets arrives).
+    pub fn pending_packets(&self) -> (usize, Option<usize>) {
+        self.assemblers
+            .get(&self.next_segment)
+            .map_or((0, None), |assembler| {
+                (assembler.num_packets(), assembler.expected_packets())
+            })
+    }
+
+    /// Returns the bytes of completed segments that are next in the stream.
+    fn release(&mut self) -> PipeFeed {
+        let mut data = Vec::new();
+        while let Some(segment) = self.ready.remove(&self.next_segment) {
+            self.next_segment += 1;
+            if segment.is_empty() {
+                self.finished = true;
+                self.assemblers.clear();
+                self.ready.clear();
+                return PipeFeed::End(data);
+            }
+            data.extend_from_slice(&segment);
+        }
+        if data.is_empty() {
+            PipeFeed::Accepted
+        } else {
+            PipeFeed::Data(data)
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+impl Decoder {
+    /// Decodes a piped stream from a directory of QR images or a GIF file, writing the stream
+    /// bytes to `out` as soon as they are available in order. Returns the number of bytes
+    /// written. Output, overwrite and session options do not apply.
+    pub fn decode_pipe<W: Write>(&self, input: &Path, mut out: W) -> Result<u64> {
+        let mut source = open_source(input)?;
+
+        let mut decoder = PipeDecoder::new();
+        let mut written = 0;
+        let mut scanned = 0;
+        let mut seen_chunks = false;
+        let started = Instant::now();
+        let mut stopped = None;
+        for SourceFrame { frame, label, .. } in source.frames() {
+            if is_cancelled(&self.options().cancel) {
+                return Err(FountainError::Cancelled);
+            }
+            if let Some(reason) = self.options().limit_reached(scanned, started) {
+                warn!("Giving up after {}", reason);
+                stopped = Some(reason);
+                break;
+            }
+            scanned += 1;
+            let fed = match frame {
+                Ok(InputFrame::Image(img)) => decoder.feed_image(&img)?,
+                Ok(InputFrame::Packet(chunk)) => decoder.feed_chunk(chunk)?,
+                Err(_) => {
+                    debug!("    Failed to load {}", label);
+                    continue;
+                }
+            };
+            let data = match fed {
+                PipeFeed::NoChunk => continue,
+                PipeFeed::Accepted | PipeFeed::Duplicate => {
+                    seen_chunks = true;
+                    continue;
+                }
+                PipeFeed::Data(data) | PipeFeed::End(data) => data,
+            };
+            seen_chunks = true;
+            out.write_all(&data)?;
+            written += data.len() as u64;
+            debug!(
+                "    Stream at {} bytes after segment {} ({})",
+                written,
+                decoder.next_segment() - 1,
+                label
+            );
+            if decoder.is_finished() {
+                break;
+            }
+        }
+        out.flush()?;
+
+        if !decoder.is_finished() {
+            let (received, needed) = decoder.pending_packets();
+            if let Some(reason) = stopped {
+                return Err(FountainError::LimitReached {
+                    reason,
+                    received,
+                    needed: needed.unwrap_or_default(),
+                    scanned,
+                });
+            }
+            if !seen_chunks {
+                return Err(FountainError::NoChunksFound);
+            }
+            return Err(FountainError::NotEnoughPackets {
+                received,
+                needed: needed.unwrap_or(1),
+                scanned,
+            });
+        }
+        info!(
+            "Decoded {} segment(s), {} bytes",
+            decoder.next_segment() - 1,
+            written
+        );
+        Ok(written)
+    }
+}
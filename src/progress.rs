@@ -0,0 +1,111 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, Level};
+
+use crate::error::{FountainError, Result};
+
+/// Callback receiving overall progress as a fraction between 0.0 and 1.0.
+pub type ProgressCallback = Arc<dyn Fn(f32) + Send + Sync>;
+
+/// Flag that aborts a running encode or decode once set to `true`, e.g. from a GUI's
+/// cancel button or a Ctrl+C handler.
+pub type CancelFlag = Arc<AtomicBool>;
+
+pub(crate) fn is_cancelled(flag: &Option<CancelFlag>) -> bool {
+    flag.as_ref()
+        .is_some_and(|flag| flag.load(Ordering::Relaxed))
+}
+
+/// Fails with [`FountainError::MemoryLimitExceeded`] if `needed` bytes are over the limit.
+pub(crate) fn check_memory(max_memory: Option<usize>, needed: usize) -> Result<()> {
+    match max_memory {
+        Some(limit) if needed > limit => Err(FountainError::MemoryLimitExceeded { needed, limit }),
+        _ => Ok(()),
+    }
+}
+
+/// Progress reporting for long loops.
+///
+/// Draws a progress bar on stderr when it is a terminal and info-level logging is enabled.
+/// Otherwise each step is logged as a plain line, so redirected output stays readable.
+pub(crate) struct Progress {
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    /// Creates a progress reporter. `total` is `None` for sources of unknown length (e.g. GIF frames).
+    pub(crate) fn new(total: Option<u64>, unit: &str) -> Self {
+        if !tracing::enabled!(Level::INFO) || !std::io::stderr().is_terminal() {
+            return Self { bar: None };
+        }
+
+        let bar = match total {
+            Some(total) => {
+                let bar = ProgressBar::new(total);
+                bar.set_style(
+                    ProgressStyle::with_template(&format!(
+                        "  [{{bar:40}}] {{pos}}/{{len}} {} {{msg}}",
+                        unit
+                    ))
+                    .expect("valid progress template")
+                    .progress_chars("=> "),
+                );
+                bar
+            }
+            None => {
+                let bar = ProgressBar::new_spinner();
+                bar.set_style(
+                    ProgressStyle::with_template(&format!(
+                        "  {{spinner}} {{pos}} {} {{msg}}",
+                        unit
+                    ))
+                    .expect("valid progress template"),
+                );
+                bar.enable_steady_tick(Duration::from_millis(100));
+                bar
+            }
+        };
+
+        Self { bar: Some(bar) }
+    }
+
+    /// Advances by one step. `line` is only evaluated when falling back to plain logging.
+    pub(crate) fn inc<F: FnOnce() -> Option<String>>(&self, line: F) {
+        match &self.bar {
+            Some(bar) => bar.inc(1),
+            None => {
+                if let Some(line) = line() {
+                    info!("{}", line);
+                }
+            }
+        }
+    }
+
+    /// Updates the trailing message shown next to the bar (ignored in plain logging mode).
+    #[cfg(feature = "decode")]
+    pub(crate) fn set_message(&self, message: String) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(message);
+        }
+    }
+
+    /// Runs `f` with the bar temporarily hidden, so log lines are not drawn over it.
+    #[cfg(feature = "decode")]
+    pub(crate) fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        match &self.bar {
+            Some(bar) => bar.suspend(f),
+            None => f(),
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
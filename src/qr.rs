@@ -1,87 +1,392 @@
-use anyhow::{anyhow, Result};
+use crate::error::{FountainError, Result};
 
-#[cfg(feature = "encode")]
+#[cfg(any(feature = "encode", feature = "wasm"))]
 use image::{Rgb, RgbImage};
 
 #[cfg(any(feature = "decode", feature = "wasm"))]
 use image::{DynamicImage, GrayImage};
 
 #[cfg(feature = "encode")]
-use qrcode::{Color, EcLevel, QrCode, Version};
+use qrcode::Color;
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+use qrcode::{EcLevel, QrCode, Version};
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+use image::imageops::{crop_imm, resize, FilterType};
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+use crate::fiducial::rectified;
+#[cfg(any(feature = "decode", feature = "wasm"))]
+use crate::locate::locate_codes;
 
 #[cfg(any(feature = "decode", feature = "wasm"))]
 use rqrr::PreparedImage;
 
+#[cfg(feature = "decode")]
+use image::{metadata::Orientation, ImageDecoder, ImageReader};
+
 #[cfg(any(feature = "encode", feature = "decode"))]
 use std::path::Path;
 
+#[cfg(feature = "decode")]
+use std::io::Cursor;
+
 pub const QR_FILE_EXTENSION: &str = "png";
 
-#[cfg(feature = "encode")]
+/// Modules of quiet zone on each side of a rendered code unless another width is set: the
+/// minimum the QR standard asks for.
+pub const QUIET_ZONE_MODULES: u32 = 4;
+
+/// Extensions of the images read from a directory when decoding: the encoder's own PNGs, and
+/// photos of the codes taken with a camera.
+#[cfg(not(feature = "heif"))]
+pub const DECODE_IMAGE_EXTENSIONS: &[&str] = &[QR_FILE_EXTENSION, "jpg", "jpeg"];
+
+/// Extensions of the images read from a directory when decoding: the encoder's own PNGs, and
+/// photos of the codes taken with a camera, iPhone HEIF photos included.
+#[cfg(feature = "heif")]
+pub const DECODE_IMAGE_EXTENSIONS: &[&str] = &[QR_FILE_EXTENSION, "jpg", "jpeg", "heic", "heif"];
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+fn qr_error(context: &str, err: qrcode::types::QrError) -> FountainError {
+    FountainError::Qr(format!("{}: {}", context, err))
+}
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
 pub fn generate_qr_image(
     data: &[u8],
     specific_version: Option<Version>,
     pixel_scale: u32,
+) -> Result<(RgbImage, Version)> {
+    generate_qr_image_with_quiet_zone(data, specific_version, pixel_scale, QUIET_ZONE_MODULES)
+}
+
+/// Like [`generate_qr_image`], with a quiet zone of `quiet_zone` modules on each side.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub fn generate_qr_image_with_quiet_zone(
+    data: &[u8],
+    specific_version: Option<Version>,
+    pixel_scale: u32,
+    quiet_zone: u32,
 ) -> Result<(RgbImage, Version)> {
     let code = if let Some(v) = specific_version {
         QrCode::with_version(data, v, EcLevel::M)
-            .map_err(|e| anyhow!("Failed to create QR code with specific version: {}", e))?
+            .map_err(|e| qr_error("Failed to create QR code with specific version", e))?
     } else {
         QrCode::with_error_correction_level(data, EcLevel::M)
-            .map_err(|e| anyhow!("Failed to create QR code: {}", e))?
+            .map_err(|e| qr_error("Failed to create QR code", e))?
     };
 
     let version = code.version();
 
-    let image = code
+    // The renderer's quiet zone is always 4 modules wide, so draw the margin here.
+    let code_image = code
         .render::<Rgb<u8>>()
-        .min_dimensions(200, 200)
-        .quiet_zone(true)
+        .quiet_zone(false)
         .module_dimensions(pixel_scale, pixel_scale)
         .build();
+    let margin = quiet_zone * pixel_scale.max(1);
+    let mut image = RgbImage::from_pixel(
+        code_image.width() + 2 * margin,
+        code_image.height() + 2 * margin,
+        Rgb([255, 255, 255]),
+    );
+    image::imageops::replace(&mut image, &code_image, margin.into(), margin.into());
 
     Ok((image, version))
 }
 
+/// Whether any chunk whose text is as long as `encoded` fits a code of at most `max_version`.
+/// Base45 text is all QR alphanumeric characters and runs of digits may pack tighter, so text
+/// of the same length without any is the most a chunk of that length can take.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub fn fits_in_version(encoded: &[u8], max_version: i16) -> bool {
+    QrCode::with_version(
+        vec![b'A'; encoded.len()],
+        Version::Normal(max_version),
+        EcLevel::M,
+    )
+    .is_ok()
+}
+
+/// QR version that [`generate_qr_image`] picks for `data`, found without rendering it.
+#[cfg(feature = "encode")]
+pub fn qr_version_for(data: &[u8]) -> Result<Version> {
+    qrcode::bits::encode_auto(data, EcLevel::M)
+        .map(|bits| bits.version())
+        .map_err(|e| qr_error("Failed to create QR code", e))
+}
+
 #[cfg(feature = "encode")]
 pub fn save_qr_image(image: &RgbImage, path: &Path) -> Result<()> {
     image.save(path)?;
     Ok(())
 }
 
+/// Opens an image file the right way up. Phone cameras store photos sideways and record the
+/// rotation in EXIF, which is applied here; a missing or unreadable orientation is ignored.
+#[cfg(feature = "decode")]
+pub fn open_image(path: &Path) -> Result<DynamicImage> {
+    #[cfg(feature = "heif")]
+    if crate::heif::is_heif_path(path) {
+        return image_from_bytes(&std::fs::read(path)?);
+    }
+    let decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    upright(decoder)
+}
+
+/// Like [`open_image`], for an encoded image already in memory.
+#[cfg(feature = "decode")]
+pub fn image_from_bytes(bytes: &[u8]) -> Result<DynamicImage> {
+    #[cfg(feature = "heif")]
+    if crate::heif::is_heif(bytes) {
+        return crate::heif::decode_heif(bytes);
+    }
+    let decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()?;
+    upright(decoder)
+}
+
+#[cfg(feature = "decode")]
+fn upright(mut decoder: impl ImageDecoder) -> Result<DynamicImage> {
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
 #[cfg(any(feature = "decode", feature = "wasm"))]
 pub fn decode_qr_from_dynamic_image(img: &DynamicImage) -> Result<Vec<u8>> {
     let gray = img.to_luma8();
     decode_qr_from_gray(&gray)
 }
 
+/// Frames whose longer side is below this are only read scaled up: a code filling them may
+/// have modules of a single pixel, which rqrr cannot measure (and panics on).
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const MIN_DIRECT_SIDE: u32 = 200;
+/// Frames whose longer side is below this are read again scaled up by [`UPSCALE_FACTORS`]
+/// when no code is found at their own size.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const UPSCALE_BELOW: u32 = 400;
+/// Nearest-neighbour factors small frames are read at, in order, until a code is found.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const UPSCALE_FACTORS: [u32; 2] = [2, 3];
+/// Frames whose shorter side is at least this, such as 4K photos, are read again at a
+/// fraction of their size when no code is found at full size, which averages out camera noise.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const DOWNSCALE_FROM: u32 = 1200;
+/// Divisors large frames are read again at, in order, until a code is found.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const DOWNSCALE_DIVISORS: [u32; 2] = [2, 4];
+
+/// Reads the QR code in `gray`. When none is found, small frames are tried again scaled up
+/// and large ones scaled down, as rqrr finds codes only within a range of module sizes.
 #[cfg(any(feature = "decode", feature = "wasm"))]
 pub fn decode_qr_from_gray(gray: &GrayImage) -> Result<Vec<u8>> {
-    let mut prepared = PreparedImage::prepare(gray.clone());
-    let grids = prepared.detect_grids();
+    decode_qrs_from_gray(gray).map(|mut codes| codes.swap_remove(0))
+}
+
+/// Like [`decode_qr_from_gray`], reading every QR code in the frame, such as the two a
+/// terminal shows side by side, from left to right.
+///
+/// Large frames, such as full-resolution photos in which the code is small, are first cropped
+/// to the area around the codes' finder patterns: rqrr reads the crop in a fraction of
+/// the time, and at a scale where the modules are big enough. The whole frame is read if the
+/// crop holds no code. If no code is read at all, a frame with corner markers (see
+/// `EncodeOptions::markers`) is warped flat by them and read again.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub fn decode_qrs_from_gray(gray: &GrayImage) -> Result<Vec<Vec<u8>>> {
+    let (width, height) = gray.dimensions();
+    if width.min(height) >= DOWNSCALE_FROM {
+        if let Some(region) = locate_codes(gray) {
+            let crop = crop_imm(gray, region.x, region.y, region.width, region.height).to_image();
+            if let Ok(codes) = decode_qrs_at_scales(&crop) {
+                return Ok(codes);
+            }
+        }
+    }
+    decode_qrs_at_scales(gray).or_else(|e| {
+        // A photo at an angle may still show the corner markers of `EncodeOptions::markers`.
+        rectified(gray)
+            .find_map(|flat| decode_qrs_at_scales(&flat).ok())
+            .ok_or(e)
+    })
+}
+
+/// Reads the codes of a frame at its own size, then scaled up or down until some are found.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn decode_qrs_at_scales(gray: &GrayImage) -> Result<Vec<Vec<u8>>> {
+    let (width, height) = gray.dimensions();
+    let mut result = Err(FountainError::Qr("No QR code found in image".to_string()));
+    if width.max(height) >= MIN_DIRECT_SIDE {
+        result = decode_qr_at_scale(gray.clone());
+    }
+    if width.max(height) < UPSCALE_BELOW {
+        for factor in UPSCALE_FACTORS {
+            if result.is_ok() {
+                break;
+            }
+            let scaled = resize(gray, width * factor, height * factor, FilterType::Nearest);
+            result = decode_qr_at_scale(scaled);
+        }
+    } else if width.min(height) >= DOWNSCALE_FROM {
+        for divisor in DOWNSCALE_DIVISORS {
+            if result.is_ok() {
+                break;
+            }
+            let scaled = resize(
+                gray,
+                width / divisor,
+                height / divisor,
+                FilterType::Triangle,
+            );
+            result = decode_qr_at_scale(scaled);
+        }
+    }
+    result
+}
+
+/// Reads every code rqrr finds at the frame's own size. Fails if none can be read.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn decode_qr_at_scale(gray: GrayImage) -> Result<Vec<Vec<u8>>> {
+    let mut prepared = PreparedImage::prepare(gray);
+    let mut grids = prepared.detect_grids();
 
     if grids.is_empty() {
-        return Err(anyhow!("No QR code found in image"));
+        return Err(FountainError::Qr("No QR code found in image".to_string()));
     }
+    grids.sort_by_key(|grid| grid.bounds.iter().map(|point| point.x).min());
+
+    let mut codes = Vec::with_capacity(grids.len());
+    let mut error = None;
+    for grid in grids {
+        match grid.decode() {
+            Ok((_, content)) => codes.push(content.into_bytes()),
+            Err(e) => error = Some(e),
+        }
+    }
+    match error {
+        Some(e) if codes.is_empty() => Err(FountainError::Qr(format!(
+            "Failed to decode QR code: {:?}",
+            e
+        ))),
+        _ => Ok(codes),
+    }
+}
 
-    let (_, content) = grids[0]
-        .decode()
-        .map_err(|e| anyhow!("Failed to decode QR code: {:?}", e))?;
+/// Sharpness below which a frame counts as blurry: its edges are smeared across more than
+/// about 2.5 pixels.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const BLURRY_SHARPNESS: f32 = 0.4;
+/// Contrast below which dark and light modules are hard to tell apart.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const LOW_CONTRAST: f32 = 0.3;
+/// Contrast a frame needs before it is taken to show anything at all.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const MIN_CODE_CONTRAST: f32 = 0.15;
 
-    Ok(content.into_bytes())
+/// Rough image quality of a frame, to tell why no QR code was read from it.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameQuality {
+    /// Spread between dark and light pixels (5th to 95th percentile of brightness), from 0
+    /// to 1.
+    pub contrast: f32,
+    /// Steepness of the edges relative to the contrast, from 0 to 1: about one over the
+    /// number of pixels an edge is smeared across.
+    pub sharpness: f32,
+    /// Whether the frame has dark and light areas in proportions a QR code could make up,
+    /// rather than being blank.
+    pub has_code_region: bool,
 }
 
-#[cfg(feature = "encode")]
-pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
-    use terminal_size::{terminal_size, Height, Width};
+#[cfg(any(feature = "decode", feature = "wasm"))]
+impl FrameQuality {
+    pub fn measure(gray: &GrayImage) -> Self {
+        let mut levels = [0u64; 256];
+        for pixel in gray.pixels() {
+            levels[pixel.0[0] as usize] += 1;
+        }
+        let total: u64 = levels.iter().sum();
+        let dark = percentile(&levels, total / 20) as i32;
+        let light = percentile(&levels, total - total / 20) as i32;
+        let spread = (light - dark).max(1);
+        let contrast = spread as f32 / 255.0;
+
+        // Differences between neighbours that are part of an edge; the steepest of them show
+        // how sharp the edges are.
+        let mut steps = [0u64; 256];
+        let mut step = |a: u8, b: u8| {
+            let diff = (a as i32 - b as i32).unsigned_abs() as usize;
+            if diff * 10 > spread as usize {
+                steps[diff] += 1;
+            }
+        };
+        for y in 0..gray.height() {
+            for x in 0..gray.width() {
+                let here = gray.get_pixel(x, y).0[0];
+                if x + 1 < gray.width() {
+                    step(here, gray.get_pixel(x + 1, y).0[0]);
+                }
+                if y + 1 < gray.height() {
+                    step(here, gray.get_pixel(x, y + 1).0[0]);
+                }
+            }
+        }
+        let edges: u64 = steps.iter().sum();
+        let steepest = percentile(&steps, edges - edges / 10) as f32;
+
+        let middle = ((dark + light) / 2) as usize;
+        let dark_share = levels[..middle].iter().sum::<u64>() as f32 / total.max(1) as f32;
+        Self {
+            contrast,
+            sharpness: (steepest / spread as f32).min(1.0),
+            has_code_region: contrast >= MIN_CODE_CONTRAST
+                && (0.02..=0.95).contains(&dark_share)
+                && edges > 0,
+        }
+    }
 
-    let code = QrCode::with_error_correction_level(data, EcLevel::M)
-        .map_err(|e| anyhow!("Failed to create QR code: {}", e))?;
+    /// Whether the edges are too smeared to read the modules, as from an out-of-focus camera
+    /// or a code too small in the frame.
+    pub fn is_blurry(&self) -> bool {
+        self.sharpness < BLURRY_SHARPNESS
+    }
 
-    let qr_size = code.width();
-    let colors = code.to_colors();
+    /// Whether dark and light modules are hard to tell apart, as under glare.
+    pub fn is_low_contrast(&self) -> bool {
+        self.contrast < LOW_CONTRAST
+    }
+}
 
-    let (term_width, term_height) = terminal_size()
+/// The level at which the histogram reaches `rank` values.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn percentile(histogram: &[u64; 256], rank: u64) -> u8 {
+    let mut seen = 0;
+    for (level, &count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen > rank {
+            return level as u8;
+        }
+    }
+    u8::MAX
+}
+
+/// Columns of blank space between codes shown side by side, on top of their quiet zones.
+#[cfg(feature = "encode")]
+const TERMINAL_CODE_GAP: usize = 4;
+
+/// Size of the terminal in characters, or 120x60 when it is unknown or implausibly small.
+#[cfg(feature = "encode")]
+fn terminal_dimensions() -> (usize, usize) {
+    use terminal_size::{terminal_size, Height, Width};
+
+    terminal_size()
         .map(|(Width(w), Height(h))| {
             if w < 40 || h < 30 {
                 (120, 60)
@@ -89,28 +394,27 @@ pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
                 (w as usize, h as usize)
             }
         })
-        .unwrap_or((120, 60));
-
-    let qr_with_quiet = qr_size + 4; // Add quiet zone
-
-    // Fixed scale=1: each QR module = 1 char wide, uses half-blocks for height
-    // This gives the most compact and square appearance
-    let scale: usize = 1;
-
-    let display_width = qr_with_quiet * scale;
-    let display_height = (qr_with_quiet + 1) / 2 * scale;
+        .unwrap_or((120, 60))
+}
 
-    // Center padding
-    let pad_left = term_width.saturating_sub(display_width) / 2;
-    let pad_top = term_height.saturating_sub(display_height + 8) / 2;
+/// Width of a code holding `data` on the terminal, quiet zone included: one character per
+/// module, two modules per line.
+#[cfg(feature = "encode")]
+fn terminal_code_width(data: &[u8]) -> Result<usize> {
+    let code = QrCode::with_error_correction_level(data, EcLevel::M)
+        .map_err(|e| qr_error("Failed to create QR code", e))?;
+    Ok(code.width() + 4)
+}
 
-    let mut result = String::new();
-    let left_pad: String = " ".repeat(pad_left);
+/// Lines of a code holding `data` drawn in half blocks, each as wide as the code.
+#[cfg(feature = "encode")]
+fn terminal_code_lines(data: &[u8]) -> Result<Vec<String>> {
+    let code = QrCode::with_error_correction_level(data, EcLevel::M)
+        .map_err(|e| qr_error("Failed to create QR code", e))?;
 
-    // Top padding
-    for _ in 0..pad_top {
-        result.push('\n');
-    }
+    let qr_size = code.width();
+    let colors = code.to_colors();
+    let qr_with_quiet = qr_size + 4; // Add quiet zone
 
     // Helper to check if a position is dark
     let is_dark = |row: usize, col: usize| -> bool {
@@ -124,72 +428,91 @@ pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
     };
 
     // Render using half-block characters
-    // Process 2 QR rows at a time, each becomes 1 terminal row (with scale repetition)
-    for qr_row_pair in 0..((qr_with_quiet + 1) / 2) {
-        let top_row = qr_row_pair * 2;
-        let bottom_row = top_row + 1;
-
-        // Repeat each output row 'scale' times for vertical scaling
-        for _ in 0..scale {
-            result.push_str(&left_pad);
-
-            for qr_col in 0..qr_with_quiet {
-                let top_dark = is_dark(top_row, qr_col);
-                let bottom_dark = if bottom_row < qr_with_quiet {
-                    is_dark(bottom_row, qr_col)
-                } else {
-                    false
-                };
-
-                let ch = match (top_dark, bottom_dark) {
-                    (true, true) => '█',
-                    (true, false) => '▀',
-                    (false, true) => '▄',
-                    (false, false) => ' ',
-                };
-
-                // Repeat char 'scale' times for horizontal scaling
-                for _ in 0..scale {
-                    result.push(ch);
-                }
+    // Process 2 QR rows at a time, each becomes 1 terminal row
+    let lines = (0..qr_with_quiet.div_ceil(2))
+        .map(|qr_row_pair| {
+            let top_row = qr_row_pair * 2;
+            let bottom_row = top_row + 1;
+            (0..qr_with_quiet)
+                .map(|qr_col| {
+                    let top_dark = is_dark(top_row, qr_col);
+                    let bottom_dark = bottom_row < qr_with_quiet && is_dark(bottom_row, qr_col);
+                    match (top_dark, bottom_dark) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Ok(lines)
+}
+
+#[cfg(feature = "encode")]
+pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
+    render_qrs_to_terminal(&[data])
+}
+
+/// Draws a QR code for each of `codes` side by side, centred in the terminal. Each module is
+/// one character wide, and half blocks fit two modules in a line, which gives the most
+/// compact and square appearance.
+#[cfg(feature = "encode")]
+pub fn render_qrs_to_terminal(codes: &[&[u8]]) -> Result<String> {
+    let rendered = codes
+        .iter()
+        .map(|data| terminal_code_lines(data))
+        .collect::<Result<Vec<_>>>()?;
+    let widths: Vec<usize> = rendered
+        .iter()
+        .map(|lines| lines[0].chars().count())
+        .collect();
+    let display_width =
+        widths.iter().sum::<usize>() + TERMINAL_CODE_GAP * codes.len().saturating_sub(1);
+    let display_height = rendered.iter().map(Vec::len).max().unwrap_or(0);
+
+    // Center padding
+    let (term_width, term_height) = terminal_dimensions();
+    let pad_left = term_width.saturating_sub(display_width) / 2;
+    let pad_top = term_height.saturating_sub(display_height + 8) / 2;
+
+    let mut result = "\n".repeat(pad_top);
+    let gap = " ".repeat(TERMINAL_CODE_GAP);
+    for row in 0..display_height {
+        result.push_str(&" ".repeat(pad_left));
+        for (i, (lines, width)) in rendered.iter().zip(&widths).enumerate() {
+            if i > 0 {
+                result.push_str(&gap);
+            }
+            match lines.get(row) {
+                Some(line) => result.push_str(line),
+                None => result.push_str(&" ".repeat(*width)),
             }
-            result.push('\n');
         }
+        result.push('\n');
     }
-
     Ok(result)
 }
 
 #[cfg(feature = "encode")]
 pub fn fits_in_terminal(data: &[u8]) -> Result<bool> {
-    use terminal_size::{terminal_size, Height, Width};
-
-    let code = QrCode::with_error_correction_level(data, EcLevel::M)
-        .map_err(|e| anyhow!("Failed to create QR code: {}", e))?;
-
-    let qr_size = code.width();
-    let qr_with_quiet = qr_size + 4; // Add quiet zone
-
-    let scale: usize = 1;
-    let display_width = qr_with_quiet * scale;
-    let display_height = (qr_with_quiet + 1) / 2 * scale;
+    Ok(terminal_codes_across(data)? > 0)
+}
 
-    let (term_width, term_height) = terminal_size()
-        .map(|(Width(w), Height(h))| {
-            if w < 40 || h < 30 {
-                (120, 60)
-            } else {
-                (w as usize, h as usize)
-            }
-        })
-        .unwrap_or((120, 60));
+/// How many codes holding as much as `data` fit side by side in the terminal; 0 if not even
+/// one does.
+#[cfg(feature = "encode")]
+pub fn terminal_codes_across(data: &[u8]) -> Result<usize> {
+    let display_width = terminal_code_width(data)?;
+    let display_height = display_width.div_ceil(2);
+    let (term_width, term_height) = terminal_dimensions();
 
-    // Check if it fits (allow 6 lines for header/footer/spacing)
+    // Allow 6 lines for header/footer/spacing
     if display_width > term_width || display_height + 6 > term_height {
-        Ok(false)
-    } else {
-        Ok(true)
+        return Ok(0);
     }
+    Ok((term_width + TERMINAL_CODE_GAP) / (display_width + TERMINAL_CODE_GAP))
 }
 
 #[cfg(all(test, feature = "encode", feature = "decode"))]
@@ -215,4 +538,138 @@ mod tests {
         let decoded = decode_qr_from_gray(&gray).unwrap();
         assert_eq!(decoded, data);
     }
+
+    #[test]
+    fn test_exif_orientation_is_applied() {
+        use image::codecs::png::PngEncoder;
+        use image::ImageEncoder;
+
+        let (image, _) = generate_qr_image(b"Taken sideways", None, 2).unwrap();
+        let upright_image = image::DynamicImage::ImageRgb8(image);
+        // Stored turned a quarter to the left, with EXIF saying to turn it back to the right.
+        let stored = upright_image.rotate270().to_rgb8();
+        let exif = [
+            b"MM\0\x2a\0\0\0\x08\0\x01".as_slice(),
+            &[0x01, 0x12, 0x00, 0x03, 0, 0, 0, 1, 0, 6, 0, 0],
+            &[0, 0, 0, 0],
+        ]
+        .concat();
+        let mut png = Vec::new();
+        let mut encoder = PngEncoder::new(&mut png);
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder
+            .write_image(
+                &stored,
+                stored.width(),
+                stored.height(),
+                image::ExtendedColorType::Rgb8,
+            )
+            .unwrap();
+
+        let loaded = image_from_bytes(&png).unwrap();
+        assert_eq!(loaded.to_rgb8(), upright_image.to_rgb8());
+        assert!(image_from_bytes(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_multi_scale_detection() {
+        let data = b"Read at another scale";
+
+        // Modules of a single pixel only read once scaled up.
+        let (tiny, _) = generate_qr_image(data, None, 1).unwrap();
+        let tiny = image::DynamicImage::ImageRgb8(tiny).to_luma8();
+        assert_eq!(decode_qr_from_gray(&tiny).unwrap(), data);
+
+        // A soft, noisy photo only reads once scaled down.
+        let (image, _) = generate_qr_image(data, None, 4).unwrap();
+        let gray = image::DynamicImage::ImageRgb8(image).to_luma8();
+        let photo = resize(&gray, 1200, 1200, FilterType::Triangle);
+        let mut photo = image::imageops::blur(&photo, 2.0);
+        let mut seed = 12345u32;
+        for pixel in photo.pixels_mut() {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let noise = (seed >> 24) as i32 - 128;
+            pixel.0[0] = (pixel.0[0] as i32 + noise * 120 / 128).clamp(0, 255) as u8;
+        }
+        assert!(decode_qr_at_scale(photo.clone()).is_err());
+        assert_eq!(decode_qr_from_gray(&photo).unwrap(), data);
+    }
+
+    #[test]
+    fn test_locate_small_code_in_photo() {
+        let data = b"A small code in a large photo";
+        let (image, _) = generate_qr_image(data, None, 5).unwrap();
+        let code = image::DynamicImage::ImageRgb8(image).to_luma8();
+
+        // A grey, textured scene with the code in one corner.
+        let mut seed = 42u32;
+        let mut photo = GrayImage::from_fn(2400, 1800, |x, y| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let shade = 90 + (x / 300 + y / 200) % 3 * 30;
+            image::Luma([(shade as i32 + (seed >> 28) as i32 - 8).clamp(0, 255) as u8])
+        });
+        let (left, top) = (1700, 1200);
+        image::imageops::overlay(&mut photo, &code, left, top);
+
+        let region = crate::locate::locate_codes(&photo).expect("no finder patterns found");
+        assert!(region.x <= left as u32 && region.y <= top as u32);
+        assert!(region.x + region.width >= left as u32 + code.width());
+        assert!(region.y + region.height >= top as u32 + code.height());
+        assert!(region.width * region.height < photo.width() * photo.height() / 10);
+        assert_eq!(decode_qr_from_gray(&photo).unwrap(), data);
+    }
+
+    #[test]
+    fn test_side_by_side_codes() {
+        let (left, right) = (
+            b"Left of the pair".as_slice(),
+            b"Right of the pair".as_slice(),
+        );
+        let rendered = render_qrs_to_terminal(&[left, right]).unwrap();
+
+        // Draw the half blocks as a camera would see them, 4x4 pixels per module.
+        let lines: Vec<Vec<char>> = rendered
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.chars().collect())
+            .collect();
+        let width = lines.iter().map(Vec::len).max().unwrap() as u32;
+        let gray = GrayImage::from_fn(width * 4, lines.len() as u32 * 8, |x, y| {
+            let c = lines[(y / 8) as usize]
+                .get((x / 4) as usize)
+                .copied()
+                .unwrap_or(' ');
+            let dark = match c {
+                '█' => true,
+                '▀' => y % 8 < 4,
+                '▄' => y % 8 >= 4,
+                _ => false,
+            };
+            image::Luma([if dark { 0 } else { 255 }])
+        });
+        // Pad so the leftmost code keeps its quiet zone whatever the centring.
+        let mut padded =
+            GrayImage::from_pixel(gray.width() + 32, gray.height() + 32, image::Luma([255]));
+        image::imageops::overlay(&mut padded, &gray, 16, 16);
+
+        assert_eq!(decode_qrs_from_gray(&padded).unwrap(), vec![left, right]);
+    }
+
+    #[test]
+    fn test_frame_quality() {
+        let (image, _) = generate_qr_image(b"Frame quality test", None, 4).unwrap();
+        let gray = image::DynamicImage::ImageRgb8(image).to_luma8();
+
+        let sharp = FrameQuality::measure(&gray);
+        assert!(sharp.has_code_region);
+        assert!(!sharp.is_blurry() && !sharp.is_low_contrast());
+
+        let blurred = image::imageops::blur(&gray, 3.0);
+        assert!(decode_qr_from_gray(&blurred).is_err());
+        let blurry = FrameQuality::measure(&blurred);
+        assert!(blurry.has_code_region && blurry.is_blurry());
+
+        let blank = GrayImage::from_pixel(64, 64, image::Luma([200]));
+        assert!(!FrameQuality::measure(&blank).has_code_region);
+    }
 }
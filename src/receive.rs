@@ -0,0 +1,388 @@
+//! Receiving frames over HTTP, so any phone with a browser can be the camera.
+//!
+//! [`Decoder::receive_http`] serves a small page on which a phone takes photos of the codes,
+//! or picks photos, GIFs or a screen recording of the carousel, and uploads them. Photos and
+//! GIFs are sent as they are; the page cuts a recording into frames itself, as decoding video
+//! here would need a codec library. Frames are decoded as they arrive, and the page polls how
+//! far the decode is until the files are written.
+
+use serde_json::json;
+use std::io::{self, Cursor, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::decode::{remove_session, write_files, DecodeOptions, DecodeResult, Decoder, Scan};
+use crate::error::Result;
+use crate::qr::image_from_bytes;
+use crate::source::{FrameSource, GifSource, SourceFrame};
+
+/// How often the accept loop checks whether to stop, and the scan for new frames.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a client may take to send a request, body included.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest request head read.
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+/// Largest upload taken, room for a long GIF recording. Only one upload is taken at a time,
+/// so this is also the most the uploads hold in memory.
+const MAX_UPLOAD_BYTES: usize = 256 * 1024 * 1024;
+/// Frames uploaded but not scanned yet; an upload waits while this many are queued.
+const QUEUED_FRAMES: usize = 16;
+/// How long the server stays up once the decode ends, so the page's next poll learns it.
+const LINGER: Duration = Duration::from_secs(3);
+
+const INDEX_HTML: &str = include_str!("receive.html");
+
+/// How far the decode is, as `GET /status` tells the page.
+#[derive(Default)]
+struct Status {
+    /// Frames uploaded, and those of them scanned.
+    uploaded: usize,
+    scanned: usize,
+    /// Estimated fraction of the files recovered.
+    progress: f32,
+    /// The file received, once the decode is done.
+    filename: Option<String>,
+    /// Why the decode failed, if it did.
+    error: Option<String>,
+}
+
+/// What the connection threads share.
+struct Site {
+    frames: SyncSender<SourceFrame>,
+    status: Mutex<Status>,
+    stop: AtomicBool,
+    /// Whether an upload is being taken; others are turned away until it is queued.
+    uploading: AtomicBool,
+}
+
+/// The right to take an upload, held until dropped.
+struct UploadSlot<'a>(&'a AtomicBool);
+
+impl<'a> UploadSlot<'a> {
+    fn take(uploading: &'a AtomicBool) -> Option<Self> {
+        uploading
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| UploadSlot(uploading))
+    }
+}
+
+impl Drop for UploadSlot<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// HTTP server taking frames uploaded from a phone, returned by [`Decoder::receive_http`].
+///
+/// `GET /` is the page, `GET /status` tells how far the decode is and `POST /upload?name=NAME`
+/// takes a photo or a GIF as the request body, one upload at a time: others meanwhile get
+/// `503 Service Unavailable`.
+pub struct UploadServer {
+    listener: TcpListener,
+    frames: Receiver<SourceFrame>,
+    site: Arc<Site>,
+    options: DecodeOptions,
+}
+
+impl UploadServer {
+    /// Address the server listens on, e.g. to learn the port picked for port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serves the page and decodes the frames uploaded until the transfer is complete, then
+    /// writes the files as [`Decoder::decode`] does. Ends with an error if the decoder's cancel
+    /// flag is set first, or it gives up on a timeout or stall, saving the session if one is
+    /// configured.
+    pub fn run(self) -> Result<DecodeResult> {
+        let UploadServer {
+            listener,
+            frames,
+            site,
+            options,
+        } = self;
+        listener.set_nonblocking(true)?;
+        let accepting = {
+            let site = Arc::clone(&site);
+            thread::spawn(move || accept(listener, site))
+        };
+
+        let result = receive(&frames, &site, &options);
+        // Uploads waiting on a full queue fail now rather than after the linger.
+        drop(frames);
+        {
+            let mut status = site.status.lock().unwrap();
+            match &result {
+                Ok(decoded) => status.filename = Some(decoded.original_filename.clone()),
+                Err(e) => status.error = Some(e.to_string()),
+            }
+        }
+        if result.is_ok() {
+            thread::sleep(LINGER);
+        }
+        site.stop.store(true, Ordering::SeqCst);
+        accepting.join().expect("accept thread panicked")?;
+        result
+    }
+}
+
+impl Decoder {
+    /// Binds an [`UploadServer`] to `addr`, taking frames from a phone's browser. The page
+    /// tracks the decoder's progress; any progress callback of its own still gets it too.
+    pub fn receive_http(&self, addr: impl ToSocketAddrs) -> Result<UploadServer> {
+        let (sender, frames) = mpsc::sync_channel(QUEUED_FRAMES);
+        let site = Arc::new(Site {
+            frames: sender,
+            status: Mutex::new(Status::default()),
+            stop: AtomicBool::new(false),
+            uploading: AtomicBool::new(false),
+        });
+
+        let mut options = self.options().clone();
+        let on_progress = options.on_progress.take();
+        let tracked = Arc::clone(&site);
+        options = options.on_progress(move |fraction| {
+            tracked.status.lock().unwrap().progress = fraction;
+            if let Some(callback) = &on_progress {
+                callback(fraction);
+            }
+        });
+
+        let listener = TcpListener::bind(addr)?;
+        info!("Receiving uploads on {}", listener.local_addr()?);
+        Ok(UploadServer {
+            listener,
+            frames,
+            site,
+            options,
+        })
+    }
+}
+
+/// Scans the frames as they are uploaded until the transfer is complete, and writes it to the
+/// current directory unless an output is set.
+fn receive(
+    frames: &Receiver<SourceFrame>,
+    site: &Site,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
+    let mut scan = Scan::new(None, options);
+    let mut files = scan.resume()?;
+    while files.is_none() && !scan.should_stop() {
+        let frame = match frames.recv_timeout(POLL_INTERVAL) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        files = scan.feed_frame(frame, None)?;
+        site.status.lock().unwrap().scanned += 1;
+    }
+    let (files, stats) = scan.conclude(files)?;
+
+    let output_paths = write_files(&files, Path::new("."), options)?;
+    options.report_complete();
+    remove_session(options)?;
+    Ok(stats.result(files, &output_paths))
+}
+
+/// Accepts connections until the decode ends, answering each on its own thread so a slow
+/// upload does not hold up the page's polls.
+fn accept(listener: TcpListener, site: Arc<Site>) -> Result<()> {
+    while !site.stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let site = Arc::clone(&site);
+                thread::spawn(move || {
+                    if let Err(e) = respond(stream, &site) {
+                        debug!("Request from {} failed: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Reads one request from `stream` and answers it.
+fn respond(mut stream: TcpStream, site: &Site) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut head = Vec::new();
+    let mut buf = [0; 4096];
+    let body_start = loop {
+        if let Some(end) = head.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        let n = stream.read(&mut buf)?;
+        if n == 0 || head.len() + n > MAX_HEAD_BYTES {
+            break head.len();
+        }
+        head.extend_from_slice(&buf[..n]);
+    };
+    let mut body = head.split_off(body_start);
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let (method, target) = (request_line.next(), request_line.next().unwrap_or_default());
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, content_type, reply) = match (method, path) {
+        (Some("GET"), "/") | (Some("GET"), "/index.html") => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            INDEX_HTML.as_bytes().to_vec(),
+        ),
+        (Some("GET"), "/status") => ("200 OK", "application/json", status_json(site)),
+        (Some("POST"), "/upload") => {
+            let (status, reply) = match content_length(&head) {
+                None => ("411 Length Required", json!({"error": "No Content-Length"})),
+                Some(len) if len > MAX_UPLOAD_BYTES => (
+                    "413 Content Too Large",
+                    json!({"error": format!("Uploads are limited to {} MB", MAX_UPLOAD_BYTES >> 20)}),
+                ),
+                Some(len) => {
+                    body.truncate(len);
+                    let read = body.len();
+                    let mut rest = Read::by_ref(&mut stream).take((len - read) as u64);
+                    let Some(_slot) = UploadSlot::take(&site.uploading) else {
+                        // The body is read all the same, so the page gets the reply.
+                        io::copy(&mut rest, &mut io::sink())?;
+                        let error = json!({"error": "Another upload is in progress"});
+                        let reply = error.to_string().into_bytes();
+                        return reply_with(
+                            &mut stream,
+                            "503 Service Unavailable",
+                            "application/json",
+                            &reply,
+                        );
+                    };
+                    rest.read_to_end(&mut body)?;
+                    let name = query
+                        .split('&')
+                        .find_map(|param| param.strip_prefix("name="))
+                        .unwrap_or("upload");
+                    match upload(body, name, site) {
+                        Ok(frames) => ("200 OK", json!({"frames": frames})),
+                        Err((status, error)) => (status, json!({"error": error})),
+                    }
+                }
+            };
+            (status, "application/json", reply.to_string().into_bytes())
+        }
+        (Some("GET"), _) => ("404 Not Found", "text/plain", b"Not found\n".to_vec()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Method not allowed\n".to_vec(),
+        ),
+    };
+    reply_with(&mut stream, status, content_type, &reply)
+}
+
+fn reply_with(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    reply: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        reply.len()
+    )?;
+    stream.write_all(reply)?;
+    stream.flush()
+}
+
+/// Queues the frames of an uploaded photo or GIF for the scan, those of a GIF one at a time as
+/// they are decoded, since all of them at once might not fit in memory. Returns how many were
+/// queued, or the status and reason to reply with.
+fn upload(
+    bytes: Vec<u8>,
+    name: &str,
+    site: &Site,
+) -> std::result::Result<usize, (&'static str, String)> {
+    // Once the decode ends, frames left over from an upload that completed it are dropped.
+    let mut queued = 0;
+    let mut queue = |frame: SourceFrame| {
+        if site.frames.send(frame).is_err() {
+            return false;
+        }
+        site.status.lock().unwrap().uploaded += 1;
+        queued += 1;
+        true
+    };
+    if bytes.starts_with(b"GIF8") {
+        let mut gif = GifSource::from_reader(Cursor::new(bytes))
+            .map_err(|e| ("415 Unsupported Media Type", format!("{}: {}", name, e)))?;
+        while let Some(frame) = gif.next_frame() {
+            let frame = SourceFrame {
+                label: format!("{}: {}", name, frame.label),
+                ..frame
+            };
+            if !queue(frame) {
+                break;
+            }
+        }
+    } else {
+        let image = image_from_bytes(&bytes)
+            .map_err(|e| ("415 Unsupported Media Type", format!("{}: {}", name, e)))?;
+        queue(SourceFrame::image(image, name));
+    }
+
+    if queued == 0 {
+        return Err(("409 Conflict", "The decode has already ended".to_string()));
+    }
+    info!("Received {} ({} frame(s))", name, queued);
+    Ok(queued)
+}
+
+fn status_json(site: &Site) -> Vec<u8> {
+    let status = site.status.lock().unwrap();
+    json!({
+        "uploaded": status.uploaded,
+        "scanned": status.scanned,
+        "progress": status.progress,
+        "filename": status.filename,
+        "error": status.error,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+/// The `Content-Length` of a request head.
+fn content_length(head: &str) -> Option<usize> {
+    head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse().ok())?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_length() {
+        let head = "POST /upload HTTP/1.1\r\nHost: phone\r\ncontent-length: 1234\r\n\r\n";
+        assert_eq!(content_length(head), Some(1234));
+        assert_eq!(content_length("POST /upload HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(
+            content_length("POST /upload HTTP/1.1\r\nContent-Length: many\r\n"),
+            None
+        );
+    }
+}
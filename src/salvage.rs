@@ -0,0 +1,85 @@
+//! What a failed decode leaves behind with
+//! [`DecodeOptions::salvage_dir`](crate::decode::DecodeOptions::salvage_dir): the packets it
+//! collected, so a long scan is not lost, marked as partial.
+//!
+//! Fountain packets cannot be turned into part of the file: RaptorQ needs enough of them to
+//! recover any byte of the compressed transfer. They are written as `.pkt` files, which the
+//! directory can be decoded from together with more captures, and as a session file for
+//! `--resume`. Only txqr's plain frames place their bytes at known offsets, so the stretches of
+//! a txqr message received are written out as they are.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+use crate::chunk::{Chunk, PACKET_FILE_EXTENSION};
+use crate::error::Result;
+use crate::session::Session;
+use crate::stream::StreamDecoder;
+use crate::txqr::{TxqrReceiver, TXQR_FILENAME};
+
+/// Note telling what the salvage directory holds.
+const NOTE_FILENAME: &str = "PARTIAL.txt";
+/// Session file of the packets, for `--resume`.
+const SESSION_FILENAME: &str = "salvage.session";
+/// Suffix of the txqr message received in part.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Writes what `stream` and `txqr` collected into `dir`. Nothing is written if they collected
+/// nothing.
+pub(crate) fn write_salvage(
+    dir: &Path,
+    stream: &StreamDecoder,
+    txqr: Option<&TxqrReceiver>,
+) -> Result<()> {
+    let chunks: Vec<Chunk> = stream.chunks().cloned().collect();
+    let txqr_partial = txqr.and_then(TxqrReceiver::partial);
+    if chunks.is_empty() && txqr_partial.is_none() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+
+    let mut note = String::from(
+        "PARTIAL: this is what an unfinished decode collected, not a decoded file.\n\n",
+    );
+    if !chunks.is_empty() {
+        for (i, chunk) in chunks.iter().enumerate() {
+            let filename = format!("packet_{:04}.{}", i + 1, PACKET_FILE_EXTENSION);
+            fs::write(dir.join(filename), chunk.to_bytes()?)?;
+        }
+        let packets = chunks.len();
+        Session {
+            chunks,
+            ..Session::default()
+        }
+        .save(&dir.join(SESSION_FILENAME))?;
+        let _ = writeln!(
+            note,
+            "{} packet(s) of at least {} needed. Decode this directory together with more \
+             captures of the same transfer, or pass --resume {} with them.",
+            packets,
+            stream.expected_packets().unwrap_or_default(),
+            SESSION_FILENAME
+        );
+    }
+    if let Some((message, ranges)) = txqr_partial {
+        let filename = format!("{}{}", TXQR_FILENAME, PARTIAL_SUFFIX);
+        fs::write(dir.join(&filename), &message)?;
+        let received: Vec<String> = ranges
+            .iter()
+            .map(|range| format!("{}..{}", range.start, range.end))
+            .collect();
+        let _ = writeln!(
+            note,
+            "{} holds the {} bytes of the txqr message; only bytes {} were received, the rest \
+             are zeros.",
+            filename,
+            message.len(),
+            received.join(", ")
+        );
+    }
+    fs::write(dir.join(NOTE_FILENAME), note)?;
+    info!("Salvaged what was collected to {} (partial)", dir.display());
+    Ok(())
+}
@@ -0,0 +1,134 @@
+//! Clean-up for frames filmed off a screen, with [`DecodeOptions::filmed_screen`].
+//!
+//! A camera filming a monitor sees moiré, the interference of the screen's pixel grid with its
+//! sensor, and with a rolling shutter, horizontal bands where the screen refreshed part way
+//! through the exposure. Both break the thresholding rqrr relies on. A frame in which no code
+//! is found is tried again with the white of its rows levelled, which evens out the bands, and
+//! smoothed, which washes out the moiré; then averaged with the frame before it, which shows
+//! the same code when the sender holds each code for more than one capture.
+//!
+//! [`DecodeOptions::filmed_screen`]: crate::decode::DecodeOptions::filmed_screen
+
+use image::imageops::blur;
+use image::GrayImage;
+
+use crate::qr::decode_qrs_from_gray;
+
+/// Blur that washes out moiré, whose period is a few pixels, but keeps modules of a code large
+/// enough to read.
+const MOIRE_SIGMA: f32 = 1.2;
+/// Share of a row's pixels darker than what is taken as its white.
+const WHITE_RANK: f32 = 0.9;
+/// Rows on each side whose whites are averaged, so a row mostly covered by dark modules does
+/// not pass for a dark band.
+const BAND_RADIUS: usize = 6;
+/// Darkest white a row is levelled from; darker rows show no screen to level.
+const MIN_WHITE: f32 = 24.0;
+
+/// Retries frames of a filmed screen, remembering the last frame to average the next with.
+#[derive(Debug, Default)]
+pub(crate) struct ScreenFilter {
+    previous: Option<GrayImage>,
+}
+
+impl ScreenFilter {
+    /// The codes of a frame: `found`, those read from it as it is, or if there are none, those
+    /// read once it is cleaned up.
+    pub(crate) fn codes(&mut self, gray: &GrayImage, found: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        let previous = self.previous.replace(gray.clone());
+        if !found.is_empty() {
+            return found;
+        }
+        let codes = decode_qrs_from_gray(&clean(gray)).unwrap_or_default();
+        if !codes.is_empty() {
+            return codes;
+        }
+        match previous.filter(|previous| previous.dimensions() == gray.dimensions()) {
+            Some(previous) => {
+                decode_qrs_from_gray(&clean(&average(gray, &previous))).unwrap_or_default()
+            }
+            None => codes,
+        }
+    }
+}
+
+/// `gray` with its rows levelled to the same white and its moiré smoothed out.
+fn clean(gray: &GrayImage) -> GrayImage {
+    blur(&level_rows(gray), MOIRE_SIGMA)
+}
+
+/// `gray` with each row scaled so its white, averaged with its neighbours', is full white.
+fn level_rows(gray: &GrayImage) -> GrayImage {
+    let (width, height) = gray.dimensions();
+    let whites: Vec<f32> = gray
+        .rows()
+        .map(|row| {
+            let mut levels = [0u32; 256];
+            for pixel in row {
+                levels[pixel.0[0] as usize] += 1;
+            }
+            let rank = (width as f32 * WHITE_RANK) as u32;
+            let mut seen = 0;
+            levels
+                .iter()
+                .position(|&count| {
+                    seen += count;
+                    seen > rank
+                })
+                .unwrap_or(255) as f32
+        })
+        .collect();
+
+    let mut levelled = gray.clone();
+    for y in 0..height as usize {
+        let band = &whites[y.saturating_sub(BAND_RADIUS)..(y + BAND_RADIUS + 1).min(whites.len())];
+        let white = band.iter().sum::<f32>() / band.len() as f32;
+        if white < MIN_WHITE {
+            continue;
+        }
+        for x in 0..width {
+            let pixel = levelled.get_pixel_mut(x, y as u32);
+            pixel.0[0] = (pixel.0[0] as f32 * 255.0 / white).min(255.0) as u8;
+        }
+    }
+    levelled
+}
+
+/// The mean of two frames of the same size.
+fn average(a: &GrayImage, b: &GrayImage) -> GrayImage {
+    let mut mean = a.clone();
+    for (pixel, other) in mean.pixels_mut().zip(b.pixels()) {
+        pixel.0[0] = (u16::from(pixel.0[0]) + u16::from(other.0[0])).div_ceil(2) as u8;
+    }
+    mean
+}
+
+#[cfg(all(test, feature = "encode"))]
+mod tests {
+    use super::*;
+    use crate::qr::generate_qr_image;
+    use image::DynamicImage;
+
+    #[test]
+    fn test_filmed_screen() {
+        let data = b"A code filmed off a monitor";
+        let (image, _) = generate_qr_image(data, None, 6).unwrap();
+        let mut filmed = DynamicImage::ImageRgb8(image).to_luma8();
+        let height = filmed.height();
+        // A dark band where the screen refreshed mid-exposure, over moiré ripples.
+        for (x, y, pixel) in filmed.enumerate_pixels_mut() {
+            let level = 40.0 + f32::from(pixel.0[0]) / 255.0 * 160.0;
+            let band = if (height / 3..height * 2 / 3).contains(&y) {
+                0.3
+            } else {
+                1.0
+            };
+            let ripple = 30.0 * (x as f32 * 2.1 + y as f32 * 1.3).sin();
+            pixel.0[0] = (level * band + ripple).clamp(0.0, 255.0) as u8;
+        }
+
+        assert!(decode_qrs_from_gray(&filmed).unwrap_or_default().is_empty());
+        let codes = ScreenFilter::default().codes(&filmed, Vec::new());
+        assert_eq!(codes, vec![data.to_vec()]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Serving the QR carousel as a web page, so any device with a browser can be the display.
+//!
+//! The frames are rendered to PNG once, then a small HTTP/1.1 server hands out a page that
+//! cycles through them, the frames themselves and a JSON description of the transfer. Each
+//! connection gets one response and is closed; that is all a browser showing the page needs.
+//!
+//! The same carousel is also served as an MJPEG stream, which digital-signage players and OBS
+//! take as a live video source. The stream of a connection runs until the client goes away.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat};
+use serde_json::json;
+use std::io::{self, Cursor, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::encode::{Encoder, QrFrames};
+use crate::error::Result;
+use crate::progress::{is_cancelled, CancelFlag, Progress};
+
+/// How often the accept loop checks the cancel flag.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest request head read; the page only ever sends short GET requests.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// JPEG quality of stream frames; high enough that module edges stay sharp.
+const JPEG_QUALITY: u8 = 95;
+/// Separates the frames of the MJPEG stream.
+const BOUNDARY: &str = "fountainframe";
+
+const INDEX_HTML: &str = include_str!("serve.html");
+
+/// What the server hands out, shared by the connection threads.
+struct Site {
+    pngs: Vec<Vec<u8>>,
+    jpegs: Vec<Vec<u8>>,
+    info: String,
+    interval_ms: u64,
+    cancel: Option<CancelFlag>,
+}
+
+/// HTTP server showing QR frames as a carousel page, returned by [`Encoder::serve`].
+///
+/// `GET /` is the page, `GET /frames.json` describes the transfer and `GET /frame/N.png` is
+/// frame N, counted from 0. `GET /stream.mjpg` streams the frames as MJPEG at the encoder's
+/// interval, or at `?interval=MS`.
+pub struct FrameServer {
+    listener: TcpListener,
+    site: Arc<Site>,
+}
+
+impl FrameServer {
+    /// Address the server listens on, e.g. to learn the port picked for port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn total(&self) -> usize {
+        self.site.pngs.len()
+    }
+
+    /// Serves requests until the encoder's cancel flag is set. Each connection is handled on
+    /// its own thread, so a slow client does not hold up the others.
+    pub fn run(&self) -> Result<()> {
+        self.listener.set_nonblocking(true)?;
+        while !is_cancelled(&self.site.cancel) {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    let site = Arc::clone(&self.site);
+                    thread::spawn(move || {
+                        if let Err(e) = respond(stream, &site) {
+                            debug!("Request from {} failed: {}", peer, e);
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Encoder {
+    /// Renders `frames` and binds a [`FrameServer`] for them to `addr`. The page starts at the
+    /// encoder's interval; viewers can change it, pause and step through frames.
+    pub fn serve(&self, mut frames: QrFrames, addr: impl ToSocketAddrs) -> Result<FrameServer> {
+        let total = frames.total();
+        let progress = Progress::new(Some(total as u64), "frames");
+        let mut pngs = Vec::with_capacity(total);
+        let mut jpegs = Vec::with_capacity(total);
+        for (i, qr_image) in frames.by_ref().enumerate() {
+            self.options().check_cancelled()?;
+            let qr_image = DynamicImage::ImageRgb8(qr_image?);
+            let mut png = Vec::new();
+            qr_image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            pngs.push(png);
+            let mut jpeg = Vec::new();
+            JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode_image(&qr_image)?;
+            jpegs.push(jpeg);
+            progress.inc(|| {
+                (total <= 10 || ((i + 1) % 10 == 0 || i + 1 == total))
+                    .then(|| format!("  Rendered frame {}/{}", i + 1, total))
+            });
+            self.options().report_frame(i, total);
+        }
+
+        let info = json!({
+            "filename": frames.filename(),
+            "total": total,
+            "interval_ms": self.options().interval_ms,
+        })
+        .to_string();
+        let listener = TcpListener::bind(addr)?;
+        info!("Serving {} frame(s) on {}", total, listener.local_addr()?);
+        Ok(FrameServer {
+            listener,
+            site: Arc::new(Site {
+                pngs,
+                jpegs,
+                info,
+                interval_ms: self.options().interval_ms,
+                cancel: self.options().cancel.clone(),
+            }),
+        })
+    }
+}
+
+/// Reads one request from `stream` and answers it.
+fn respond(mut stream: TcpStream, site: &Site) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        if n == 0 || head.len() + n > MAX_REQUEST_BYTES {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let (method, target) = (request_line.next(), request_line.next().unwrap_or_default());
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    if (method, path) == (Some("GET"), "/stream.mjpg") {
+        let interval_ms = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("interval="))
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(site.interval_ms);
+        return stream_frames(stream, site, interval_ms);
+    }
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), "/") | (Some("GET"), "/index.html") => {
+            ("200 OK", "text/html; charset=utf-8", INDEX_HTML.as_bytes())
+        }
+        (Some("GET"), "/frames.json") => ("200 OK", "application/json", site.info.as_bytes()),
+        (Some("GET"), path) => match frame_index(path).and_then(|i| site.pngs.get(i)) {
+            Some(png) => ("200 OK", "image/png", png.as_slice()),
+            None => ("404 Not Found", "text/plain", &b"Not found\n"[..]),
+        },
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            &b"Method not allowed\n"[..],
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Sends the frames as an endless MJPEG stream, one every `interval_ms` on a fixed schedule,
+/// until the client disconnects or the server is cancelled.
+fn stream_frames(mut stream: TcpStream, site: &Site, interval_ms: u64) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+    let interval = Duration::from_millis(interval_ms.max(1));
+    let started = Instant::now();
+    for (shown, jpeg) in (1..).zip(site.jpegs.iter().cycle()) {
+        if is_cancelled(&site.cancel) {
+            break;
+        }
+        write!(
+            stream,
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            BOUNDARY,
+            jpeg.len()
+        )?;
+        stream.write_all(jpeg)?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+
+        let due = started + interval * shown;
+        while Instant::now() < due && !is_cancelled(&site.cancel) {
+            thread::sleep(POLL_INTERVAL.min(due.saturating_duration_since(Instant::now())));
+        }
+    }
+    Ok(())
+}
+
+/// Frame index of a `/frame/N.png` path.
+fn frame_index(path: &str) -> Option<usize> {
+    path.strip_prefix("/frame/")?
+        .strip_suffix(".png")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_index() {
+        assert_eq!(frame_index("/frame/0.png"), Some(0));
+        assert_eq!(frame_index("/frame/12.png"), Some(12));
+        assert_eq!(frame_index("/frame/12.gif"), None);
+        assert_eq!(frame_index("/frame/-1.png"), None);
+        assert_eq!(frame_index("/frames.json"), None);
+    }
+}
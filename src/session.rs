@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::chunk::Chunk;
+use crate::error::{FountainError, Result};
+
+// Session file: [Magic 6B] [Version 1B] [Count 4B] then Count x ([Length 2B] [Chunk bytes]),
+// then from version 2 [Count 4B] then Count x ([Frame hash 8B] [Codes 1B] then Codes x
+// ([Length 2B] [Code bytes]))
+const SESSION_MAGIC: &[u8; 6] = b"FQSESS";
+const SESSION_VERSION: u8 = 2;
+
+/// Packets collected by an unfinished decode, persisted so a later run can continue it. The
+/// session file holds [`Session::to_bytes`]; the serde form is for sending it elsewhere.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub chunks: Vec<Chunk>,
+    /// The QR codes read off every frame scanned so far, by a hash of its pixels; none for a
+    /// frame without a code. A later run over the same frames, such as a resumed or repeated
+    /// decode of one recording, takes them from here instead of detecting them again.
+    #[serde(default)]
+    pub detections: BTreeMap<u64, Vec<Vec<u8>>>,
+}
+
+impl Session {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(SESSION_MAGIC);
+        bytes.push(SESSION_VERSION);
+        bytes.extend_from_slice(&(self.chunks.len() as u32).to_be_bytes());
+
+        for chunk in &self.chunks {
+            let chunk_bytes = chunk.to_bytes()?;
+            let len = u16::try_from(chunk_bytes.len()).map_err(|_| invalid("chunk too large"))?;
+            bytes.extend_from_slice(&len.to_be_bytes());
+            bytes.extend_from_slice(&chunk_bytes);
+        }
+
+        bytes.extend_from_slice(&(self.detections.len() as u32).to_be_bytes());
+        for (hash, codes) in &self.detections {
+            bytes.extend_from_slice(&hash.to_be_bytes());
+            bytes.push(u8::try_from(codes.len()).map_err(|_| invalid("too many codes"))?);
+            for code in codes {
+                let len = u16::try_from(code.len()).map_err(|_| invalid("code too large"))?;
+                bytes.extend_from_slice(&len.to_be_bytes());
+                bytes.extend_from_slice(code);
+            }
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let header_len = SESSION_MAGIC.len() + 1 + 4;
+        if bytes.len() < header_len || &bytes[..SESSION_MAGIC.len()] != SESSION_MAGIC {
+            return Err(invalid("bad header"));
+        }
+        let version = bytes[SESSION_MAGIC.len()];
+        if !(1..=SESSION_VERSION).contains(&version) {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let mut reader = Reader {
+            bytes,
+            pos: SESSION_MAGIC.len() + 1,
+        };
+        let count = u32::from_be_bytes(reader.take_array()?) as usize;
+        let mut chunks = Vec::with_capacity(count.min(bytes.len() / 2));
+        for _ in 0..count {
+            let len = u16::from_be_bytes(reader.take_array()?) as usize;
+            chunks.push(Chunk::from_bytes(reader.take(len)?)?);
+        }
+
+        // Version 1 sessions predate the detection cache.
+        let mut detections = BTreeMap::new();
+        if version >= 2 {
+            let count = u32::from_be_bytes(reader.take_array()?);
+            for _ in 0..count {
+                let hash = u64::from_be_bytes(reader.take_array()?);
+                let [codes] = reader.take_array()?;
+                let codes = (0..codes)
+                    .map(|_| {
+                        let len = u16::from_be_bytes(reader.take_array()?) as usize;
+                        Ok(reader.take(len)?.to_vec())
+                    })
+                    .collect::<Result<_>>()?;
+                detections.insert(hash, codes);
+            }
+        }
+
+        Ok(Session { chunks, detections })
+    }
+
+    /// Loads a session, or returns an empty one if the file does not exist yet.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Session::default());
+        }
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes).map_err(|e| {
+            FountainError::InvalidSession(format!("failed to load {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Reads a session file front to back, failing on one that ends early.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let taken = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated"))?;
+        self.pos += len;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+}
+
+fn invalid(reason: &str) -> FountainError {
+    FountainError::InvalidSession(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkHeader;
+
+    #[test]
+    fn test_session_roundtrip() {
+        let session = Session {
+            chunks: (0..3)
+                .map(|i| Chunk {
+                    header: ChunkHeader {
+                        version: 1,
+                        file_id: 0,
+                        file_count: 1,
+                        segment: 0,
+                        transfer: 0,
+                        total: 1000,
+                        index: i,
+                        packet_size: 96,
+                        sequence: None,
+                    },
+                    data: [vec![0, 0, 0, i as u8], vec![i as u8; 96]].concat(),
+                })
+                .collect(),
+            detections: BTreeMap::from([(7, vec![]), (u64::MAX, vec![b"code".to_vec(); 2])]),
+        };
+
+        let restored = Session::from_bytes(&session.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.chunks.len(), 3);
+        assert_eq!(restored.detections, session.detections);
+        assert_eq!(restored.chunks[2].header.index, 2);
+        assert_eq!(restored.chunks[2].data, session.chunks[2].data);
+
+        let json = serde_json::to_string(&session).unwrap();
+        let restored: Session = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.chunks[1].header.index, 1);
+        assert_eq!(restored.chunks[1].data, session.chunks[1].data);
+
+        assert!(Session::from_bytes(b"garbage").is_err());
+
+        // A version 1 session ends after its chunks.
+        let mut old = session.to_bytes().unwrap();
+        old[SESSION_MAGIC.len()] = 1;
+        let chunks_end = old.len() - (4 + 8 + 1 + 8 + 1 + 2 * (2 + 4));
+        old.truncate(chunks_end);
+        let restored = Session::from_bytes(&old).unwrap();
+        assert_eq!(restored.chunks.len(), 3);
+        assert!(restored.detections.is_empty());
+    }
+}
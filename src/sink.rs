@@ -0,0 +1,315 @@
+//! Destinations for the frames of an encode. The image directory, GIF and terminal outputs are
+//! [`OutputSink`]s; implement the trait to send frames anywhere else, such as an LED matrix or
+//! a custom display, and pass it to [`Encoder::encode_to_sink`](crate::Encoder::encode_to_sink).
+
+use image::buffer::ConvertBuffer;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbImage, RgbaImage};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::chunk::Chunk;
+use crate::cover::cover_file_name;
+use crate::encode::{frame_file_name, gif_interval_ms, image_stem};
+use crate::error::Result;
+use crate::progress::Progress;
+use crate::qr::{render_qr_to_terminal, save_qr_image};
+
+/// What a sink learns about a transfer before its first frame.
+#[derive(Debug, Clone)]
+pub struct SinkInfo {
+    /// Name of the encoded file, as embedded in the transfer; the first one of a multi-file
+    /// transfer.
+    pub filename: String,
+    /// Frames that will follow, parity frames included.
+    pub total: usize,
+    /// Parity frames, which come after the regular ones.
+    pub parity: usize,
+    pub effective_size: usize,
+}
+
+/// One frame of a transfer.
+pub struct SinkFrame<'a> {
+    /// 0-based position among the frames.
+    pub index: usize,
+    pub total: usize,
+    /// Whether this is one of the parity frames at the end.
+    pub is_parity: bool,
+    /// The chunk the QR code holds, for sinks that render it themselves.
+    pub chunk: &'a Chunk,
+    /// The QR code, rendered at the encoder's pixel scale and quiet zone.
+    pub image: &'a RgbImage,
+}
+
+/// Receives the frames of an encode, in order.
+///
+/// The encoder calls [`OutputSink::begin`] once, [`OutputSink::cover`] if a cover sheet was
+/// asked for, [`OutputSink::write_frame`] for each frame, then [`OutputSink::finish`]. It checks
+/// for cancellation and reports progress callbacks between frames itself.
+pub trait OutputSink {
+    fn begin(&mut self, _info: &SinkInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// The cover sheet (see [`EncodeOptions::cover`](crate::EncodeOptions::cover)), drawn the
+    /// size of a frame. Ignored unless implemented.
+    fn cover(&mut self, _cover: &RgbImage) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &SinkFrame<'_>) -> Result<()>;
+
+    /// Completes the output and returns the names of the files written, if any, for
+    /// [`EncodeResult::output_files`](crate::EncodeResult::output_files).
+    fn finish(&mut self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Writes one PNG per frame into a directory, named after the file, with the cover sheet
+/// first.
+pub struct ImageDirSink {
+    output_dir: PathBuf,
+    stem: Option<String>,
+    regular: usize,
+    output_files: Vec<String>,
+    progress: Option<Progress>,
+}
+
+impl ImageDirSink {
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            stem: None,
+            regular: 0,
+            output_files: Vec::new(),
+            progress: None,
+        }
+    }
+
+    /// Names the images after `stem` instead of the encoded file.
+    pub fn with_stem(mut self, stem: String) -> Self {
+        self.stem = Some(stem);
+        self
+    }
+}
+
+impl OutputSink for ImageDirSink {
+    fn begin(&mut self, info: &SinkInfo) -> Result<()> {
+        fs::create_dir_all(&self.output_dir)?;
+        self.stem.get_or_insert_with(|| image_stem(&info.filename));
+        self.regular = info.total - info.parity;
+        self.output_files = Vec::with_capacity(info.total + 1);
+        self.progress = Some(Progress::new(Some(info.total as u64), "QR codes"));
+        Ok(())
+    }
+
+    fn cover(&mut self, cover: &RgbImage) -> Result<()> {
+        let cover_filename = cover_file_name(self.stem.as_deref().unwrap_or_default());
+        save_qr_image(cover, &self.output_dir.join(&cover_filename))?;
+        self.output_files.push(cover_filename);
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &SinkFrame<'_>) -> Result<()> {
+        let stem = self.stem.as_deref().unwrap_or_default();
+        let output_filename = frame_file_name(stem, frame.index, self.regular);
+        save_qr_image(frame.image, &self.output_dir.join(&output_filename))?;
+
+        if let Some(progress) = &self.progress {
+            progress.inc(|| {
+                Some(format!(
+                    "  Generated QR code {}/{}: {}",
+                    frame.index + 1,
+                    frame.total,
+                    output_filename
+                ))
+            });
+        }
+        self.output_files.push(output_filename);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<String>> {
+        self.progress = None;
+        Ok(std::mem::take(&mut self.output_files))
+    }
+}
+
+/// Writes the frames as a looping animated GIF, the cover sheet first.
+pub struct GifSink<W: Write> {
+    encoder: GifEncoder<W>,
+    delay: Delay,
+    /// Whether every pixel is snapped to black or white, see [`GifSink::strict_palette`].
+    strict_palette: bool,
+    /// Times each frame is written in a row, see [`GifSink::repeat_frames`].
+    repeat: u32,
+    output: Option<String>,
+    progress: Option<Progress>,
+}
+
+impl GifSink<File> {
+    /// Creates the GIF file at `path`, showing each frame for `interval_ms` (see
+    /// [`gif_interval_ms`]).
+    pub fn create(path: &Path, interval_ms: u64) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut sink = Self::new(File::create(path)?, interval_ms)?;
+        sink.output = Some(path.to_string_lossy().to_string());
+        Ok(sink)
+    }
+}
+
+impl<W: Write> GifSink<W> {
+    /// Writes the GIF to `writer`, showing each frame for `interval_ms`.
+    pub fn new(writer: W, interval_ms: u64) -> Result<Self> {
+        let mut encoder = GifEncoder::new(writer);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let delay =
+            Delay::from_saturating_duration(Duration::from_millis(gif_interval_ms(interval_ms)));
+        Ok(Self::with_encoder(encoder, delay))
+    }
+
+    /// Adds the frames to a GIF being written, each shown for `delay`.
+    pub(crate) fn with_encoder(encoder: GifEncoder<W>, delay: Delay) -> Self {
+        Self {
+            encoder,
+            delay,
+            strict_palette: false,
+            repeat: 1,
+            output: None,
+            progress: None,
+        }
+    }
+
+    /// Snap every pixel to black or white by its brightness, so each frame is written with a
+    /// palette of exactly those two colours whatever was drawn on it. QR frames are already
+    /// pure black and white; this keeps anything else from bringing in gray.
+    pub fn strict_palette(mut self, strict: bool) -> Self {
+        self.strict_palette = strict;
+        self
+    }
+
+    /// Write each frame `times` times in a row (0 counts as 1), so it stays up for that many
+    /// intervals. A camera with a slow shutter then gets several exposures of the same code,
+    /// which reads more reliably than one long frame. The cover sheet is written once.
+    pub fn repeat_frames(mut self, times: u32) -> Self {
+        self.repeat = times.max(1);
+        self
+    }
+
+    /// Writes `image` as [`GifSink::repeat_frames`] frames.
+    pub(crate) fn encode(&mut self, image: &RgbImage) -> Result<()> {
+        for _ in 0..self.repeat {
+            self.encode_once(image)?;
+        }
+        Ok(())
+    }
+
+    fn encode_once(&mut self, image: &RgbImage) -> Result<()> {
+        let mut rgba_image: RgbaImage = image.convert();
+        if self.strict_palette {
+            for pixel in rgba_image.pixels_mut() {
+                let [r, g, b, _] = pixel.0;
+                let luma = (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000;
+                let level = if luma < 128 { 0 } else { 255 };
+                pixel.0 = [level, level, level, 255];
+            }
+        }
+        self.encoder
+            .encode_frame(Frame::from_parts(rgba_image, 0, 0, self.delay))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputSink for GifSink<W> {
+    fn begin(&mut self, info: &SinkInfo) -> Result<()> {
+        self.progress = Some(Progress::new(Some(info.total as u64), "frames"));
+        Ok(())
+    }
+
+    fn cover(&mut self, cover: &RgbImage) -> Result<()> {
+        self.encode_once(cover)
+    }
+
+    fn write_frame(&mut self, frame: &SinkFrame<'_>) -> Result<()> {
+        self.encode(frame.image)?;
+        if let Some(progress) = &self.progress {
+            let (i, total) = (frame.index, frame.total);
+            progress.inc(|| {
+                (total <= 10 || ((i + 1) % 10 == 0 || i + 1 == total))
+                    .then(|| format!("  Processed frame {}/{}", i + 1, total))
+            });
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<String>> {
+        self.progress = None;
+        Ok(self.output.iter().cloned().collect())
+    }
+}
+
+/// Prints every frame once as a text QR code, one after the other, each under a line naming
+/// the file and its position.
+pub struct TerminalSink<W: Write> {
+    writer: W,
+    filename: String,
+}
+
+impl<W: Write> TerminalSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            filename: String::new(),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for TerminalSink<W> {
+    fn begin(&mut self, info: &SinkInfo) -> Result<()> {
+        self.filename = info.filename.clone();
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &SinkFrame<'_>) -> Result<()> {
+        let qr_string = render_qr_to_terminal(frame.chunk.to_text()?.as_bytes())?;
+        if frame.index > 0 {
+            writeln!(self.writer, "\n{}\n", "-".repeat(50))?;
+        }
+        write_terminal_frame(
+            &mut self.writer,
+            &qr_string,
+            &self.filename,
+            frame.index + 1,
+            frame.total,
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<String>> {
+        self.writer.flush()?;
+        Ok(Vec::new())
+    }
+}
+
+/// Writes one terminal QR code under a header naming the file and its position.
+pub(crate) fn write_terminal_frame<W: Write>(
+    writer: &mut W,
+    qr_string: &str,
+    filename: &str,
+    current: usize,
+    total: usize,
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "File: {}  |  QR Code {}/{}",
+        filename, current, total
+    )?;
+    writeln!(writer, "{}", "=".repeat(50))?;
+    writeln!(writer)?;
+    writeln!(writer, "{}", qr_string)
+}
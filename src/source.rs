@@ -0,0 +1,352 @@
+//! Inputs of a decode. A GIF, a directory of QR images or packet files, a text file of packet
+//! lines and images already in memory are [`FrameSource`]s; implement the trait to decode from
+//! anything else, such as a camera or a screen capture, with
+//! [`Decoder::decode_source`](crate::Decoder::decode_source).
+
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::chunk::Chunk;
+use crate::decode::{is_packet_file, is_packet_lines, is_qr_image, parse_packet_line};
+use crate::error::{FountainError, Result};
+use crate::qr::open_image;
+
+/// One frame read from an input: a QR image, or a packet written by
+/// [`crate::Encoder::export_packets`] or read from a text line, which needs no QR decoding.
+pub enum InputFrame {
+    Image(DynamicImage),
+    Packet(Chunk),
+}
+
+/// A frame of a [`FrameSource`], with where it came from.
+pub struct SourceFrame {
+    /// The frame, or why it could not be read; a frame that fails is counted and skipped.
+    pub frame: Result<InputFrame>,
+    /// Where the frame came from, such as `frame 12` or an image's file name, for log messages
+    /// and [`ScannedFrame::label`](crate::ScannedFrame::label).
+    pub label: String,
+    /// When the frame was shown or captured, from the start of the source, if known.
+    pub timestamp: Option<Duration>,
+}
+
+impl SourceFrame {
+    pub fn image(image: DynamicImage, label: impl Into<String>) -> Self {
+        Self {
+            frame: Ok(InputFrame::Image(image)),
+            label: label.into(),
+            timestamp: None,
+        }
+    }
+
+    pub fn timestamp(mut self, timestamp: Duration) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+}
+
+/// Frames to decode, read one at a time. The decoder stops asking once the transfer
+/// completes, so a live source need not end.
+pub trait FrameSource {
+    /// The next frame, or `None` once the source is exhausted.
+    fn next_frame(&mut self) -> Option<SourceFrame>;
+
+    /// Number of frames, when known up front, for progress.
+    fn len_hint(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl dyn FrameSource {
+    /// The remaining frames as an iterator.
+    pub fn frames(&mut self) -> impl Iterator<Item = SourceFrame> + '_ {
+        std::iter::from_fn(move || self.next_frame())
+    }
+}
+
+/// Opens the source for a GIF file, a text file of packet lines or a directory of QR images
+/// or packets.
+pub fn open_source(input: &Path) -> Result<Box<dyn FrameSource>> {
+    if !input.exists() {
+        return Err(FountainError::InputNotFound(input.to_path_buf()));
+    }
+    if input.is_dir() {
+        return Ok(Box::new(DirSource::open(input)?));
+    }
+
+    let is_gif = input
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("gif"))
+        .unwrap_or(false);
+    if is_gif {
+        Ok(Box::new(GifSource::open(input)?))
+    } else if is_packet_lines(input) {
+        Ok(Box::new(PacketLinesSource::open(input)?))
+    } else {
+        Err(FountainError::UnsupportedInput(input.to_path_buf()))
+    }
+}
+
+/// Consecutive frames that may fail to load before the rest of a GIF is given up on.
+const MAX_GIF_FRAME_ERRORS: usize = 3;
+
+/// Frames of a GIF, stamped with the time each is shown at.
+///
+/// A frame that fails to load is passed on as an error so it can be skipped. Once a truncated
+/// or corrupt GIF stops yielding frames altogether (the decoder cannot resynchronise and fails
+/// on every call), the source ends, keeping the frames read so far.
+pub struct GifSource<'a> {
+    frames: Box<dyn Iterator<Item = SourceFrame> + 'a>,
+}
+
+impl GifSource<'static> {
+    pub fn open(input_file: &Path) -> Result<Self> {
+        if !input_file.exists() {
+            return Err(FountainError::InputNotFound(input_file.to_path_buf()));
+        }
+        let file = File::open(input_file)?;
+        info!("Decoding QR codes from GIF: {}", input_file.display());
+        Self::from_reader(BufReader::new(file))
+    }
+}
+
+impl<'a> GifSource<'a> {
+    pub fn from_reader<R: BufRead + Seek + 'a>(reader: R) -> Result<Self> {
+        let frames = GifDecoder::new(reader)?.into_frames();
+        let mut errors = 0;
+        let mut shown_at = Duration::ZERO;
+        let frames = frames.enumerate().map_while(move |(i, frame_result)| {
+            let label = format!("frame {}", i + 1);
+            match frame_result {
+                Ok(frame) => {
+                    errors = 0;
+                    let timestamp = shown_at;
+                    shown_at += Duration::from(frame.delay());
+                    let image = DynamicImage::ImageRgba8(frame.into_buffer());
+                    Some(SourceFrame::image(image, label).timestamp(timestamp))
+                }
+                Err(_) if errors >= MAX_GIF_FRAME_ERRORS => {
+                    warn!("GIF is unreadable after {}; ignoring the rest", label);
+                    None
+                }
+                Err(e) => {
+                    errors += 1;
+                    Some(SourceFrame {
+                        frame: Err(FountainError::from(e)),
+                        label,
+                        timestamp: None,
+                    })
+                }
+            }
+        });
+        Ok(Self {
+            frames: Box::new(frames),
+        })
+    }
+}
+
+impl FrameSource for GifSource<'_> {
+    fn next_frame(&mut self) -> Option<SourceFrame> {
+        self.frames.next()
+    }
+}
+
+/// QR images and packet files of a directory, in file name order, each read when it is
+/// reached.
+pub struct DirSource {
+    paths: std::vec::IntoIter<PathBuf>,
+    total: u64,
+}
+
+impl DirSource {
+    pub fn open(input_dir: &Path) -> Result<Self> {
+        if !input_dir.exists() {
+            return Err(FountainError::InputNotFound(input_dir.to_path_buf()));
+        }
+        let mut frame_files: Vec<_> = fs::read_dir(input_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_qr_image(path) || is_packet_file(path))
+            .collect();
+
+        #[cfg(not(feature = "heif"))]
+        {
+            let heif_photos = fs::read_dir(input_dir)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry.path().extension().is_some_and(|ext| {
+                        ext.eq_ignore_ascii_case("heic") || ext.eq_ignore_ascii_case("heif")
+                    })
+                })
+                .count();
+            if heif_photos > 0 {
+                warn!(
+                    "Skipping {} HEIC/HEIF photo(s): this build cannot read them; build with --features heif, or convert them to JPEG",
+                    heif_photos
+                );
+            }
+        }
+        if frame_files.is_empty() {
+            return Err(FountainError::NoImagesFound(input_dir.to_path_buf()));
+        }
+        frame_files.sort();
+
+        let packets = frame_files
+            .iter()
+            .filter(|path| is_packet_file(path))
+            .count();
+        if packets < frame_files.len() {
+            info!("Found {} QR code image(s)", frame_files.len() - packets);
+        }
+        if packets > 0 {
+            info!("Found {} packet file(s)", packets);
+        }
+        Ok(Self {
+            total: frame_files.len() as u64,
+            paths: frame_files.into_iter(),
+        })
+    }
+}
+
+impl FrameSource for DirSource {
+    fn next_frame(&mut self) -> Option<SourceFrame> {
+        let path = self.paths.next()?;
+        let label = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let frame = if is_packet_file(&path) {
+            read_packet(&path)
+        } else {
+            open_image(&path).map(InputFrame::Image)
+        };
+        Some(SourceFrame {
+            frame,
+            label,
+            timestamp: None,
+        })
+    }
+
+    fn len_hint(&self) -> Option<u64> {
+        Some(self.total)
+    }
+}
+
+fn read_packet(path: &Path) -> Result<InputFrame> {
+    Ok(InputFrame::Packet(Chunk::from_bytes(&fs::read(path)?)?))
+}
+
+/// Packets of a text file with one per line (see
+/// [`Decoder::decode_packet_lines`](crate::Decoder::decode_packet_lines)). Lines that are not
+/// packets are passed on as errors.
+pub struct PacketLinesSource {
+    lines: std::vec::IntoIter<(usize, String)>,
+    total: u64,
+}
+
+impl PacketLinesSource {
+    pub fn open(input_file: &Path) -> Result<Self> {
+        if !input_file.exists() {
+            return Err(FountainError::InputNotFound(input_file.to_path_buf()));
+        }
+        let text = fs::read_to_string(input_file)?;
+        info!("Decoding packets from text file: {}", input_file.display());
+        Ok(Self::from_text(&text))
+    }
+
+    /// The packets of `text`, already read from a file.
+    pub(crate) fn from_text(text: &str) -> Self {
+        let lines: Vec<(usize, String)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim().to_string()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+        info!("Found {} packet line(s)", lines.len());
+        Self {
+            total: lines.len() as u64,
+            lines: lines.into_iter(),
+        }
+    }
+}
+
+impl FrameSource for PacketLinesSource {
+    fn next_frame(&mut self) -> Option<SourceFrame> {
+        let (number, line) = self.lines.next()?;
+        Some(SourceFrame {
+            frame: parse_packet_line(&line).map(InputFrame::Packet),
+            label: format!("line {}", number),
+            timestamp: None,
+        })
+    }
+
+    fn len_hint(&self) -> Option<u64> {
+        Some(self.total)
+    }
+}
+
+/// Images already in memory, such as camera captures or an upload, labelled `frame N`.
+pub struct ImageSource<I> {
+    images: I,
+    index: usize,
+}
+
+impl<I: Iterator<Item = DynamicImage>> ImageSource<I> {
+    pub fn new(images: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            images: images.into_iter(),
+            index: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = DynamicImage>> FrameSource for ImageSource<I> {
+    fn next_frame(&mut self) -> Option<SourceFrame> {
+        let image = self.images.next()?;
+        self.index += 1;
+        Some(SourceFrame::image(image, format!("frame {}", self.index)))
+    }
+
+    fn len_hint(&self) -> Option<u64> {
+        self.images.size_hint().1.map(|n| n as u64)
+    }
+}
+
+/// Several sources read one after the other, each frame labelled with its source's name.
+pub(crate) struct ChainSource {
+    sources: VecDeque<(String, Box<dyn FrameSource>)>,
+}
+
+impl ChainSource {
+    pub(crate) fn new(sources: Vec<(String, Box<dyn FrameSource>)>) -> Self {
+        Self {
+            sources: sources.into(),
+        }
+    }
+}
+
+impl FrameSource for ChainSource {
+    fn next_frame(&mut self) -> Option<SourceFrame> {
+        while let Some((name, source)) = self.sources.front_mut() {
+            if let Some(mut frame) = source.next_frame() {
+                frame.label = format!("{}: {}", name, frame.label);
+                return Some(frame);
+            }
+            self.sources.pop_front();
+        }
+        None
+    }
+
+    fn len_hint(&self) -> Option<u64> {
+        self.sources
+            .iter()
+            .map(|(_, source)| source.len_hint())
+            .sum()
+    }
+}
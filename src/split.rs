@@ -0,0 +1,339 @@
+//! Very large files can be split into several independent transfers, one GIF or image set per
+//! part, and joined again by the decoder.
+//!
+//! Each part is an ordinary single-file transfer. Its embedded filename records the original
+//! name, the part number and the checksum of the whole file:
+//! `<name>.part<N>-of-<count>.<checksum>`.
+
+use std::path::Path;
+#[cfg(feature = "encode")]
+use std::path::PathBuf;
+#[cfg(feature = "encode")]
+use std::time::Instant;
+use tracing::info;
+
+use crate::chunk::calculate_checksum;
+#[cfg(feature = "encode")]
+use crate::chunker::{encode_memory, IMG_REDUNDANCY_FACTOR};
+#[cfg(feature = "decode")]
+use crate::decode::{scan_frames, write_files, DecodeOptions, DecodeResult, Decoder};
+#[cfg(feature = "encode")]
+use crate::encode::{read_inputs, EncodeResult, Encoder};
+use crate::error::{FountainError, Result};
+#[cfg(feature = "decode")]
+use crate::source::open_source;
+#[cfg(feature = "decode")]
+use crate::stream::DecodedFile;
+
+/// Metadata of one part of a split file, as recorded in the part's filename.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitPart {
+    /// Name of the whole file.
+    pub filename: String,
+    /// 1-based part number.
+    pub index: usize,
+    pub count: usize,
+    /// Hex checksum of the whole file.
+    pub checksum: String,
+}
+
+impl SplitPart {
+    /// Filename under which this part travels.
+    pub fn part_name(&self) -> String {
+        format!(
+            "{}.part{}-of-{}.{}",
+            self.filename, self.index, self.count, self.checksum
+        )
+    }
+
+    /// Reads the metadata back from a part's filename. `None` if it does not name a part.
+    pub fn parse(part_name: &str) -> Option<Self> {
+        let (rest, checksum) = part_name.rsplit_once('.')?;
+        let (filename, part) = rest.rsplit_once('.')?;
+        let (index, count) = part.strip_prefix("part")?.split_once("-of-")?;
+        let (index, count) = (index.parse().ok()?, count.parse().ok()?);
+        let valid = !filename.is_empty()
+            && (1..=count).contains(&index)
+            && checksum.len() == 2 * crate::chunk::CHECKSUM_SIZE
+            && checksum.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| SplitPart {
+            filename: filename.to_string(),
+            index,
+            count,
+            checksum: checksum.to_string(),
+        })
+    }
+}
+
+/// Hex checksum of a whole file, as recorded in the names of its parts.
+fn hex_checksum(data: &[u8]) -> String {
+    calculate_checksum(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Cuts `data` into parts of at most `split_size` bytes, each paired with the filename it
+/// travels under. An empty file still makes one part.
+pub fn split_file<'a>(
+    data: &'a [u8],
+    filename: &str,
+    split_size: usize,
+) -> Vec<(&'a [u8], String)> {
+    let checksum = hex_checksum(data);
+    let parts: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(split_size.max(1)).collect()
+    };
+    let count = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let meta = SplitPart {
+                filename: filename.to_string(),
+                index: i + 1,
+                count,
+                checksum: checksum.clone(),
+            };
+            (part, meta.part_name())
+        })
+        .collect()
+}
+
+/// Joins the decoded parts of a split file, in any order, and verifies the checksum of the
+/// whole file. Every part must be present exactly once.
+#[cfg(feature = "decode")]
+pub fn join_parts(parts: Vec<DecodedFile>) -> Result<DecodedFile> {
+    let mut parsed = Vec::with_capacity(parts.len());
+    for part in parts {
+        let meta = SplitPart::parse(&part.filename).ok_or_else(|| {
+            FountainError::InvalidSplit(format!("{} is not part of a split file", part.filename))
+        })?;
+        parsed.push((meta, part.data));
+    }
+    let Some((first, _)) = parsed.first() else {
+        return Err(FountainError::InvalidSplit("no parts given".to_string()));
+    };
+    let (filename, count, checksum) = (first.filename.clone(), first.count, first.checksum.clone());
+    if let Some((other, _)) = parsed.iter().find(|(meta, _)| {
+        (&meta.filename, meta.count, &meta.checksum) != (&filename, count, &checksum)
+    }) {
+        return Err(FountainError::InvalidSplit(format!(
+            "{} does not belong with {}",
+            other.part_name(),
+            first.part_name()
+        )));
+    }
+
+    parsed.sort_by_key(|(meta, _)| meta.index);
+    for (expected, (meta, _)) in (1..).zip(&parsed) {
+        if meta.index < expected {
+            return Err(FountainError::InvalidSplit(format!(
+                "part {} of {} is repeated",
+                meta.index, count
+            )));
+        }
+        if meta.index > expected {
+            return Err(FountainError::InvalidSplit(format!(
+                "part {} of {} is missing",
+                expected, count
+            )));
+        }
+    }
+    if parsed.len() != count {
+        return Err(FountainError::InvalidSplit(format!(
+            "part {} of {} is missing",
+            parsed.len() + 1,
+            count
+        )));
+    }
+
+    let data: Vec<u8> = parsed.into_iter().flat_map(|(_, data)| data).collect();
+    let actual = hex_checksum(&data);
+    if actual != checksum {
+        return Err(FountainError::ChecksumMismatch {
+            expected: checksum.into_bytes(),
+            actual: actual.into_bytes(),
+        });
+    }
+    Ok(DecodedFile { filename, data })
+}
+
+/// Output path of part `index` (1-based) for a split encode to `output`: `out.gif` becomes
+/// `out.part1.gif`, and an image directory `out` becomes `out/part1`.
+#[cfg(feature = "encode")]
+fn part_output(output: &Path, index: usize, gif: bool) -> PathBuf {
+    if !gif {
+        return output.join(format!("part{}", index));
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}.part{}.gif", stem, index))
+}
+
+#[cfg(feature = "encode")]
+impl Encoder {
+    /// Splits the input into parts of at most `split_size` bytes and writes each part as its
+    /// own GIF next to `output_gif`, named `<stem>.part<N>.gif`. Returns one result per part.
+    pub fn encode_split_to_gif(
+        &self,
+        input_path: &Path,
+        output_gif: &Path,
+        split_size: usize,
+    ) -> Result<Vec<EncodeResult>> {
+        self.encode_split(input_path, output_gif, split_size, true)
+    }
+
+    /// Like [`Encoder::encode_split_to_gif`], writing the images of each part to
+    /// `<output_dir>/part<N>`.
+    pub fn encode_split_to_images(
+        &self,
+        input_path: &Path,
+        output_dir: &Path,
+        split_size: usize,
+    ) -> Result<Vec<EncodeResult>> {
+        self.encode_split(input_path, output_dir, split_size, false)
+    }
+
+    fn encode_split(
+        &self,
+        input_path: &Path,
+        output: &Path,
+        split_size: usize,
+        gif: bool,
+    ) -> Result<Vec<EncodeResult>> {
+        let (data, filename) = read_inputs(&[input_path], self.options().max_memory)?.remove(0);
+        let split_size = self.split_size_within_memory(data.len(), split_size)?;
+        let parts = split_file(&data, &filename, split_size);
+
+        let mut results = Vec::with_capacity(parts.len());
+        for (i, (part, part_name)) in parts.iter().enumerate() {
+            let started = Instant::now();
+            let output = part_output(output, i + 1, gif);
+            info!(
+                "Encoding part {}/{}: {}",
+                i + 1,
+                parts.len(),
+                output.display()
+            );
+
+            let mut frames = self.frames_from_bytes(part, part_name)?;
+            let output_files = if gif {
+                if let Some(parent) = output.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                self.write_gif(&mut frames, std::fs::File::create(&output)?)?;
+                vec![output.to_string_lossy().to_string()]
+            } else {
+                self.write_images(&mut frames, &output)?
+            };
+            results.push(frames.result(output_files, started));
+        }
+        Ok(results)
+    }
+
+    /// Lowers `split_size` so that encoding one part, with the whole input in memory, stays
+    /// within the memory limit.
+    fn split_size_within_memory(&self, input_bytes: usize, split_size: usize) -> Result<usize> {
+        let Some(limit) = self.options().max_memory else {
+            return Ok(split_size);
+        };
+        let needed = |part: usize| input_bytes + encode_memory(part, part, IMG_REDUNDANCY_FACTOR);
+        if needed(split_size) <= limit {
+            return Ok(split_size);
+        }
+        // A part that does not compress takes its size three times over, plus its packets.
+        let spare = limit.saturating_sub(input_bytes) as f64;
+        let fitting = (spare / (3.0 + IMG_REDUNDANCY_FACTOR)) as usize;
+        if fitting == 0 || needed(fitting) > limit {
+            return Err(FountainError::MemoryLimitExceeded {
+                needed: needed(1),
+                limit,
+            });
+        }
+        info!(
+            "Lowering the split size to {} bytes to stay within the memory limit",
+            fitting
+        );
+        Ok(fitting)
+    }
+}
+
+#[cfg(feature = "decode")]
+impl Decoder {
+    /// Decodes the parts of a split file, one GIF or image directory per input in any order,
+    /// then joins them, verifies the whole file and writes it like [`Decoder::decode`]. The
+    /// session file option does not apply.
+    pub fn decode_split(&self, inputs: &[&Path]) -> Result<DecodeResult> {
+        let options = DecodeOptions {
+            session_file: None,
+            ..self.options().clone()
+        };
+
+        let mut parts = Vec::with_capacity(inputs.len());
+        let mut stats = None;
+        for input in inputs {
+            let (files, part_stats) = scan_frames(open_source(input)?.as_mut(), &options)?;
+            match &mut stats {
+                None => stats = Some(part_stats),
+                Some(stats) => stats.merge(part_stats),
+            }
+            parts.extend(files);
+        }
+        let Some(stats) = stats else {
+            return Err(FountainError::InvalidSplit("no parts given".to_string()));
+        };
+
+        let count = parts.len();
+        let file = join_parts(parts)?;
+        info!("Joined {} part(s) into {}", count, file.filename);
+
+        let files = vec![file];
+        let output_paths = write_files(&files, Path::new("."), &options)?;
+        options.report_complete();
+        Ok(stats.result(files, &output_paths))
+    }
+}
+
+#[cfg(all(test, feature = "decode"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_join() {
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+        let parts = split_file(&data, "big.bin", 1000);
+        assert_eq!(parts.len(), 3);
+
+        let meta = SplitPart::parse(&parts[1].1).unwrap();
+        assert_eq!(
+            (meta.filename.as_str(), meta.index, meta.count),
+            ("big.bin", 2, 3)
+        );
+        assert_eq!(SplitPart::parse("notes.txt"), None);
+
+        let decoded: Vec<DecodedFile> = parts
+            .iter()
+            .rev()
+            .map(|(part, name)| DecodedFile {
+                filename: name.clone(),
+                data: part.to_vec(),
+            })
+            .collect();
+        let joined = join_parts(decoded.clone()).unwrap();
+        assert_eq!(joined.filename, "big.bin");
+        assert_eq!(joined.data, data);
+
+        assert!(matches!(
+            join_parts(decoded[1..].to_vec()),
+            Err(FountainError::InvalidSplit(_))
+        ));
+        let mut tampered = decoded;
+        tampered[0].data[0] ^= 1;
+        assert!(matches!(
+            join_parts(tampered),
+            Err(FountainError::ChecksumMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,258 @@
+use fountain_core::{AssembleResult, ChunkAssembler, ManifestAssembler, ManifestResult};
+use image::{DynamicImage, GrayImage};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::warn;
+
+use crate::chunk::{Chunk, Manifest};
+use crate::error::{FountainError, Result};
+use crate::qr::decode_qr_from_gray;
+
+/// A file reconstructed by a [`StreamDecoder`].
+#[derive(Debug, Clone)]
+pub struct DecodedFile {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+/// Outcome of feeding one frame or chunk to a [`StreamDecoder`].
+#[derive(Debug)]
+pub enum FeedResult {
+    /// The frame holds no QR code, or the QR code is not a fountain chunk.
+    NoChunk,
+    /// A new packet was added, but more are needed.
+    Accepted,
+    /// The packet had already been received, or belongs to a file that is already complete. Also
+    /// reported for a manifest part that completed a manifest that did not parse, which is
+    /// dropped.
+    Duplicate,
+    /// One file of a multi-file transfer is reconstructed; other files still need packets.
+    FileComplete(DecodedFile),
+    /// Enough packets have arrived and the original file is reconstructed. In a multi-file
+    /// transfer, this is the last file to complete. In an output that holds several transfers,
+    /// it is reported once per transfer.
+    Complete(DecodedFile),
+    /// The manifest of a multi-file transfer is complete: the files it will bring, known
+    /// before any of them decodes. Reported once per transfer.
+    Manifest(Manifest),
+}
+
+/// Push-based decoder: feed it frames from any source and it reports when the file is complete.
+/// Packets of a multi-file transfer are sorted by file, and each file is reported as it
+/// completes. Packets of different transfers (files appended to an output) are kept apart, and
+/// so are packets of another encoding of the same file, which cannot be combined with them.
+///
+/// ```no_run
+/// use fountain::{FeedResult, StreamDecoder};
+///
+/// let mut decoder = StreamDecoder::new();
+/// for path in ["frame1.png", "frame2.png"] {
+///     if let FeedResult::Complete(file) = decoder.feed_image(&image::open(path)?)? {
+///         std::fs::write(&file.filename, &file.data)?;
+///         break;
+///     }
+/// }
+/// # Ok::<(), fountain::FountainError>(())
+/// ```
+#[derive(Default)]
+pub struct StreamDecoder {
+    assemblers: BTreeMap<Group, ChunkAssembler>,
+    completed: BTreeSet<(u16, u8)>,
+    manifests: BTreeMap<u16, ManifestAssembler>,
+    dictionaries: Vec<Vec<u8>>,
+}
+
+/// Packets decode together only if they agree on all of these. Two encodings of one file with
+/// a different size or payload size share a transfer and file ID, but not a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Group {
+    transfer: u16,
+    file_id: u8,
+    total: u32,
+    packet_size: u16,
+}
+
+impl Group {
+    fn of(chunk: &Chunk) -> Self {
+        Self {
+            transfer: chunk.header.transfer,
+            file_id: chunk.header.file_id,
+            total: chunk.header.total,
+            packet_size: chunk.header.packet_size,
+        }
+    }
+
+    fn file(&self) -> (u16, u8) {
+        (self.transfer, self.file_id)
+    }
+}
+
+impl StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like [`StreamDecoder::new`], for transfers that may have been compressed with one of
+    /// `dictionaries`, preset dictionaries shared out of band. Each transfer names the one it
+    /// needs; without it, completing the transfer fails with
+    /// [`FountainError::MissingDictionary`].
+    pub fn with_dictionaries(dictionaries: Vec<Vec<u8>>) -> Self {
+        Self {
+            dictionaries,
+            ..Self::default()
+        }
+    }
+
+    /// Scans an image for a QR code and feeds the chunk it carries, if any.
+    pub fn feed_image(&mut self, img: &DynamicImage) -> Result<FeedResult> {
+        self.feed_luma(&img.to_luma8())
+    }
+
+    /// Like [`StreamDecoder::feed_image`], for frames already converted to grayscale.
+    pub fn feed_luma(&mut self, img: &GrayImage) -> Result<FeedResult> {
+        let chunk = decode_qr_from_gray(img)
+            .ok()
+            .and_then(|qr_bytes| decode_qr_bytes_to_chunk(&qr_bytes));
+        match chunk {
+            Some(chunk) => self.feed_chunk(chunk),
+            None => Ok(FeedResult::NoChunk),
+        }
+    }
+
+    /// Feeds a chunk that was already extracted from its QR code. Chunks of a piped stream
+    /// are rejected with [`FountainError::PipedStream`]; feed those to a `PipeDecoder`.
+    pub fn feed_chunk(&mut self, chunk: Chunk) -> Result<FeedResult> {
+        if chunk.header.version == fountain_core::STREAM_VERSION {
+            return Err(FountainError::PipedStream);
+        }
+        if chunk.is_manifest() {
+            let transfer = chunk.header.transfer;
+            let assembler = self.manifests.entry(transfer).or_default();
+            return Ok(match assembler.push(chunk) {
+                Ok(ManifestResult::Accepted) => FeedResult::Accepted,
+                Ok(ManifestResult::Duplicate) => FeedResult::Duplicate,
+                Ok(ManifestResult::Complete(manifest)) => FeedResult::Manifest(manifest),
+                Err(e) => {
+                    // Parts of two manifests with the same part count, such as those of two
+                    // encodes of one transfer ID, can complete each other into garbage. The
+                    // manifest is only informational, so start over with the parts to come.
+                    warn!("Discarding the manifest of transfer {}: {}", transfer, e);
+                    self.manifests.remove(&transfer);
+                    FeedResult::Duplicate
+                }
+            });
+        }
+        let group = Group::of(&chunk);
+        let file_count = chunk.header.file_count.max(1) as usize;
+        if self.completed.contains(&group.file()) {
+            return Ok(FeedResult::Duplicate);
+        }
+
+        let assembler = self
+            .assemblers
+            .entry(group)
+            .or_insert_with(|| ChunkAssembler::with_dictionaries(self.dictionaries.clone()));
+        match assembler.push(chunk)? {
+            AssembleResult::Accepted => Ok(FeedResult::Accepted),
+            AssembleResult::Duplicate => Ok(FeedResult::Duplicate),
+            AssembleResult::Complete { filename, data } => {
+                self.completed.insert(group.file());
+                let file = DecodedFile { filename, data };
+                if self.completed_files(group.transfer) < file_count {
+                    Ok(FeedResult::FileComplete(file))
+                } else {
+                    Ok(FeedResult::Complete(file))
+                }
+            }
+        }
+    }
+
+    /// Number of unique packets received so far, across all files.
+    pub fn num_packets(&self) -> usize {
+        self.assemblers
+            .values()
+            .map(ChunkAssembler::num_packets)
+            .sum()
+    }
+
+    /// Number of source packets in the transfer, the minimum needed to decode it.
+    /// Unknown until the first packet arrives; in a multi-file transfer, only files seen so
+    /// far are counted.
+    pub fn expected_packets(&self) -> Option<usize> {
+        if self.assemblers.is_empty() {
+            return None;
+        }
+        self.dominant_groups()
+            .map(|(_, assembler)| assembler.expected_packets())
+            .sum()
+    }
+
+    /// Compressed size of the files seen so far, going by the encoding with the most packets
+    /// for each. Decoding holds the packets, and then the file, in memory.
+    pub fn transfer_bytes(&self) -> usize {
+        self.dominant_groups()
+            .map(|(group, _)| group.total as usize)
+            .sum()
+    }
+
+    /// Packets that cannot be combined with the rest: for each file, those outside the group
+    /// with the most packets. Non-zero when the input mixes frames of different encodings.
+    pub fn mismatched_packets(&self) -> usize {
+        let dominant: usize = self
+            .dominant_groups()
+            .map(|(_, assembler)| assembler.num_packets())
+            .sum();
+        self.num_packets() - dominant
+    }
+
+    /// For each file, the group with the most packets and its assembler.
+    fn dominant_groups(&self) -> impl Iterator<Item = (&Group, &ChunkAssembler)> {
+        let mut dominant: BTreeMap<(u16, u8), (&Group, &ChunkAssembler)> = BTreeMap::new();
+        for (group, assembler) in &self.assemblers {
+            let best = dominant.entry(group.file()).or_insert((group, assembler));
+            if assembler.num_packets() > best.1.num_packets() {
+                *best = (group, assembler);
+            }
+        }
+        dominant.into_values()
+    }
+
+    /// Number of distinct transfers seen so far. More than one means the input holds files
+    /// appended to an earlier output.
+    pub fn num_transfers(&self) -> usize {
+        let transfers: BTreeSet<u16> = self
+            .assemblers
+            .keys()
+            .map(Group::file)
+            .chain(self.completed.iter().copied())
+            .map(|(transfer, _)| transfer)
+            .collect();
+        transfers.len()
+    }
+
+    /// Number of transfers whose files are all reconstructed.
+    pub fn num_complete_transfers(&self) -> usize {
+        let file_counts: BTreeMap<u16, u8> = self
+            .chunks()
+            .map(|chunk| (chunk.header.transfer, chunk.header.file_count.max(1)))
+            .collect();
+        file_counts
+            .into_iter()
+            .filter(|&(transfer, count)| self.completed_files(transfer) >= count as usize)
+            .count()
+    }
+
+    fn completed_files(&self, transfer: u16) -> usize {
+        self.completed
+            .range((transfer, 0)..=(transfer, u8::MAX))
+            .count()
+    }
+
+    /// Unique packets received so far, e.g. to persist them in a session file.
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.assemblers.values().flat_map(ChunkAssembler::chunks)
+    }
+}
+
+pub(crate) fn decode_qr_bytes_to_chunk(qr_bytes: &[u8]) -> Option<Chunk> {
+    Chunk::from_text(std::str::from_utf8(qr_bytes).ok()?).ok()
+}
@@ -1,5 +1,9 @@
-use crate::encode::TerminalQrData;
-use std::io::{self, Write};
+use crate::encode::{EndlessFrames, TerminalQrData};
+use crate::error::Result;
+use crate::pipe::PipeEncoder;
+use crate::qr::render_qr_to_terminal;
+use crate::sink::write_terminal_frame;
+use std::io::{self, Read, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
@@ -47,17 +51,7 @@ pub fn display_qr_carousel(data: &TerminalQrData, interval_ms: u64) {
                 interval_ms
             );
 
-            // Wait for interval or until interrupted
-            let start = std::time::Instant::now();
-            let duration = Duration::from_millis(interval_ms);
-
-            while start.elapsed() < duration {
-                if !running.load(Ordering::SeqCst) {
-                    break;
-                }
-                thread::sleep(Duration::from_millis(std::cmp::min(50, interval_ms)));
-            }
-
+            wait_interval(&running, interval_ms);
             current = (current + 1) % total;
         }
 
@@ -70,23 +64,123 @@ pub fn display_qr_carousel(data: &TerminalQrData, interval_ms: u64) {
     println!("Exited.");
 }
 
-fn display_single_qr(qr_string: &str, filename: &str, current: usize, total: usize) {
+/// Shows an endless carousel until Ctrl+C is pressed, each QR code for `interval_ms`. Every
+/// round after the first shows fresh repair packets.
+pub fn display_qr_endless(mut frames: EndlessFrames, interval_ms: u64) -> Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    print!("{}", HIDE_CURSOR);
+    io::stdout().flush()?;
+
+    let round_len = frames.round_len();
+    let mut shown = 0;
+    let result = (|| {
+        while running.load(Ordering::SeqCst) {
+            let Some(qr_string) = frames.next().transpose()? else {
+                break;
+            };
+            display_single_qr(
+                &qr_string,
+                frames.filename(),
+                shown % round_len + 1,
+                round_len,
+            );
+            println!(
+                "\nRound {} | Auto-switching in {}ms | Press Ctrl+C to exit...",
+                frames.round() + 1,
+                interval_ms
+            );
+            wait_interval(&running, interval_ms);
+            shown += 1;
+        }
+        Ok(())
+    })();
+
+    print!("{}", SHOW_CURSOR);
+    print!("{}", CLEAR_SCREEN);
+    println!("Exited.");
+    io::stdout().flush()?;
+    result
+}
+
+/// Shows a piped stream segment by segment as it is read, each QR code for `interval_ms`,
+/// until the stream ends or Ctrl+C is pressed. Returns the number of segments shown.
+pub fn display_pipe<R: Read>(encoder: PipeEncoder<R>, interval_ms: u64) -> Result<u32> {
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    print!("{}", HIDE_CURSOR);
+    io::stdout().flush()?;
+
+    let mut shown = 0;
+    let result = (|| {
+        for segment in encoder {
+            let chunks = segment?;
+            let total = chunks.len();
+            for (i, chunk) in chunks.iter().enumerate() {
+                if !running.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                let qr_string = render_qr_to_terminal(chunk.to_text()?.as_bytes())?;
+                print!("{}", CLEAR_SCREEN);
+                println!(
+                    "Stream segment {}  |  QR Code {}/{}",
+                    chunk.header.segment,
+                    i + 1,
+                    total
+                );
+                println!("{}", "=".repeat(50));
+                println!();
+                println!("{}", qr_string);
+                println!("\nReading from stdin | Press Ctrl+C to exit...");
+                wait_interval(&running, interval_ms);
+            }
+            shown += 1;
+        }
+        Ok(())
+    })();
+
+    print!("{}", SHOW_CURSOR);
     print!("{}", CLEAR_SCREEN);
+    println!("Exited.");
+    io::stdout().flush()?;
+    result.map(|()| shown)
+}
+
+/// Waits for `interval_ms`, returning early once `running` is cleared.
+fn wait_interval(running: &AtomicBool, interval_ms: u64) {
+    let start = std::time::Instant::now();
+    let duration = Duration::from_millis(interval_ms);
 
-    println!("File: {}  |  QR Code {}/{}", filename, current, total);
-    println!("{}", "=".repeat(50));
-    println!();
-    println!("{}", qr_string);
+    while start.elapsed() < duration {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(std::cmp::min(50, interval_ms)));
+    }
+}
+
+fn display_single_qr(qr_string: &str, filename: &str, current: usize, total: usize) {
+    print!("{}", CLEAR_SCREEN);
+    let _ = write_terminal_frame(&mut io::stdout(), qr_string, filename, current, total);
 }
 
 pub fn display_qr_once(data: &TerminalQrData) {
     let total = data.qr_strings.len();
 
     for (i, qr_string) in data.qr_strings.iter().enumerate() {
-        println!("File: {}  |  QR Code {}/{}", data.filename, i + 1, total);
-        println!("{}", "=".repeat(50));
-        println!();
-        println!("{}", qr_string);
+        let _ = write_terminal_frame(&mut io::stdout(), qr_string, &data.filename, i + 1, total);
 
         if i < total - 1 {
             println!();
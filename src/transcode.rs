@@ -0,0 +1,126 @@
+//! Re-rendering an output written earlier with new display settings.
+//!
+//! The frames of a GIF or a directory of images or packet files are read back to their chunks,
+//! which are rendered again as they are: no packet is decoded or encoded anew, so neither the
+//! original file nor enough packets to rebuild it are needed. Frames without a readable QR code
+//! are skipped, and a packet seen in several frames is kept once, in the order it first came.
+//! The codes can also be shown in the terminal again, for a sender that only has the output.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::chunk::Chunk;
+use crate::decode::input_chunks;
+use crate::encode::{
+    collect_terminal, common_version, EncodeResult, Encoder, QrFrames, TerminalFrames,
+    TerminalQrData,
+};
+use crate::error::{FountainError, Result};
+use crate::qr::fits_in_terminal;
+
+impl Encoder {
+    /// Reads the chunks of `input`, a GIF or a directory of QR images or packet files, and
+    /// returns frames that render them at this encoder's pixel scale. The frames are named after
+    /// the input, as the embedded filename is only known once the transfer is decoded, and the
+    /// byte counts of their result are the compressed size of the transfers.
+    pub fn transcode_frames(&self, input: &Path) -> Result<QrFrames> {
+        let (chunks, payload_size) = self.transcode_chunks(input)?;
+        let transfers: BTreeMap<_, _> = chunks
+            .iter()
+            .filter(|chunk| !chunk.is_manifest())
+            .map(|chunk| {
+                let header = &chunk.header;
+                (
+                    (header.transfer, header.segment, header.file_id),
+                    header.total as usize,
+                )
+            })
+            .collect();
+        let compressed_bytes = transfers.values().sum();
+        let filename = transcode_name(input);
+
+        let fixed_version = common_version(&chunks)?;
+        Ok(QrFrames {
+            pixel_scale: match fixed_version {
+                Some(version) => self.options().pixel_scale_for(version)?,
+                None => self.options().pixel_scale,
+            },
+            quiet_zone: self.options().quiet_zone,
+            fixed_version,
+            total: chunks.len(),
+            parity: 0,
+            effective_size: payload_size,
+            chunks: chunks.into_iter(),
+            filename,
+            total_bytes: compressed_bytes,
+            compressed_bytes,
+            cover: None,
+            receipt: None,
+            caption: self.options().caption.clone(),
+            markers: self.options().markers,
+            #[cfg(feature = "color")]
+            color_matrix: None,
+            canvas: self.options().canvas,
+        })
+    }
+
+    /// Re-renders the frames of `input` (see [`Encoder::transcode_frames`]) as a looping GIF
+    /// at this encoder's interval and pixel scale. `output_gif` may be the input itself.
+    pub fn transcode_to_gif(&self, input: &Path, output_gif: &Path) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let frames = self.transcode_frames(input)?;
+        self.save_gif(frames, output_gif, started)
+    }
+
+    /// Like [`Encoder::transcode_to_gif`], writing one PNG per frame into `output_dir`.
+    pub fn transcode_to_images(&self, input: &Path, output_dir: &Path) -> Result<EncodeResult> {
+        let started = Instant::now();
+        let mut frames = self.transcode_frames(input)?;
+        let output_files = self.write_images(&mut frames, output_dir)?;
+        Ok(frames.result(output_files, started))
+    }
+
+    /// Reads the codes of `input` (see [`Encoder::transcode_frames`]) and renders them for a
+    /// terminal carousel, two side by side with
+    /// [`EncodeOptions::dual`](crate::EncodeOptions::dual). The payload size is the input's,
+    /// so this fails with [`FountainError::TerminalTooSmall`] if the terminal cannot fit a code.
+    pub fn transcode_for_terminal(&self, input: &Path) -> Result<TerminalQrData> {
+        let (chunks, effective_size) = self.transcode_chunks(input)?;
+        for chunk in &chunks {
+            if !fits_in_terminal(chunk.to_text()?.as_bytes())? {
+                return Err(FountainError::TerminalTooSmall);
+            }
+        }
+
+        let per_frame = self.terminal_codes_per_frame(&chunks)?;
+        collect_terminal(TerminalFrames {
+            total: chunks.len().div_ceil(per_frame),
+            chunks: chunks.into_iter(),
+            per_frame,
+            effective_size,
+            filename: transcode_name(input),
+        })
+    }
+
+    /// The chunks of `input` and the largest payload among them.
+    fn transcode_chunks(&self, input: &Path) -> Result<(Vec<Chunk>, usize)> {
+        let chunks = input_chunks(input, &self.options().cancel)?;
+        let payload_size = chunks
+            .iter()
+            .map(|chunk| chunk.header.size() + chunk.data.len())
+            .max()
+            .unwrap_or_default();
+        self.options()
+            .check_chunk_count(chunks.len(), payload_size)?;
+        Ok((chunks, payload_size))
+    }
+}
+
+/// Name of the frames re-rendered from `input`, which is all that is known of the transfer.
+fn transcode_name(input: &Path) -> String {
+    input.file_stem().map_or_else(
+        || "frames".to_string(),
+        |stem| stem.to_string_lossy().to_string(),
+    )
+}
@@ -0,0 +1,282 @@
+//! Interop with txqr, the Go project whose phone apps send a file as a loop of QR codes.
+//!
+//! Each txqr code holds `<offset>/<total>|<data>`: where `data` starts in the message and the
+//! length of the whole message, in decimal, then the bytes themselves. Nothing is compressed
+//! or checksummed, and no filename travels, so a received message is named
+//! [`TXQR_FILENAME`].
+//!
+//! Newer txqr senders can also send LT fountain blocks, headed `<block>/<chunk size>/<total>|`.
+//! Which parts of the message a block combines depends on the random generator of the Go
+//! library that made it, so these frames are recognised but not decoded.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+#[cfg(feature = "encode")]
+use std::path::Path;
+#[cfg(feature = "encode")]
+use std::time::Instant;
+
+#[cfg(feature = "encode")]
+use qrcode::Version;
+
+#[cfg(feature = "encode")]
+use crate::encode::{EncodeResult, Encoder};
+#[cfg(feature = "encode")]
+use crate::error::{FountainError, Result};
+#[cfg(feature = "encode")]
+use crate::qr::{generate_qr_image_with_quiet_zone, qr_version_for};
+#[cfg(feature = "encode")]
+use crate::sink::GifSink;
+
+/// Name given to a message received from a txqr sender, which sends none.
+pub const TXQR_FILENAME: &str = "txqr.bin";
+/// Message bytes per code when no chunk size is given, as the txqr apps default to.
+pub const DEFAULT_TXQR_CHUNK_SIZE: usize = 100;
+/// Longest header a code may start with, digits and separators included.
+const MAX_HEADER_LEN: usize = 64;
+
+/// What one QR code held, for [`TxqrReceiver::feed`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum TxqrFeed {
+    /// The code is not a txqr frame.
+    NotTxqr,
+    /// An LT fountain block of a newer txqr sender, which is not decoded.
+    Fountain,
+    /// A part of the message already received.
+    Duplicate,
+    /// A new part of the message; more are needed.
+    Accepted,
+    /// The last missing part: the whole message.
+    Complete(Vec<u8>),
+}
+
+/// Header of a plain txqr frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameHeader {
+    offset: usize,
+    total: usize,
+}
+
+/// Splits a code into its header fields and data. `None` if it does not start with a header
+/// of decimal numbers separated by `/` and ended by `|`.
+fn split_frame(code: &[u8]) -> Option<(Vec<usize>, &[u8])> {
+    let end = code.iter().take(MAX_HEADER_LEN).position(|&b| b == b'|')?;
+    let header = std::str::from_utf8(&code[..end]).ok()?;
+    let fields = header
+        .split('/')
+        .map(|field| {
+            field
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| field.parse().ok())
+                .flatten()
+        })
+        .collect::<Option<Vec<usize>>>()?;
+    Some((fields, &code[end + 1..]))
+}
+
+/// Collects the frames of one txqr message until every byte of it has arrived. A frame of a
+/// message of another length starts over, as the sender moved on to another file.
+#[derive(Debug, Default)]
+pub struct TxqrReceiver {
+    total: Option<usize>,
+    /// Parts received, by offset.
+    parts: BTreeMap<usize, Vec<u8>>,
+}
+
+impl TxqrReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the content of one QR code.
+    pub fn feed(&mut self, code: &[u8]) -> TxqrFeed {
+        let Some((fields, data)) = split_frame(code) else {
+            return TxqrFeed::NotTxqr;
+        };
+        let header = match fields[..] {
+            [offset, total] => FrameHeader { offset, total },
+            [_, chunk_size, _] if chunk_size > 0 => return TxqrFeed::Fountain,
+            _ => return TxqrFeed::NotTxqr,
+        };
+        if data.is_empty() || header.offset + data.len() > header.total {
+            return TxqrFeed::NotTxqr;
+        }
+
+        if self.total != Some(header.total) {
+            self.total = Some(header.total);
+            self.parts.clear();
+        }
+        if self.parts.contains_key(&header.offset) {
+            return TxqrFeed::Duplicate;
+        }
+        self.parts.insert(header.offset, data.to_vec());
+        match self.message() {
+            Some(message) => {
+                self.total = None;
+                self.parts.clear();
+                TxqrFeed::Complete(message)
+            }
+            None => TxqrFeed::Accepted,
+        }
+    }
+
+    /// Fraction of the message received so far.
+    pub fn progress(&self) -> f32 {
+        match self.total {
+            Some(total) if total > 0 => self.received() as f32 / total as f32,
+            _ => 0.0,
+        }
+    }
+
+    /// Bytes of the message covered by the parts received, overlaps counted once.
+    fn received(&self) -> usize {
+        self.ranges().iter().map(|range| range.len()).sum()
+    }
+
+    /// The stretches of the message covered by the parts received, in order.
+    pub fn ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (&offset, data) in &self.parts {
+            let end = offset + data.len();
+            match ranges.last_mut() {
+                Some(last) if offset <= last.end => last.end = last.end.max(end),
+                _ => ranges.push(offset..end),
+            }
+        }
+        ranges
+    }
+
+    /// The message as far as it was received, the bytes of parts not seen left as zeros, with
+    /// its [`TxqrReceiver::ranges`]. `None` before any part.
+    pub fn partial(&self) -> Option<(Vec<u8>, Vec<Range<usize>>)> {
+        let mut message = vec![0; self.total?];
+        for (&offset, data) in &self.parts {
+            message[offset..offset + data.len()].copy_from_slice(data);
+        }
+        Some((message, self.ranges()))
+    }
+
+    /// The message, once the parts received cover it end to end.
+    fn message(&self) -> Option<Vec<u8>> {
+        let total = self.total?;
+        if self.received() < total {
+            return None;
+        }
+        let mut message = Vec::with_capacity(total);
+        for (&offset, data) in &self.parts {
+            if offset + data.len() > message.len() {
+                message.extend_from_slice(&data[message.len() - offset..]);
+            }
+        }
+        Some(message)
+    }
+}
+
+/// The contents of the QR codes a txqr sender would show for `data`, `chunk_size` bytes each.
+pub fn txqr_frames(data: &[u8], chunk_size: usize) -> Vec<Vec<u8>> {
+    let chunk_size = chunk_size.max(1);
+    data.chunks(chunk_size)
+        .enumerate()
+        .map(|(i, part)| {
+            let mut frame = format!("{}/{}|", i * chunk_size, data.len()).into_bytes();
+            frame.extend_from_slice(part);
+            frame
+        })
+        .collect()
+}
+
+#[cfg(feature = "encode")]
+impl Encoder {
+    /// Writes `data` as a looping GIF of txqr frames, for the txqr reader apps. Takes the
+    /// chunk size (default [`DEFAULT_TXQR_CHUNK_SIZE`]), interval, pixel scale and quiet zone
+    /// of the options; nothing is compressed and the filename does not travel.
+    pub fn encode_bytes_to_txqr_gif(&self, data: &[u8], output_gif: &Path) -> Result<EncodeResult> {
+        if data.is_empty() {
+            return Err(FountainError::InvalidConfig(
+                "txqr cannot send an empty message".to_string(),
+            ));
+        }
+        let started = Instant::now();
+        let options = self.options();
+        let chunk_size = options.chunk_size.unwrap_or(DEFAULT_TXQR_CHUNK_SIZE);
+        let frames = txqr_frames(data, chunk_size);
+
+        // Offsets gain digits down the message, so every code takes the size the largest needs.
+        let mut version = None;
+        for frame in &frames {
+            let needed = qr_version_for(frame)?;
+            if version.is_none_or(|version: Version| version.width() < needed.width()) {
+                version = Some(needed);
+            }
+        }
+        if let (Some(version), Some(max)) = (version, options.version_cap()?) {
+            if version.width() > Version::Normal(max).width() {
+                return Err(FountainError::InvalidConfig(format!(
+                    "txqr codes of {} bytes need a QR version above {}; use a smaller chunk size",
+                    chunk_size, max
+                )));
+            }
+        }
+
+        let mut sink = GifSink::create(output_gif, options.interval_ms)?
+            .strict_palette(options.strict_palette)
+            .repeat_frames(options.repeat_frames);
+        for (index, frame) in frames.iter().enumerate() {
+            options.check_cancelled()?;
+            let (image, _) = generate_qr_image_with_quiet_zone(
+                frame,
+                version,
+                options.pixel_scale,
+                options.quiet_zone,
+            )?;
+            sink.encode(&image)?;
+            options.report_frame(index, frames.len());
+        }
+
+        Ok(EncodeResult {
+            num_chunks: frames.len(),
+            output_files: vec![output_gif.to_string_lossy().to_string()],
+            effective_size: chunk_size.min(data.len()),
+            total_bytes: data.len(),
+            compressed_bytes: data.len(),
+            duration: started.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_txqr_frames_roundtrip() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let frames = txqr_frames(&data, 300);
+        assert_eq!(frames.len(), 4);
+        assert!(frames[1].starts_with(b"300/1000|"));
+
+        // Parts arrive out of order and repeated, as from a looping sender.
+        let mut receiver = TxqrReceiver::new();
+        assert_eq!(receiver.feed(&frames[2]), TxqrFeed::Accepted);
+        assert_eq!(receiver.feed(&frames[0]), TxqrFeed::Accepted);
+        assert_eq!(receiver.feed(&frames[2]), TxqrFeed::Duplicate);
+        assert_eq!(receiver.feed(&frames[3]), TxqrFeed::Accepted);
+        assert!((receiver.progress() - 0.7).abs() < 1e-6);
+        let (partial, ranges) = receiver.partial().unwrap();
+        assert_eq!(ranges, vec![0..300, 600..1000]);
+        assert_eq!(partial[600..], data[600..]);
+        assert!(partial[300..600].iter().all(|&b| b == 0));
+        assert_eq!(receiver.feed(&frames[1]), TxqrFeed::Complete(data));
+    }
+
+    #[test]
+    fn test_txqr_other_codes() {
+        let mut receiver = TxqrReceiver::new();
+        assert_eq!(receiver.feed(b"NCF*1Y9:2"), TxqrFeed::NotTxqr);
+        assert_eq!(receiver.feed(b"0/4|toolong"), TxqrFeed::NotTxqr);
+        assert_eq!(receiver.feed(b"-1/4|abc"), TxqrFeed::NotTxqr);
+        assert_eq!(receiver.feed(b"17/100/2048|xyz"), TxqrFeed::Fountain);
+    }
+}
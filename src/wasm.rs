@@ -1,12 +1,25 @@
-use crate::chunk::{decompress, unpack_data, Chunk};
-use crate::qr::decode_qr_from_gray;
-use image::GrayImage;
+use crate::chunk::{compress, decompress, pack_data, unpack_data, Chunk, MAX_PAYLOAD_SIZE};
+use crate::chunker::{
+    build_chunks, fit_payload_size, fits_in_image, gif_interval_ms, packet_size_for,
+    IMG_MIN_PAYLOAD_SIZE, IMG_REDUCTION_STEP, IMG_REDUNDANCY_FACTOR,
+};
+use crate::qr::{decode_qr_from_gray, generate_qr_image};
+use crate::stream::{DecodedFile, FeedResult, StreamDecoder};
+use image::buffer::ConvertBuffer;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, GrayImage, ImageFormat, RgbImage, RgbaImage};
+use js_sys::{Array, ArrayBuffer, Object, Reflect, Uint8Array};
 use raptorq::{Decoder, EncodingPacket, ObjectTransmissionInformation};
 use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 
+/// Single-file scanner kept for existing pages. It follows the first file it sees in a
+/// multi-file transfer; use [`WasmDecoder`] to receive all of them.
 #[wasm_bindgen]
 pub struct QrStreamDecoder {
+    file_id: Option<u8>,
     chunks: HashMap<u32, Chunk>,
     total_chunks: Option<u32>,
     decoder_raptorq: Option<Decoder>,
@@ -49,6 +62,7 @@ impl QrStreamDecoder {
     pub fn new() -> QrStreamDecoder {
         console_error_panic_hook::set_once();
         QrStreamDecoder {
+            file_id: None,
             chunks: HashMap::new(),
             total_chunks: None,
             decoder_raptorq: None,
@@ -112,13 +126,19 @@ impl QrStreamDecoder {
     }
 
     fn process_chunk(&mut self, chunk: Chunk) -> ScanResult {
+        // Manifests list the files of a transfer, but carry none of their packets.
+        if chunk.is_manifest()
+            || *self.file_id.get_or_insert(chunk.header.file_id) != chunk.header.file_id
+        {
+            return self.current_status(ScanStatus::Scanning);
+        }
+
         if self.decoder_raptorq.is_none() {
             let transfer_len = chunk.header.total as u64;
             let packet_size = chunk.header.packet_size;
             self.raptorq_transfer_length = Some(transfer_len);
 
-            let config =
-                ObjectTransmissionInformation::with_defaults(transfer_len, packet_size);
+            let config = ObjectTransmissionInformation::with_defaults(transfer_len, packet_size);
             self.decoder_raptorq = Some(Decoder::new(config));
 
             // Estimate total packets needed (K) for progress bar
@@ -159,7 +179,7 @@ impl QrStreamDecoder {
         self.current_status(ScanStatus::Scanning)
     }
 
-    fn finalize_raptorq(&self, data: Vec<u8>) -> anyhow::Result<(String, Vec<u8>)> {
+    fn finalize_raptorq(&self, data: Vec<u8>) -> crate::Result<(String, Vec<u8>)> {
         let packed = decompress(&data)?;
         unpack_data(&packed)
     }
@@ -180,3 +200,340 @@ impl QrStreamDecoder {
         }
     }
 }
+
+/// QR code frames produced by [`encode_file`], each stored as PNG bytes.
+#[wasm_bindgen]
+pub struct EncodedFrames {
+    frames: Vec<Vec<u8>>,
+    effective_size: usize,
+}
+
+#[wasm_bindgen]
+impl EncodedFrames {
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// PNG bytes of the frame at `index`, or an empty array if it is out of range.
+    pub fn get_frame(&self, index: usize) -> Vec<u8> {
+        self.frames.get(index).cloned().unwrap_or_default()
+    }
+
+    pub fn effective_size(&self) -> usize {
+        self.effective_size
+    }
+}
+
+/// Encodes file contents into QR code frames for a page to show as a carousel.
+/// `chunk_size` defaults to the largest payload that fits; `pixel_scale` is the module size.
+#[wasm_bindgen]
+pub fn encode_file(
+    data: &[u8],
+    filename: &str,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+) -> Result<EncodedFrames, JsError> {
+    console_error_panic_hook::set_once();
+    encode_frames(data, filename, chunk_size, pixel_scale)
+        .map_err(|e| JsError::new(&format!("{:#}", e)))
+}
+
+/// Container of the animation [`encode_animation`] writes.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationFormat {
+    /// A looping GIF, as `fountain-encode --gif-output-file` writes; frame delays are rounded
+    /// to 10ms steps of at least 20ms.
+    Gif = 0,
+    /// A looping animated PNG, which keeps the interval to the millisecond.
+    Apng = 1,
+}
+
+/// Encodes file contents into the whole animation a page can offer for download or show,
+/// each frame shown for `interval_ms`. The chunking and RaptorQ parameters are those of
+/// [`encode_file`] and the command-line encoder's GIF output.
+#[wasm_bindgen]
+pub fn encode_animation(
+    data: &[u8],
+    filename: &str,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+    interval_ms: u32,
+    format: AnimationFormat,
+) -> Result<Vec<u8>, JsError> {
+    console_error_panic_hook::set_once();
+    let (frames, _) = render_frames(data, filename, chunk_size, pixel_scale)
+        .map_err(|e| JsError::new(&format!("{:#}", e)))?;
+    let written = match format {
+        AnimationFormat::Gif => write_gif(&frames, u64::from(interval_ms)),
+        AnimationFormat::Apng => write_apng(&frames, interval_ms),
+    };
+    written.map_err(|e| JsError::new(&format!("{:#}", e)))
+}
+
+fn write_gif(frames: &[RgbImage], interval_ms: u64) -> anyhow::Result<Vec<u8>> {
+    let delay =
+        Delay::from_saturating_duration(Duration::from_millis(gif_interval_ms(interval_ms)));
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut gif);
+        encoder.set_repeat(Repeat::Infinite)?;
+        for frame in frames {
+            let rgba: RgbaImage = frame.convert();
+            encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay))?;
+        }
+    }
+    Ok(gif)
+}
+
+fn write_apng(frames: &[RgbImage], interval_ms: u32) -> anyhow::Result<Vec<u8>> {
+    let (width, height) = frames
+        .first()
+        .map(|frame| frame.dimensions())
+        .ok_or_else(|| anyhow::anyhow!("No frames to write"))?;
+    let mut apng = Vec::new();
+    let mut encoder = png::Encoder::new(&mut apng, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // 0 plays loops forever; the delay is a fraction of a second, here in milliseconds.
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(interval_ms.clamp(1, u32::from(u16::MAX)) as u16, 1000)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(frame.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(apng)
+}
+
+fn encode_frames(
+    data: &[u8],
+    filename: &str,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+) -> anyhow::Result<EncodedFrames> {
+    let (images, effective_size) = render_frames(data, filename, chunk_size, pixel_scale)?;
+    let mut frames = Vec::with_capacity(images.len());
+    for qr_image in &images {
+        let mut png = Vec::new();
+        qr_image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        frames.push(png);
+    }
+
+    Ok(EncodedFrames {
+        frames,
+        effective_size,
+    })
+}
+
+/// QR code images for file contents, all of one version, and the payload size they carry.
+fn render_frames(
+    data: &[u8],
+    filename: &str,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+) -> anyhow::Result<(Vec<RgbImage>, usize)> {
+    let compressed = compress(&pack_data(data, filename))?;
+    let effective_size = fit_payload_size(
+        &compressed,
+        chunk_size.unwrap_or(MAX_PAYLOAD_SIZE),
+        IMG_MIN_PAYLOAD_SIZE,
+        IMG_REDUCTION_STEP,
+        |encoded| fits_in_image(encoded, pixel_scale, None),
+    )?;
+    let chunks = build_chunks(
+        &compressed,
+        packet_size_for(effective_size),
+        IMG_REDUNDANCY_FACTOR,
+    );
+
+    let mut fixed_version = None;
+    let mut frames = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        let encoded = base45::encode(&chunk.to_bytes()?);
+        let (qr_image, version) =
+            generate_qr_image(encoded.as_bytes(), fixed_version, pixel_scale)?;
+        fixed_version.get_or_insert(version);
+        frames.push(qr_image);
+    }
+
+    Ok((frames, effective_size))
+}
+
+/// Outcome of [`WasmDecoder::feed_rgba`] for one frame.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum FeedStatus {
+    NoChunk = 0,
+    Accepted = 1,
+    Duplicate = 2,
+    Complete = 3,
+}
+
+/// Packet-accumulating decoder for camera frames, e.g. from `getUserMedia` drawn to a canvas.
+///
+/// Detection takes tens of milliseconds per frame, so a page that scans continuously should run
+/// the decoder in a Web Worker: post each frame's pixel buffer to the worker as a transferable,
+/// feed it with [`WasmDecoder::feed_buffer`] and post the returned progress event back.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct WasmDecoder {
+    stream: StreamDecoder,
+    files: Vec<DecodedFile>,
+    complete: bool,
+}
+
+#[wasm_bindgen]
+impl WasmDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmDecoder {
+        console_error_panic_hook::set_once();
+        WasmDecoder::default()
+    }
+
+    /// Feeds one RGBA frame (`ImageData.data`). Inverted QR codes are tried as well.
+    pub fn feed_rgba(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<FeedStatus, JsError> {
+        self.feed_pixels(width, height, data.to_vec())
+    }
+
+    /// Feeds one RGBA frame held in an `ArrayBuffer`, such as `ImageData.data.buffer` posted to
+    /// a worker, and returns the progress event to post back: an object with `status`,
+    /// `received`, `expected` and `progress`, plus `files` (`{ filename, data }` each) once the
+    /// transfer is complete. Each file's data has a buffer of its own, so it can be transferred.
+    pub fn feed_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        buffer: &ArrayBuffer,
+    ) -> Result<Object, JsError> {
+        let status = self.feed_pixels(width, height, Uint8Array::new(buffer).to_vec())?;
+        self.progress_event(status)
+    }
+
+    /// The progress event [`WasmDecoder::feed_buffer`] returns, for the decoder's current state.
+    pub fn progress_event(&self, status: FeedStatus) -> Result<Object, JsError> {
+        let event = Object::new();
+        set_field(&event, "status", &JsValue::from(status as u32))?;
+        set_field(
+            &event,
+            "received",
+            &JsValue::from(self.stream.num_packets() as u32),
+        )?;
+        let expected = self.stream.expected_packets().map(|n| n as u32);
+        set_field(&event, "expected", &JsValue::from(expected))?;
+        set_field(&event, "progress", &JsValue::from(self.progress()))?;
+        if self.complete {
+            let files = Array::new();
+            for file in &self.files {
+                let entry = Object::new();
+                set_field(&entry, "filename", &JsValue::from(file.filename.as_str()))?;
+                set_field(&entry, "data", &Uint8Array::from(file.data.as_slice()))?;
+                files.push(&entry);
+            }
+            set_field(&event, "files", &files)?;
+        }
+        Ok(event)
+    }
+
+    fn feed_pixels(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Result<FeedStatus, JsError> {
+        if self.complete {
+            return Ok(FeedStatus::Complete);
+        }
+
+        let rgba = RgbaImage::from_raw(width, height, data)
+            .ok_or_else(|| JsError::new("Invalid data length"))?;
+        let mut gray = DynamicImage::ImageRgba8(rgba).into_luma8();
+
+        let mut result = self.feed_luma(&gray)?;
+        if matches!(result, FeedResult::NoChunk) {
+            image::imageops::invert(&mut gray);
+            result = self.feed_luma(&gray)?;
+        }
+
+        Ok(match result {
+            FeedResult::NoChunk => FeedStatus::NoChunk,
+            FeedResult::Accepted | FeedResult::Manifest(_) => FeedStatus::Accepted,
+            FeedResult::Duplicate => FeedStatus::Duplicate,
+            FeedResult::FileComplete(file) => {
+                self.files.push(file);
+                FeedStatus::Accepted
+            }
+            FeedResult::Complete(file) => {
+                self.files.push(file);
+                self.complete = true;
+                FeedStatus::Complete
+            }
+        })
+    }
+
+    fn feed_luma(&mut self, gray: &GrayImage) -> Result<FeedResult, JsError> {
+        self.stream
+            .feed_luma(gray)
+            .map_err(|e| JsError::new(&format!("{:#}", e)))
+    }
+
+    /// Unique packets received so far.
+    pub fn packets_received(&self) -> usize {
+        self.stream.num_packets()
+    }
+
+    /// Minimum number of packets needed to decode, once the first one has arrived.
+    pub fn packets_expected(&self) -> Option<usize> {
+        self.stream.expected_packets()
+    }
+
+    /// Estimated fraction of the file recovered, between 0.0 and 1.0.
+    pub fn progress(&self) -> f32 {
+        if self.complete {
+            return 1.0;
+        }
+        match self.stream.expected_packets() {
+            Some(expected) => (self.stream.num_packets() as f32 / expected as f32).min(0.99),
+            None => 0.0,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Name of the decoded file once complete; the first file of a multi-file transfer.
+    pub fn filename(&self) -> Option<String> {
+        self.complete.then(|| self.filename_at(0)).flatten()
+    }
+
+    /// Contents of the decoded file once complete; the first file of a multi-file transfer.
+    pub fn file_data(&self) -> Option<Vec<u8>> {
+        self.complete.then(|| self.file_data_at(0)).flatten()
+    }
+
+    /// Number of files decoded so far. Files of a multi-file transfer become available as each
+    /// one completes.
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn filename_at(&self, index: usize) -> Option<String> {
+        self.files.get(index).map(|file| file.filename.clone())
+    }
+
+    pub fn file_data_at(&self, index: usize) -> Option<Vec<u8>> {
+        self.files.get(index).map(|file| file.data.clone())
+    }
+}
+
+fn set_field(object: &Object, key: &str, value: &JsValue) -> Result<(), JsError> {
+    Reflect::set(object, &JsValue::from(key), value)
+        .map(|_| ())
+        .map_err(|_| JsError::new(&format!("Failed to set {}", key)))
+}
@@ -0,0 +1,302 @@
+//! Showing QR frames fullscreen in a native window, via the X11 protocol.
+//!
+//! Every frame is drawn at the largest whole number of pixels per module that fits the window,
+//! so the payload size is not capped by the terminal. Frames are due at fixed offsets from the
+//! start rather than an interval after the previous one, so a late frame does not push the
+//! rest back the way GIF players and terminals drift.
+
+use image::RgbImage;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{
+    AtomEnum, ConnectionExt as _, CreateGCAux, CreateWindowAux, EventMask, PropMode, Rectangle,
+    WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::encode::QrFrames;
+use crate::error::Result;
+
+/// Longest sleep between checks for window events and Ctrl+C.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Dark modules of one frame, as horizontal runs of (row, first column, length).
+struct Modules {
+    size: u16,
+    runs: Vec<(u16, u16, u16)>,
+}
+
+impl Modules {
+    /// Reads the modules of a frame rendered at one pixel per module.
+    fn from_image(img: &RgbImage) -> Self {
+        let mut runs = Vec::new();
+        for y in 0..img.height() {
+            let mut start = None;
+            for x in 0..=img.width() {
+                let dark = x < img.width() && img.get_pixel(x, y)[0] < 128;
+                match (dark, start) {
+                    (true, None) => start = Some(x),
+                    (false, Some(from)) => {
+                        runs.push((y as u16, from as u16, (x - from) as u16));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Self {
+            size: img.width() as u16,
+            runs,
+        }
+    }
+}
+
+/// A fullscreen window with a back buffer the size of the window.
+struct Window {
+    conn: RustConnection,
+    window: u32,
+    buffer: u32,
+    light: u32,
+    dark: u32,
+    depth: u8,
+    width: u16,
+    height: u16,
+    delete_atom: u32,
+    /// Rectangles that fit in one request.
+    batch: usize,
+}
+
+impl Window {
+    fn open(title: &str) -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let screen = conn.setup().roots[screen_num].clone();
+        let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+
+        let protocols = conn.intern_atom(false, b"WM_PROTOCOLS")?;
+        let delete = conn.intern_atom(false, b"WM_DELETE_WINDOW")?;
+        let state = conn.intern_atom(false, b"_NET_WM_STATE")?;
+        let fullscreen = conn.intern_atom(false, b"_NET_WM_STATE_FULLSCREEN")?;
+        let (protocols, delete_atom, state, fullscreen) = (
+            protocols.reply()?.atom,
+            delete.reply()?.atom,
+            state.reply()?.atom,
+            fullscreen.reply()?.atom,
+        );
+
+        // An empty cursor, so the pointer does not cover a module.
+        let blank = conn.generate_id()?;
+        let cursor = conn.generate_id()?;
+        conn.create_pixmap(1, blank, screen.root, 1, 1)?;
+        conn.create_cursor(cursor, blank, blank, 0, 0, 0, 0, 0, 0, 0, 0)?;
+        conn.free_pixmap(blank)?;
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            0,
+            0,
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            0,
+            &CreateWindowAux::new()
+                .background_pixel(screen.white_pixel)
+                .cursor(cursor)
+                .event_mask(
+                    EventMask::EXPOSURE | EventMask::KEY_PRESS | EventMask::STRUCTURE_NOTIFY,
+                ),
+        )?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            title.as_bytes(),
+        )?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            protocols,
+            AtomEnum::ATOM,
+            &[delete_atom],
+        )?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            state,
+            AtomEnum::ATOM,
+            &[fullscreen],
+        )?;
+
+        let light = conn.generate_id()?;
+        let dark = conn.generate_id()?;
+        let gc = CreateGCAux::new().graphics_exposures(0);
+        conn.create_gc(light, window, &gc.foreground(screen.white_pixel))?;
+        conn.create_gc(dark, window, &gc.foreground(screen.black_pixel))?;
+        let buffer = conn.generate_id()?;
+        conn.create_pixmap(screen.root_depth, buffer, window, width, height)?;
+        conn.map_window(window)?;
+        conn.flush()?;
+
+        // A request is a 12-byte header followed by 8 bytes per rectangle.
+        let batch = (conn.maximum_request_bytes() - 12) / 8;
+        Ok(Self {
+            conn,
+            window,
+            buffer,
+            light,
+            dark,
+            depth: screen.root_depth,
+            width,
+            height,
+            delete_atom,
+            batch,
+        })
+    }
+
+    /// Draws a frame into the back buffer, centred at the largest module size that fits.
+    fn draw(&self, modules: &Modules) -> Result<()> {
+        let (width, height) = (self.width, self.height);
+        let scale = (width.min(height) / modules.size).max(1);
+        let x0 = (width as i32 - (modules.size * scale) as i32) / 2;
+        let y0 = (height as i32 - (modules.size * scale) as i32) / 2;
+
+        let background = Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        self.conn
+            .poly_fill_rectangle(self.buffer, self.light, &[background])?;
+        let rectangles: Vec<Rectangle> = modules
+            .runs
+            .iter()
+            .map(|&(row, column, len)| Rectangle {
+                x: (x0 + (column * scale) as i32) as i16,
+                y: (y0 + (row * scale) as i32) as i16,
+                width: len * scale,
+                height: scale,
+            })
+            .collect();
+        for batch in rectangles.chunks(self.batch) {
+            self.conn
+                .poly_fill_rectangle(self.buffer, self.dark, batch)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the back buffer to the window and waits until the server has done so.
+    fn present(&self) -> Result<()> {
+        self.conn.copy_area(
+            self.buffer,
+            self.window,
+            self.dark,
+            0,
+            0,
+            0,
+            0,
+            self.width,
+            self.height,
+        )?;
+        self.conn.get_input_focus()?.reply()?;
+        Ok(())
+    }
+
+    /// Handles pending events. Returns `false` once the window was closed or a key pressed.
+    fn handle_events(&mut self, current: &Modules) -> Result<bool> {
+        while let Some(event) = self.conn.poll_for_event()? {
+            match event {
+                Event::KeyPress(_) => return Ok(false),
+                Event::ClientMessage(event) if event.data.as_data32()[0] == self.delete_atom => {
+                    return Ok(false)
+                }
+                Event::ConfigureNotify(event)
+                    if (event.width, event.height) != (self.width, self.height) =>
+                {
+                    self.conn.free_pixmap(self.buffer)?;
+                    self.conn.create_pixmap(
+                        self.depth,
+                        self.buffer,
+                        self.window,
+                        event.width,
+                        event.height,
+                    )?;
+                    (self.width, self.height) = (event.width, event.height);
+                    self.draw(current)?;
+                    self.present()?;
+                }
+                Event::Expose(event) if event.count == 0 => self.present()?,
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Shows the frames fullscreen, one every `interval_ms`, until a key is pressed, the window is
+/// closed or Ctrl+C. The frames are rendered up front, so rendering never delays a frame.
+pub fn display_qr_window(mut frames: QrFrames, interval_ms: u64) -> Result<()> {
+    // The window picks the module size, so read the codes at one pixel per module, bare.
+    frames.pixel_scale = 1;
+    frames.caption = None;
+    frames.markers = false;
+    let title = format!("{} - fountain", frames.filename());
+    let modules = frames
+        .map(|img| img.map(|img| Modules::from_image(&img)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    let mut window = Window::open(&title)?;
+    let interval = Duration::from_millis(interval_ms.max(1));
+    let started = Instant::now();
+    for shown in 1.. {
+        let current = &modules[(shown - 1) % modules.len()];
+        window.draw(current)?;
+        window.present()?;
+
+        let due = started + interval * shown as u32;
+        if Instant::now() > due {
+            debug!("Frame {} shown late", shown);
+        }
+        while Instant::now() < due {
+            if !running.load(Ordering::SeqCst) || !window.handle_events(current)? {
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL.min(due.saturating_duration_since(Instant::now())));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_modules_from_image() {
+        let mut img = RgbImage::from_pixel(4, 2, Rgb([255, 255, 255]));
+        for (x, y) in [(0, 0), (1, 0), (3, 0), (1, 1), (2, 1), (3, 1)] {
+            img.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+        let modules = Modules::from_image(&img);
+        assert_eq!(modules.size, 4);
+        assert_eq!(modules.runs, [(0, 0, 2), (0, 3, 1), (1, 1, 3)]);
+    }
+}
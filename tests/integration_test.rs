@@ -37,6 +37,15 @@ fn test_encode_decode_roundtrip() {
         fs::read_to_string(&decoded_output_path).expect("Failed to read decoded file");
 
     assert_eq!(original_content, decoded_content);
+
+    // Results serialize with the field names of the binaries' --json output.
+    let json = serde_json::to_value(&decode_result).unwrap();
+    assert_eq!(json["original_filename"], "source.txt");
+    assert!(json["duration_ms"].is_u64());
+    let restored: fountain::DecodeResult = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.output_files, decode_result.output_files);
+    let json = serde_json::to_value(&encode_result).unwrap();
+    assert_eq!(json["effective_payload_size"], encode_result.effective_size);
 }
 
 #[test]
@@ -132,6 +141,52 @@ fn test_encode_gif_size_consistency() {
     }
 }
 
+#[test]
+#[cfg(feature = "encode")]
+fn test_dry_run_estimate_matches_encoding() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("estimate.bin");
+    let output_gif_path = temp_dir.path().join("estimate.gif");
+
+    let data: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+    fs::write(&source_file_path, &data).expect("Failed to write source file");
+
+    let estimate = fountain::estimate_encoding(&source_file_path, Some(200), 500, 4)
+        .expect("Estimation failed");
+    let result =
+        fountain::encode_file_to_gif(&source_file_path, &output_gif_path, Some(200), 500, 4)
+            .expect("GIF encoding failed");
+
+    assert_eq!(estimate.num_chunks, result.num_chunks);
+    assert_eq!(estimate.effective_size, result.effective_size);
+    assert_eq!(estimate.duration_ms, 500 * result.num_chunks as u64);
+    assert!(estimate.frame_bytes <= estimate.effective_size);
+    assert!(estimate.estimated_gif_bytes > 0);
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_target_frames_picks_payload_size() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("budget.bin");
+
+    let data: Vec<u8> = (0..5000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &data).expect("Failed to write source file");
+
+    let size = fountain::payload_size_for_frame_budget(&source_file_path, 40, 4)
+        .expect("Payload size selection failed");
+    let chosen = fountain::estimate_encoding(&source_file_path, Some(size), 500, 4)
+        .expect("Estimation failed");
+    assert!(chosen.num_chunks <= 40);
+
+    // The next smaller payload size would break the budget.
+    let smaller = fountain::estimate_encoding(&source_file_path, Some(size - 1), 500, 4)
+        .expect("Estimation failed");
+    assert!(smaller.num_chunks > 40);
+
+    assert!(fountain::payload_size_for_frame_budget(&source_file_path, 2, 4).is_err());
+}
+
 #[test]
 #[cfg(all(feature = "encode", feature = "decode"))]
 fn test_encode_decode_gif_roundtrip() {
@@ -164,6 +219,2522 @@ fn test_encode_decode_gif_roundtrip() {
     assert_eq!(original_content, decoded_content);
 }
 
+#[test]
+#[cfg(feature = "encode")]
+fn test_gif_interval_is_rounded_to_playable_delays() {
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    assert_eq!(fountain::gif_interval_ms(2000), 2000);
+    assert_eq!(fountain::gif_interval_ms(84), 80);
+    assert_eq!(fountain::gif_interval_ms(5), fountain::MIN_GIF_INTERVAL_MS);
+    assert!(fountain::gif_timing_warnings(2000).is_empty());
+    assert!(fountain::gif_timing_warnings(100).is_empty());
+    assert_eq!(fountain::gif_timing_warnings(125).len(), 1);
+    assert_eq!(fountain::gif_timing_warnings(5).len(), 2);
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("source.txt");
+    let output_gif_path = temp_dir.path().join("output.gif");
+    fs::write(&source_file_path, "GIF timing test.").expect("Failed to write source file");
+
+    fountain::encode_file_to_gif(&source_file_path, &output_gif_path, None, 5, 2)
+        .expect("GIF encoding failed");
+    let gif = fs::File::open(&output_gif_path).expect("Failed to open GIF");
+    let frames = GifDecoder::new(std::io::BufReader::new(gif))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    for frame in frames {
+        assert_eq!(frame.delay().numer_denom_ms(), (20, 1));
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_builder_api_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("builder.txt");
+    let output_gif_path = temp_dir.path().join("builder.gif");
+    let decoded_path = temp_dir.path().join("builder_decoded.txt");
+
+    let original_content = "Builder API roundtrip test. ".repeat(20);
+    fs::write(&source_file_path, &original_content).expect("Failed to write source file");
+
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300).interval_ms(100));
+    let result = encoder
+        .encode_to_gif(&source_file_path, &output_gif_path)
+        .expect("GIF encoding failed");
+    assert!(result.effective_size <= 300);
+    assert_eq!(result.total_bytes, original_content.len());
+    assert!(result.compressed_bytes > 0 && result.compressed_bytes < result.total_bytes);
+
+    let decoder = Decoder::new(DecodeOptions::new().output(&decoded_path));
+    let decoded = decoder.decode(&output_gif_path).expect("Decoding failed");
+    assert_eq!(decoded.original_filename, "builder.txt");
+    assert_eq!(decoded.total_bytes, original_content.len());
+    assert_eq!(decoded.compressed_bytes, result.compressed_bytes);
+    assert!(decoded.frames_scanned >= decoded.num_chunks + decoded.duplicate_packets);
+    assert_eq!(fs::read_to_string(&decoded_path).unwrap(), original_content);
+
+    let unsupported_path = temp_dir.path().join("builder.bin");
+    fs::write(&unsupported_path, &original_content).unwrap();
+    let err = decoder
+        .decode(&unsupported_path)
+        .expect_err("Decoding a non-GIF file should fail");
+    assert!(matches!(err, FountainError::UnsupportedInput(_)));
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_lazy_frames_match_image_output() {
+    use fountain::{EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("frames.txt");
+    fs::write(&source_file_path, "Lazy frame iterator test. ".repeat(30))
+        .expect("Failed to write source file");
+
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(200));
+    let mut frames = encoder
+        .frames(&source_file_path)
+        .expect("Preparing frames failed");
+    let total = frames.total();
+    assert_eq!(frames.len(), total);
+
+    let first = frames
+        .next()
+        .unwrap()
+        .expect("Rendering first frame failed");
+    assert_eq!(frames.len(), total - 1);
+    for frame in frames {
+        assert_eq!(
+            frame.expect("Rendering frame failed").dimensions(),
+            first.dimensions()
+        );
+    }
+
+    let result = encoder
+        .encode_to_images(&source_file_path, &temp_dir.path().join("images"))
+        .expect("Image encoding failed");
+    assert_eq!(result.num_chunks, total);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_served_frames_roundtrip() {
+    use fountain::{CancelFlag, EncodeOptions, Encoder};
+    use std::io::{BufRead, Read, Write};
+    use std::net::TcpStream;
+
+    fn get(addr: std::net::SocketAddr, path: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..split]).to_string();
+        (head, response[split + 4..].to_vec())
+    }
+
+    let original_content: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let cancel = CancelFlag::default();
+    let encoder = Encoder::new(
+        EncodeOptions::new()
+            .chunk_size(400)
+            .interval_ms(700)
+            .cancel_flag(cancel.clone()),
+    );
+    let frames = encoder
+        .frames_from_bytes(&original_content, "served.bin")
+        .expect("Encoding failed");
+    let server = encoder
+        .serve(frames, "127.0.0.1:0")
+        .expect("Failed to serve");
+    let addr = server.local_addr().unwrap();
+    let total = server.total();
+    let running = std::thread::spawn(move || server.run());
+
+    let (head, page) = get(addr, "/");
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert!(String::from_utf8_lossy(&page).contains("frames.json"));
+    let (_, info) = get(addr, "/frames.json");
+    let info: serde_json::Value = serde_json::from_slice(&info).unwrap();
+    assert_eq!(info["filename"], "served.bin");
+    assert_eq!(info["total"], total);
+    assert_eq!(info["interval_ms"], 700);
+    let (head, _) = get(addr, &format!("/frame/{}.png", total));
+    assert!(head.starts_with("HTTP/1.1 404"));
+
+    let images = (0..total).map(|i| {
+        let (head, png) = get(addr, &format!("/frame/{}.png", i));
+        assert!(head.contains("image/png"), "{}", head);
+        image::load_from_memory(&png).expect("Served frame is not a PNG")
+    });
+    let files = fountain::decode_frames(images.collect::<Vec<_>>()).expect("Decoding failed");
+    assert_eq!(files[0].filename, "served.bin");
+    assert_eq!(files[0].data, original_content);
+
+    // The MJPEG stream cycles through the same frames, at the interval asked for.
+    let mut stream = std::io::BufReader::new(TcpStream::connect(addr).unwrap());
+    write!(
+        stream.get_mut(),
+        "GET /stream.mjpg?interval=20 HTTP/1.1\r\n\r\n"
+    )
+    .unwrap();
+    let mut jpegs = Vec::new();
+    let mut line = String::new();
+    while jpegs.len() < total {
+        line.clear();
+        stream.read_line(&mut line).unwrap();
+        if let Some(len) = line.trim().strip_prefix("Content-Length: ") {
+            let mut jpeg = vec![0; len.parse().unwrap()];
+            stream.read_line(&mut line).unwrap();
+            stream.read_exact(&mut jpeg).unwrap();
+            jpegs.push(image::load_from_memory(&jpeg).expect("Streamed frame is not a JPEG"));
+        }
+    }
+    let files = fountain::decode_frames(jpegs).expect("Decoding the stream failed");
+    assert_eq!(files[0].data, original_content);
+
+    cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+    running.join().unwrap().expect("Server failed");
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_stream_decoder_feed_frames() {
+    use fountain::{EncodeOptions, Encoder, FeedResult, StreamDecoder};
+    use image::DynamicImage;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("stream.txt");
+    let original_content = "Streaming decode test. ".repeat(30);
+    fs::write(&source_file_path, &original_content).expect("Failed to write source file");
+
+    let frames = Encoder::new(EncodeOptions::new().chunk_size(200))
+        .frames(&source_file_path)
+        .expect("Preparing frames failed");
+
+    let mut decoder = StreamDecoder::new();
+    let blank = DynamicImage::new_rgb8(64, 64);
+    assert!(matches!(
+        decoder.feed_image(&blank).unwrap(),
+        FeedResult::NoChunk
+    ));
+
+    let mut completed = None;
+    for frame in frames {
+        let img = DynamicImage::ImageRgb8(frame.expect("Rendering frame failed"));
+        match decoder.feed_image(&img).expect("Feeding frame failed") {
+            FeedResult::Accepted => {
+                assert!(matches!(
+                    decoder.feed_image(&img).unwrap(),
+                    FeedResult::Duplicate
+                ));
+            }
+            FeedResult::Complete(file) => {
+                completed = Some(file);
+                break;
+            }
+            other => panic!("Unexpected feed result: {:?}", other),
+        }
+    }
+
+    let file = completed.expect("Decoder never completed");
+    assert_eq!(file.filename, "stream.txt");
+    assert_eq!(file.data, original_content.as_bytes());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_in_memory_roundtrip() {
+    use fountain::{EncodeOptions, FountainError};
+    use image::DynamicImage;
+
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let frames = fountain::encode_bytes(
+        &original_content,
+        "memory.txt",
+        EncodeOptions::new().chunk_size(300),
+    )
+    .expect("In-memory encoding failed");
+    assert!(frames.len() > 1);
+
+    let files = fountain::decode_frames(frames.iter().cloned().map(DynamicImage::ImageRgb8))
+        .expect("In-memory decoding failed");
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].filename, "memory.txt");
+    assert_eq!(files[0].data, original_content);
+
+    let err = fountain::decode_frames(frames.into_iter().take(1).map(DynamicImage::ImageRgb8))
+        .expect_err("A single frame should not be enough");
+    assert!(matches!(err, FountainError::NotEnoughPackets { .. }));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_custom_output_sink() {
+    use fountain::{EncodeOptions, Encoder, OutputSink, SinkFrame, SinkInfo, TerminalSink};
+    use image::{DynamicImage, RgbImage};
+
+    #[derive(Default)]
+    struct Collect {
+        info: Option<SinkInfo>,
+        frames: Vec<RgbImage>,
+        parity: usize,
+        finished: bool,
+    }
+
+    impl OutputSink for Collect {
+        fn begin(&mut self, info: &SinkInfo) -> fountain::Result<()> {
+            self.info = Some(info.clone());
+            Ok(())
+        }
+
+        fn write_frame(&mut self, frame: &SinkFrame<'_>) -> fountain::Result<()> {
+            assert_eq!(frame.index, self.frames.len());
+            self.parity += usize::from(frame.is_parity);
+            self.frames.push(frame.image.clone());
+            Ok(())
+        }
+
+        fn finish(&mut self) -> fountain::Result<Vec<String>> {
+            self.finished = true;
+            Ok(vec!["led-matrix".to_string()])
+        }
+    }
+
+    let data: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300).parity(50).cover(true));
+    let mut sink = Collect::default();
+    let result = encoder
+        .encode_bytes_to_sink(&data, "sink.bin", &mut sink)
+        .expect("Encoding to a sink failed");
+    let info = sink.info.expect("begin was not called");
+    assert_eq!(info.filename, "sink.bin");
+    assert_eq!(info.total, sink.frames.len());
+    assert_eq!(info.parity, sink.parity);
+    assert!(sink.parity > 0 && sink.finished);
+    assert_eq!(result.num_chunks, info.total);
+    assert_eq!(result.output_files, ["led-matrix"]);
+
+    let files = fountain::decode_frames(sink.frames.into_iter().map(DynamicImage::ImageRgb8))
+        .expect("Decoding the sink's frames failed");
+    assert_eq!(files[0].data, data);
+
+    let mut printed = Vec::new();
+    Encoder::new(EncodeOptions::new())
+        .encode_bytes_to_sink(b"short", "short.txt", &mut TerminalSink::new(&mut printed))
+        .expect("Encoding to the terminal sink failed");
+    let printed = String::from_utf8(printed).unwrap();
+    assert!(printed.starts_with("File: short.txt  |  QR Code 1/"));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_bench() {
+    use fountain::{EncodeOptions, Encoder};
+
+    for camera in [false, true] {
+        let result = Encoder::new(EncodeOptions::new().chunk_size(300))
+            .bench(3000, camera)
+            .expect("Benchmark failed");
+        assert_eq!(result.payload_bytes, 3000);
+        assert_eq!(result.effective_size, 300);
+        assert!(result.frames_scanned > 0 && result.frames_scanned <= result.frames);
+        assert!(result.encode_fps() > 0.0 && result.decode_fps() > 0.0);
+        assert!(result.throughput() > 0.0);
+        assert_eq!(serde_json::to_value(&result).unwrap()["camera"], camera);
+    }
+}
+
+#[test]
+#[cfg(all(feature = "color", feature = "encode", feature = "decode"))]
+fn test_color_matrix_frames() {
+    use fountain::{ColorMatrix, DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::{DynamicImage, RgbImage};
+
+    let mut state = 0x2545_f491_u32;
+    let data: Vec<u8> = (0..40_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let qr_frames = Encoder::new(EncodeOptions::new())
+        .frames_from_bytes(&data, "noise.bin")
+        .unwrap()
+        .total();
+    let matrix = ColorMatrix::new(fountain::matrix::DEFAULT_MATRIX_CELLS).unwrap();
+    let frames = Encoder::new(EncodeOptions::new().color_matrix(matrix))
+        .frames_from_bytes(&data, "noise.bin")
+        .unwrap();
+    assert_eq!(frames.effective_size(), matrix.capacity());
+    assert!(frames.total() * 3 < qr_frames);
+
+    let images = frames.collect::<fountain::Result<Vec<RgbImage>>>().unwrap();
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_frames(images.into_iter().map(DynamicImage::ImageRgb8))
+        .unwrap();
+    assert_eq!(files[0].data, data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_txqr_gif_roundtrip() {
+    use fountain::txqr::TXQR_FILENAME;
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let dir = TempDir::new().unwrap();
+    let gif = dir.path().join("txqr.gif");
+    let data = b"Sent as txqr frames, readable by its phone apps. ".repeat(12);
+    let result = Encoder::new(EncodeOptions::new().chunk_size(150))
+        .encode_bytes_to_txqr_gif(&data, &gif)
+        .unwrap();
+    assert_eq!(result.num_chunks, data.len().div_ceil(150));
+
+    // Without the option, the frames are QR codes that hold no chunk.
+    assert!(Decoder::new(DecodeOptions::new()).decode(&gif).is_err());
+
+    let output = dir.path().join("out");
+    let decoded = Decoder::new(DecodeOptions::new().txqr(true).output(&output))
+        .decode(&gif)
+        .unwrap();
+    assert_eq!(decoded.original_filename, TXQR_FILENAME);
+    assert_eq!(fs::read(&output).unwrap(), data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_custom_frame_source() {
+    use fountain::{
+        DecodeOptions, Decoder, EncodeOptions, Encoder, FrameSource, GifSource, SourceFrame,
+    };
+    use image::DynamicImage;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A camera pointed at a looping display: it never runs out of frames.
+    struct Camera {
+        frames: Vec<DynamicImage>,
+        captured: usize,
+    }
+
+    impl FrameSource for Camera {
+        fn next_frame(&mut self) -> Option<SourceFrame> {
+            let image = self.frames[self.captured % self.frames.len()].clone();
+            let at = Duration::from_millis(40 * self.captured as u64);
+            self.captured += 1;
+            Some(SourceFrame::image(image, format!("capture {}", self.captured)).timestamp(at))
+        }
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data: Vec<u8> = (0..1500).map(|_| rand::random::<u8>()).collect();
+    let frames = fountain::encode_bytes(&data, "camera.bin", EncodeOptions::new().chunk_size(200))
+        .expect("Encoding failed");
+    let mut camera = Camera {
+        frames: frames.into_iter().map(DynamicImage::ImageRgb8).collect(),
+        captured: 0,
+    };
+
+    let timestamps = Arc::new(Mutex::new(Vec::new()));
+    let recorded = timestamps.clone();
+    let output = temp_dir.path().join("camera.bin");
+    let options = DecodeOptions::new()
+        .output(&output)
+        .on_frame_scanned(move |frame| recorded.lock().unwrap().push(frame.timestamp));
+    let result = Decoder::new(options)
+        .decode_source(&mut camera)
+        .expect("Decoding from the camera failed");
+    assert_eq!(fs::read(&output).unwrap(), data);
+    assert_eq!(result.frames_scanned, camera.captured);
+    assert_eq!(
+        timestamps.lock().unwrap()[..2],
+        [Some(Duration::ZERO), Some(Duration::from_millis(40))]
+    );
+
+    // A GIF stamps each frame with the time it is shown at.
+    let gif = temp_dir.path().join("camera.gif");
+    Encoder::new(EncodeOptions::new().chunk_size(200).interval_ms(100))
+        .encode_bytes_to_gif(&data, "camera.bin", &gif)
+        .expect("Encoding to a GIF failed");
+    let mut source = GifSource::open(&gif).expect("Opening the GIF failed");
+    let shown_at: Vec<_> = std::iter::from_fn(|| source.next_frame())
+        .map(|frame| frame.timestamp)
+        .take(3)
+        .collect();
+    assert_eq!(
+        shown_at,
+        [0, 100, 200].map(|ms| Some(Duration::from_millis(ms)))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_empty_and_tiny_files_roundtrip() {
+    use fountain::{EncodeOptions, FountainError, MIN_PAYLOAD_SIZE};
+    use image::DynamicImage;
+
+    for len in [0, 1, 3, 8] {
+        let content: Vec<u8> = (0..len).map(|_| rand::random::<u8>()).collect();
+        for chunk_size in [MIN_PAYLOAD_SIZE, 100] {
+            let frames = fountain::encode_bytes(
+                &content,
+                "tiny.bin",
+                EncodeOptions::new().chunk_size(chunk_size),
+            )
+            .expect("Encoding a tiny file failed");
+            let files = fountain::decode_frames(frames.into_iter().map(DynamicImage::ImageRgb8))
+                .expect("Decoding a tiny file failed");
+            assert_eq!(files[0].data, content, "{} bytes at {}", len, chunk_size);
+        }
+    }
+
+    let err = fountain::encode_bytes(
+        b"",
+        "tiny.bin",
+        EncodeOptions::new().chunk_size(MIN_PAYLOAD_SIZE - 1),
+    )
+    .expect_err("A payload without room for a packet should be rejected");
+    assert!(matches!(err, FountainError::PayloadTooSmall { .. }));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_mismatched_encodings_are_kept_apart() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, FountainError, ImageSource};
+    use image::DynamicImage;
+
+    let content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let encode = |chunk_size| {
+        fountain::encode_bytes(
+            &content,
+            "mixed.bin",
+            EncodeOptions::new().chunk_size(chunk_size),
+        )
+        .expect("In-memory encoding failed")
+    };
+    let (small, large) = (encode(300), encode(500));
+
+    // A few stray frames of another encoding must not corrupt the transfer, and are reported.
+    let frames = small[..2].iter().chain(&large).cloned();
+    let files = fountain::decode_frames(frames.map(DynamicImage::ImageRgb8))
+        .expect("Decoding with stray frames failed");
+    assert_eq!(files[0].data, content);
+    let frames = small[..2].iter().chain(&large).cloned();
+    let result = Decoder::new(DecodeOptions::new().check_only(true))
+        .decode_source(&mut ImageSource::new(frames.map(DynamicImage::ImageRgb8)))
+        .expect("Decoding with stray frames failed");
+    assert_eq!(result.warnings.len(), 1);
+    assert!(result.warnings[0].contains("different encoding"));
+
+    let frames = small[..3].iter().chain(&large[..2]).cloned();
+    let err = fountain::decode_frames(frames.map(DynamicImage::ImageRgb8))
+        .expect_err("Neither encoding is complete");
+    assert!(matches!(
+        err,
+        FountainError::MismatchedPackets {
+            ignored: 2,
+            received: 3,
+            ..
+        }
+    ));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_truncated_gif_decodes_from_remaining_frames() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("truncated.bin");
+    let output_gif_path = temp_dir.path().join("truncated.gif");
+    let content: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &content).expect("Failed to write source file");
+
+    Encoder::new(EncodeOptions::new().chunk_size(300))
+        .encode_to_gif(&source_file_path, &output_gif_path)
+        .expect("GIF encoding failed");
+    let gif = fs::read(&output_gif_path).expect("Failed to read GIF");
+
+    // Cutting off the tail leaves a broken last frame, but the repair packets cover the loss.
+    fs::write(&output_gif_path, &gif[..gif.len() * 9 / 10]).unwrap();
+    let decoder = Decoder::new(DecodeOptions::new());
+    let files = decoder
+        .decode_files(&output_gif_path)
+        .expect("Decoding a truncated GIF failed");
+    assert_eq!(files[0].data, content);
+
+    // With most of the GIF gone, the scan still ends and reports the shortfall.
+    fs::write(&output_gif_path, &gif[..gif.len() / 3]).unwrap();
+    let err = decoder
+        .decode_files(&output_gif_path)
+        .expect_err("A third of the GIF is not enough");
+    assert!(matches!(err, FountainError::NotEnoughPackets { .. }));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_unicode_and_long_filenames_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300));
+    let decoder = Decoder::new(DecodeOptions::new());
+    let long_name = format!("{}.bin", "n".repeat(251));
+    for (i, name) in ["季度报告.txt", "🎉 party 🎈.txt", long_name.as_str()]
+        .into_iter()
+        .enumerate()
+    {
+        let source_file_path = temp_dir.path().join(name);
+        let content = format!("File number {} with an unusual name. ", i).repeat(10);
+        fs::write(&source_file_path, &content).expect("Failed to write source file");
+
+        let output_dir = temp_dir.path().join(format!("images{}", i));
+        encoder
+            .encode_to_images(&source_file_path, &output_dir)
+            .expect("Image encoding failed");
+        let files = decoder
+            .decode_files(&output_dir)
+            .expect("Image decoding failed");
+        assert_eq!(files[0].filename, name);
+        assert_eq!(files[0].data, content.as_bytes());
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_multi_file_roundtrip() {
+    use fountain::{
+        DecodeOptions, Decoder, EncodeOptions, Encoder, FeedResult, FountainError, StreamDecoder,
+    };
+    use image::DynamicImage;
+    use sha2::{Digest, Sha256};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let first_path = temp_dir.path().join("first.bin");
+    let second_path = temp_dir.path().join("second.txt");
+    let first: Vec<u8> = (0..1500).map(|_| rand::random::<u8>()).collect();
+    let second = "Second file of a multi-file transfer. ".repeat(10);
+    fs::write(&first_path, &first).expect("Failed to write first file");
+    fs::write(&second_path, &second).expect("Failed to write second file");
+
+    let gif_path = temp_dir.path().join("multi.gif");
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300));
+    let result = encoder
+        .encode_files_to_gif(&[&first_path, &second_path], &gif_path)
+        .expect("Multi-file encoding failed");
+    assert_eq!(result.total_bytes, first.len() + second.len());
+
+    let output_dir = temp_dir.path().join("received");
+    let decoded = Decoder::new(DecodeOptions::new().output(&output_dir))
+        .decode(&gif_path)
+        .expect("Multi-file decoding failed");
+    assert_eq!(decoded.output_files.len(), 2);
+    assert_eq!(fs::read(output_dir.join("first.bin")).unwrap(), first);
+    assert_eq!(
+        fs::read_to_string(output_dir.join("second.txt")).unwrap(),
+        second
+    );
+
+    // The manifest leads the frames, so the files are known before either decodes.
+    let mut stream = StreamDecoder::new();
+    let mut results = encoder
+        .frames_for_files(&[&first_path, &second_path])
+        .expect("Multi-file frames failed")
+        .map(|frame| {
+            let frame = DynamicImage::ImageRgb8(frame.expect("Rendering failed"));
+            stream.feed_image(&frame).expect("Feeding failed")
+        });
+    let Some(FeedResult::Manifest(manifest)) = results.next() else {
+        panic!("Multi-file transfer should start with its manifest");
+    };
+    let names: Vec<&str> = manifest
+        .files
+        .iter()
+        .map(|file| file.name.as_str())
+        .collect();
+    assert_eq!(names, ["first.bin", "second.txt"]);
+    assert_eq!(manifest.files[1].size, second.len() as u64);
+    assert_eq!(
+        manifest.files[0].sha256,
+        <[u8; 32]>::from(Sha256::digest(&first))
+    );
+    assert!(results.all(|fed| !matches!(fed, FeedResult::Manifest(_))));
+
+    let err = encoder
+        .frames_for_files(&[&first_path, &first_path])
+        .err()
+        .expect("Duplicate filenames should be rejected");
+    assert!(matches!(err, FountainError::InvalidFilename(_)));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_mixed_manifest_parts_are_dropped() {
+    use fountain::{
+        Chunk, EncodeOptions, Encoder, FeedResult, Manifest, PacketEncoding, StreamDecoder,
+    };
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let first_path = temp_dir.path().join("first.bin");
+    let second_path = temp_dir.path().join("second.txt");
+    let first: Vec<u8> = (0..1500).map(|_| rand::random::<u8>()).collect();
+    let second = "Second file of a multi-file transfer. ".repeat(10);
+    fs::write(&first_path, &first).expect("Failed to write first file");
+    fs::write(&second_path, &second).expect("Failed to write second file");
+
+    let mut lines = Vec::new();
+    Encoder::new(EncodeOptions::new().chunk_size(300))
+        .encode_files_to_packets(
+            &[&first_path, &second_path],
+            &mut lines,
+            PacketEncoding::Base45,
+        )
+        .expect("Packet encoding failed");
+    let chunks: Vec<Chunk> = String::from_utf8(lines)
+        .unwrap()
+        .lines()
+        .map(|line| Chunk::from_text(line).expect("Invalid packet line"))
+        .collect();
+    let transfer = chunks[0].header.transfer;
+
+    // Two manifests of the same transfer in two parts each: the first part of one and the
+    // second of the other complete a manifest that does not parse.
+    let ours = Manifest::new(&[(&first, "first.bin"), (second.as_bytes(), "second.txt")])
+        .to_chunks(transfer, 66)
+        .unwrap();
+    let other = Manifest::new(&[(b"a", "a.bin"), (b"b", "b.txt")])
+        .to_chunks(transfer, 66)
+        .unwrap();
+    assert_eq!((ours.len(), other.len()), (2, 2));
+
+    let mut decoder = StreamDecoder::new();
+    let fed = decoder.feed_chunk(ours[0].clone()).unwrap();
+    assert!(matches!(fed, FeedResult::Accepted));
+    let fed = decoder.feed_chunk(other[1].clone()).unwrap();
+    assert!(matches!(fed, FeedResult::Duplicate));
+
+    let mut manifest = None;
+    let mut files = Vec::new();
+    for chunk in chunks {
+        match decoder.feed_chunk(chunk).expect("Feeding failed") {
+            FeedResult::Manifest(complete) => manifest = Some(complete),
+            FeedResult::FileComplete(file) | FeedResult::Complete(file) => files.push(file),
+            _ => {}
+        }
+    }
+    let names: Vec<String> = manifest
+        .expect("The transfer's own manifest should still complete")
+        .files
+        .into_iter()
+        .map(|file| file.name)
+        .collect();
+    assert_eq!(names, ["first.bin", "second.txt"]);
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].data, first);
+    assert_eq!(files[1].data, second.as_bytes());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_split_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_path = temp_dir.path().join("big.bin");
+    let data: Vec<u8> = (0..2500).map(|_| rand::random::<u8>()).collect();
+    fs::write(&input_path, &data).expect("Failed to write input file");
+
+    let gif_path = temp_dir.path().join("big.gif");
+    let results = Encoder::new(EncodeOptions::new().chunk_size(600))
+        .encode_split_to_gif(&input_path, &gif_path, 1000)
+        .expect("Split encoding failed");
+    assert_eq!(results.len(), 3);
+
+    let parts: Vec<_> = (1..=3)
+        .rev()
+        .map(|i| temp_dir.path().join(format!("big.part{}.gif", i)))
+        .collect();
+    let parts: Vec<&std::path::Path> = parts.iter().map(|p| p.as_path()).collect();
+    let output_path = temp_dir.path().join("joined.bin");
+    let result = Decoder::new(DecodeOptions::new().output(&output_path))
+        .decode_split(&parts)
+        .expect("Split decoding failed");
+    assert_eq!(result.original_filename, "big.bin");
+    assert_eq!(fs::read(&output_path).unwrap(), data);
+
+    assert!(
+        Decoder::new(DecodeOptions::new().output(&output_path).overwrite(true))
+            .decode_split(&parts[1..])
+            .is_err()
+    );
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_max_chunks() {
+    use fountain::{EncodeOptions, Encoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("capped.gif");
+    let data: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+
+    let options = EncodeOptions::new().chunk_size(100);
+    let err = Encoder::new(options.clone().max_chunks(20))
+        .encode_bytes_to_gif(&data, "capped.bin", &gif_path)
+        .expect_err("Encoding over the chunk limit should fail");
+    assert!(
+        matches!(err, FountainError::TooManyChunks { chunks, limit: 20, .. } if chunks > 20),
+        "{:?}",
+        err
+    );
+    assert_eq!(err.exit_code(), 13);
+    assert!(!gif_path.exists());
+
+    // Denser codes fit under the same limit.
+    let result = Encoder::new(options.chunk_size(1000).max_chunks(20))
+        .encode_bytes_to_gif(&data, "capped.bin", &gif_path)
+        .expect("Encoding within the chunk limit failed");
+    assert!(result.num_chunks <= 20);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_memory_limit() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_path = temp_dir.path().join("big.bin");
+    let data: Vec<u8> = (0..2500).map(|_| rand::random::<u8>()).collect();
+    fs::write(&input_path, &data).expect("Failed to write input file");
+    let gif_path = temp_dir.path().join("big.gif");
+
+    // Too little for the input itself, then for encoding it.
+    for limit in [1000, 5000] {
+        let err = Encoder::new(EncodeOptions::new().max_memory(limit))
+            .encode_to_gif(&input_path, &gif_path)
+            .expect_err("Encoding over the memory limit should fail");
+        assert!(matches!(err, FountainError::MemoryLimitExceeded { .. }));
+        assert_eq!(err.exit_code(), 12);
+        assert!(!gif_path.exists());
+    }
+
+    // A split encode makes its parts small enough instead.
+    let results = Encoder::new(EncodeOptions::new().chunk_size(600).max_memory(5200))
+        .encode_split_to_gif(&input_path, &gif_path, 2000)
+        .expect("Split encoding within the memory limit failed");
+    assert_eq!(results.len(), 5);
+    let parts: Vec<_> = (1..=5)
+        .map(|i| temp_dir.path().join(format!("big.part{}.gif", i)))
+        .collect();
+    let parts: Vec<&std::path::Path> = parts.iter().map(|p| p.as_path()).collect();
+
+    let output_path = temp_dir.path().join("joined.bin");
+    let err = Decoder::new(DecodeOptions::new().output(&output_path).max_memory(1000))
+        .decode_split(&parts)
+        .expect_err("Decoding over the memory limit should fail");
+    assert!(matches!(err, FountainError::MemoryLimitExceeded { .. }));
+
+    Decoder::new(DecodeOptions::new().output(&output_path).max_memory(5200))
+        .decode_split(&parts)
+        .expect("Split decoding within the memory limit failed");
+    assert_eq!(fs::read(&output_path).unwrap(), data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_append_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let first_path = temp_dir.path().join("first.bin");
+    let second_path = temp_dir.path().join("second.bin");
+    let first: Vec<u8> = (0..800).map(|_| rand::random::<u8>()).collect();
+    let second: Vec<u8> = (0..1200).map(|_| rand::random::<u8>()).collect();
+    fs::write(&first_path, &first).expect("Failed to write input file");
+    fs::write(&second_path, &second).expect("Failed to write input file");
+
+    let gif_path = temp_dir.path().join("out.gif");
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300));
+    let original = encoder
+        .encode_to_gif(&first_path, &gif_path)
+        .expect("GIF encoding failed");
+    let appended = Encoder::new(EncodeOptions::new())
+        .append_to_gif(&[second_path.as_path()], &gif_path)
+        .expect("Appending failed");
+
+    // Old and new frames share one size, so the appended transfer reused the QR version.
+    let file = std::io::BufReader::new(fs::File::open(&gif_path).unwrap());
+    let frames = GifDecoder::new(file)
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .expect("Failed to decode GIF frames");
+    assert_eq!(frames.len(), original.num_chunks + appended.num_chunks);
+    let size = frames[0].buffer().dimensions();
+    assert!(frames.iter().all(|f| f.buffer().dimensions() == size));
+
+    let out_dir = temp_dir.path().join("decoded");
+    let result = Decoder::new(DecodeOptions::new().output(&out_dir).all_transfers(true))
+        .decode(&gif_path)
+        .expect("Decoding all transfers failed");
+    assert_eq!(result.output_files.len(), 2);
+    assert_eq!(fs::read(out_dir.join("first.bin")).unwrap(), first);
+    assert_eq!(fs::read(out_dir.join("second.bin")).unwrap(), second);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_text_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, TEXT_FILENAME};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("text.gif");
+    let text = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHk user@host";
+    Encoder::new(EncodeOptions::new())
+        .encode_bytes_to_gif(text.as_bytes(), TEXT_FILENAME, &gif_path)
+        .expect("Text encoding failed");
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&gif_path)
+        .expect("Decoding to memory failed");
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].filename, TEXT_FILENAME);
+    assert_eq!(files[0].data, text.as_bytes());
+    assert!(!temp_dir.path().join(TEXT_FILENAME).exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_check_only_writes_nothing() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("frames");
+    let data = b"Archived and checked now and then".repeat(20);
+    Encoder::new(EncodeOptions::new().chunk_size(200))
+        .encode_bytes_to_images(&data, "archived.txt", &images_dir)
+        .expect("Encoding failed");
+
+    // Without an output, the file would be written next to the image directory.
+    let result = Decoder::new(DecodeOptions::new().check_only(true))
+        .decode(&images_dir)
+        .expect("Check failed");
+    assert_eq!(result.original_filename, "archived.txt");
+    assert_eq!(result.total_bytes, data.len());
+    assert!(result.output_files.is_empty());
+    assert!(result.output_path.is_empty());
+    assert!(!temp_dir.path().join("archived.txt").exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_packet_lines_roundtrip() {
+    use base64::prelude::{Engine as _, BASE64_STANDARD};
+    use fountain::{Chunk, EncodeOptions, Encoder, FeedResult, PacketEncoding, StreamDecoder};
+
+    let original_content: Vec<u8> = (0..2500).map(|_| rand::random::<u8>()).collect();
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300));
+    let lines = |encoding| {
+        let mut out = Vec::new();
+        let result = encoder
+            .encode_bytes_to_packets(&original_content, "lines.bin", &mut out, encoding)
+            .expect("Packet output failed");
+        let lines: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(lines.len(), result.num_chunks);
+        lines
+    };
+    let base64_lines = lines(PacketEncoding::Base64);
+    let base45_lines = lines(PacketEncoding::Base45);
+
+    // Both forms carry the same packets, and the base45 lines are what the QR codes hold.
+    let mut decoder = StreamDecoder::new();
+    let mut decoded = None;
+    for (base64, base45) in base64_lines.iter().zip(&base45_lines) {
+        let chunk = Chunk::from_bytes(&BASE64_STANDARD.decode(base64).unwrap()).unwrap();
+        assert_eq!(chunk.to_text().unwrap(), *base45);
+        if let FeedResult::Complete(file) = decoder.feed_chunk(chunk).unwrap() {
+            decoded = Some(file);
+        }
+    }
+    let file = decoded.expect("Packets did not complete the file");
+    assert_eq!(file.filename, "lines.bin");
+    assert_eq!(file.data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_parity_frames_replace_lost_pages() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("pages");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+
+    let result = Encoder::new(EncodeOptions::new().chunk_size(300).parity(60))
+        .encode_bytes_to_images(&original_content, "archive.bin", &images_dir)
+        .expect("Image encoding failed");
+    let parity: Vec<_> = result
+        .output_files
+        .iter()
+        .filter(|name| name.starts_with("archive_bin_parity_"))
+        .collect();
+    let regular = result.num_chunks - parity.len();
+    assert_eq!(parity.len(), (regular * 60).div_ceil(100));
+    assert_eq!(*parity[0], "archive_bin_parity_0001.png");
+
+    // Every regular page is lost; the parity pages alone carry enough fresh packets.
+    for name in &result.output_files[..regular] {
+        fs::remove_file(images_dir.join(name)).unwrap();
+    }
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&images_dir)
+        .expect("Decoding parity pages failed");
+    assert_eq!(files[0].filename, "archive.bin");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_quiet_zone_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let narrow_dir = temp_dir.path().join("narrow");
+    let wide_dir = temp_dir.path().join("wide");
+    let original_content: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+
+    let options = EncodeOptions::new().chunk_size(400).pixel_scale(2);
+    let narrow = Encoder::new(options.clone())
+        .encode_bytes_to_images(&original_content, "margin.bin", &narrow_dir)
+        .expect("Image encoding failed");
+    let wide = Encoder::new(options.quiet_zone(10))
+        .encode_bytes_to_images(&original_content, "margin.bin", &wide_dir)
+        .expect("Image encoding with a wide quiet zone failed");
+
+    // 6 more modules on each side, at 2 pixels each.
+    let (narrow_width, _) =
+        image::image_dimensions(narrow_dir.join(&narrow.output_files[0])).unwrap();
+    let (wide_width, _) = image::image_dimensions(wide_dir.join(&wide.output_files[0])).unwrap();
+    assert_eq!(wide_width, narrow_width + 2 * 6 * 2);
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&wide_dir)
+        .expect("Decoding wide margins failed");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_cover_sheet() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("covered");
+    let original_content: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+
+    let result = Encoder::new(EncodeOptions::new().chunk_size(400).cover(true))
+        .encode_bytes_to_images(&original_content, "cover.bin", &images_dir)
+        .expect("Image encoding failed");
+    assert_eq!(result.output_files[0], "cover_bin_0000_cover.png");
+    assert_eq!(result.output_files.len(), result.num_chunks + 1);
+    let cover_size = image::image_dimensions(images_dir.join(&result.output_files[0])).unwrap();
+    let frame_size = image::image_dimensions(images_dir.join(&result.output_files[1])).unwrap();
+    assert_eq!(cover_size, frame_size);
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&images_dir)
+        .expect("Decoding past the cover failed");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_packet_order() {
+    use fountain::chunk::{is_source_packet, Chunk};
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, PacketEncoding, PacketOrder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let first = temp_dir.path().join("first.bin");
+    let second = temp_dir.path().join("second.bin");
+    fs::write(
+        &first,
+        (0..1500).map(|_| rand::random::<u8>()).collect::<Vec<_>>(),
+    )
+    .unwrap();
+    fs::write(
+        &second,
+        (0..1500).map(|_| rand::random::<u8>()).collect::<Vec<_>>(),
+    )
+    .unwrap();
+
+    for order in [PacketOrder::SystematicFirst, PacketOrder::Interleaved] {
+        let mut lines = Vec::new();
+        Encoder::new(EncodeOptions::new().chunk_size(300).packet_order(order))
+            .encode_files_to_packets(&[&first, &second], &mut lines, PacketEncoding::Base45)
+            .expect("Encoding failed");
+        let sources: Vec<bool> = String::from_utf8(lines.clone())
+            .unwrap()
+            .lines()
+            .filter_map(|line| Chunk::from_text(line).ok())
+            .filter(|chunk| !chunk.is_manifest())
+            .map(|chunk| is_source_packet(&chunk))
+            .collect();
+        let last_source = sources.iter().rposition(|&source| source).unwrap();
+        let first_repair = sources.iter().position(|&source| !source).unwrap();
+        // Both files' source packets come first, or the repair packets are spread among them.
+        assert_eq!(
+            first_repair > last_source,
+            order == PacketOrder::SystematicFirst
+        );
+
+        let packets_path = temp_dir.path().join("packets.txt");
+        fs::write(&packets_path, &lines).unwrap();
+        let files = Decoder::new(DecodeOptions::new())
+            .decode_files(&packets_path)
+            .expect("Decoding failed");
+        assert_eq!(files.len(), 2);
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_http_upload_receiver() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn request(addr: std::net::SocketAddr, head: &str, body: &[u8]) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        write!(stream, "{}Content-Length: {}\r\n\r\n", head, body.len()).unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..split]).to_string();
+        (head, response[split + 4..].to_vec())
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let output = temp_dir.path().join("received.bin");
+    let original_content: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(400));
+    let gif = temp_dir.path().join("upload.gif");
+    encoder
+        .encode_bytes_to_gif(&original_content, "upload.bin", &gif)
+        .expect("GIF encoding failed");
+    let frames: Vec<image::RgbImage> = encoder
+        .frames_from_bytes(&original_content, "upload.bin")
+        .expect("Encoding failed")
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let server = Decoder::new(DecodeOptions::new().output(&output))
+        .receive_http("127.0.0.1:0")
+        .expect("Failed to bind");
+    let addr = server.local_addr().unwrap();
+    let running = std::thread::spawn(move || server.run());
+
+    let (head, page) = request(addr, "GET / HTTP/1.1\r\n", b"");
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert!(String::from_utf8_lossy(&page).contains("upload?name="));
+    let (head, _) = request(
+        addr,
+        "POST /upload?name=notes.txt HTTP/1.1\r\n",
+        b"not a photo",
+    );
+    assert!(head.starts_with("HTTP/1.1 415"), "{}", head);
+
+    // One upload at a time: another one meanwhile is turned away.
+    let mut slow = TcpStream::connect(addr).expect("Failed to connect");
+    write!(
+        slow,
+        "POST /upload?name=slow.png HTTP/1.1\r\nContent-Length: 4\r\n\r\n"
+    )
+    .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let (head, _) = request(addr, "POST /upload?name=fast.png HTTP/1.1\r\n", b"fast");
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    slow.write_all(b"slow").unwrap();
+    let mut response = String::new();
+    slow.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 415"), "{}", response);
+
+    // A photo of the first frame, then the rest as a GIF.
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgb8(frames[0].clone())
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let (head, reply) = request(addr, "POST /upload?name=photo.png HTTP/1.1\r\n", &png);
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&reply).unwrap()["frames"],
+        1
+    );
+    let (head, _) = request(
+        addr,
+        "POST /upload?name=capture.gif HTTP/1.1\r\n",
+        &fs::read(&gif).unwrap(),
+    );
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let result = running.join().unwrap().expect("Receiving failed");
+    assert_eq!(result.original_filename, "upload.bin");
+    assert_eq!(fs::read(&output).unwrap(), original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_skips_frames_after_completion() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("images");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let encoded = Encoder::new(EncodeOptions::new().chunk_size(300).parity(100))
+        .encode_bytes_to_images(&original_content, "skip.bin", &images_dir)
+        .expect("Image encoding failed");
+
+    // The source packets alone complete the file, so the parity frames go unread.
+    let decoded = Decoder::new(DecodeOptions::new())
+        .decode_images(&images_dir)
+        .expect("Decoding failed");
+    assert!(decoded.frames_skipped > 0);
+    assert_eq!(
+        decoded.frames_scanned + decoded.frames_skipped,
+        encoded.output_files.len()
+    );
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_beacon_interval() {
+    use fountain::chunk::Chunk;
+    use fountain::qr::decode_qr_from_dynamic_image;
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("images");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let result = Encoder::new(EncodeOptions::new().chunk_size(300).beacon_interval(3))
+        .encode_bytes_to_images(&original_content, "beacon.bin", &images_dir)
+        .expect("Image encoding failed");
+
+    let manifests: Vec<bool> = result
+        .output_files
+        .iter()
+        .map(|name| {
+            let frame = image::open(images_dir.join(name)).unwrap();
+            let text = String::from_utf8(decode_qr_from_dynamic_image(&frame).unwrap()).unwrap();
+            Chunk::from_text(&text).unwrap().is_manifest()
+        })
+        .collect();
+    // A single file gets a manifest too, ahead of every third packet.
+    let packets = manifests.iter().filter(|&&manifest| !manifest).count();
+    assert_eq!(manifests.len() - packets, packets.div_ceil(3));
+    for (i, &manifest) in manifests.iter().enumerate() {
+        assert_eq!(manifest, i % 4 == 0);
+    }
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&images_dir)
+        .expect("Decoding with beacons failed");
+    assert_eq!(files[0].data, original_content);
+
+    let err = Encoder::new(EncodeOptions::new().beacon_interval(0)).encode_bytes_to_images(
+        &original_content,
+        "beacon.bin",
+        &images_dir,
+    );
+    assert!(err.is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_receipt() {
+    use fountain::qr::decode_qr_from_dynamic_image;
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use sha2::{Digest, Sha256};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("images");
+    let output_gif = temp_dir.path().join("receipt.gif");
+    let original_content: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(400).receipt(true));
+
+    let result = encoder
+        .encode_bytes_to_images(&original_content, "receipt.bin", &images_dir)
+        .expect("Image encoding failed");
+    assert_eq!(result.output_files.len(), result.num_chunks + 1);
+    assert_eq!(
+        result.output_files.last().unwrap(),
+        "receipt_bin.receipt.png"
+    );
+    let receipt = image::open(images_dir.join("receipt_bin.receipt.png")).unwrap();
+    let text = String::from_utf8(decode_qr_from_dynamic_image(&receipt).unwrap()).unwrap();
+    assert_eq!(
+        text,
+        format!(
+            "File: receipt.bin\nSize: 1000 bytes\nSHA-256: {}",
+            hex::encode(Sha256::digest(&original_content))
+        )
+    );
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&images_dir)
+        .expect("Decoding past the receipt failed");
+    assert_eq!(files[0].data, original_content);
+
+    let result = encoder
+        .encode_bytes_to_gif(&original_content, "receipt.bin", &output_gif)
+        .expect("GIF encoding failed");
+    let receipt_path = temp_dir.path().join("receipt.receipt.png");
+    assert_eq!(result.output_files[1], receipt_path.to_string_lossy());
+    assert!(receipt_path.exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_caption() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let original_content: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+    let encode = |name: &str| {
+        let images_dir = temp_dir.path().join(name);
+        let options = EncodeOptions::new()
+            .chunk_size(400)
+            .cover(true)
+            .caption("CONFIDENTIAL \u{2014} project X");
+        let result = Encoder::new(options)
+            .encode_bytes_to_images(&original_content, "caption.bin", &images_dir)
+            .expect("Image encoding failed");
+        (images_dir, result.output_files)
+    };
+
+    let (images_dir, output_files) = encode("first");
+    let cover_size = image::image_dimensions(images_dir.join(&output_files[0])).unwrap();
+    let (width, height) = image::image_dimensions(images_dir.join(&output_files[1])).unwrap();
+    assert_eq!(cover_size, (width, height));
+    assert!(height > width);
+
+    // The caption is rendered with the codes, so the files come out the same every time.
+    let (again_dir, _) = encode("again");
+    assert_eq!(
+        fs::read(images_dir.join(&output_files[1])).unwrap(),
+        fs::read(again_dir.join(&output_files[1])).unwrap()
+    );
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&images_dir)
+        .expect("Decoding captioned frames failed");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_corner_markers() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let original_content: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+    let images_dir = temp_dir.path().join("marked");
+    let encoder = Encoder::new(
+        EncodeOptions::new()
+            .chunk_size(400)
+            .cover(true)
+            .markers(true),
+    );
+    let result = encoder
+        .encode_bytes_to_images(&original_content, "markers.bin", &images_dir)
+        .expect("Image encoding failed");
+    let cover_size = image::image_dimensions(images_dir.join(&result.output_files[0])).unwrap();
+    let frame_size = image::image_dimensions(images_dir.join(&result.output_files[1])).unwrap();
+    assert_eq!(cover_size, frame_size);
+
+    // Photos of the frames, turned a quarter and seen from low down, on a grey table. The
+    // code's own finder patterns rarely survive the angle; the markers do.
+    let photos_dir = temp_dir.path().join("photos");
+    fs::create_dir_all(&photos_dir).unwrap();
+    for name in &result.output_files[1..] {
+        let frame = image::open(images_dir.join(name)).unwrap().to_luma8();
+        let frame = image::imageops::rotate90(&frame);
+        let (w, h) = (frame.width() as f32, frame.height() as f32);
+        let shade = |x: f32, y: f32| {
+            let (x0, y0) = (x.max(0.0) as u32, y.max(0.0) as u32);
+            let (x1, y1) = ((x0 + 1).min(w as u32 - 1), (y0 + 1).min(h as u32 - 1));
+            let (tx, ty) = (x.max(0.0).fract(), y.max(0.0).fract());
+            let at = |x, y| f32::from(frame.get_pixel(x, y).0[0]);
+            let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+            let bottom = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+            (top * (1.0 - ty) + bottom * ty) as u8
+        };
+        let photo = image::GrayImage::from_fn(w as u32 * 2, h as u32 * 2, |x, y| {
+            // The bottom right of the frame is farther away, so it comes out smaller.
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let depth = 1.0 - 0.3 * px / w - 0.5 * py / h;
+            let (fx, fy) = ((px - 0.2 * w) * 0.5 / depth, (py - 0.2 * h) * 0.5 / depth);
+            if depth <= 0.0 || fx < 0.0 || fy < 0.0 || fx >= w || fy >= h {
+                image::Luma([110])
+            } else {
+                image::Luma([shade(fx - 0.5, fy - 0.5)])
+            }
+        });
+        photo.save(photos_dir.join(name)).unwrap();
+    }
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&photos_dir)
+        .expect("Decoding photos of marked frames failed");
+    assert_eq!(files[0].data, original_content);
+
+    #[cfg(feature = "color")]
+    {
+        let options = EncodeOptions::new()
+            .markers(true)
+            .color_matrix(fountain::ColorMatrix::new(129).unwrap());
+        assert!(Encoder::new(options)
+            .encode_bytes_to_images(b"x", "x.bin", &temp_dir.path().join("color"))
+            .is_err());
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_sequence_measures_missed_frames() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("sequence");
+    let original_content: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let options = EncodeOptions::new()
+        .chunk_size(200)
+        .parity(100)
+        .sequence(true);
+    let result = Encoder::new(options)
+        .encode_bytes_to_images(&original_content, "sequence.bin", &images_dir)
+        .expect("Image encoding failed");
+
+    // A camera that catches every other frame.
+    for name in result.output_files.iter().skip(1).step_by(2) {
+        fs::remove_file(images_dir.join(name)).unwrap();
+    }
+    let output = temp_dir.path().join("out.bin");
+    let decoded = Decoder::new(DecodeOptions::new().output(&output))
+        .decode(&images_dir)
+        .expect("Decoding every other frame failed");
+    assert_eq!(fs::read(&output).unwrap(), original_content);
+
+    let channel = decoded.channel.expect("Numbered frames should be measured");
+    assert_eq!(channel.frames_shown, 2 * channel.frames_caught - 1);
+    assert_eq!(channel.longest_gap, 1);
+    assert_eq!(channel.interval_factor(), Some(2));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_print_size_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("print");
+    let original_content: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+
+    // 40mm at 300 dpi is 472 pixels; codes take the largest whole module size within that.
+    let result = Encoder::new(EncodeOptions::new().chunk_size(400).print_size(40.0, 300))
+        .encode_bytes_to_images(&original_content, "print.bin", &images_dir)
+        .expect("Image encoding failed");
+    let (width, height) =
+        image::image_dimensions(images_dir.join(&result.output_files[0])).unwrap();
+    assert_eq!(width, height);
+    assert!(width <= 472 && width > 472 * 9 / 10, "{} pixels", width);
+
+    // Codes cannot fit 5mm at 300 dpi.
+    let result = Encoder::new(EncodeOptions::new().chunk_size(400).print_size(5.0, 300))
+        .encode_bytes_to_images(&original_content, "print.bin", &images_dir);
+    assert!(matches!(
+        result,
+        Err(fountain::FountainError::InvalidConfig(_))
+    ));
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&images_dir)
+        .expect("Decoding print images failed");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_eink_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EinkDisplay, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("frame.bin");
+    let output_dir = temp_dir.path().join("eink");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &original_content).unwrap();
+
+    let panel: EinkDisplay = "waveshare-7.5".parse().unwrap();
+    let result = Encoder::new(EncodeOptions::new().chunk_size(400).interval_ms(15000))
+        .encode_to_eink(&[source_file_path.as_path()], &output_dir, panel)
+        .expect("E-ink encoding failed");
+
+    for name in &result.output_files {
+        let decoder = png::Decoder::new(std::io::BufReader::new(
+            fs::File::open(output_dir.join(name)).unwrap(),
+        ));
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (800, 480));
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+        assert_eq!(info.bit_depth, png::BitDepth::One);
+    }
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(output_dir.join("slideshow.json")).unwrap())
+            .unwrap();
+    assert_eq!(manifest["interval_ms"], 15000);
+    assert_eq!(manifest["frames"], serde_json::json!(result.output_files));
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&output_dir)
+        .expect("Decoding e-ink frames failed");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_transcode_gif() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let fast_gif = temp_dir.path().join("fast.gif");
+    let slow_gif = temp_dir.path().join("slow.gif");
+    let images_dir = temp_dir.path().join("images");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+
+    let encoded = Encoder::new(EncodeOptions::new().chunk_size(400).interval_ms(40))
+        .encode_bytes_to_gif(&original_content, "fast.bin", &fast_gif)
+        .expect("GIF encoding failed");
+
+    // Only the GIF is re-rendered, slower and at a smaller scale; no source file is involved.
+    let transcoder = Encoder::new(EncodeOptions::new().interval_ms(500).pixel_scale(2));
+    let result = transcoder
+        .transcode_to_gif(&fast_gif, &slow_gif)
+        .expect("Transcoding failed");
+    assert_eq!(result.num_chunks, encoded.num_chunks);
+    let frames = GifDecoder::new(std::io::BufReader::new(fs::File::open(&slow_gif).unwrap()))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), encoded.num_chunks);
+    assert_eq!(frames[0].delay().numer_denom_ms(), (500, 1));
+
+    let result = transcoder
+        .transcode_to_images(&slow_gif, &images_dir)
+        .expect("Transcoding to images failed");
+    assert_eq!(result.output_files[0], "slow_0001.png");
+
+    let terminal = transcoder
+        .transcode_for_terminal(&slow_gif)
+        .expect("Rendering for the terminal failed");
+    assert_eq!(terminal.total, encoded.num_chunks);
+    assert_eq!(terminal.filename, "slow");
+
+    for input in [&slow_gif, &images_dir] {
+        let files = Decoder::new(DecodeOptions::new())
+            .decode_files(input)
+            .expect("Decoding transcoded frames failed");
+        assert_eq!(files[0].filename, "fast.bin");
+        assert_eq!(files[0].data, original_content);
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_diff_inputs() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let first = temp_dir.path().join("first");
+    let second = temp_dir.path().join("second");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+
+    let result = Encoder::new(EncodeOptions::new().chunk_size(100))
+        .export_bytes_packets(&original_content, "take.bin", &first)
+        .expect("Packet export failed");
+
+    // Two partial recordings, each of a third of the loop, and sharing one packet. Neither is
+    // enough on its own; the remaining third is dropped.
+    let third = result.num_chunks / 3;
+    fs::create_dir(&second).unwrap();
+    for (i, name) in result.output_files.iter().enumerate().skip(third) {
+        if i < 2 * third {
+            fs::rename(first.join(name), second.join(name)).unwrap();
+        } else {
+            fs::remove_file(first.join(name)).unwrap();
+        }
+    }
+    let shared = &result.output_files[0];
+    fs::copy(first.join(shared), second.join(shared)).unwrap();
+
+    let diff = Decoder::new(DecodeOptions::new())
+        .diff(&first, &second)
+        .expect("Diff failed");
+    assert_eq!(diff.packets_a, third);
+    assert_eq!(diff.packets_b, third + 1);
+    assert_eq!(diff.common_esis, [0]);
+    assert_eq!((diff.only_a, diff.only_b), (third - 1, third));
+    assert_eq!(diff.combined_packets(), 2 * third);
+    assert!(diff.expected_packets > third + 1);
+    assert!(!diff.complete_a && !diff.complete_b);
+    assert!(diff.complete_combined);
+    assert_eq!(diff.missing_packets(), 0);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_pooled_inputs() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let first = temp_dir.path().join("take1");
+    let second = temp_dir.path().join("take2");
+    let output_path = temp_dir.path().join("pooled.bin");
+    let original_content: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+
+    let result = Encoder::new(EncodeOptions::new().chunk_size(100))
+        .export_bytes_packets(&original_content, "pooled.bin", &first)
+        .expect("Packet export failed");
+
+    // Two recordings of a third of the loop each; the last third is lost.
+    let third = result.num_chunks / 3;
+    fs::create_dir(&second).unwrap();
+    for (i, name) in result.output_files.iter().enumerate().skip(third) {
+        if i < 2 * third {
+            fs::rename(first.join(name), second.join(name)).unwrap();
+        } else {
+            fs::remove_file(first.join(name)).unwrap();
+        }
+    }
+
+    let decoder = Decoder::new(DecodeOptions::new().output(&output_path));
+    for input in [&first, &second] {
+        assert!(matches!(
+            decoder.decode(input),
+            Err(FountainError::NotEnoughPackets { .. })
+        ));
+    }
+    let result = decoder
+        .decode_inputs(&[first.as_path(), second.as_path()])
+        .expect("Pooled decoding failed");
+    assert_eq!(result.original_filename, "pooled.bin");
+    assert_eq!(fs::read(&output_path).unwrap(), original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_mixed_inputs() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, PacketEncoding};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let images_dir = temp_dir.path().join("images");
+    let photos_dir = temp_dir.path().join("photos");
+    let recording_dir = temp_dir.path().join("recording");
+    let gif_path = temp_dir.path().join("recording.gif");
+    let lines_path = temp_dir.path().join("scanned.txt");
+    let output_path = temp_dir.path().join("mixed.bin");
+    let original_content: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(300));
+    let result = encoder
+        .encode_bytes_to_images(&original_content, "mixed.bin", &images_dir)
+        .expect("Image encoding failed");
+    let mut lines = Vec::new();
+    encoder
+        .encode_bytes_to_packets(
+            &original_content,
+            "mixed.bin",
+            &mut lines,
+            PacketEncoding::Base45,
+        )
+        .expect("Packet encoding failed");
+
+    // One session, three kinds of capture: photos of the first third of the loop, a recording
+    // of the second and a scanner's text of the last.
+    let third = result.num_chunks / 3;
+    fs::create_dir(&photos_dir).unwrap();
+    fs::create_dir(&recording_dir).unwrap();
+    for (i, name) in result.output_files.iter().enumerate() {
+        let image = image::open(images_dir.join(name)).unwrap();
+        if i < third {
+            image
+                .save(photos_dir.join(name).with_extension("jpg"))
+                .unwrap();
+        } else if i < 2 * third {
+            image.save(recording_dir.join(name)).unwrap();
+        }
+    }
+    encoder
+        .transcode_to_gif(&recording_dir, &gif_path)
+        .expect("Transcoding the recording failed");
+    let scanned: Vec<&str> = std::str::from_utf8(&lines)
+        .unwrap()
+        .lines()
+        .skip(2 * third)
+        .collect();
+    fs::write(&lines_path, scanned.join("\n")).unwrap();
+
+    let decoder = Decoder::new(DecodeOptions::new().output(&output_path));
+    decoder
+        .decode_inputs(&[
+            photos_dir.as_path(),
+            gif_path.as_path(),
+            lines_path.as_path(),
+        ])
+        .expect("Decoding mixed inputs failed");
+    assert_eq!(fs::read(&output_path).unwrap(), original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_packet_files_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("raw.bin");
+    let packets_dir = temp_dir.path().join("packets");
+    let original_content: Vec<u8> = (0..4000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &original_content).unwrap();
+
+    let result = Encoder::new(EncodeOptions::new().chunk_size(500))
+        .export_packets(&[source_file_path.as_path()], &packets_dir)
+        .expect("Exporting packets failed");
+    assert_eq!(result.output_files[0], "raw_bin_0001.pkt");
+    assert_eq!(
+        fs::read_dir(&packets_dir).unwrap().count(),
+        result.num_chunks
+    );
+
+    // A lost packet is made up for by the repair packets; a corrupt one is skipped.
+    fs::remove_file(packets_dir.join("raw_bin_0001.pkt")).unwrap();
+    fs::write(packets_dir.join("raw_bin_0002.pkt"), b"not a packet").unwrap();
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&packets_dir)
+        .expect("Decoding packets failed");
+    assert_eq!(files[0].filename, "raw.bin");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_packet_lines_decode() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, PacketEncoding};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let original_content: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let encoder = Encoder::new(EncodeOptions::new().chunk_size(400));
+    let mut base64 = Vec::new();
+    encoder
+        .encode_bytes_to_packets(
+            &original_content,
+            "scan.bin",
+            &mut base64,
+            PacketEncoding::Base64,
+        )
+        .expect("Packet output failed");
+    let mut base45 = Vec::new();
+    encoder
+        .encode_bytes_to_packets(
+            &original_content,
+            "scan.bin",
+            &mut base45,
+            PacketEncoding::Base45,
+        )
+        .expect("Packet output failed");
+
+    // A scanner's output: every other code in base64, the rest as zbarimg prints QR text, plus
+    // blank lines and a line that is no packet.
+    let base64 = String::from_utf8(base64).unwrap();
+    let base45 = String::from_utf8(base45).unwrap();
+    let mut text = String::from("\nnot a packet\n");
+    for (i, (b64, b45)) in base64.lines().zip(base45.lines()).enumerate() {
+        if i % 2 == 0 {
+            text.push_str(&format!("{}\r\n", b64));
+        } else {
+            text.push_str(&format!("QR-Code:{}\n\n", b45));
+        }
+    }
+    let input = temp_dir.path().join("scanned.txt");
+    fs::write(&input, text).unwrap();
+
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&input)
+        .expect("Decoding packet lines failed");
+    assert_eq!(files[0].filename, "scan.bin");
+    assert_eq!(files[0].data, original_content);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_pipe_roundtrip() {
+    use fountain::pipe::{PipeDecoder, PipeEncoder, PipeFeed};
+    use fountain::{Chunk, FountainError, StreamDecoder};
+    use std::io::Cursor;
+
+    let stream: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let segments: Vec<Vec<Chunk>> = PipeEncoder::new(Cursor::new(&stream), 1024, 120)
+        .collect::<Result<_, _>>()
+        .expect("Pipe encoding failed");
+    // Three full-or-partial segments, then the empty one that ends the stream.
+    assert_eq!(segments.len(), 4);
+
+    // Later segments arrive first and every third packet is lost; bytes still come out in order.
+    let mut decoder = PipeDecoder::new();
+    let mut received = Vec::new();
+    for chunk in segments
+        .iter()
+        .rev()
+        .flatten()
+        .step_by(3)
+        .chain(segments.iter().rev().flatten().skip(1).step_by(3))
+    {
+        let chunk = Chunk::from_text(&chunk.to_text().unwrap()).unwrap();
+        match decoder.feed_chunk(chunk).expect("Pipe decoding failed") {
+            PipeFeed::Data(data) | PipeFeed::End(data) => received.extend(data),
+            _ => {}
+        }
+    }
+    assert!(decoder.is_finished());
+    assert_eq!(received, stream);
+
+    let err = StreamDecoder::new()
+        .feed_chunk(segments[0][0].clone())
+        .expect_err("A piped stream is not a file transfer");
+    assert!(matches!(err, FountainError::PipedStream));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_progress_callbacks() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("callbacks.txt");
+    let output_gif_path = temp_dir.path().join("callbacks.gif");
+    fs::write(&source_file_path, "Progress callback test. ".repeat(30))
+        .expect("Failed to write source file");
+
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let frames_cb = frames.clone();
+    let result = Encoder::new(
+        EncodeOptions::new()
+            .chunk_size(200)
+            .on_frame_encoded(move |i, total| frames_cb.lock().unwrap().push((i, total))),
+    )
+    .encode_to_gif(&source_file_path, &output_gif_path)
+    .expect("GIF encoding failed");
+
+    let frames = frames.lock().unwrap();
+    assert_eq!(frames.len(), result.num_chunks);
+    assert_eq!(
+        frames.last(),
+        Some(&(result.num_chunks - 1, result.num_chunks))
+    );
+
+    let packets = Arc::new(Mutex::new(Vec::new()));
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let (packets_cb, progress_cb) = (packets.clone(), progress.clone());
+    let decoded = Decoder::new(
+        DecodeOptions::new()
+            .output(temp_dir.path().join("callbacks_decoded.txt"))
+            .on_packet_received(move |n| packets_cb.lock().unwrap().push(n))
+            .on_progress(move |pct| progress_cb.lock().unwrap().push(pct)),
+    )
+    .decode(&output_gif_path)
+    .expect("Decoding failed");
+
+    assert_eq!(packets.lock().unwrap().last(), Some(&decoded.num_chunks));
+    let progress = progress.lock().unwrap();
+    assert!(progress.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(progress.last(), Some(&1.0));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_cancellation_keeps_session() {
+    use fountain::{CancelFlag, DecodeOptions, Decoder, EncodeOptions, FountainError};
+    use image::DynamicImage;
+    use std::sync::atomic::Ordering;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let session_path = temp_dir.path().join("cancel.session");
+    let data: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+
+    let cancelled = CancelFlag::default();
+    cancelled.store(true, Ordering::SeqCst);
+    let err = fountain::encode_bytes(
+        &data,
+        "cancel.bin",
+        EncodeOptions::new().cancel_flag(cancelled),
+    )
+    .expect_err("A cancelled encode should fail");
+    assert!(matches!(err, FountainError::Cancelled));
+
+    let frames = fountain::encode_bytes(&data, "cancel.bin", EncodeOptions::new().chunk_size(200))
+        .expect("Encoding failed");
+
+    // Cancel as soon as the first packet arrives.
+    let cancel = CancelFlag::default();
+    let flag = cancel.clone();
+    let options = DecodeOptions::new()
+        .session_file(&session_path)
+        .cancel_flag(cancel)
+        .on_packet_received(move |_| flag.store(true, Ordering::SeqCst));
+    let err = Decoder::new(options)
+        .decode_frames(frames.iter().cloned().map(DynamicImage::ImageRgb8))
+        .expect_err("A cancelled decode should fail");
+    assert!(matches!(err, FountainError::Cancelled));
+    assert_eq!(err.exit_code(), 130);
+
+    let decoder = Decoder::new(DecodeOptions::new().session_file(&session_path));
+    let files = decoder
+        .decode_frames(frames.into_iter().skip(1).map(DynamicImage::ImageRgb8))
+        .expect("Resuming the cancelled session failed");
+    assert_eq!(files[0].data, data);
+    assert!(!session_path.exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_frame_scan_report() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, FrameOutcome};
+    use image::{DynamicImage, RgbImage};
+    use std::sync::{Arc, Mutex};
+
+    let data: Vec<u8> = (0..1000).map(|_| rand::random::<u8>()).collect();
+    let frames = fountain::encode_bytes(&data, "stats.bin", EncodeOptions::new().chunk_size(400))
+        .expect("Encoding failed");
+
+    // A blank frame, then the first frame twice, then the rest.
+    let mut input = vec![RgbImage::from_pixel(200, 200, image::Rgb([255, 255, 255]))];
+    input.push(frames[0].clone());
+    input.extend(frames.iter().cloned());
+
+    let outcomes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = outcomes.clone();
+    let options = DecodeOptions::new().on_frame_scanned(move |frame| {
+        assert!(frame.detect_time.is_some());
+        recorded.lock().unwrap().push(frame.outcome);
+    });
+    let files = Decoder::new(options)
+        .decode_frames(input.into_iter().map(DynamicImage::ImageRgb8))
+        .expect("Decoding failed");
+    assert_eq!(files[0].data, data);
+
+    let outcomes = outcomes.lock().unwrap();
+    assert_eq!(
+        outcomes[..3],
+        [
+            FrameOutcome::NoCode,
+            FrameOutcome::New,
+            FrameOutcome::Duplicate
+        ]
+    );
+    assert!(outcomes[3..].iter().all(|&o| o == FrameOutcome::New));
+    assert!(!FrameOutcome::NoCode.detected() && FrameOutcome::Duplicate.detected());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_estimate_partial_session() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::DynamicImage;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let session_path = temp_dir.path().join("partial.session");
+    let images_dir = temp_dir.path().join("images");
+    let data: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let options = EncodeOptions::new().chunk_size(200);
+    let frames =
+        fountain::encode_bytes(&data, "partial.bin", options.clone()).expect("Encoding failed");
+
+    let decoder = Decoder::new(
+        DecodeOptions::new()
+            .session_file(&session_path)
+            .max_frames(5),
+    );
+    decoder
+        .decode_frames(frames.iter().cloned().map(DynamicImage::ImageRgb8))
+        .expect_err("A decode limited to 5 frames should fail");
+
+    let estimate = decoder
+        .estimate(&[])
+        .expect("Estimating the session failed");
+    assert_eq!(estimate.packets, 5);
+    assert!(!estimate.complete);
+    assert_eq!(
+        estimate.missing_packets(),
+        estimate.expected_packets - estimate.packets
+    );
+    // Some frames of the loop repeat the packets already held.
+    assert!(estimate.capture_frames() > estimate.missing_packets());
+    assert!(estimate.capture_frames() <= estimate.loop_frames);
+    assert_eq!(
+        estimate.capture_secs(500),
+        estimate.capture_frames() as f64 / 2.0
+    );
+
+    Encoder::new(options)
+        .encode_bytes_to_images(&data, "partial.bin", &images_dir)
+        .expect("Image encoding failed");
+    let estimate = decoder
+        .estimate(&[images_dir.as_path()])
+        .expect("Estimating the session with more frames failed");
+    assert!(estimate.complete);
+    assert_eq!(estimate.capture_frames(), 0);
+    assert!(session_path.exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_dictionary_roundtrip() {
+    use fountain::dictionary::{train_dictionary, MAX_DICTIONARY_SIZE};
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, FountainError};
+
+    let config = |host: &str| {
+        format!(
+            "[server]\nhost = {}\nport = 443\ncertificate = /etc/ssl/certs/server.pem\n\
+             private_key = /etc/ssl/private/server.key\nprotocols = TLSv1.2 TLSv1.3\n",
+            host
+        )
+    };
+    let samples: Vec<String> = (1..=4).map(|i| config(&format!("node{}", i))).collect();
+    let samples: Vec<&[u8]> = samples.iter().map(|sample| sample.as_bytes()).collect();
+    let dictionary = train_dictionary(&samples, MAX_DICTIONARY_SIZE);
+    assert!(!dictionary.is_empty() && dictionary.len() < samples[0].len());
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data = config("node9");
+    let plain_dir = temp_dir.path().join("plain");
+    let plain = Encoder::new(EncodeOptions::new())
+        .export_bytes_packets(data.as_bytes(), "node9.conf", &plain_dir)
+        .expect("Encoding failed");
+    let packets_dir = temp_dir.path().join("packets");
+    let result = Encoder::new(EncodeOptions::new().dictionary(dictionary.clone()))
+        .export_bytes_packets(data.as_bytes(), "node9.conf", &packets_dir)
+        .expect("Encoding with a dictionary failed");
+    assert!(result.compressed_bytes < plain.compressed_bytes * 2 / 3);
+
+    let output_path = temp_dir.path().join("node9.conf");
+    let err = Decoder::new(DecodeOptions::new().output(&output_path))
+        .decode(&packets_dir)
+        .expect_err("Decoding without the dictionary should fail");
+    assert!(matches!(err, FountainError::MissingDictionary(_)));
+    assert_eq!(err.exit_code(), 14);
+
+    Decoder::new(
+        DecodeOptions::new()
+            .output(&output_path)
+            .dictionary(b"another dictionary".to_vec())
+            .dictionary(dictionary),
+    )
+    .decode(&packets_dir)
+    .expect("Decoding with the dictionary failed");
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_delta_roundtrip() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder, FountainError};
+
+    let base: Vec<u8> = (0..100_000).map(|_| rand::random::<u8>()).collect();
+    let mut data = base.clone();
+    data[50_000..50_016].copy_from_slice(b"a small revision");
+    data.extend_from_slice(b"and a new ending");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let packets_dir = temp_dir.path().join("packets");
+    let result = Encoder::new(EncodeOptions::new().delta_base(base.clone()))
+        .export_bytes_packets(&data, "v2.bin", &packets_dir)
+        .expect("Encoding a delta failed");
+    assert!(result.compressed_bytes < 1000);
+
+    let files = Decoder::new(DecodeOptions::new().delta_base(base.clone()))
+        .decode_files(&packets_dir)
+        .expect("Applying the delta failed");
+    assert_eq!(files[0].filename, "v2.bin");
+    assert_eq!(files[0].data, data);
+
+    // Without the base, the delta itself comes out.
+    let files = Decoder::new(DecodeOptions::new())
+        .decode_files(&packets_dir)
+        .expect("Decoding the delta failed");
+    assert_eq!(files[0].filename, "v2.bin.fdelta");
+
+    let err = Decoder::new(DecodeOptions::new().delta_base(data))
+        .decode_files(&packets_dir)
+        .map(|_| ())
+        .expect_err("Applying the delta to another base should fail");
+    assert!(matches!(err, FountainError::DeltaBaseMismatch));
+    assert_eq!(err.exit_code(), 15);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_limits_keep_session() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, FountainError};
+    use image::DynamicImage;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let session_path = temp_dir.path().join("limit.session");
+    let data: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let frames = fountain::encode_bytes(&data, "limit.bin", EncodeOptions::new().chunk_size(200))
+        .expect("Encoding failed");
+
+    let options = DecodeOptions::new()
+        .session_file(&session_path)
+        .max_frames(3);
+    let err = Decoder::new(options)
+        .decode_frames(frames.iter().cloned().map(DynamicImage::ImageRgb8))
+        .expect_err("A decode limited to 3 frames should fail");
+    assert!(matches!(
+        err,
+        FountainError::LimitReached {
+            received: 3,
+            scanned: 3,
+            ..
+        }
+    ));
+    assert_eq!(err.exit_code(), 11);
+    assert!(session_path.exists());
+
+    // A zero timeout gives up before the first frame, without touching the session.
+    let options = DecodeOptions::new()
+        .session_file(&session_path)
+        .timeout(Duration::ZERO);
+    let err = Decoder::new(options)
+        .decode_frames(frames.iter().cloned().map(DynamicImage::ImageRgb8))
+        .expect_err("A decode with a zero timeout should fail");
+    assert!(matches!(
+        err,
+        FountainError::LimitReached {
+            received: 3,
+            scanned: 0,
+            ..
+        }
+    ));
+
+    let decoder = Decoder::new(DecodeOptions::new().session_file(&session_path));
+    let files = decoder
+        .decode_frames(frames.into_iter().skip(3).map(DynamicImage::ImageRgb8))
+        .expect("Resuming the limited session failed");
+    assert_eq!(files[0].data, data);
+    assert!(!session_path.exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_session_caches_detections() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions};
+    use image::{DynamicImage, RgbImage};
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let session_path = temp_dir.path().join("cache.session");
+    let data: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let mut frames =
+        fountain::encode_bytes(&data, "cache.bin", EncodeOptions::new().chunk_size(200))
+            .expect("Encoding failed");
+    // A frame without a code is cached as such.
+    frames.insert(
+        0,
+        RgbImage::from_pixel(200, 200, image::Rgb([255, 255, 255])),
+    );
+
+    let decoder = Decoder::new(DecodeOptions::new().session_file(&session_path));
+    decoder
+        .decode_frames(frames.iter().take(4).cloned().map(DynamicImage::ImageRgb8))
+        .expect_err("Four frames should not be enough");
+    let session = fountain::session::Session::load_or_default(&session_path).unwrap();
+    assert_eq!(session.chunks.len(), 3);
+    assert_eq!(session.detections.len(), 4);
+
+    // Running over the same recording again only detects the frames not seen before.
+    let detected = Arc::new(Mutex::new(Vec::new()));
+    let recorded = detected.clone();
+    let options = DecodeOptions::new()
+        .session_file(&session_path)
+        .threads(1)
+        .on_frame_scanned(move |frame| {
+            recorded.lock().unwrap().push(frame.detect_time.is_some());
+        });
+    let files = Decoder::new(options)
+        .decode_frames(frames.into_iter().map(DynamicImage::ImageRgb8))
+        .expect("Resuming with the whole recording failed");
+    assert_eq!(files[0].data, data);
+    let detected = detected.lock().unwrap();
+    assert!(detected[..4].iter().all(|&detected| !detected));
+    assert!(detected[4..].iter().all(|&detected| detected));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_salvage_after_failed_decode() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::DynamicImage;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let salvage_dir = temp_dir.path().join("salvage");
+    let data: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let frames = fountain::encode_bytes(&data, "salvage.bin", EncodeOptions::new().chunk_size(200))
+        .expect("Encoding failed");
+
+    Decoder::new(DecodeOptions::new().salvage_dir(&salvage_dir))
+        .decode_frames(frames.iter().take(3).cloned().map(DynamicImage::ImageRgb8))
+        .expect_err("Three frames should not be enough");
+    let note = fs::read_to_string(salvage_dir.join("PARTIAL.txt")).unwrap();
+    assert!(note.starts_with("PARTIAL"));
+    assert!(salvage_dir.join("salvage.session").exists());
+
+    // The packets salvaged count towards a later decode of the rest of the frames.
+    let rest = temp_dir.path().join("rest");
+    fs::create_dir(&rest).unwrap();
+    for (i, frame) in frames.iter().enumerate().skip(3) {
+        frame
+            .save(rest.join(format!("frame_{:04}.png", i)))
+            .unwrap();
+    }
+    let output = temp_dir.path().join("out.bin");
+    Decoder::new(DecodeOptions::new().output(&output))
+        .decode_inputs(&[salvage_dir.as_path(), rest.as_path()])
+        .expect("Decoding the salvage with the rest failed");
+    assert_eq!(fs::read(&output).unwrap(), data);
+
+    // A txqr message received in part keeps the bytes of the frames seen.
+    let txqr_dir = temp_dir.path().join("txqr");
+    let gif = temp_dir.path().join("txqr.gif");
+    let message = b"Only some of these txqr frames are captured. ".repeat(10);
+    Encoder::new(EncodeOptions::new().chunk_size(100))
+        .encode_bytes_to_txqr_gif(&message, &gif)
+        .unwrap();
+    Decoder::new(
+        DecodeOptions::new()
+            .txqr(true)
+            .max_frames(2)
+            .salvage_dir(&txqr_dir),
+    )
+    .decode(&gif)
+    .expect_err("Two txqr frames should not be enough");
+    let partial = fs::read(txqr_dir.join("txqr.bin.partial")).unwrap();
+    assert_eq!(partial.len(), message.len());
+    assert_eq!(partial[..200], message[..200]);
+    assert!(fs::read_to_string(txqr_dir.join("PARTIAL.txt"))
+        .unwrap()
+        .contains("0..200"));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_threads_keep_frame_order() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data: Vec<u8> = (0..4000).map(|_| rand::random::<u8>()).collect();
+    let gifs: Vec<Vec<u8>> = [1, 4]
+        .into_iter()
+        .map(|threads| {
+            let gif = temp_dir.path().join(format!("threads_{}.gif", threads));
+            Encoder::new(EncodeOptions::new().chunk_size(300).threads(threads))
+                .encode_bytes_to_gif(&data, "threads.bin", &gif)
+                .expect("Encoding failed");
+            fs::read(gif).unwrap()
+        })
+        .collect();
+    assert_eq!(gifs[0], gifs[1]);
+
+    let output = temp_dir.path().join("out.bin");
+    Decoder::new(DecodeOptions::new().output(&output).threads(3))
+        .decode(&temp_dir.path().join("threads_4.gif"))
+        .expect("Decoding failed");
+    assert_eq!(fs::read(&output).unwrap(), data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_frames_on_canvas() {
+    use fountain::{EncodeOptions, FountainError};
+    use image::DynamicImage;
+
+    let data = b"Every frame the same size, the code in the middle".repeat(20);
+    let frames = fountain::encode_bytes(
+        &data,
+        "canvas.txt",
+        EncodeOptions::new().chunk_size(200).canvas(640, 480),
+    )
+    .expect("Encoding failed");
+    assert!(frames
+        .iter()
+        .all(|frame| frame.dimensions() == (640, 480) && frame.get_pixel(0, 0).0 == [255; 3]));
+    let files = fountain::decode_frames(frames.into_iter().map(DynamicImage::ImageRgb8))
+        .expect("Decoding failed");
+    assert_eq!(files[0].data, data);
+
+    let err = fountain::encode_bytes(&data, "canvas.txt", EncodeOptions::new().canvas(100, 100))
+        .expect_err("Codes larger than the canvas should fail");
+    assert!(matches!(err, FountainError::InvalidConfig(_)));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_max_qr_version() {
+    use fountain::{EncodeOptions, FountainError};
+    use image::DynamicImage;
+
+    let data: Vec<u8> = (0..3000u32).map(|i| (i * 7919 % 251) as u8).collect();
+    // Version 10 is 57 modules across, 65 with the quiet zone, at 4 pixels each.
+    let frames = fountain::encode_bytes(&data, "dense.bin", EncodeOptions::new().max_version(10))
+        .expect("Encoding failed");
+    assert!(frames.iter().all(|frame| frame.width() <= 65 * 4));
+    let files = fountain::decode_frames(frames.into_iter().map(DynamicImage::ImageRgb8))
+        .expect("Decoding failed");
+    assert_eq!(files[0].data, data);
+
+    let uncapped =
+        fountain::encode_bytes(&data, "dense.bin", EncodeOptions::new()).expect("Encoding failed");
+    assert!(uncapped[0].width() > 65 * 4);
+
+    let err = fountain::encode_bytes(&data, "dense.bin", EncodeOptions::new().max_version(41))
+        .expect_err("There is no version 41");
+    assert!(matches!(err, FountainError::InvalidConfig(_)));
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_strict_palette() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data = b"Nothing but black and white".repeat(10);
+    for strict in [false, true] {
+        let output_gif_path = temp_dir.path().join(format!("strict_{}.gif", strict));
+        let mut options = EncodeOptions::new().chunk_size(100).strict_palette(strict);
+        if strict {
+            options = options.caption("Scan me").cover(true);
+        }
+        Encoder::new(options)
+            .encode_bytes_to_gif(&data, "strict.txt", &output_gif_path)
+            .expect("GIF encoding failed");
+
+        let file = fs::File::open(&output_gif_path).expect("Failed to open GIF");
+        let frames = GifDecoder::new(std::io::BufReader::new(file))
+            .expect("Failed to read GIF")
+            .into_frames()
+            .collect_frames()
+            .expect("Failed to read GIF frames");
+        assert!(frames.iter().all(|frame| frame
+            .buffer()
+            .pixels()
+            .all(|pixel| matches!(pixel.0, [0, 0, 0, 255] | [255, 255, 255, 255]))));
+
+        let decoded_path = temp_dir.path().join(format!("strict_{}.txt", strict));
+        Decoder::new(DecodeOptions::new().output(&decoded_path))
+            .decode(&output_gif_path)
+            .expect("Decoding failed");
+        assert_eq!(fs::read(&decoded_path).unwrap(), data);
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_repeat_frames() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, Encoder};
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data = b"Hold still for the camera".repeat(10);
+    let mut counts = Vec::new();
+    for repeat in [1, 3] {
+        let output_gif_path = temp_dir.path().join(format!("repeat_{}.gif", repeat));
+        let result = Encoder::new(EncodeOptions::new().chunk_size(100).repeat_frames(repeat))
+            .encode_bytes_to_gif(&data, "repeat.txt", &output_gif_path)
+            .expect("GIF encoding failed");
+
+        let file = fs::File::open(&output_gif_path).expect("Failed to open GIF");
+        let frames = GifDecoder::new(std::io::BufReader::new(file))
+            .expect("Failed to read GIF")
+            .into_frames()
+            .collect_frames()
+            .expect("Failed to read GIF frames");
+        assert_eq!(frames.len(), result.num_chunks * repeat as usize);
+        // Each code is written as a run of identical frames.
+        for run in frames.chunks(repeat as usize) {
+            assert!(run.iter().all(|frame| frame.buffer() == run[0].buffer()));
+        }
+        counts.push(result.num_chunks);
+
+        let decoded_path = temp_dir.path().join(format!("repeat_{}.txt", repeat));
+        Decoder::new(DecodeOptions::new().output(&decoded_path))
+            .decode(&output_gif_path)
+            .expect("Decoding failed");
+        assert_eq!(fs::read(&decoded_path).unwrap(), data);
+    }
+    assert_eq!(counts[0], counts[1]);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_error_categories() {
+    use fountain::{DecodeOptions, Decoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("source.txt");
+    let output_gif_path = temp_dir.path().join("output.gif");
+    let existing_output_path = temp_dir.path().join("existing.txt");
+
+    fs::write(&source_file_path, "Error category test.").expect("Failed to write source file");
+    fs::write(&existing_output_path, "keep me").expect("Failed to write existing file");
+
+    fountain::encode_file_to_gif(&source_file_path, &output_gif_path, None, 100, 4)
+        .expect("GIF encoding failed");
+
+    let refusing = Decoder::new(DecodeOptions::new().output(&existing_output_path));
+    let err = refusing
+        .decode(&output_gif_path)
+        .expect_err("Decoding over an existing file should fail");
+    assert!(matches!(err, FountainError::OutputExists(_)));
+    assert_eq!(
+        fs::read_to_string(&existing_output_path).unwrap(),
+        "keep me"
+    );
+
+    // The free functions replace the output, as they always have.
+    fountain::decode_from_gif(&output_gif_path, Some(&existing_output_path))
+        .expect("Decoding with overwrite failed");
+    assert_eq!(
+        fs::read_to_string(&existing_output_path).unwrap(),
+        "Error category test."
+    );
+
+    let err = fountain::decode_from_gif(&temp_dir.path().join("missing.gif"), None)
+        .expect_err("Decoding a missing file should fail");
+    assert_eq!(err.exit_code(), 3);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_stall_timeout() {
+    use fountain::{DecodeOptions, Decoder, EncodeOptions, FountainError};
+    use image::{DynamicImage, RgbImage};
+    use std::time::Duration;
+
+    let data: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let frames = fountain::encode_bytes(&data, "stall.bin", EncodeOptions::new().chunk_size(200))
+        .expect("Encoding failed");
+    let decoder = Decoder::new(DecodeOptions::new().stall_timeout(Duration::from_secs(1)));
+    // Each frame arrives 50ms after the last: packets keep the scan going, a blank screen
+    // stalls it.
+    let slowly = |frames: Vec<RgbImage>| {
+        frames.into_iter().map(|frame| {
+            std::thread::sleep(Duration::from_millis(50));
+            DynamicImage::ImageRgb8(frame)
+        })
+    };
+
+    let blank = vec![RgbImage::from_pixel(200, 200, image::Rgb([255; 3])); 40];
+    let stalled = [frames[..2].to_vec(), blank].concat();
+    let err = decoder
+        .decode_frames(slowly(stalled))
+        .expect_err("A blank screen should stall the scan");
+    assert!(matches!(err, FountainError::Stalled { received: 2, .. }));
+    assert_eq!(err.exit_code(), 16);
+
+    let files = decoder
+        .decode_frames(slowly(frames))
+        .expect("Decoding failed");
+    assert_eq!(files[0].data, data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_resume_session_across_partial_inputs() {
+    use fountain::{DecodeOptions, Decoder, FountainError};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("partial.bin");
+    let all_frames_dir = temp_dir.path().join("all");
+    let first_part_dir = temp_dir.path().join("first");
+    let second_part_dir = temp_dir.path().join("second");
+    let session_path = temp_dir.path().join("transfer.session");
+    let decoded_output_path = temp_dir.path().join("decoded.bin");
+
+    let data: Vec<u8> = (0..4000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &data).expect("Failed to write source file");
+
+    let encode_result =
+        fountain::encode_file_to_images(&source_file_path, &all_frames_dir, Some(200), 4)
+            .expect("Encoding failed");
+
+    fs::create_dir(&first_part_dir).unwrap();
+    fs::create_dir(&second_part_dir).unwrap();
+    // Each partial capture alone stays below the number of source packets, together they suffice.
+    let split = encode_result.output_files.len() / 3;
+    for (i, filename) in encode_result
+        .output_files
+        .iter()
+        .enumerate()
+        .take(2 * split)
+    {
+        let target = if i < split {
+            &first_part_dir
+        } else {
+            &second_part_dir
+        };
+        fs::rename(all_frames_dir.join(filename), target.join(filename)).unwrap();
+    }
+
+    let decoder = Decoder::new(
+        DecodeOptions::new()
+            .output(&decoded_output_path)
+            .session_file(&session_path),
+    );
+    let err = decoder
+        .decode_images(&first_part_dir)
+        .expect_err("A third of the frames should not be enough");
+    assert!(matches!(
+        err,
+        FountainError::NotEnoughPackets { received, needed, .. } if received < needed
+    ));
+    assert!(session_path.exists());
+
+    decoder
+        .decode_images(&second_part_dir)
+        .expect("Resumed decoding failed");
+
+    assert_eq!(fs::read(&decoded_output_path).unwrap(), data);
+    assert!(!session_path.exists());
+}
+
 #[test]
 #[cfg(feature = "encode")]
 fn test_terminal_generation() {
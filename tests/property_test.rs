@@ -0,0 +1,180 @@
+//! Randomized properties of the wire format. Each test draws its cases from a seed printed on
+//! failure; set `FOUNTAIN_TEST_SEED` to replay one.
+
+use fountain::chunk::{compress, pack_data};
+use fountain_core::{
+    encode_chunks, AssembleResult, Chunk, ChunkAssembler, ChunkHeader, CHUNK_VERSION,
+    MULTI_FILE_VERSION, STREAM_VERSION, TAGGED_VERSION,
+};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use raptorq::ObjectTransmissionInformation;
+
+const BASE45_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+fn seeded_rng() -> (StdRng, u64) {
+    let seed = std::env::var("FOUNTAIN_TEST_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random);
+    (StdRng::seed_from_u64(seed), seed)
+}
+
+fn random_bytes(rng: &mut StdRng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+/// Assembles chunks until the file is complete. `None` if they run out first.
+fn assemble(chunks: impl IntoIterator<Item = Chunk>) -> Option<(String, Vec<u8>)> {
+    let mut assembler = ChunkAssembler::new();
+    for chunk in chunks {
+        if let Ok(AssembleResult::Complete { filename, data }) = assembler.push(chunk) {
+            return Some((filename, data));
+        }
+    }
+    None
+}
+
+#[test]
+fn test_any_header_survives_text_roundtrip() {
+    let (mut rng, seed) = seeded_rng();
+    for _ in 0..500 {
+        let version = *[
+            CHUNK_VERSION,
+            MULTI_FILE_VERSION,
+            STREAM_VERSION,
+            TAGGED_VERSION,
+        ]
+        .choose(&mut rng)
+        .unwrap();
+        // Fields a version does not carry take the values parsing gives them.
+        let names_file = matches!(version, MULTI_FILE_VERSION | TAGGED_VERSION);
+        let header = ChunkHeader {
+            version,
+            file_id: if names_file { rng.gen() } else { 0 },
+            file_count: if names_file { rng.gen() } else { 1 },
+            segment: if version == STREAM_VERSION {
+                rng.gen()
+            } else {
+                0
+            },
+            transfer: if version == TAGGED_VERSION {
+                rng.gen()
+            } else {
+                0
+            },
+            total: rng.gen_range(1..100_000),
+            index: rng.gen(),
+            packet_size: rng.gen_range(1..2000),
+            sequence: rng.gen::<bool>().then(|| rng.gen()),
+        };
+        let config =
+            ObjectTransmissionInformation::with_defaults(header.total as u64, header.packet_size);
+        let mut data = vec![rng.gen_range(0..config.source_blocks()), 0, 0, 0];
+        data.extend(random_bytes(&mut rng, config.symbol_size() as usize));
+        let chunk = Chunk { header, data };
+
+        let text = chunk.to_text().unwrap();
+        let parsed = Chunk::from_text(&text)
+            .unwrap_or_else(|e| panic!("seed {}: {:?} did not parse: {}", seed, chunk.header, e));
+        assert_eq!(
+            parsed.to_bytes().unwrap(),
+            chunk.to_bytes().unwrap(),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn test_roundtrip_with_any_packet_size_and_redundancy() {
+    let (mut rng, seed) = seeded_rng();
+    for _ in 0..40 {
+        let len = rng.gen_range(0..4000);
+        let content = random_bytes(&mut rng, len);
+        let compressed = compress(&pack_data(&content, "prop.bin")).unwrap();
+        let packet_size = 2 * rng.gen_range(1..700);
+        // The encoder's redundancy factor comes down to this many repair packets.
+        let repair_packets = rng.gen_range(2..30);
+        let mut chunks = encode_chunks(&compressed, packet_size, repair_packets);
+
+        // Any order, and any repair packets' worth of losses beyond a margin of two.
+        chunks.shuffle(&mut rng);
+        let lost = rng.gen_range(0..=repair_packets as usize - 2);
+        chunks.truncate(chunks.len() - lost);
+
+        let (filename, data) = assemble(chunks).unwrap_or_else(|| {
+            panic!(
+                "seed {}: {} bytes, packet size {}, {} repair packets, {} lost",
+                seed,
+                content.len(),
+                packet_size,
+                repair_packets,
+                lost
+            )
+        });
+        assert_eq!(
+            (filename.as_str(), data),
+            ("prop.bin", content),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn test_arbitrary_input_never_panics() {
+    let (mut rng, _) = seeded_rng();
+    let mut assembler = ChunkAssembler::new();
+    for _ in 0..20_000 {
+        let len = rng.gen_range(0..64);
+        let mut bytes = random_bytes(&mut rng, len);
+        if let Some(version) = bytes.first_mut() {
+            *version = rng.gen_range(0..6);
+        }
+        if let Ok(chunk) = Chunk::from_bytes(&bytes) {
+            let _ = assembler.push(chunk);
+        }
+
+        let text: String = (0..len)
+            .map(|_| *BASE45_ALPHABET.choose(&mut rng).unwrap() as char)
+            .collect();
+        if let Ok(chunk) = Chunk::from_text(&text) {
+            let _ = assembler.push(chunk);
+        }
+
+        let _ = fountain_core::unpack_data(&bytes);
+        let _ = fountain_core::decompress(&bytes);
+    }
+}
+
+#[test]
+fn test_corrupted_chunks_never_yield_a_wrong_file() {
+    let (mut rng, seed) = seeded_rng();
+    for _ in 0..40 {
+        let len = rng.gen_range(1..2000);
+        let content = random_bytes(&mut rng, len);
+        let compressed = compress(&pack_data(&content, "prop.bin")).unwrap();
+        let chunks = encode_chunks(&compressed, 2 * rng.gen_range(8..100), 8);
+
+        // Flip a byte in some of the chunks, header or packet, as a misread QR code might.
+        let frames = chunks.iter().filter_map(|chunk| {
+            let mut bytes = chunk.to_bytes().unwrap();
+            if rng.gen_bool(0.2) {
+                let at = rng.gen_range(0..bytes.len());
+                bytes[at] ^= rng.gen_range(1..=u8::MAX);
+            }
+            Chunk::from_bytes(&bytes).ok()
+        });
+        let frames: Vec<Chunk> = frames.collect();
+        if let Some((filename, data)) = assemble(frames) {
+            assert_eq!(
+                (filename.as_str(), data),
+                ("prop.bin", content),
+                "seed {}",
+                seed
+            );
+        }
+    }
+}
@@ -1,6 +1,10 @@
-import init, { QrStreamDecoder, ScanStatus } from "/scanner/pkg/fountain.js";
+// Matches FeedStatus.Complete of the wasm module, which only the worker loads.
+const FEED_STATUS_COMPLETE = 3;
 
-let decoder = null;
+// Frames are decoded in a worker so the page stays responsive; while it is busy with one
+// frame, newer ones are skipped rather than queued.
+const worker = new Worker("/scanner/worker.js", { type: "module" });
+let workerBusy = false;
 let stream = null;
 let animationId = null;
 
@@ -15,22 +19,21 @@ const progressFill = document.getElementById("progress-fill");
 const downloadArea = document.getElementById("download-area");
 const downloadBtn = document.getElementById("download-btn");
 
-async function run() {
-    try {
-        await init("/scanner/pkg/fountain_bg.wasm");
-        console.log("Wasm module loaded");
-        statusDiv.firstChild.textContent = "Ready to scan.";
-        startBtn.onclick = startCamera;
-        stopBtn.onclick = stopCamera;
-    } catch (e) {
+function run() {
+    worker.onmessage = (message) => handleWorkerEvent(message.data);
+    worker.onerror = (e) => {
         console.error("Failed to load wasm:", e);
         statusDiv.firstChild.textContent = "Error loading Wasm module.";
-    }
+    };
+    statusDiv.firstChild.textContent = "Ready to scan.";
+    startBtn.onclick = startCamera;
+    stopBtn.onclick = stopCamera;
 }
 
 async function startCamera() {
     try {
-        decoder = new QrStreamDecoder();
+        worker.postMessage({ type: "start" });
+        workerBusy = false;
 
         // Prefer rear camera
         const constraints = {
@@ -87,45 +90,58 @@ function scanLoop() {
         canvas.height = video.videoHeight;
     }
 
-    ctx.drawImage(video, 0, 0, canvas.width, canvas.height);
-    const imageData = ctx.getImageData(0, 0, canvas.width, canvas.height);
+    if (!workerBusy) {
+        ctx.drawImage(video, 0, 0, canvas.width, canvas.height);
+        const imageData = ctx.getImageData(0, 0, canvas.width, canvas.height);
+        const buffer = imageData.data.buffer;
+        workerBusy = true;
+        worker.postMessage(
+            { type: "frame", width: canvas.width, height: canvas.height, buffer },
+            [buffer],
+        );
+    }
 
-    const result = decoder.scan_frame(
-        imageData.data,
-        canvas.width,
-        canvas.height,
-    );
-    const status = result.status;
+    animationId = requestAnimationFrame(scanLoop);
+}
 
-    if (result.progress_total > 0) {
-        const percent = (result.progress_current / result.progress_total) * 100;
-        progressFill.style.width = `${percent}%`;
-        statusDiv.firstChild.textContent = `Found ${result.progress_current} / ${result.progress_total} chunks...`;
+function handleWorkerEvent(event) {
+    if (event.type === "error") {
+        stopCamera();
+        statusDiv.firstChild.textContent = "Error: " + event.message;
+        return;
+    }
+    if (event.type !== "progress") return;
+    workerBusy = false;
+    if (!stream) return;
+
+    if (event.expected !== undefined) {
+        progressFill.style.width = `${event.progress * 100}%`;
+        statusDiv.firstChild.textContent = `Found ${event.received} / ${event.expected} chunks...`;
     }
 
-    if (status === ScanStatus.Complete) {
+    if (event.status === FEED_STATUS_COMPLETE) {
         stopCamera();
-        statusDiv.firstChild.textContent = `Completed! Decoded: ${result.get_filename()}`;
+        const files = event.files;
+        statusDiv.firstChild.textContent = `Completed! Decoded: ${files.map((f) => f.filename).join(", ")}`;
         progressFill.style.width = "100%";
-        enableDownload(result.get_filename(), result.get_file_data());
-        return;
+        enableDownload(files);
     }
-
-    animationId = requestAnimationFrame(scanLoop);
 }
 
-function enableDownload(filename, data) {
+function enableDownload(files) {
     downloadArea.style.display = "block";
     downloadBtn.onclick = () => {
-        const blob = new Blob([data], { type: "application/octet-stream" });
-        const url = URL.createObjectURL(blob);
-        const a = document.createElement("a");
-        a.href = url;
-        a.download = filename;
-        document.body.appendChild(a);
-        a.click();
-        document.body.removeChild(a);
-        URL.revokeObjectURL(url);
+        for (const { filename, data } of files) {
+            const blob = new Blob([data], { type: "application/octet-stream" });
+            const url = URL.createObjectURL(blob);
+            const a = document.createElement("a");
+            a.href = url;
+            a.download = filename;
+            document.body.appendChild(a);
+            a.click();
+            document.body.removeChild(a);
+            URL.revokeObjectURL(url);
+        }
     };
 }
 
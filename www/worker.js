@@ -0,0 +1,28 @@
+// Runs the decoder off the main thread. The page posts camera frames with their pixel buffers
+// transferred; each frame is answered with a progress event, its buffer handed back for reuse.
+import init, { WasmDecoder } from "/scanner/pkg/fountain.js";
+
+let decoder = null;
+const ready = init("/scanner/pkg/fountain_bg.wasm");
+
+self.onmessage = async (message) => {
+    const { type, width, height, buffer } = message.data;
+    try {
+        await ready;
+        if (type === "start") {
+            decoder = new WasmDecoder();
+            self.postMessage({ type: "ready" });
+            return;
+        }
+        if (type !== "frame" || !decoder) return;
+
+        const event = decoder.feed_buffer(width, height, buffer);
+        const transfer = [buffer];
+        if (event.files) {
+            transfer.push(...event.files.map((file) => file.data.buffer));
+        }
+        self.postMessage({ type: "progress", buffer, ...event }, transfer);
+    } catch (e) {
+        self.postMessage({ type: "error", message: e.message ?? String(e) });
+    }
+};